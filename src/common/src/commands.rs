@@ -19,6 +19,8 @@ pub enum Commands {
     ConvertQuery(String),
     /// Show the tables of a database.
     ShowTables,
+    /// Show the indexes of a database.
+    ShowIndexes,
     /// Show the registered queries of a database.
     ShowQueries,
     /// List databases
@@ -94,6 +96,9 @@ pub fn parse_command(mut cmd: String) -> Option<Commands> {
     } else if cmd == "\\dt" {
         // usage: \dt
         return Some(Commands::ShowTables);
+    } else if cmd == "\\di" {
+        // usage: \di
+        return Some(Commands::ShowIndexes);
     } else if cmd == "\\dq" {
         // useage: \dq
         return Some(Commands::ShowQueries);
@@ -159,4 +164,10 @@ mod test {
         let show_tables: String = String::from("\\dt\n");
         assert_eq!(Commands::ShowTables, parse_command(show_tables).unwrap());
     }
+
+    #[test]
+    fn test_show_indexes() {
+        let show_indexes: String = String::from("\\di\n");
+        assert_eq!(Commands::ShowIndexes, parse_command(show_indexes).unwrap());
+    }
 }
@@ -39,10 +39,80 @@ impl Database {
         }
     }
 
+    /// Unregisters a table and its secondary indexes from the catalog, returning the table if
+    /// it was there.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_id` - Container of the table to remove.
+    pub fn remove_table(&self, container_id: ContainerId) -> Option<Arc<RwLock<Table>>> {
+        let table = self.tables.write().unwrap().remove(&container_id);
+        let mut containers = self.named_containers.write().unwrap();
+        containers.remove(&container_id);
+        let table = table?;
+        for index in table.read().unwrap().secondary_indexes() {
+            containers.remove(&index.container_id);
+        }
+        Some(table)
+    }
+
+    /// Finds the container of the secondary index with a name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the index.
+    pub fn get_index_id(&self, name: &str) -> Option<ContainerId> {
+        let containers = self.named_containers.read().unwrap();
+        containers.iter().find_map(|(id, c)| match c {
+            (n, StateType::Index) if n == name => Some(*id),
+            _ => None,
+        })
+    }
+
+    /// Finds the table a secondary index belongs to.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_id` - Container of the index.
+    pub fn get_index_table(&self, index_id: ContainerId) -> Option<ContainerId> {
+        let tables = self.tables.read().unwrap();
+        tables
+            .iter()
+            .find_map(|(id, table)| table.read().unwrap().secondary_index(index_id).map(|_| *id))
+    }
+
+    /// Renames a table in the catalog.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_id` - Container of the table to rename.
+    /// * `name` - New name of the table.
+    pub fn rename_table(&self, container_id: ContainerId, name: &str) -> Result<(), CrustyError> {
+        if self.get_table_id(name).is_some() {
+            return Err(CrustyError::CrustyError(format!(
+                "Table {} already exists",
+                name
+            )));
+        }
+        let table = self.get_table_ptr(container_id)?;
+        table.write().unwrap().name = name.to_string();
+        if let Some(entry) = self.named_containers.write().unwrap().get_mut(&container_id) {
+            entry.0 = name.to_string();
+        }
+        Ok(())
+    }
+
     pub fn load(filename: PathBuf) -> Self {
         debug!("Loading database from file {}", filename.display());
         let reader = File::open(&filename).expect("error opening file");
-        serde_json::from_reader(reader).expect("error reading from json")
+        let db: Database = serde_json::from_reader(reader).expect("error reading from json");
+        // New containers must not reuse the ids of the ones loaded
+        let containers = db.named_containers.read().unwrap();
+        if let Some(max) = containers.keys().max() {
+            CONTAINER_COUNTER.fetch_max(max + 1, Ordering::SeqCst);
+        }
+        drop(containers);
+        db
     }
 }
 
@@ -88,6 +158,14 @@ impl Catalog for Database {
                 }
             }
         }
+        if let (StateType::Index, Some(name)) = (&state_type, &name) {
+            if self.get_index_id(name).is_some() {
+                return Err(CrustyError::CrustyError(format!(
+                    "Index {} already exists",
+                    name
+                )));
+            }
+        }
         let new_cid = CONTAINER_COUNTER.fetch_add(1, Ordering::SeqCst);
         if let Some(n) = name {
             //Save the cid if this has a name
@@ -0,0 +1,285 @@
+use crate::ids::{ContainerId, TransactionId, ValueId};
+use crate::storage_trait::StorageTrait;
+use crate::table::Table;
+use crate::{CrustyError, Field, Tuple};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// What happens to the rows referring to a row of the parent table when it is deleted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ReferentialAction {
+    /// The delete fails.
+    Restrict,
+    /// The rows referring to it are deleted too.
+    Cascade,
+    /// The columns of the rows referring to it are set to NULL.
+    SetNull,
+}
+
+/// A foreign key: the values of some columns of each row must be the key of a row of the
+/// parent table.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ForeignKey {
+    /// Name of the constraint.
+    pub name: String,
+    /// Columns of the table referring to the parent.
+    pub columns: Vec<usize>,
+    /// Container of the parent table.
+    pub parent: ContainerId,
+    /// Columns of the primary key or unique column of the parent referred to, in the order of
+    /// `columns`.
+    pub parent_columns: Vec<usize>,
+    /// What deleting a row of the parent does to the rows referring to it.
+    pub on_delete: ReferentialAction,
+}
+
+impl ForeignKey {
+    fn key(&self, tuple: &Tuple) -> Vec<Field> {
+        self.columns
+            .iter()
+            .map(|i| tuple.field_vals[*i].clone())
+            .collect()
+    }
+
+    fn parent_key(&self, tuple: &Tuple) -> Vec<Field> {
+        self.parent_columns
+            .iter()
+            .map(|i| tuple.field_vals[*i].clone())
+            .collect()
+    }
+}
+
+/// What deleting rows does to the rows of other tables referring to them.
+#[derive(Debug, Default, PartialEq)]
+pub struct Cascade {
+    /// Rows deleted along with them.
+    pub deleted: Vec<ValueId>,
+    /// Rows whose columns referring to them are set to NULL, with their new values encoded.
+    pub updated: Vec<(ValueId, Vec<u8>)>,
+}
+
+/// Checks the foreign keys between the tables of a database as rows are written.
+#[derive(Clone, Default)]
+pub struct ForeignKeys {
+    tables: Arc<RwLock<HashMap<ContainerId, Arc<RwLock<Table>>>>>,
+}
+
+impl ForeignKeys {
+    /// Creates a checker over the tables of a catalog.
+    ///
+    /// # Arguments
+    ///
+    /// * `tables` - Tables of the catalog.
+    pub fn new(tables: Arc<RwLock<HashMap<ContainerId, Arc<RwLock<Table>>>>>) -> Self {
+        ForeignKeys { tables }
+    }
+
+    fn table(&self, container_id: ContainerId) -> Result<Table, CrustyError> {
+        let tables = self.tables.read().unwrap();
+        match tables.get(&container_id) {
+            Some(table) => Ok(table.read().unwrap().clone()),
+            None => Err(CrustyError::CrustyError(format!(
+                "Table with container {} does not exist",
+                container_id
+            ))),
+        }
+    }
+
+    /// Tables with a foreign key referring to the parent.
+    fn children(&self, parent: ContainerId) -> Vec<(ContainerId, Table)> {
+        let tables = self.tables.read().unwrap();
+        let mut children: Vec<(ContainerId, Table)> = tables
+            .iter()
+            .map(|(id, table)| (*id, table.read().unwrap().clone()))
+            .filter(|(_, table)| table.foreign_keys().iter().any(|fk| fk.parent == parent))
+            .collect();
+        children.sort_by_key(|(id, _)| *id);
+        children
+    }
+
+    /// Checks that the rows written to a table refer to existing rows of its parents.
+    ///
+    /// # Arguments
+    ///
+    /// * `sm` - Storage manager holding the tables.
+    /// * `tid` - Transaction writing the rows.
+    /// * `table` - Table written to.
+    /// * `container_id` - Container of the table.
+    /// * `rows` - Rows written.
+    pub fn check_references<S: StorageTrait>(
+        &self,
+        sm: &S,
+        tid: TransactionId,
+        table: &Table,
+        container_id: ContainerId,
+        rows: &[&Tuple],
+    ) -> Result<(), CrustyError> {
+        for fk in table.foreign_keys() {
+            let parent = if fk.parent == container_id {
+                table.clone()
+            } else {
+                self.table(fk.parent)?
+            };
+            // A row may refer to another row written along with it.
+            let written: HashSet<Vec<Field>> = if fk.parent == container_id {
+                rows.iter().map(|t| fk.parent_key(t)).collect()
+            } else {
+                HashSet::new()
+            };
+            for row in rows {
+                let key = fk.key(row);
+                // A key holding NULL refers to nothing, so it needs no parent row.
+                if key.iter().any(Field::is_null)
+                    || written.contains(&key)
+                    || !parent
+                        .find_rows(sm, fk.parent, tid, &fk.parent_columns, &key)?
+                        .is_empty()
+                {
+                    continue;
+                }
+                return Err(CrustyError::ValidationError(format!(
+                    "Insert or update on table {} violates foreign key {}: key {} is not in table {}",
+                    table.name,
+                    fk.name,
+                    table.describe_key(&fk.columns, &key),
+                    parent.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the rows that deleting rows of a table deletes as well, following the foreign
+    /// keys with ON DELETE CASCADE, and those whose columns it sets to NULL, following the
+    /// ones with ON DELETE SET NULL. Fails if a foreign key restricts the delete.
+    ///
+    /// # Arguments
+    ///
+    /// * `sm` - Storage manager holding the tables.
+    /// * `tid` - Transaction deleting the rows.
+    /// * `container_id` - Container of the table deleted from.
+    /// * `rows` - Rows deleted.
+    pub fn cascade_delete<S: StorageTrait>(
+        &self,
+        sm: &S,
+        tid: TransactionId,
+        container_id: ContainerId,
+        rows: &[(ValueId, Tuple)],
+    ) -> Result<Cascade, CrustyError> {
+        let mut deleted: HashSet<ValueId> = rows.iter().map(|(id, _)| *id).collect();
+        let mut pending: Vec<(ContainerId, Tuple)> = rows
+            .iter()
+            .map(|(_, tuple)| (container_id, tuple.clone()))
+            .collect();
+        let mut cascaded = Vec::new();
+        // Old and new value of each row set to NULL, by table
+        let mut nulled: HashMap<ContainerId, HashMap<ValueId, (Tuple, Tuple)>> = HashMap::new();
+        while let Some((parent_id, tuple)) = pending.pop() {
+            for (child_id, child) in self.children(parent_id) {
+                for fk in child.foreign_keys().iter().filter(|fk| fk.parent == parent_id) {
+                    let key = fk.parent_key(&tuple);
+                    if key.iter().any(Field::is_null) {
+                        continue;
+                    }
+                    let referring: Vec<(ValueId, Tuple)> = child
+                        .find_rows(sm, child_id, tid, &fk.columns, &key)?
+                        .into_iter()
+                        .filter(|(id, _)| !deleted.contains(id))
+                        .collect();
+                    if referring.is_empty() {
+                        continue;
+                    }
+                    match fk.on_delete {
+                        ReferentialAction::Cascade => {
+                            for (id, row) in referring {
+                                deleted.insert(id);
+                                cascaded.push(id);
+                                if let Some(rows) = nulled.get_mut(&child_id) {
+                                    rows.remove(&id);
+                                }
+                                pending.push((child_id, row));
+                            }
+                        }
+                        ReferentialAction::SetNull => {
+                            let rows = nulled.entry(child_id).or_default();
+                            for (id, row) in referring {
+                                let (_, new) = rows.entry(id).or_insert_with(|| (row.clone(), row));
+                                for i in &fk.columns {
+                                    new.field_vals[*i] = Field::Null;
+                                }
+                            }
+                        }
+                        ReferentialAction::Restrict => {
+                            let parent = self.table(parent_id)?;
+                            return Err(CrustyError::ValidationError(format!(
+                                "Delete from table {} violates foreign key {} of table {}: key {} is still referenced",
+                                parent.name,
+                                fk.name,
+                                child.name,
+                                parent.describe_key(&fk.parent_columns, &key)
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+        let mut updated = Vec::new();
+        for (child_id, rows) in nulled {
+            let child = self.table(child_id)?;
+            // The columns set to NULL may be a key other rows refer to
+            let changes: Vec<(ValueId, &Tuple, &Tuple)> =
+                rows.iter().map(|(id, (old, new))| (*id, old, new)).collect();
+            self.check_key_changes(sm, tid, &child, child_id, &changes)?;
+            updated.extend(rows.iter().map(|(id, (_, new))| (*id, child.encode(new))));
+        }
+        Ok(Cascade {
+            deleted: cascaded,
+            updated,
+        })
+    }
+
+    /// Checks that an update of a table does not change keys that rows of other tables still
+    /// refer to.
+    ///
+    /// # Arguments
+    ///
+    /// * `sm` - Storage manager holding the tables.
+    /// * `tid` - Transaction updating the rows.
+    /// * `table` - Table updated.
+    /// * `container_id` - Container of the table.
+    /// * `rows` - Id, old value and new value of each updated row.
+    pub fn check_key_changes<S: StorageTrait>(
+        &self,
+        sm: &S,
+        tid: TransactionId,
+        table: &Table,
+        container_id: ContainerId,
+        rows: &[(ValueId, &Tuple, &Tuple)],
+    ) -> Result<(), CrustyError> {
+        for (child_id, child) in self.children(container_id) {
+            for fk in child.foreign_keys().iter().filter(|fk| fk.parent == container_id) {
+                for (id, old, new) in rows {
+                    let key = fk.parent_key(old);
+                    if key == fk.parent_key(new) || key.iter().any(Field::is_null) {
+                        continue;
+                    }
+                    let referring = child.find_rows(sm, child_id, tid, &fk.columns, &key)?;
+                    // A row referring to itself is checked with its new value.
+                    if referring
+                        .iter()
+                        .any(|(r, _)| child_id != container_id || r != id)
+                    {
+                        return Err(CrustyError::ValidationError(format!(
+                            "Update of table {} violates foreign key {} of table {}: key {} is still referenced",
+                            table.name,
+                            fk.name,
+                            child.name,
+                            table.describe_key(&fk.parent_columns, &key)
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -4,11 +4,22 @@ static TXN_COUNTER: AtomicU64 = AtomicU64::new(0);
 pub static CONTAINER_COUNTER: AtomicContainerId = AtomicContainerId::new(0);
 
 /// Permissions for locks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Permissions {
     ReadOnly,
     ReadWrite,
 }
 
+/// How much of what other transactions commit a transaction sees while it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IsolationLevel {
+    /// Each statement sees what was committed before the statement started.
+    #[default]
+    ReadCommitted,
+    /// Every statement sees what was committed before the transaction first read.
+    Snapshot,
+}
+
 /// Implementation of transaction id.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TransactionId {
@@ -28,6 +39,11 @@ impl TransactionId {
     pub fn id(&self) -> u64 {
         self.id
     }
+
+    /// Rebuilds a transaction id from a value returned by `id`, e.g. one read back from a log.
+    pub fn from_raw(id: u64) -> Self {
+        Self { id }
+    }
 }
 
 impl Default for TransactionId {
@@ -50,6 +66,7 @@ pub enum StateType {
     HashTable,
     BaseTable,
     MatView,
+    Index,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -102,6 +119,15 @@ impl ValueId {
             slot_id: None,
         }
     }
+
+    pub fn new_slot(container_id: ContainerId, page_id: PageId, slot_id: SlotId) -> Self {
+        ValueId {
+            container_id,
+            segment_id: None,
+            page_id: Some(page_id),
+            slot_id: Some(slot_id),
+        }
+    }
 }
 
 /// Stuff delta storage manager
@@ -0,0 +1,65 @@
+use crate::ids::ContainerId;
+use crate::row::RowLayout;
+use crate::{Field, TableSchema, Tuple, PAGE_SIZE};
+
+/// Longest a stored key of a secondary index may be, so that a node of the tree always holds
+/// several keys.
+pub const MAX_KEY_SIZE: usize = PAGE_SIZE / 4;
+
+/// How a secondary index is stored.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexMethod {
+    /// A B+ tree, which finds the keys in a range.
+    #[default]
+    BTree,
+    /// A hash table, which only finds the keys equal to a given one.
+    Hash,
+}
+
+/// A secondary index: a B+ tree or a hash table, stored in a container of its own, from the
+/// values of some columns of each row to where the row is stored.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SecondaryIndex {
+    /// Name of the index.
+    pub name: String,
+    /// Container the tree is stored in.
+    pub container_id: ContainerId,
+    /// Columns of the key, in the order they are compared.
+    pub columns: Vec<usize>,
+    /// Whether no two rows may share a key. Keys holding NULL are never shared.
+    #[serde(default)]
+    pub unique: bool,
+    /// How the index is stored.
+    #[serde(default)]
+    pub method: IndexMethod,
+    /// Whether the index was made by CREATE TABLE to enforce the primary key or a unique
+    /// column, rather than by CREATE INDEX. It goes away with the key, not by DROP INDEX.
+    #[serde(default)]
+    pub constraint: bool,
+}
+
+impl SecondaryIndex {
+    /// Layout the keys are stored in.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Schema of the table.
+    pub fn key_layout(&self, schema: &TableSchema) -> RowLayout {
+        let attrs = self
+            .columns
+            .iter()
+            .map(|i| schema.get_attribute(*i).unwrap().clone())
+            .collect();
+        RowLayout::new(&TableSchema::new(attrs))
+    }
+
+    /// Stores a key in its layout.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Schema of the table.
+    /// * `key` - Values of the columns of the key.
+    pub fn encode_key(&self, schema: &TableSchema, key: &[Field]) -> Vec<u8> {
+        self.key_layout(schema).encode(&Tuple::new(key.to_vec()), 0)
+    }
+}
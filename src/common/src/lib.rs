@@ -9,9 +9,11 @@ use delta_storage_trait::ValueDiff;
 use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
 use sqlparser::ast;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::io;
 // use proc_macro::bridge::client::ProcMacro::Attr;
 
@@ -20,26 +22,36 @@ pub mod commands;
 pub mod crusty_graph;
 pub mod database;
 pub mod delta_storage_trait;
+pub mod foreign_key;
 pub mod ids;
+pub mod index;
 pub mod logical_plan;
 pub use logical_plan::{AggOp, SimplePredicateOp};
 pub mod physical_plan;
+pub mod row;
 pub mod storage_trait;
 pub mod table;
+pub mod temporal;
 pub mod testutil;
 
 /// How big each page is
 pub const PAGE_SIZE: usize = 4096;
+/// Most digits a DECIMAL can have, so that its unscaled value fits in an i64.
+pub const MAX_DECIMAL_PRECISION: u8 = 18;
 // How many pages a buffer pool can hold
 pub const PAGE_SLOTS: usize = 50;
+/// Bytes of tuples a sort holds in memory before spilling them to disk as a sorted run.
+pub const SORT_BUDGET: usize = 64 << 20;
+/// Bytes of distinct tuples a distinct holds in memory before spilling the others to disk.
+pub const DISTINCT_BUDGET: usize = 64 << 20;
 // Default method for how to retun string results
 pub const QUERY_RESULT_TYPE: QueryResultType = QueryResultType::CSV(false); //QueryResultType::WIDTH(10);
 
 pub mod prelude {
-    pub use crate::ids::Permissions;
     pub use crate::ids::{
         ContainerId, LogicalTimeStamp, PageId, SlotId, StateType, TransactionId, ValueId,
     };
+    pub use crate::ids::{IsolationLevel, Permissions};
     pub use crate::table::Table;
     pub use crate::CrustyError;
     pub use crate::{DataType, Field, TableSchema, Tuple};
@@ -248,6 +260,48 @@ impl TableSchema {
         }
         total
     }
+
+    /// Appends an attribute to the schema.
+    ///
+    /// # Arguments
+    ///
+    /// * `attr` - Attribute to append.
+    pub fn add_attribute(&mut self, attr: Attribute) {
+        self.name_map
+            .insert(attr.name().to_string(), self.attributes.len());
+        self.attributes.push(attr);
+    }
+
+    /// Removes the attribute at the index, moving the ones after it up.
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - Index of the attribute to remove.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out-of-bounds.
+    pub fn remove_attribute(&mut self, i: usize) -> Attribute {
+        let attr = self.attributes.remove(i);
+        *self = TableSchema::new(std::mem::take(&mut self.attributes));
+        attr
+    }
+
+    /// Renames the attribute at the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - Index of the attribute to rename.
+    /// * `name` - New name of the attribute.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out-of-bounds.
+    pub fn rename_attribute(&mut self, i: usize, name: String) {
+        self.name_map.remove(self.attributes[i].name());
+        self.name_map.insert(name.clone(), i);
+        self.attributes[i].name = name;
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
@@ -261,6 +315,19 @@ pub enum Constraint {
     NotNullFKey(prelude::ContainerId),
 }
 
+impl Constraint {
+    /// Returns whether the constraint keeps NULL out of the column.
+    pub fn is_not_null(&self) -> bool {
+        matches!(
+            self,
+            Constraint::PrimaryKey
+                | Constraint::NotNull
+                | Constraint::UniqueNotNull
+                | Constraint::NotNullFKey(_)
+        )
+    }
+}
+
 /// Handle attributes. Pairs the name with the dtype.
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct Attribute {
@@ -270,6 +337,9 @@ pub struct Attribute {
     pub dtype: DataType,
     /// Attribute constraint
     pub constraint: Constraint,
+    /// Value an INSERT that leaves the attribute out gives it, if not NULL.
+    #[serde(default)]
+    pub default: Option<Field>,
 }
 
 impl Attribute {
@@ -286,6 +356,7 @@ impl Attribute {
             name,
             dtype,
             constraint: Constraint::None,
+            default: None,
         }
     }
 
@@ -294,6 +365,7 @@ impl Attribute {
             name,
             dtype,
             constraint,
+            default: None,
         }
     }
 
@@ -302,6 +374,7 @@ impl Attribute {
             name,
             dtype,
             constraint: Constraint::PrimaryKey,
+            default: None,
         }
     }
 
@@ -315,12 +388,17 @@ impl Attribute {
         &self.dtype
     }
 
-    // TODO(williamma12): Where does the 132 come from?
-    /// Returns the length of the dtype in bytes.
+    /// Returns the length of the attribute's slot in a stored row in bytes. Text is stored
+    /// after the slots, so its slot only holds where it ends.
     pub fn get_byte_len(&self) -> usize {
         match self.dtype {
             DataType::Int => 4,
-            DataType::String => 132,
+            DataType::String | DataType::Varchar(_) => 4,
+            DataType::BigInt | DataType::Double | DataType::Decimal(_, _) => 8,
+            DataType::Boolean => 1,
+            DataType::Date => 4,
+            DataType::Timestamp => 8,
+            DataType::Interval => 16,
         }
     }
 }
@@ -329,14 +407,138 @@ impl Attribute {
 #[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
 pub enum DataType {
     Int,
+    /// Text of any length.
     String,
+    /// Text of at most the given number of characters.
+    Varchar(u32),
+    BigInt,
+    Double,
+    Boolean,
+    /// Fixed-point number with a precision and a scale: the number of digits, and how many of
+    /// them are after the point.
+    Decimal(u8, u8),
+    Date,
+    Timestamp,
+    Interval,
+}
+
+impl DataType {
+    /// Returns whether the type holds text.
+    pub fn is_string(&self) -> bool {
+        matches!(self, DataType::String | DataType::Varchar(_))
+    }
+
+    /// Returns whether the type holds numbers.
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            DataType::Int | DataType::BigInt | DataType::Double | DataType::Decimal(_, _)
+        )
+    }
+
+    /// Returns whether values of another type can be stored in a column of this type. Text
+    /// fits in a column of any length, as lengths are checked when values are written.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Type of the values to store.
+    pub fn accepts(&self, other: &DataType) -> bool {
+        self == other || (self.is_string() && other.is_string())
+    }
+
+    /// Returns whether a field is a value of the type. NULL is a value of every type.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - Field to check.
+    pub fn matches(&self, field: &Field) -> bool {
+        match (self, field) {
+            (DataType::Int, Field::IntField(_))
+            | (DataType::BigInt, Field::BigIntField(_))
+            | (DataType::Double, Field::DoubleField(_))
+            | (DataType::Boolean, Field::BoolField(_))
+            | (DataType::Date, Field::DateField(_))
+            | (DataType::Timestamp, Field::TimestampField(_))
+            | (DataType::Interval, Field::IntervalField(_))
+            | (_, Field::Null) => true,
+            (DataType::Decimal(_, scale), Field::DecimalField(_, s)) => scale == s,
+            (dtype, Field::StringField(_)) => dtype.is_string(),
+            _ => false,
+        }
+    }
+
+    /// Returns whether a field fits the length of the type. Only VARCHAR limits length.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - Field to check.
+    pub fn fits(&self, field: &Field) -> bool {
+        match (self, field) {
+            (DataType::Varchar(len), Field::StringField(s)) => s.chars().count() <= *len as usize,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::Decimal(precision, scale) => write!(f, "Decimal({},{})", precision, scale),
+            dtype => write!(f, "{:?}", dtype),
+        }
+    }
+}
+
+/// A DOUBLE value. Doubles are totally ordered, as by `f64::total_cmp`, so that fields can
+/// be sorted, grouped and hashed. Predicates compare them by value instead.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Double(pub f64);
+
+impl PartialEq for Double {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Double {}
+
+impl PartialOrd for Double {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Double {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for Double {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state)
+    }
 }
 
 /// For each of the dtypes, make sure that there is a corresponding field type.
+///
+/// Null is the SQL NULL of any type. It is last in the ordering, so NULLs sort after all
+/// other values.
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, PartialOrd, Ord, Clone, Hash)]
 pub enum Field {
     IntField(i32),
     StringField(String),
+    BigIntField(i64),
+    DoubleField(Double),
+    BoolField(bool),
+    /// Unscaled value and scale of a DECIMAL, so 1.50 in a DECIMAL(5,2) is (150, 2).
+    DecimalField(i64, u8),
+    /// Days since 1970-01-01.
+    DateField(i32),
+    /// Microseconds since 1970-01-01 00:00:00.
+    TimestampField(i64),
+    IntervalField(temporal::Interval),
+    Null,
 }
 
 impl Field {
@@ -356,6 +558,161 @@ impl Field {
                 result.extend(s_bytes);
                 result
             }
+            Field::BigIntField(x) => x.to_le_bytes().to_vec(),
+            Field::DoubleField(x) => x.0.to_le_bytes().to_vec(),
+            Field::BoolField(x) => vec![*x as u8],
+            Field::DecimalField(x, _) => x.to_le_bytes().to_vec(),
+            Field::DateField(x) => x.to_le_bytes().to_vec(),
+            Field::TimestampField(x) => x.to_le_bytes().to_vec(),
+            Field::IntervalField(x) => {
+                let mut result = x.months.to_le_bytes().to_vec();
+                result.extend(x.days.to_le_bytes());
+                result.extend(x.micros.to_le_bytes());
+                result
+            }
+            Field::Null => Vec::new(),
+        }
+    }
+
+    /// Parses text, as in a CSV file or a SQL literal, to a field of the type. Returns None
+    /// if the text is not a value of the type.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Text to parse.
+    /// * `dtype` - Type of the field.
+    pub fn parse(value: &str, dtype: &DataType) -> Option<Field> {
+        let trimmed = value.trim();
+        match dtype {
+            DataType::Int => trimmed.parse().ok().map(Field::IntField),
+            DataType::BigInt => trimmed.parse().ok().map(Field::BigIntField),
+            DataType::Double => trimmed.parse().ok().map(|x| Field::DoubleField(Double(x))),
+            DataType::Boolean => match &trimmed.to_lowercase()[..] {
+                "true" | "t" | "1" => Some(Field::BoolField(true)),
+                "false" | "f" | "0" => Some(Field::BoolField(false)),
+                _ => None,
+            },
+            DataType::Decimal(precision, scale) => {
+                parse_decimal(trimmed, *precision, *scale).map(|x| Field::DecimalField(x, *scale))
+            }
+            DataType::Date => temporal::parse_date(trimmed).map(Field::DateField),
+            DataType::Timestamp => temporal::parse_timestamp(trimmed).map(Field::TimestampField),
+            DataType::Interval => temporal::Interval::parse(trimmed).map(Field::IntervalField),
+            DataType::String | DataType::Varchar(_) => Some(Field::StringField(value.to_string())),
+        }
+    }
+
+    /// Compares the field with another as SQL does, comparing numbers of different types by
+    /// value. Returns None if either field is NULL.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Field to compare with.
+    pub fn compare(&self, other: &Field) -> Option<Ordering> {
+        match (self, other) {
+            (Field::Null, _) | (_, Field::Null) => None,
+            (Field::IntervalField(l), Field::IntervalField(r)) => Some(l.span().cmp(&r.span())),
+            (Field::DateField(_), Field::TimestampField(_))
+            | (Field::TimestampField(_), Field::DateField(_)) => {
+                let micros = |field: &Field| match field {
+                    Field::DateField(days) => *days as i128 * temporal::MICROS_PER_DAY as i128,
+                    Field::TimestampField(micros) => *micros as i128,
+                    _ => unreachable!(),
+                };
+                Some(micros(self).cmp(&micros(other)))
+            }
+            (Field::DoubleField(_), _) | (_, Field::DoubleField(_)) => {
+                match (self.to_f64(), other.to_f64()) {
+                    (Some(l), Some(r)) => {
+                        Some(l.partial_cmp(&r).unwrap_or_else(|| l.total_cmp(&r)))
+                    }
+                    _ => Some(self.cmp(other)),
+                }
+            }
+            _ => match (self.to_decimal(), other.to_decimal()) {
+                (Some((l, l_scale)), Some((r, r_scale))) => {
+                    let scale = l_scale.max(r_scale);
+                    let l = l as i128 * 10i128.pow((scale - l_scale) as u32);
+                    let r = r as i128 * 10i128.pow((scale - r_scale) as u32);
+                    Some(l.cmp(&r))
+                }
+                _ => Some(self.cmp(other)),
+            },
+        }
+    }
+
+    /// Returns the unscaled value and scale of an exact number.
+    pub fn to_decimal(&self) -> Option<(i64, u8)> {
+        match self {
+            Field::IntField(x) => Some((*x as i64, 0)),
+            Field::BigIntField(x) => Some((*x, 0)),
+            Field::DecimalField(x, scale) => Some((*x, *scale)),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a number as a double, which may lose precision.
+    pub fn to_f64(&self) -> Option<f64> {
+        match self {
+            Field::DoubleField(x) => Some(x.0),
+            Field::DecimalField(x, scale) => Some(*x as f64 / 10f64.powi(*scale as i32)),
+            _ => self.to_decimal().map(|(x, _)| x as f64),
+        }
+    }
+
+    /// Converts the field to a value of a type, as when a computed value is assigned to a
+    /// column. Numbers convert to any numeric type, rounding half away from zero. Returns None
+    /// if the field is not a value of the type or is out of its range.
+    ///
+    /// # Arguments
+    ///
+    /// * `dtype` - Type to convert to.
+    pub fn cast(&self, dtype: &DataType) -> Option<Field> {
+        // A decimal of the right scale may still have too many digits
+        if dtype.matches(self) && !matches!(self, Field::DecimalField(..)) {
+            return Some(self.clone());
+        }
+        let scale = match dtype {
+            DataType::Decimal(_, scale) => *scale,
+            _ => 0,
+        };
+        let unscaled = match (self, dtype) {
+            (_, DataType::Double) => return self.to_f64().map(|x| Field::DoubleField(Double(x))),
+            (Field::DoubleField(x), _) => {
+                let scaled = (x.0 * 10f64.powi(scale as i32)).round();
+                if !scaled.is_finite() || scaled.abs() >= i64::MAX as f64 {
+                    return None;
+                }
+                scaled as i128
+            }
+            _ => {
+                let (x, from) = self.to_decimal()?;
+                rescale(x as i128, from, scale)?
+            }
+        };
+        match dtype {
+            DataType::Int => i32::try_from(unscaled).ok().map(Field::IntField),
+            DataType::BigInt => i64::try_from(unscaled).ok().map(Field::BigIntField),
+            DataType::Decimal(precision, scale)
+                if unscaled.abs() < 10i128.pow(*precision as u32) =>
+            {
+                Some(Field::DecimalField(unscaled as i64, *scale))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether the field is NULL.
+    pub fn is_null(&self) -> bool {
+        matches!(self, Field::Null)
+    }
+
+    /// Converts the field to a CSV cell. NULL is an empty cell, which CSV imports read back
+    /// as NULL, so an empty string becomes NULL on the way through a CSV file.
+    pub fn to_csv(&self) -> String {
+        match self {
+            Field::Null => String::new(),
+            field => field.to_string(),
         }
     }
 
@@ -381,10 +738,78 @@ impl fmt::Display for Field {
         match self {
             Field::IntField(x) => write!(f, "{}", x),
             Field::StringField(x) => write!(f, "{}", x),
+            Field::BigIntField(x) => write!(f, "{}", x),
+            Field::DoubleField(x) => write!(f, "{}", x.0),
+            Field::BoolField(x) => write!(f, "{}", x),
+            Field::DecimalField(x, 0) => write!(f, "{}", x),
+            Field::DecimalField(x, scale) => {
+                let pow = 10u64.pow(*scale as u32);
+                let sign = if *x < 0 { "-" } else { "" };
+                let (int, frac) = (x.unsigned_abs() / pow, x.unsigned_abs() % pow);
+                let width = *scale as usize;
+                write!(f, "{}{}.{:0width$}", sign, int, frac, width = width)
+            }
+            Field::DateField(x) => write!(f, "{}", temporal::format_date(*x)),
+            Field::TimestampField(x) => write!(f, "{}", temporal::format_timestamp(*x)),
+            Field::IntervalField(x) => write!(f, "{}", x),
+            Field::Null => write!(f, "NULL"),
         }
     }
 }
 
+/// Parses a decimal number to its unscaled value at a scale, rounding digits past the scale
+/// half away from zero. Returns None if the number has more than precision digits.
+///
+/// # Arguments
+///
+/// * `value` - Number to parse, like `-12.345`.
+/// * `precision` - Most digits the number may have.
+/// * `scale` - Digits after the point.
+fn parse_decimal(value: &str, precision: u8, scale: u8) -> Option<i64> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+    let frac_digits = frac_part.chars().chain(std::iter::repeat('0'));
+    let mut unscaled: i128 = 0;
+    for c in int_part.chars().chain(frac_digits.take(scale as usize)) {
+        unscaled = unscaled
+            .checked_mul(10)?
+            .checked_add(c.to_digit(10)? as i128)?;
+    }
+    let mut dropped = frac_part.chars().skip(scale as usize);
+    if !dropped.all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if matches!(frac_part.chars().nth(scale as usize), Some('5'..='9')) {
+        unscaled += 1;
+    }
+    if unscaled >= 10i128.pow(precision as u32) {
+        return None;
+    }
+    Some(if negative { -unscaled } else { unscaled } as i64)
+}
+
+/// Changes the scale of the unscaled value of a decimal, rounding half away from zero.
+/// Returns None if the value overflows.
+///
+/// # Arguments
+///
+/// * `value` - Unscaled value.
+/// * `from` - Scale of the value.
+/// * `to` - Scale to change to.
+pub(crate) fn rescale(value: i128, from: u8, to: u8) -> Option<i128> {
+    if to >= from {
+        return value.checked_mul(10i128.checked_pow((to - from) as u32)?);
+    }
+    let divisor = 10i128.checked_pow((from - to) as u32)?;
+    Some(value.signum() * ((value.abs() + divisor / 2) / divisor))
+}
+
 /// Tuple type.
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct Tuple {
@@ -454,28 +879,54 @@ impl Tuple {
         serde_cbor::from_slice(bytes).unwrap()
     }
 
+    /// Get the bytes of the tuple tagged with the version of its table's schema.
+    ///
+    /// Version 0 gives the same bytes as get_bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - Schema version the tuple is laid out in.
+    pub fn get_versioned_bytes(&self, version: u32) -> Vec<u8> {
+        let stored = StoredTuple {
+            version,
+            field_vals: &self.field_vals,
+        };
+        serde_cbor::to_vec(&stored).unwrap()
+    }
+
+    /// Read a tuple and the schema version it was written at, which is 0 for bytes
+    /// from get_bytes.
+    pub fn from_versioned_bytes(bytes: &[u8]) -> (u32, Self) {
+        let stored: StoredTuple<Vec<Field>> = serde_cbor::from_slice(bytes).unwrap();
+        (stored.version, Tuple::new(stored.field_vals))
+    }
+
     pub fn to_csv(&self) -> String {
         let mut res = Vec::new();
         for field in &self.field_vals {
-            let val = match field {
-                Field::IntField(i) => i.to_string(),
-                Field::StringField(s) => s.to_string(),
-            };
-            res.push(val);
+            res.push(field.to_csv());
         }
         res.join(",")
     }
 }
 
+/// Stored form of a tuple. It reads as a plain Tuple too, ignoring the version.
+#[derive(Serialize, Deserialize)]
+struct StoredTuple<F> {
+    #[serde(default, skip_serializing_if = "is_zero")]
+    version: u32,
+    field_vals: F,
+}
+
+fn is_zero(version: &u32) -> bool {
+    *version == 0
+}
+
 impl fmt::Display for Tuple {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut res = String::new();
         for field in &self.field_vals {
-            let val = match field {
-                Field::IntField(i) => i.to_string(),
-                Field::StringField(s) => s.to_string(),
-            };
-            res.push_str(&val);
+            res.push_str(&field.to_string());
             res.push('\t');
         }
         write!(f, "{}", res)
@@ -567,7 +1018,34 @@ pub fn get_name(name: &ast::ObjectName) -> Result<String, CrustyError> {
 pub fn get_attr(dtype: &ast::DataType) -> Result<DataType, CrustyError> {
     match dtype {
         ast::DataType::Int => Ok(DataType::Int),
-        ast::DataType::Varchar(_) => Ok(DataType::String),
+        ast::DataType::Varchar(Some(len)) => match u32::try_from(*len) {
+            Ok(len) if len > 0 => Ok(DataType::Varchar(len)),
+            _ => Err(CrustyError::CrustyError(format!(
+                "VARCHAR({}) needs a length from 1 to {}",
+                len,
+                u32::MAX
+            ))),
+        },
+        ast::DataType::Varchar(None) | ast::DataType::Text => Ok(DataType::String),
+        ast::DataType::BigInt => Ok(DataType::BigInt),
+        ast::DataType::Float(_) | ast::DataType::Real | ast::DataType::Double => {
+            Ok(DataType::Double)
+        }
+        ast::DataType::Boolean => Ok(DataType::Boolean),
+        ast::DataType::Date => Ok(DataType::Date),
+        ast::DataType::Timestamp => Ok(DataType::Timestamp),
+        ast::DataType::Interval => Ok(DataType::Interval),
+        ast::DataType::Decimal(precision, scale) => {
+            let precision = precision.unwrap_or(MAX_DECIMAL_PRECISION as u64);
+            let scale = scale.unwrap_or(0);
+            if precision == 0 || precision > MAX_DECIMAL_PRECISION as u64 || scale > precision {
+                return Err(CrustyError::CrustyError(format!(
+                    "DECIMAL({},{}) needs a precision from 1 to {} and a scale of at most the precision",
+                    precision, scale, MAX_DECIMAL_PRECISION
+                )));
+            }
+            Ok(DataType::Decimal(precision as u8, scale as u8))
+        }
         //TODO append type
         _ => Err(CrustyError::CrustyError(String::from(
             "Unsupported data type ",
@@ -593,4 +1071,164 @@ mod libtests {
         let check_tuple: Tuple = Tuple::from_bytes(&tuple_bytes);
         assert_eq!(tuple, check_tuple);
     }
+
+    #[test]
+    fn test_tuple_versioned_bytes() {
+        let tuple = int_vec_to_tuple(vec![0, 1, 0]);
+        assert_eq!(tuple.get_bytes(), tuple.get_versioned_bytes(0));
+        assert_eq!((0, tuple.clone()), Tuple::from_versioned_bytes(&tuple.get_bytes()));
+        let bytes = tuple.get_versioned_bytes(3);
+        assert_eq!((3, tuple.clone()), Tuple::from_versioned_bytes(&bytes));
+        assert_eq!(tuple, Tuple::from_bytes(&bytes));
+    }
+
+    #[test]
+    fn test_schema_changes() {
+        let mut schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int; 2]);
+        schema.add_attribute(Attribute::new("c".to_string(), DataType::String));
+        assert_eq!(Some(&2), schema.get_field_index("c"));
+        schema.rename_attribute(0, "z".to_string());
+        assert!(!schema.contains("a"));
+        assert_eq!(Some(&0), schema.get_field_index("z"));
+        assert_eq!("b", schema.remove_attribute(1).name());
+        assert_eq!(
+            TableSchema::new(vec![
+                Attribute::new("z".to_string(), DataType::Int),
+                Attribute::new("c".to_string(), DataType::String),
+            ]),
+            schema
+        );
+        assert_eq!(Some(&1), schema.get_field_index("c"));
+    }
+
+    #[test]
+    fn test_null_fields() {
+        let tuple = Tuple::new(vec![
+            Field::IntField(1),
+            Field::Null,
+            Field::StringField(String::new()),
+        ]);
+        assert_eq!(tuple, Tuple::from_bytes(&tuple.get_bytes()));
+        assert_eq!("1,,", tuple.to_csv());
+        assert_eq!("1\tNULL\t\t", tuple.to_string());
+        // NULLs sort last
+        assert!(Field::Null > Field::IntField(i32::MAX));
+        assert!(Field::Null > Field::StringField("z".to_string()));
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let decimal = DataType::Decimal(5, 2);
+        assert_eq!(Some(Field::DecimalField(-1235, 2)), Field::parse("-12.345", &decimal));
+        assert_eq!(Some(Field::DecimalField(50, 2)), Field::parse(".5", &decimal));
+        assert_eq!(Some(Field::DecimalField(99999, 2)), Field::parse("999.99", &decimal));
+        assert_eq!(None, Field::parse("1000", &decimal));
+        assert_eq!(None, Field::parse("1.2.3", &decimal));
+        assert_eq!(None, Field::parse("", &decimal));
+        assert_eq!(Some(Field::BoolField(true)), Field::parse("TRUE", &DataType::Boolean));
+        assert_eq!(Some(Field::BoolField(false)), Field::parse("0", &DataType::Boolean));
+        assert_eq!(None, Field::parse("yes", &DataType::Boolean));
+        assert_eq!(None, Field::parse("3000000000", &DataType::Int));
+        assert_eq!(
+            Some(Field::BigIntField(3000000000)),
+            Field::parse(" 3000000000 ", &DataType::BigInt)
+        );
+        assert_eq!(
+            Some(Field::DoubleField(Double(-1.5e3))),
+            Field::parse("-1.5e3", &DataType::Double)
+        );
+    }
+
+    #[test]
+    fn test_typed_fields() {
+        let tuple = Tuple::new(vec![
+            Field::BigIntField(-1),
+            Field::DoubleField(Double(0.25)),
+            Field::BoolField(true),
+            Field::DecimalField(-5, 2),
+            Field::DecimalField(1200, 0),
+        ]);
+        assert_eq!(tuple, Tuple::from_bytes(&tuple.get_bytes()));
+        assert_eq!("-1,0.25,true,-0.05,1200", tuple.to_csv());
+        assert_eq!(8, Field::DecimalField(1, 1).to_bytes().len());
+
+        // Numbers of different types compare by value
+        let half = Field::DecimalField(50, 2);
+        assert_eq!(Some(Ordering::Equal), half.compare(&Field::DoubleField(Double(0.5))));
+        assert_eq!(Some(Ordering::Less), half.compare(&Field::IntField(1)));
+        assert_eq!(Some(Ordering::Greater), Field::DecimalField(10, 1).compare(&half));
+        assert_eq!(Some(Ordering::Equal), Field::BigIntField(7).compare(&Field::IntField(7)));
+        assert_eq!(None, half.compare(&Field::Null));
+    }
+
+    #[test]
+    fn test_cast_fields() {
+        let decimal = DataType::Decimal(4, 1);
+        assert_eq!(
+            Some(Field::DecimalField(125, 1)),
+            Field::DecimalField(1245, 2).cast(&decimal)
+        );
+        assert_eq!(
+            Some(Field::DecimalField(-13, 1)),
+            Field::DecimalField(-125, 2).cast(&decimal)
+        );
+        assert_eq!(
+            Some(Field::DecimalField(70, 1)),
+            Field::IntField(7).cast(&decimal)
+        );
+        assert_eq!(
+            Some(Field::DecimalField(-25, 1)),
+            Field::DoubleField(Double(-2.45)).cast(&decimal)
+        );
+        assert_eq!(None, Field::IntField(1000).cast(&decimal));
+        assert_eq!(None, Field::DecimalField(10000, 1).cast(&decimal));
+        assert_eq!(
+            Some(Field::IntField(3)),
+            Field::DoubleField(Double(2.5)).cast(&DataType::Int)
+        );
+        assert_eq!(None, Field::BigIntField(1 << 40).cast(&DataType::Int));
+        assert_eq!(
+            Some(Field::DoubleField(Double(0.5))),
+            Field::DecimalField(50, 2).cast(&DataType::Double)
+        );
+        assert_eq!(Some(Field::Null), Field::Null.cast(&DataType::Int));
+        assert_eq!(
+            None,
+            Field::StringField("1".to_string()).cast(&DataType::Int)
+        );
+        assert_eq!(None, Field::IntField(1).cast(&DataType::Date));
+    }
+
+    #[test]
+    fn test_temporal_fields() {
+        let date = Field::parse("2021-03-04", &DataType::Date).unwrap();
+        let ts = Field::parse("2021-03-04 00:00:01", &DataType::Timestamp).unwrap();
+        let interval = Field::parse("1 mon 2 days 00:00:03", &DataType::Interval).unwrap();
+        let tuple = Tuple::new(vec![date.clone(), ts.clone(), interval.clone()]);
+        assert_eq!(tuple, Tuple::from_bytes(&tuple.get_bytes()));
+        assert_eq!(
+            "2021-03-04,2021-03-04 00:00:01,1 mon 2 days 00:00:03",
+            tuple.to_csv()
+        );
+        assert_eq!(16, interval.to_bytes().len());
+
+        // Dates compare with timestamps in time, and intervals by their length
+        assert_eq!(Some(Ordering::Less), date.compare(&ts));
+        let days = Field::parse("32 days 00:00:03", &DataType::Interval).unwrap();
+        assert_eq!(Some(Ordering::Equal), interval.compare(&days));
+        assert_eq!(None, Field::parse("2021-02-30", &DataType::Date));
+    }
+
+    #[test]
+    fn test_varchar() {
+        let varchar = get_attr(&ast::DataType::Varchar(Some(3))).unwrap();
+        assert_eq!(DataType::Varchar(3), varchar);
+        assert_eq!(DataType::String, get_attr(&ast::DataType::Text).unwrap());
+        assert!(get_attr(&ast::DataType::Varchar(Some(0))).is_err());
+        assert!(varchar.accepts(&DataType::String));
+        assert!(!varchar.accepts(&DataType::Int));
+        assert!(varchar.fits(&Field::StringField("äöü".to_string())));
+        assert!(!varchar.fits(&Field::StringField("abcd".to_string())));
+        assert!(varchar.fits(&Field::Null));
+    }
 }
@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Debug;
 
 use crate::ids::ContainerId;
-use crate::Field;
+use crate::temporal::{self, TimeUnit};
+use crate::MAX_DECIMAL_PRECISION;
+use crate::{rescale, Attribute, CrustyError, DataType, Double, Field, TableSchema};
 
 /// Scan node.
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -67,6 +70,304 @@ pub struct FilterNode {
     pub predicate: Predicate,
 }
 
+/// Values node. Rows of literals, already converted to the types of the columns they fill.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ValuesNode {
+    /// Schema of the rows.
+    pub schema: TableSchema,
+    /// Rows to produce.
+    pub rows: Vec<Vec<Field>>,
+}
+
+/// Insert node. Writes the tuples of its child into a table.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InsertNode {
+    /// Table to insert into.
+    pub table: String,
+    pub container_id: ContainerId,
+    /// Index in the table's schema of the column each field of the child's tuples goes in.
+    pub columns: Vec<usize>,
+}
+
+/// Update node. Rewrites the records of a table produced by its child.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateNode {
+    /// Table to update.
+    pub table: String,
+    pub container_id: ContainerId,
+    /// New values of the columns set by the update.
+    pub assignments: Vec<ColumnAssignment>,
+}
+
+/// Delete node. Removes the records of a table produced by its child.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeleteNode {
+    /// Table to delete from.
+    pub table: String,
+    pub container_id: ContainerId,
+}
+
+/// Map node. Appends columns computed from each tuple of its child.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MapNode {
+    /// Columns to append and the expressions computing them.
+    pub columns: Vec<(Attribute, ValueExpr)>,
+}
+
+/// Sort node. Orders the tuples of its child by some of their fields.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SortNode {
+    /// Fields to sort by, the first compared first.
+    pub fields: Vec<OrderByField>,
+}
+
+/// Distinct node. Produces each distinct tuple of its child once.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DistinctNode {}
+
+/// Limit node. Skips the first tuples of its child and stops after a number of tuples.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LimitNode {
+    /// Most tuples to produce, or None for all of them.
+    pub limit: Option<usize>,
+    /// Tuples to skip before producing any.
+    pub offset: usize,
+}
+
+/// Field to sort by, and the order to sort its values in.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrderByField {
+    pub field: FieldIdentifier,
+    /// Whether the values go from the highest to the lowest.
+    pub descending: bool,
+    /// Whether NULLs come before the other values rather than after them.
+    pub nulls_first: bool,
+}
+
+/// Assignment of a new value to a column in an update.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ColumnAssignment {
+    /// Index in the table's schema of the column to set.
+    pub column: usize,
+    /// Expression computing the new value from the old tuple.
+    pub value: ValueExpr,
+}
+
+/// Scalar expression over the fields of a tuple.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum ValueExpr {
+    Literal(Field),
+    /// Index of a field in the tuple.
+    Column(usize),
+    Arith(Box<ValueExpr>, ArithOp, Box<ValueExpr>),
+    /// Part of a date, timestamp or interval, as in `EXTRACT(YEAR FROM d)`.
+    Extract(TimeUnit, Box<ValueExpr>),
+    /// Date or timestamp truncated to a unit, as in `date_trunc('month', ts)`.
+    Trunc(TimeUnit, Box<ValueExpr>),
+}
+
+impl ValueExpr {
+    /// Evaluates the expression against a tuple's fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Fields that Column expressions index into.
+    pub fn eval(&self, fields: &[Field]) -> Result<Field, CrustyError> {
+        match self {
+            ValueExpr::Literal(f) => Ok(f.clone()),
+            ValueExpr::Column(i) => fields
+                .get(*i)
+                .cloned()
+                .ok_or_else(|| CrustyError::ExecutionError(format!("No field at index {}", i))),
+            ValueExpr::Arith(left, op, right) => match (left.eval(fields)?, right.eval(fields)?) {
+                (Field::IntField(l), Field::IntField(r)) => op.apply(l, r).map(Field::IntField),
+                (Field::Null, _) | (_, Field::Null) => Ok(Field::Null),
+                (l, r) if l.to_f64().is_some() && r.to_f64().is_some() => op.apply_numeric(&l, &r),
+                (l, r) => temporal::arith(&l, *op, &r),
+            },
+            ValueExpr::Extract(unit, expr) => temporal::extract(*unit, &expr.eval(fields)?),
+            ValueExpr::Trunc(unit, expr) => temporal::truncate(*unit, &expr.eval(fields)?),
+        }
+    }
+}
+
+/// Arithmetic operations on numbers, and on dates, timestamps and intervals.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ArithOp {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+impl ArithOp {
+    /// Applies the operation, failing on overflow or division by zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - Left operand.
+    /// * `right` - Right operand.
+    pub fn apply(&self, left: i32, right: i32) -> Result<i32, CrustyError> {
+        if right == 0 && matches!(self, ArithOp::Divide | ArithOp::Modulo) {
+            return Err(CrustyError::ExecutionError(String::from(
+                "Division by zero",
+            )));
+        }
+        let res = match self {
+            ArithOp::Plus => left.checked_add(right),
+            ArithOp::Minus => left.checked_sub(right),
+            ArithOp::Multiply => left.checked_mul(right),
+            ArithOp::Divide => left.checked_div(right),
+            ArithOp::Modulo => left.checked_rem(right),
+        };
+        res.ok_or_else(|| {
+            CrustyError::ExecutionError(format!("{} {} {} is out of range", left, self, right))
+        })
+    }
+
+    /// Applies the operation to numbers of any types. A DOUBLE operand makes the result a
+    /// DOUBLE, and otherwise a DECIMAL operand makes it a DECIMAL, rounded half away from zero
+    /// to its scale. Other integers give a BIGINT. Fails on overflow or division by zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - Left operand.
+    /// * `right` - Right operand.
+    pub fn apply_numeric(&self, left: &Field, right: &Field) -> Result<Field, CrustyError> {
+        let out_of_range =
+            || CrustyError::ExecutionError(format!("{} {} {} is out of range", left, self, right));
+        let divides = matches!(self, ArithOp::Divide | ArithOp::Modulo);
+        if matches!(left, Field::DoubleField(_)) || matches!(right, Field::DoubleField(_)) {
+            let (l, r) = match (left.to_f64(), right.to_f64()) {
+                (Some(l), Some(r)) => (l, r),
+                _ => return Err(out_of_range()),
+            };
+            if divides && r == 0.0 {
+                return Err(CrustyError::ExecutionError(String::from(
+                    "Division by zero",
+                )));
+            }
+            let res = match self {
+                ArithOp::Plus => l + r,
+                ArithOp::Minus => l - r,
+                ArithOp::Multiply => l * r,
+                ArithOp::Divide => l / r,
+                ArithOp::Modulo => l % r,
+            };
+            if !res.is_finite() {
+                return Err(out_of_range());
+            }
+            return Ok(Field::DoubleField(Double(res)));
+        }
+        let ((l, l_scale), (r, r_scale)) = match (left.to_decimal(), right.to_decimal()) {
+            (Some(l), Some(r)) => (l, r),
+            _ => return Err(out_of_range()),
+        };
+        if divides && r == 0 {
+            return Err(CrustyError::ExecutionError(String::from(
+                "Division by zero",
+            )));
+        }
+        let decimal =
+            matches!(left, Field::DecimalField(..)) || matches!(right, Field::DecimalField(..));
+        let scale = self.decimal_scale(l_scale, r_scale);
+        let (l, r) = (l as i128, r as i128);
+        let res = match self {
+            ArithOp::Multiply => rescale(l * r, l_scale + r_scale, scale),
+            // Integer division truncates
+            ArithOp::Divide if !decimal => Some(l / r),
+            ArithOp::Divide => rescale(l, l_scale, scale + r_scale).map(|l| {
+                let (quotient, rem) = (l / r, l % r);
+                if 2 * rem.abs() >= r.abs() {
+                    quotient + l.signum() * r.signum()
+                } else {
+                    quotient
+                }
+            }),
+            _ => rescale(l, l_scale, scale)
+                .zip(rescale(r, r_scale, scale))
+                .map(|(l, r)| match self {
+                    ArithOp::Plus => l + r,
+                    ArithOp::Minus => l - r,
+                    _ => l % r,
+                }),
+        };
+        let res = res.ok_or_else(out_of_range)?;
+        if !decimal {
+            return i64::try_from(res)
+                .map(Field::BigIntField)
+                .map_err(|_| out_of_range());
+        }
+        if res.abs() >= 10i128.pow(MAX_DECIMAL_PRECISION as u32) {
+            return Err(out_of_range());
+        }
+        Ok(Field::DecimalField(res as i64, scale))
+    }
+
+    /// Returns the scale of the DECIMAL result of the operation on numbers of the scales.
+    /// A product keeps every digit after the point, up to the most a DECIMAL holds, and the
+    /// other operations keep the digits of the operand with more.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - Scale of the left operand.
+    /// * `right` - Scale of the right operand.
+    pub fn decimal_scale(&self, left: u8, right: u8) -> u8 {
+        match self {
+            ArithOp::Multiply => (left + right).min(MAX_DECIMAL_PRECISION),
+            _ => left.max(right),
+        }
+    }
+
+    /// Returns the type of the operation's result on operands of the types, or None if
+    /// the operation does not apply to them.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - Type of the left operand.
+    /// * `right` - Type of the right operand.
+    pub fn result_type(&self, left: &DataType, right: &DataType) -> Option<DataType> {
+        use DataType::*;
+        match (left, self, right) {
+            (Int, _, Int) => Some(Int),
+            (Date, ArithOp::Plus, Int) | (Int, ArithOp::Plus, Date) => Some(Date),
+            (Date, ArithOp::Minus, Int) => Some(Date),
+            (Date, ArithOp::Minus, Date) => Some(Int),
+            (Date | Timestamp, ArithOp::Plus | ArithOp::Minus, Interval)
+            | (Interval, ArithOp::Plus, Date | Timestamp) => Some(Timestamp),
+            (Timestamp, ArithOp::Minus, Timestamp) => Some(Interval),
+            (Interval, ArithOp::Plus | ArithOp::Minus, Interval) => Some(Interval),
+            (Interval, ArithOp::Multiply, Int) | (Int, ArithOp::Multiply, Interval) => {
+                Some(Interval)
+            }
+            (l, _, r) if l.is_numeric() && r.is_numeric() => Some(match (l, r) {
+                (Double, _) | (_, Double) => Double,
+                (Decimal(_, l), Decimal(_, r)) => {
+                    Decimal(MAX_DECIMAL_PRECISION, self.decimal_scale(*l, *r))
+                }
+                (Decimal(_, l), _) => Decimal(MAX_DECIMAL_PRECISION, self.decimal_scale(*l, 0)),
+                (_, Decimal(_, r)) => Decimal(MAX_DECIMAL_PRECISION, self.decimal_scale(0, *r)),
+                _ => BigInt,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ArithOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op_str = match self {
+            ArithOp::Plus => "+",
+            ArithOp::Minus => "-",
+            ArithOp::Multiply => "*",
+            ArithOp::Divide => "/",
+            ArithOp::Modulo => "%",
+        };
+        write!(f, "{}", op_str)
+    }
+}
+
 /// Predicate to be used in filter
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Predicate {
@@ -93,20 +394,29 @@ pub struct SimplePredicate {
 impl SimplePredicateOp {
     /// Do predicate comparison.
     ///
+    /// Returns None when the result is unknown, which it is for comparisons with NULL.
+    ///
     /// # Arguments
     ///
     /// * `left_field` - Left field of the predicate.
-    /// * `right_field` - Right field of the predicate.
-    pub fn compare<T: Ord>(&self, left_field: &T, right_field: &T) -> bool {
+    /// * `right_field` - Right field of the predicate. Ignored by IS NULL and IS NOT NULL.
+    pub fn compare(&self, left_field: &Field, right_field: &Field) -> Option<bool> {
         match self {
-            SimplePredicateOp::Equals => left_field == right_field,
-            SimplePredicateOp::GreaterThan => left_field > right_field,
-            SimplePredicateOp::LessThan => left_field < right_field,
-            SimplePredicateOp::LessThanOrEq => left_field <= right_field,
-            SimplePredicateOp::GreaterThanOrEq => left_field >= right_field,
-            SimplePredicateOp::NotEq => left_field != right_field,
-            SimplePredicateOp::All => true,
+            SimplePredicateOp::IsNull => return Some(left_field.is_null()),
+            SimplePredicateOp::IsNotNull => return Some(!left_field.is_null()),
+            SimplePredicateOp::All => return Some(true),
+            _ => {}
         }
+        let ordering = left_field.compare(right_field)?;
+        Some(match self {
+            SimplePredicateOp::Equals => ordering == Ordering::Equal,
+            SimplePredicateOp::GreaterThan => ordering == Ordering::Greater,
+            SimplePredicateOp::LessThan => ordering == Ordering::Less,
+            SimplePredicateOp::LessThanOrEq => ordering != Ordering::Greater,
+            SimplePredicateOp::GreaterThanOrEq => ordering != Ordering::Less,
+            SimplePredicateOp::NotEq => ordering != Ordering::Equal,
+            _ => unreachable!(),
+        })
     }
 
     /// Flip the operator.
@@ -131,6 +441,10 @@ pub enum SimplePredicateOp {
     GreaterThanOrEq,
     NotEq,
     All,
+    /// The left field is NULL.
+    IsNull,
+    /// The left field is not NULL.
+    IsNotNull,
 }
 
 /// Compound Predicate
@@ -162,6 +476,16 @@ impl CompoundPredicateOp {
             CompoundPredicateOp::Or => left || right,
         }
     }
+
+    /// Applies the operator in three-valued logic, where None is unknown. An unknown
+    /// operand only decides the result when the other operand does not.
+    pub fn apply_unknown(&self, left: Option<bool>, right: Option<bool>) -> Option<bool> {
+        match (left, right) {
+            (Some(l), Some(r)) => Some(self.apply(l, r)),
+            (Some(v), None) | (None, Some(v)) if v != self.identity() => Some(v),
+            _ => None,
+        }
+    }
 }
 
 /// Predicate expression.
@@ -169,6 +493,9 @@ impl CompoundPredicateOp {
 pub enum PredExpr {
     Literal(Field),
     Ident(FieldIdentifier),
+    /// Result of an aggregate, as in a HAVING clause. Filters above the aggregate read it
+    /// under the column name of the field.
+    Aggregate(FieldIdentifier),
 }
 
 impl PredExpr {
@@ -182,23 +509,56 @@ impl PredExpr {
 }
 
 /// Aggregation operations.
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum AggOp {
     Avg,
+    /// Counts the non-NULL values of a column.
     Count,
+    /// Counts the rows, as COUNT(*) does.
+    CountAll,
     Max,
     Min,
     Sum,
+    /// Averages the distinct non-NULL values of a column, as AVG(DISTINCT c) does.
+    AvgDistinct,
+    /// Counts the distinct non-NULL values of a column, as COUNT(DISTINCT c) does.
+    CountDistinct,
+    /// Sums the distinct non-NULL values of a column, as SUM(DISTINCT c) does.
+    SumDistinct,
+}
+
+impl AggOp {
+    /// Whether the aggregate only merges each distinct value once.
+    pub fn is_distinct(self) -> bool {
+        matches!(
+            self,
+            AggOp::AvgDistinct | AggOp::CountDistinct | AggOp::SumDistinct
+        )
+    }
+
+    /// Returns the aggregate merging every value that this aggregate merges the distinct
+    /// values with.
+    pub fn without_distinct(self) -> Self {
+        match self {
+            AggOp::AvgDistinct => AggOp::Avg,
+            AggOp::CountDistinct => AggOp::Count,
+            AggOp::SumDistinct => AggOp::Sum,
+            op => op,
+        }
+    }
 }
 
 impl fmt::Display for AggOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let op_str = match self {
             AggOp::Avg => "avg",
-            AggOp::Count => "count",
+            AggOp::Count | AggOp::CountAll => "count",
             AggOp::Max => "max",
             AggOp::Min => "min",
             AggOp::Sum => "sum",
+            AggOp::AvgDistinct => "avg_distinct",
+            AggOp::CountDistinct => "count_distinct",
+            AggOp::SumDistinct => "sum_distinct",
         };
         write!(f, "{}", op_str)
     }
@@ -277,8 +637,10 @@ impl FieldIdentifier {
 
     /// If an op is some, sets the alias to a default alias>
     pub fn default_alias(&mut self) {
-        if let Some(op) = self.op {
-            self.alias = Some(format!("{}_{}", op, self.column));
+        match self.op {
+            Some(AggOp::CountAll) => self.alias = Some(String::from("count_*")),
+            Some(op) => self.alias = Some(format!("{}_{}", op, self.column)),
+            None => {}
         }
     }
 
@@ -24,6 +24,14 @@ pub enum LogicalOp {
     Aggregate(AggregateNode),
     Join(JoinNode),
     Filter(FilterNode),
+    Map(MapNode),
+    Sort(SortNode),
+    Limit(LimitNode),
+    Distinct(DistinctNode),
+    Values(ValuesNode),
+    Insert(InsertNode),
+    Update(UpdateNode),
+    Delete(DeleteNode),
     ReadDeltas(ReadDeltasNode),
     WriteDeltas(WriteDeltasNode),
 }
@@ -416,4 +424,68 @@ mod test {
         lp.add_edge(scan, project);
         assert!(lp.all_reachable_from_root().unwrap());
     }
+
+    #[test]
+    fn test_numeric_arith() -> Result<(), CrustyError> {
+        use crate::{DataType, Double, Field, MAX_DECIMAL_PRECISION};
+        let decimal = |x, scale| Field::DecimalField(x, scale);
+        let double = |x| Field::DoubleField(Double(x));
+        let apply = |l: Field, op: ArithOp, r: Field| op.apply_numeric(&l, &r);
+        assert_eq!(
+            decimal(251, 2),
+            apply(decimal(150, 2), ArithOp::Plus, decimal(101, 2))?
+        );
+        assert_eq!(
+            decimal(2250, 3),
+            apply(decimal(150, 2), ArithOp::Multiply, decimal(15, 1))?
+        );
+        // Quotients round half away from zero to the larger scale
+        assert_eq!(
+            decimal(-67, 2),
+            apply(decimal(-200, 2), ArithOp::Divide, Field::IntField(3))?
+        );
+        assert_eq!(
+            decimal(5, 1),
+            apply(Field::IntField(3), ArithOp::Modulo, decimal(25, 1))?
+        );
+        assert_eq!(
+            double(3.0),
+            apply(double(2.0), ArithOp::Multiply, decimal(15, 1))?
+        );
+        assert_eq!(
+            Field::BigIntField(-2),
+            apply(Field::BigIntField(-7), ArithOp::Divide, Field::IntField(3))?
+        );
+        assert!(apply(
+            Field::BigIntField(i64::MAX),
+            ArithOp::Plus,
+            Field::IntField(1)
+        )
+        .is_err());
+        assert!(apply(decimal(1, 1), ArithOp::Divide, decimal(0, 2)).is_err());
+        assert!(apply(double(1.0), ArithOp::Modulo, Field::IntField(0)).is_err());
+
+        let max = MAX_DECIMAL_PRECISION;
+        let types = [
+            (DataType::Int, DataType::BigInt, DataType::BigInt),
+            (
+                DataType::Decimal(5, 2),
+                DataType::Int,
+                DataType::Decimal(max, 2),
+            ),
+            (DataType::Decimal(5, 2), DataType::Double, DataType::Double),
+        ];
+        for (l, r, res) in types {
+            assert_eq!(Some(res), ArithOp::Plus.result_type(&l, &r));
+        }
+        assert_eq!(
+            Some(DataType::Decimal(max, 3)),
+            ArithOp::Multiply.result_type(&DataType::Decimal(5, 2), &DataType::Decimal(3, 1))
+        );
+        assert_eq!(
+            None,
+            ArithOp::Plus.result_type(&DataType::Double, &DataType::Date)
+        );
+        Ok(())
+    }
 }
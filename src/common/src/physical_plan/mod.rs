@@ -15,12 +15,23 @@ mod physical_op;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum PhysicalOp {
     Scan(PhysicalScanNode),
+    IndexScan(PhysicalIndexScanNode),
     Project(PhysicalProjectNode),
     HashAggregate(PhysicalHashAggregateNode),
     SortedAggregate(PhysicalSortedAggregateNode),
     NestedLoopJoin(PhysicalNestedLoopJoinNode),
     HashJoin(PhysicalHashJoinNode),
+    IndexNestedLoopJoin(PhysicalIndexNestedLoopJoinNode),
     Filter(PhysicalFilterNode),
+    Map(PhysicalMapNode),
+    Sort(PhysicalSortNode),
+    TopN(PhysicalTopNNode),
+    Limit(PhysicalLimitNode),
+    Distinct(PhysicalDistinctNode),
+    Values(PhysicalValuesNode),
+    Insert(PhysicalInsertNode),
+    Update(PhysicalUpdateNode),
+    Delete(PhysicalDeleteNode),
     MaterializedView(MaterializedViewNode),
 }
 
@@ -285,6 +296,18 @@ impl PhysicalPlan {
                 ..
             })) => Some(*hash_table_state_id),
             Some(PhysicalOp::Scan(PhysicalScanNode { container_id, .. })) => Some(*container_id),
+            Some(PhysicalOp::IndexScan(PhysicalIndexScanNode { container_id, .. })) => {
+                Some(*container_id)
+            }
+            Some(PhysicalOp::Insert(PhysicalInsertNode { container_id, .. })) => {
+                Some(*container_id)
+            }
+            Some(PhysicalOp::Update(PhysicalUpdateNode { container_id, .. })) => {
+                Some(*container_id)
+            }
+            Some(PhysicalOp::Delete(PhysicalDeleteNode { container_id, .. })) => {
+                Some(*container_id)
+            }
             _ => None,
         }
     }
@@ -399,7 +422,7 @@ impl fmt::Display for PhysicalPlan {
 mod tests {
     use super::*;
     use crate::logical_plan::{
-        FieldIdentifier, PredExpr, Predicate, ProjectIdentifiers, SimplePredicate,
+        FieldIdentifier, OrderByField, PredExpr, Predicate, ProjectIdentifiers, SimplePredicate,
         SimplePredicateOp,
     };
     use crate::Field;
@@ -562,6 +585,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_json_sort() {
+        let mut physical_plan = PhysicalPlan::new();
+        let scan = physical_plan.add_node(PhysicalOp::Scan(PhysicalScanNode {
+            alias: String::from("Table"),
+            container_id: 0,
+        }));
+        let sort = physical_plan.add_node(PhysicalOp::Sort(PhysicalSortNode {
+            fields: vec![
+                OrderByField {
+                    field: FieldIdentifier::new("Table", "a"),
+                    descending: true,
+                    nulls_first: false,
+                },
+                OrderByField {
+                    field: FieldIdentifier::new("Table", "b"),
+                    descending: false,
+                    nulls_first: false,
+                },
+            ],
+        }));
+        physical_plan.set_root(sort).unwrap();
+        physical_plan.add_edge(sort, scan);
+
+        let json = physical_plan.to_json();
+        let new_physical_plan = PhysicalPlan::from_json(&json.to_string()).unwrap();
+        let root = new_physical_plan.root().unwrap();
+        match new_physical_plan.get_operator(root) {
+            Some(PhysicalOp::Sort(PhysicalSortNode { fields })) => {
+                let columns: Vec<_> = fields.iter().map(|f| f.field.column()).collect();
+                assert_eq!(vec!["a", "b"], columns);
+                assert!(fields[0].descending && !fields[0].nulls_first);
+                assert!(!fields[1].descending);
+            }
+            root => panic!("Incorrect root {:?}", root),
+        }
+        assert_eq!(1, new_physical_plan.edges(root).count());
+    }
+
     #[test]
     fn test_cycle_free() {
         let mut physical_plan = PhysicalPlan::new();
@@ -720,6 +782,7 @@ mod tests {
             right_table: Some(String::from("b")),
             hash_table_state_id: 3,
             hash_table_key: FieldIdentifier::new("a", "acol"),
+            index_id: None,
         }));
         let ab_join2 = physical_plan2.add_node(PhysicalOp::HashJoin(PhysicalHashJoinNode {
             left: FieldIdentifier::new("a", "acol"),
@@ -729,6 +792,7 @@ mod tests {
             right_table: Some(String::from("b")),
             hash_table_state_id: 3,
             hash_table_key: FieldIdentifier::new("a", "acol"),
+            index_id: None,
         }));
 
         let abc_join = physical_plan2.add_node(PhysicalOp::HashJoin(PhysicalHashJoinNode {
@@ -739,6 +803,7 @@ mod tests {
             right_table: Some(String::from("b")),
             hash_table_state_id: 4,
             hash_table_key: FieldIdentifier::new("a", "acol"),
+            index_id: None,
         }));
 
         let aggregate =
@@ -766,6 +831,7 @@ mod tests {
             right_table: Some(String::from("b")),
             hash_table_state_id: 6,
             hash_table_key: FieldIdentifier::new("a", "acol"),
+            index_id: None,
         }));
 
         let project = physical_plan2.add_node(PhysicalOp::Project(PhysicalProjectNode {
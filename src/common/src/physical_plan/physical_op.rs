@@ -1,5 +1,10 @@
-use crate::logical_plan::{FieldIdentifier, Predicate, ProjectIdentifiers, SimplePredicateOp};
+use crate::logical_plan::{
+    ColumnAssignment, FieldIdentifier, OrderByField, Predicate, ProjectIdentifiers,
+    SimplePredicateOp, ValueExpr,
+};
 use crate::prelude::*;
+use crate::Attribute;
+use std::ops::Bound;
 
 /// Physical Scan Operator
 /// Same as Logical
@@ -9,6 +14,20 @@ pub struct PhysicalScanNode {
     pub container_id: ContainerId,
 }
 
+/// Physical Index Scan Operator
+/// Reads the rows of a table whose keys in one of its secondary indexes are between bounds.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalIndexScanNode {
+    pub alias: String,
+    pub container_id: ContainerId,
+    /// Container of the index.
+    pub index_id: ContainerId,
+    /// Lowest key to read, which may hold values for only the first columns of the key.
+    pub lower: Bound<Vec<Field>>,
+    /// Highest key to read, which may hold values for only the first columns of the key.
+    pub upper: Bound<Vec<Field>>,
+}
+
 /// Physical Project Operator
 /// Same as Logical
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -66,6 +85,28 @@ pub struct PhysicalHashJoinNode {
     pub hash_table_state_id: ContainerId,
     /// Vector of the keys to hash by (seems like it may only need to be a single key for now)
     pub hash_table_key: FieldIdentifier,
+    /// Hash index on the right column of the table the right child scans, which each row of
+    /// the left child probes instead of a hash table being built. The left column is then on
+    /// the left side.
+    #[serde(default)]
+    pub index_id: Option<ContainerId>,
+}
+
+/// Joins each row of the outer (left) child with the rows of the inner (right) child's table
+/// found through an index whose key starts with the inner column. The inner child is a scan
+/// of the table, which is not read.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalIndexNestedLoopJoinNode {
+    /// Column of the outer child.
+    pub outer: FieldIdentifier,
+    /// Column of the inner table.
+    pub inner: FieldIdentifier,
+    /// Predicate operator, with the outer column on its left.
+    pub op: SimplePredicateOp,
+    /// Container of the inner table.
+    pub container_id: ContainerId,
+    /// Container of the index.
+    pub index_id: ContainerId,
 }
 
 /// Physical Filter Operator
@@ -79,6 +120,90 @@ pub struct PhysicalFilterNode {
     pub predicate: Predicate,
 }
 
+/// Physical Map Operator
+/// Same as Logical
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalMapNode {
+    /// Columns to append and the expressions computing them.
+    pub columns: Vec<(Attribute, ValueExpr)>,
+}
+
+/// Physical Sort Operator
+/// Same as Logical, sorting in memory until the tuples outgrow the sort budget and then
+/// merging sorted runs spilled to disk
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalSortNode {
+    /// Fields to sort by, the first compared first.
+    pub fields: Vec<OrderByField>,
+}
+
+/// Physical Top-N Operator
+/// Sort that only keeps the first tuples, for a limit above it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalTopNNode {
+    /// Fields to sort by, the first compared first.
+    pub fields: Vec<OrderByField>,
+    /// Number of first tuples to keep.
+    pub limit: usize,
+}
+
+/// Physical Limit Operator
+/// Same as Logical
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalLimitNode {
+    /// Most tuples to produce, or None for all of them.
+    pub limit: Option<usize>,
+    /// Tuples to skip before producing any.
+    pub offset: usize,
+}
+
+/// Physical Distinct Operator
+/// Same as Logical, keeping the distinct tuples in a hash set until they outgrow the distinct
+/// budget and then spilling the rest to disk in hash partitions
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalDistinctNode {}
+
+/// Physical Values Operator
+/// Same as Logical
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalValuesNode {
+    /// Schema of the rows.
+    pub schema: TableSchema,
+    /// Rows to produce.
+    pub rows: Vec<Vec<Field>>,
+}
+
+/// Physical Insert Operator
+/// Same as Logical
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalInsertNode {
+    /// Table to insert into.
+    pub table: String,
+    pub container_id: ContainerId,
+    /// Index in the table's schema of the column each field of the child's tuples goes in.
+    pub columns: Vec<usize>,
+}
+
+/// Physical Update Operator
+/// Same as Logical
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalUpdateNode {
+    /// Table to update.
+    pub table: String,
+    pub container_id: ContainerId,
+    /// New values of the columns set by the update.
+    pub assignments: Vec<ColumnAssignment>,
+}
+
+/// Physical Delete Operator
+/// Same as Logical
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhysicalDeleteNode {
+    /// Table to delete from.
+    pub table: String,
+    pub container_id: ContainerId,
+}
+
 /// Materialized View Node
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MaterializedViewNode {
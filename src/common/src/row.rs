@@ -0,0 +1,228 @@
+//! Binary layout of stored rows.
+//!
+//! A row starts with `ROW_FORMAT` and the version of its table's schema it was written at,
+//! followed by a bitmap with a bit set for each NULL column. Then comes a slot for every
+//! column, as long as the column's `Attribute::get_byte_len`, so the slots take
+//! `TableSchema::byte_size` bytes. Fixed-length values are stored in their slots in little
+//! endian, and text after the slots, with each text slot holding where its text ends.
+//! Any column can be read without decoding the rest of the row.
+//!
+//! Rows written before this layout are CBOR maps, which never start with `ROW_FORMAT`.
+use crate::temporal::Interval;
+use crate::{DataType, Double, Field, TableSchema, Tuple};
+use std::convert::TryInto;
+
+/// First byte of a row in this layout.
+pub const ROW_FORMAT: u8 = 1;
+/// Bytes before the null bitmap: the format and the schema version.
+const HEADER_SIZE: usize = 5;
+
+/// Where the columns of a schema are stored in a row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowLayout {
+    dtypes: Vec<DataType>,
+    /// Offset of each column's slot from the start of the row.
+    offsets: Vec<usize>,
+    /// For each text column, the text column before it, whose text ends where its starts.
+    prev_text: Vec<Option<usize>>,
+    /// Where the text starts.
+    text_start: usize,
+}
+
+impl RowLayout {
+    /// Lays out the columns of a schema.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Schema of the rows.
+    pub fn new(schema: &TableSchema) -> Self {
+        let mut offset = HEADER_SIZE + schema.size().div_ceil(8);
+        let mut offsets = Vec::new();
+        let mut prev_text = Vec::new();
+        let mut last_text = None;
+        for (i, attr) in schema.attributes().enumerate() {
+            offsets.push(offset);
+            offset += attr.get_byte_len();
+            prev_text.push(last_text);
+            if attr.dtype().is_string() {
+                last_text = Some(i);
+            }
+        }
+        RowLayout {
+            dtypes: schema.attributes().map(|a| a.dtype().clone()).collect(),
+            offsets,
+            prev_text,
+            text_start: offset,
+        }
+    }
+
+    /// Returns the number of columns.
+    pub fn size(&self) -> usize {
+        self.dtypes.len()
+    }
+
+    /// Encodes a tuple as a row written at a schema version.
+    ///
+    /// # Arguments
+    ///
+    /// * `tuple` - Tuple to encode, with a field of the matching type for every column.
+    /// * `version` - Version of the schema the tuple is laid out in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a field is not NULL and not of its column's type.
+    pub fn encode(&self, tuple: &Tuple, version: u32) -> Vec<u8> {
+        assert_eq!(
+            self.size(),
+            tuple.size(),
+            "Tuple does not match the row layout"
+        );
+        let mut row = vec![0; self.text_start];
+        row[0] = ROW_FORMAT;
+        row[1..HEADER_SIZE].copy_from_slice(&version.to_le_bytes());
+        let mut text = Vec::new();
+        for (i, (field, dtype)) in tuple.field_vals().zip(self.dtypes.iter()).enumerate() {
+            let offset = self.offsets[i];
+            if field.is_null() {
+                row[HEADER_SIZE + i / 8] |= 1 << (i % 8);
+            }
+            let bytes = match (field, dtype) {
+                (Field::StringField(s), _) if dtype.is_string() => {
+                    text.extend_from_slice(s.as_bytes());
+                    (text.len() as u32).to_le_bytes().to_vec()
+                }
+                (Field::Null, _) if dtype.is_string() => (text.len() as u32).to_le_bytes().to_vec(),
+                (Field::Null, _) => continue,
+                (Field::IntField(x), DataType::BigInt) => (*x as i64).to_le_bytes().to_vec(),
+                (field, _) if dtype.matches(field) => field.to_bytes(),
+                (field, _) => panic!("Field {} is not of type {}", field, dtype),
+            };
+            row[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        }
+        row.extend(text);
+        row
+    }
+
+    /// Decodes a whole row.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Row in this layout.
+    pub fn decode(&self, row: &[u8]) -> Tuple {
+        Tuple::new((0..self.size()).map(|i| self.read_field(row, i)).collect())
+    }
+
+    /// Reads one column of a row.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Row in this layout.
+    /// * `i` - Index of the column.
+    pub fn read_field(&self, row: &[u8], i: usize) -> Field {
+        if row[HEADER_SIZE + i / 8] & (1 << (i % 8)) != 0 {
+            return Field::Null;
+        }
+        let slot = &row[self.offsets[i]..];
+        let read4 = |b: &[u8]| -> [u8; 4] { b[..4].try_into().unwrap() };
+        let read8 = |b: &[u8]| -> [u8; 8] { b[..8].try_into().unwrap() };
+        match &self.dtypes[i] {
+            DataType::Int => Field::IntField(i32::from_le_bytes(read4(slot))),
+            DataType::String | DataType::Varchar(_) => {
+                let end = |j: usize| u32::from_le_bytes(read4(&row[self.offsets[j]..])) as usize;
+                let start = self.prev_text[i].map_or(0, end) + self.text_start;
+                let end = end(i) + self.text_start;
+                Field::StringField(String::from_utf8_lossy(&row[start..end]).into_owned())
+            }
+            DataType::BigInt => Field::BigIntField(i64::from_le_bytes(read8(slot))),
+            DataType::Double => Field::DoubleField(Double(f64::from_le_bytes(read8(slot)))),
+            DataType::Boolean => Field::BoolField(slot[0] != 0),
+            DataType::Decimal(_, scale) => {
+                Field::DecimalField(i64::from_le_bytes(read8(slot)), *scale)
+            }
+            DataType::Date => Field::DateField(i32::from_le_bytes(read4(slot))),
+            DataType::Timestamp => Field::TimestampField(i64::from_le_bytes(read8(slot))),
+            DataType::Interval => Field::IntervalField(Interval::new(
+                i32::from_le_bytes(read4(slot)),
+                i32::from_le_bytes(read4(&slot[4..])),
+                i64::from_le_bytes(read8(&slot[8..])),
+            )),
+        }
+    }
+}
+
+/// Returns the schema version a row was written at, or None if it is not in this layout.
+///
+/// # Arguments
+///
+/// * `row` - Stored row.
+pub fn version(row: &[u8]) -> Option<u32> {
+    match row.first() {
+        Some(&ROW_FORMAT) if row.len() >= HEADER_SIZE => {
+            Some(u32::from_le_bytes(row[1..HEADER_SIZE].try_into().unwrap()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Attribute;
+
+    #[test]
+    fn test_round_trip() {
+        let schema = TableSchema::new(vec![
+            Attribute::new("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::String),
+            Attribute::new("c".to_string(), DataType::Decimal(5, 2)),
+            Attribute::new("d".to_string(), DataType::Varchar(10)),
+            Attribute::new("e".to_string(), DataType::Interval),
+            Attribute::new("f".to_string(), DataType::Boolean),
+            Attribute::new("g".to_string(), DataType::BigInt),
+            Attribute::new("h".to_string(), DataType::String),
+            Attribute::new("i".to_string(), DataType::Double),
+        ]);
+        let layout = RowLayout::new(&schema);
+        let tuple = Tuple::new(vec![
+            Field::IntField(-7),
+            Field::StringField("héllo".to_string()),
+            Field::DecimalField(-150, 2),
+            Field::Null,
+            Field::IntervalField(Interval::new(1, -2, 3)),
+            Field::BoolField(true),
+            Field::Null,
+            Field::StringField("world".to_string()),
+            Field::DoubleField(Double(0.5)),
+        ]);
+        let row = layout.encode(&tuple, 3);
+        assert_eq!(Some(3), version(&row));
+        assert_eq!(tuple, layout.decode(&row));
+        assert_eq!(
+            Field::StringField("world".to_string()),
+            layout.read_field(&row, 7)
+        );
+        assert_eq!(Field::Null, layout.read_field(&row, 3));
+        // Header, a bitmap of 2 bytes, the slots and 11 bytes of text
+        assert_eq!(5 + 2 + schema.byte_size() + 11, row.len());
+
+        // Rows written before this layout are CBOR
+        assert_eq!(None, version(&tuple.get_bytes()));
+        assert_eq!(None, version(&tuple.get_versioned_bytes(3)));
+    }
+
+    #[test]
+    fn test_widens_ints() {
+        let schema = TableSchema::from_vecs(vec!["a"], vec![DataType::BigInt]);
+        let layout = RowLayout::new(&schema);
+        let row = layout.encode(&Tuple::new(vec![Field::IntField(-1)]), 0);
+        assert_eq!(Field::BigIntField(-1), layout.read_field(&row, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_wrong_type() {
+        let schema = TableSchema::from_vecs(vec!["a"], vec![DataType::Int]);
+        let row = Tuple::new(vec![Field::StringField("a".to_string())]);
+        RowLayout::new(&schema).encode(&row, 0);
+    }
+}
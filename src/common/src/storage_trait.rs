@@ -1,18 +1,28 @@
-use crate::ids::Permissions;
+use crate::foreign_key::ForeignKeys;
+use crate::ids::{IsolationLevel, Permissions};
 use crate::prelude::*;
 use crate::ContainerConfig;
 use crate::CrustyError;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
 
 // TODO: What does ContainerId add as a type? If nothing, then make it u16 and make it easier for clients of
 // TODO: storage managers to use them
 
+/// An iterator over the records of a container that can tell where each record is stored,
+/// so that callers can update or delete the records they scan.
+pub trait ValueIdIterator: Iterator<Item = Vec<u8>> {
+    /// The id of the value last returned by `next`, if any.
+    fn last_value_id(&self) -> Option<ValueId>;
+}
+
 /// The trait for a storage manager in crustyDB.
 /// A StorageManager should impl Drop also so a storage manager can clean up on shut down and
 /// for testing storage managers to remove any state.
 pub trait StorageTrait {
     /// The associated type of the iterator that will need to be written and defined for the storage manager
     /// This iterator will be used to scan records of a container
-    type ValIterator: Iterator<Item = Vec<u8>>;
+    type ValIterator: ValueIdIterator;
 
     /// Create a new storage manager that will use storage_path as the location to persist data
     /// (if the storage manager persists records on disk)
@@ -99,6 +109,110 @@ pub trait StorageTrait {
     /// Notify the storage manager that the transaction is finished so that any held resources can be released.
     fn transaction_finished(&self, tid: TransactionId);
 
+    /// Abort the transaction, undoing the changes it made, and release its resources
+    /// as `transaction_finished` does. Storage managers that cannot undo changes
+    /// only release resources.
+    fn abort_transaction(&self, tid: TransactionId) {
+        self.transaction_finished(tid)
+    }
+
+    /// Check whether the transaction can continue. Returns `TransactionAbortedError` if the
+    /// storage manager aborted it (e.g. it could not get a lock), so the caller can stop.
+    /// Operations without a `Result`, such as `get_iterator`, only report an abort here.
+    fn check_transaction(&self, _tid: TransactionId) -> Result<(), CrustyError> {
+        Ok(())
+    }
+
+    /// Called before each statement tid runs, with the isolation level it runs at, so a
+    /// storage manager with snapshots can take a new one when the level calls for it.
+    fn begin_statement(&self, _tid: TransactionId, _isolation: IsolationLevel) {}
+
+    /// Reclaim space held by record versions no transaction can see any more.
+    /// Returns the number of versions removed.
+    fn vacuum(&self) -> Result<usize, CrustyError> {
+        Ok(0)
+    }
+
+    /// Abort transactions to break any deadlocks between lock waits.
+    /// Returns the transactions that were aborted.
+    fn detect_deadlocks(&self) -> Vec<TransactionId> {
+        Vec::new()
+    }
+
+    /// Open the B+ tree of a secondary index of a table, so that writes to the table keep it
+    /// up to date. The tree is created, and built from the rows of the table, if its
+    /// container does not exist or is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_id` - Container of the tree, as given by the table's `SecondaryIndex`.
+    /// * `table_id` - Container of the table.
+    /// * `table` - The table, from which the columns of the key are read.
+    fn open_index(
+        &self,
+        _index_id: ContainerId,
+        _table_id: ContainerId,
+        _table: Arc<RwLock<Table>>,
+    ) -> Result<(), CrustyError> {
+        Err(CrustyError::CrustyError(
+            "Indexes are not supported by this storage manager".to_string(),
+        ))
+    }
+
+    /// Close the B+ tree of a secondary index and remove its container.
+    fn drop_index(&self, _index_id: ContainerId) -> Result<(), CrustyError> {
+        Err(CrustyError::CrustyError(
+            "Indexes are not supported by this storage manager".to_string(),
+        ))
+    }
+
+    /// Get the ids of the rows visible to the transaction whose keys in a secondary index are
+    /// between the bounds, in the order of their keys. The rows are read with `get_value`. A
+    /// bound may hold fewer values than the key has columns, in which case only the first
+    /// columns of the key are compared with it. Keys holding NULL sort after all others. A hash
+    /// index only finds the keys equal to both bounds, which must hold every column, and
+    /// returns their rows in the order they are stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_id` - Container of the index.
+    /// * `lower` - Lowest key to return.
+    /// * `upper` - Highest key to return.
+    /// * `tid` - Transaction reading the rows.
+    fn index_scan(
+        &self,
+        _index_id: ContainerId,
+        _lower: Bound<&[Field]>,
+        _upper: Bound<&[Field]>,
+        _tid: TransactionId,
+    ) -> Result<Vec<ValueId>, CrustyError> {
+        Err(CrustyError::CrustyError(
+            "Indexes are not supported by this storage manager".to_string(),
+        ))
+    }
+
+    /// Get the ids of the rows whose keys in a secondary index equal key and that another row
+    /// with the key would conflict with: the rows written, and not deleted, by committed
+    /// transactions or by tid itself. Unlike `index_scan`, rows the transaction's snapshot
+    /// does not see count. A row another transaction is still writing or deleting is locked
+    /// first, waiting for that transaction to finish.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_id` - Container of the index.
+    /// * `key` - Values of every column of the key.
+    /// * `tid` - Transaction looking for the rows.
+    fn find_key_conflicts(
+        &self,
+        _index_id: ContainerId,
+        _key: &[Field],
+        _tid: TransactionId,
+    ) -> Result<Vec<ValueId>, CrustyError> {
+        Err(CrustyError::CrustyError(
+            "Indexes are not supported by this storage manager".to_string(),
+        ))
+    }
+
     /// Reset all state associated the storage manager.
     /// Deletes all tables and stored items
     fn reset(&self) -> Result<(), CrustyError>;
@@ -122,6 +236,7 @@ pub trait StorageTrait {
     fn import_csv(
         &self,
         table: &Table,
+        foreign_keys: &ForeignKeys,
         path: String,
         tid: TransactionId,
         container_id: ContainerId,
@@ -1,4 +1,33 @@
-use crate::TableSchema;
+use crate::foreign_key::ForeignKey;
+use crate::ids::{ContainerId, Permissions, TransactionId, ValueId};
+use crate::index::{SecondaryIndex, MAX_KEY_SIZE};
+use crate::row::{self, RowLayout};
+use crate::storage_trait::{StorageTrait, ValueIdIterator};
+use crate::{Attribute, Constraint, CrustyError, Field, TableSchema, Tuple};
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Bound;
+use std::sync::OnceLock;
+
+/// A change to the layout of a table's rows.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RowMigration {
+    /// A column was appended. Rows written before hold the value in it.
+    AddColumn(Field),
+    /// The column at the index was removed.
+    DropColumn(usize),
+}
+
+impl RowMigration {
+    /// Changes the layout of a tuple from the version before the migration to the one after.
+    fn apply(&self, tuple: &mut Tuple) {
+        match self {
+            RowMigration::AddColumn(default) => tuple.field_vals.push(default.clone()),
+            RowMigration::DropColumn(i) => {
+                tuple.field_vals.remove(*i);
+            }
+        }
+    }
+}
 
 /// Table implementation.
 #[derive(Serialize, Deserialize, Clone)]
@@ -7,6 +36,23 @@ pub struct Table {
     pub name: String,
     /// Table schema.
     pub schema: TableSchema,
+    /// Changes made to the layout of the rows, oldest first. Rows are written at version
+    /// `migrations.len()` and upgraded when read, so altering a table rewrites no rows.
+    #[serde(default)]
+    migrations: Vec<RowMigration>,
+    /// Schema of each earlier version, to read the rows written at it. Versions from before
+    /// rows were stored in the binary layout are missing, as their rows are all CBOR.
+    #[serde(default)]
+    old_schemas: BTreeMap<u32, TableSchema>,
+    /// Foreign keys of the table.
+    #[serde(default)]
+    foreign_keys: Vec<ForeignKey>,
+    /// B+ tree indexes of the table, kept up to date by the storage manager.
+    #[serde(default)]
+    secondary_indexes: Vec<SecondaryIndex>,
+    /// Layout of the rows written at each version with a schema, built when first used.
+    #[serde(skip)]
+    layouts: OnceLock<BTreeMap<u32, RowLayout>>,
 }
 
 impl Table {
@@ -17,6 +63,761 @@ impl Table {
     /// * `name` - Name of table.
     /// * `file` - HeapFile of the table.
     pub fn new(name: String, schema: TableSchema) -> Self {
-        Table { name, schema }
+        Table {
+            name,
+            schema,
+            migrations: Vec::new(),
+            old_schemas: BTreeMap::new(),
+            foreign_keys: Vec::new(),
+            secondary_indexes: Vec::new(),
+            layouts: OnceLock::new(),
+        }
+    }
+
+    /// Version of the schema that rows are written at.
+    pub fn schema_version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+
+    /// Appends a column holding the default in every existing row.
+    ///
+    /// # Arguments
+    ///
+    /// * `attr` - Column to add.
+    /// * `default` - Value of the column in rows written before it was added.
+    pub fn add_column(&mut self, attr: Attribute, default: Field) -> Result<(), CrustyError> {
+        if self.schema.contains(attr.name()) {
+            return Err(CrustyError::CrustyError(format!(
+                "Column {} already exists",
+                attr.name()
+            )));
+        }
+        if attr.constraint == Constraint::PrimaryKey {
+            return Err(CrustyError::CrustyError(format!(
+                "Cannot add primary key column {}",
+                attr.name()
+            )));
+        }
+        check_length(&attr, &default)?;
+        self.push_migration(RowMigration::AddColumn(default));
+        self.schema.add_attribute(attr);
+        Ok(())
+    }
+
+    /// Removes a column from the table, along with the index enforcing that it is unique,
+    /// which is returned for the storage manager to drop.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the column to remove.
+    pub fn drop_column(&mut self, name: &str) -> Result<Vec<SecondaryIndex>, CrustyError> {
+        let i = self.column_index(name)?;
+        if self.schema.get_attribute(i).unwrap().constraint == Constraint::PrimaryKey {
+            return Err(CrustyError::CrustyError(format!(
+                "Cannot drop primary key column {}",
+                name
+            )));
+        }
+        if let Some(fk) = self.foreign_keys.iter().find(|fk| fk.columns.contains(&i)) {
+            return Err(CrustyError::CrustyError(format!(
+                "Cannot drop column {} used by foreign key {}",
+                name, fk.name
+            )));
+        }
+        if let Some(index) = self
+            .secondary_indexes
+            .iter()
+            .find(|index| !index.constraint && index.columns.contains(&i))
+        {
+            return Err(CrustyError::CrustyError(format!(
+                "Cannot drop column {} used by index {}",
+                name, index.name
+            )));
+        }
+        let (dropped, kept) = std::mem::take(&mut self.secondary_indexes)
+            .into_iter()
+            .partition(|index| index.columns.contains(&i));
+        self.secondary_indexes = kept;
+        self.push_migration(RowMigration::DropColumn(i));
+        self.schema.remove_attribute(i);
+        for fk in self.foreign_keys.iter_mut() {
+            fk.columns.iter_mut().filter(|c| **c > i).for_each(|c| *c -= 1);
+        }
+        for index in self.secondary_indexes.iter_mut() {
+            for c in index.columns.iter_mut().filter(|c| **c > i) {
+                *c -= 1;
+            }
+        }
+        Ok(dropped)
+    }
+
+    /// Records a change to the layout of the rows, before the schema is changed.
+    fn push_migration(&mut self, migration: RowMigration) {
+        self.old_schemas
+            .insert(self.schema_version(), self.schema.clone());
+        self.migrations.push(migration);
+        self.layouts = OnceLock::new();
+    }
+
+    /// Renames a column. Rows are laid out the same, so this does not change the version.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the column to rename.
+    /// * `new_name` - New name of the column.
+    pub fn rename_column(&mut self, name: &str, new_name: &str) -> Result<(), CrustyError> {
+        let i = self.column_index(name)?;
+        if self.schema.contains(new_name) {
+            return Err(CrustyError::CrustyError(format!(
+                "Column {} already exists",
+                new_name
+            )));
+        }
+        self.schema.rename_attribute(i, new_name.to_string());
+        Ok(())
+    }
+
+    /// Foreign keys of the table.
+    pub fn foreign_keys(&self) -> &[ForeignKey] {
+        &self.foreign_keys
+    }
+
+    /// Adds a foreign key, marking its columns as referring to the parent table.
+    ///
+    /// # Arguments
+    ///
+    /// * `fk` - Foreign key to add.
+    pub fn add_foreign_key(&mut self, fk: ForeignKey) {
+        for i in &fk.columns {
+            let attr = &mut self.schema.attributes[*i];
+            attr.constraint = match attr.constraint {
+                Constraint::None => Constraint::ForeignKey(fk.parent),
+                Constraint::NotNull => Constraint::NotNullFKey(fk.parent),
+                ref c => c.clone(),
+            };
+        }
+        self.foreign_keys.push(fk);
+    }
+
+    /// Removes the foreign keys referring to a table, returning their names.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent` - Container of the table referred to.
+    pub fn remove_foreign_keys_to(&mut self, parent: ContainerId) -> Vec<String> {
+        let (removed, kept) = std::mem::take(&mut self.foreign_keys)
+            .into_iter()
+            .partition(|fk: &ForeignKey| fk.parent == parent);
+        self.foreign_keys = kept;
+        for fk in &removed {
+            for i in &fk.columns {
+                let attr = &mut self.schema.attributes[*i];
+                attr.constraint = match attr.constraint {
+                    Constraint::ForeignKey(_) => Constraint::None,
+                    Constraint::NotNullFKey(_) => Constraint::NotNull,
+                    ref c => c.clone(),
+                };
+            }
+        }
+        removed.into_iter().map(|fk| fk.name).collect()
+    }
+
+    /// Updates the foreign keys referring to a table after a column of it was dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent` - Container of the table referred to.
+    /// * `i` - Index the dropped column had.
+    pub fn parent_column_dropped(&mut self, parent: ContainerId, i: usize) {
+        for fk in self.foreign_keys.iter_mut().filter(|fk| fk.parent == parent) {
+            fk.parent_columns
+                .iter_mut()
+                .filter(|c| **c > i)
+                .for_each(|c| *c -= 1);
+        }
+    }
+
+    /// Secondary indexes of the table.
+    pub fn secondary_indexes(&self) -> &[SecondaryIndex] {
+        &self.secondary_indexes
+    }
+
+    /// Finds the secondary index stored in a container.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_id` - Container of the index.
+    pub fn secondary_index(&self, container_id: ContainerId) -> Option<&SecondaryIndex> {
+        self.secondary_indexes
+            .iter()
+            .find(|index| index.container_id == container_id)
+    }
+
+    /// Adds a secondary index. The storage manager has to open it before it is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index to add.
+    pub fn add_secondary_index(&mut self, index: SecondaryIndex) {
+        self.secondary_indexes.push(index);
+    }
+
+    /// Removes the secondary index stored in a container, returning it.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_id` - Container of the index.
+    pub fn remove_secondary_index(&mut self, container_id: ContainerId) -> Option<SecondaryIndex> {
+        let i = self
+            .secondary_indexes
+            .iter()
+            .position(|index| index.container_id == container_id)?;
+        Some(self.secondary_indexes.remove(i))
+    }
+
+    fn column_index(&self, name: &str) -> Result<usize, CrustyError> {
+        self.schema
+            .get_field_index(name)
+            .copied()
+            .ok_or_else(|| CrustyError::CrustyError(format!("Column {} does not exist", name)))
+    }
+
+    /// Columns of each key whose values no two rows may share: the primary key, then each
+    /// unique column.
+    pub fn unique_keys(&self) -> Vec<Vec<usize>> {
+        let mut pk = Vec::new();
+        let mut keys = Vec::new();
+        for (i, attr) in self.schema.attributes().enumerate() {
+            match attr.constraint {
+                Constraint::PrimaryKey => pk.push(i),
+                Constraint::Unique | Constraint::UniqueNotNull => keys.push(vec![i]),
+                _ => {}
+            }
+        }
+        if !pk.is_empty() {
+            keys.insert(0, pk);
+        }
+        keys
+    }
+
+    /// Columns of each key whose values no two rows may share, including those of the unique
+    /// indexes made by CREATE INDEX.
+    fn unique_index_keys(&self) -> Vec<Vec<usize>> {
+        let mut keys = self.unique_keys();
+        for index in self.secondary_indexes.iter().filter(|i| i.unique) {
+            if !keys.contains(&index.columns) {
+                keys.push(index.columns.clone());
+            }
+        }
+        keys
+    }
+
+    /// Finds the unique index on exactly the columns of a key.
+    fn unique_index(&self, columns: &[usize]) -> Option<&SecondaryIndex> {
+        self.secondary_indexes
+            .iter()
+            .find(|i| i.unique && i.columns == columns)
+    }
+
+    /// Checks that rows can be written to the table without two rows sharing a unique key,
+    /// without NULL in columns that do not allow it, without text longer than its column
+    /// allows and without keys too long for the secondary indexes. Keys holding NULL are
+    /// never shared.
+    ///
+    /// Keys are looked up in their unique index, where rows the transaction cannot see yet
+    /// count too. A key without one, as in a table of a storage manager without indexes, is
+    /// only checked against the rows the transaction can see.
+    ///
+    /// # Arguments
+    ///
+    /// * `sm` - Storage manager holding the table.
+    /// * `container_id` - Container of the table.
+    /// * `tid` - Transaction writing the rows.
+    /// * `rows` - Rows to write, each with the id of the row it replaces, if any.
+    pub fn check_rows<S: StorageTrait>(
+        &self,
+        sm: &S,
+        container_id: ContainerId,
+        tid: TransactionId,
+        rows: &[(Option<ValueId>, &Tuple)],
+    ) -> Result<(), CrustyError> {
+        for (_, tuple) in rows {
+            for (field, attr) in tuple.field_vals().zip(self.schema.attributes()) {
+                if field.is_null() && attr.constraint.is_not_null() {
+                    return Err(CrustyError::ValidationError(format!(
+                        "NULL value in column {} of table {} violates not-null constraint",
+                        attr.name(),
+                        self.name
+                    )));
+                }
+                check_length(attr, field)?;
+            }
+            for index in &self.secondary_indexes {
+                let key: Vec<Field> = index
+                    .columns
+                    .iter()
+                    .map(|i| tuple.field_vals[*i].clone())
+                    .collect();
+                if index.encode_key(&self.schema, &key).len() > MAX_KEY_SIZE {
+                    return Err(CrustyError::ValidationError(format!(
+                        "Key of index {} is longer than {} bytes",
+                        index.name, MAX_KEY_SIZE
+                    )));
+                }
+            }
+        }
+        let replaced: HashSet<ValueId> = rows.iter().filter_map(|(id, _)| *id).collect();
+        for columns in self.unique_index_keys() {
+            let mut keys = HashSet::new();
+            for (_, tuple) in rows {
+                let key = key_of(tuple, &columns);
+                if key.iter().any(Field::is_null) {
+                    continue;
+                }
+                let taken = !keys.insert(key.clone())
+                    || self
+                        .key_rows(sm, container_id, tid, &columns, &key)?
+                        .iter()
+                        .any(|id| !replaced.contains(id));
+                if taken {
+                    return Err(CrustyError::ValidationError(self.duplicate_msg(&columns, &key)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks again, once rows are written, that no other row shares their unique keys. Two
+    /// transactions writing the same key at once can both pass `check_rows`, but the later
+    /// of them to write finds the row of the other in the unique index here, and waits for
+    /// the other to finish to know if it was committed.
+    ///
+    /// # Arguments
+    ///
+    /// * `sm` - Storage manager holding the table.
+    /// * `tid` - Transaction that wrote the rows.
+    /// * `rows` - Rows written, with their ids.
+    pub fn check_written<S: StorageTrait>(
+        &self,
+        sm: &S,
+        tid: TransactionId,
+        rows: &[(ValueId, &Tuple)],
+    ) -> Result<(), CrustyError> {
+        let written: HashSet<ValueId> = rows.iter().map(|(id, _)| *id).collect();
+        for columns in self.unique_index_keys() {
+            let index = match self.unique_index(&columns) {
+                Some(index) => index,
+                None => continue,
+            };
+            for (_, tuple) in rows {
+                let key = key_of(tuple, &columns);
+                if key.iter().any(Field::is_null) {
+                    continue;
+                }
+                let conflicts = sm.find_key_conflicts(index.container_id, &key, tid)?;
+                if conflicts.iter().any(|id| !written.contains(id)) {
+                    return Err(CrustyError::ValidationError(self.duplicate_msg(&columns, &key)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the stored rows holding a unique key, through its unique index if there is one.
+    fn key_rows<S: StorageTrait>(
+        &self,
+        sm: &S,
+        container_id: ContainerId,
+        tid: TransactionId,
+        columns: &[usize],
+        key: &[Field],
+    ) -> Result<Vec<ValueId>, CrustyError> {
+        match self.unique_index(columns) {
+            Some(index) => sm.find_key_conflicts(index.container_id, key, tid),
+            None => Ok(self
+                .scan_rows(sm, container_id, tid, columns, key)?
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect()),
+        }
+    }
+
+    /// Finds the rows the transaction can see whose columns hold the key, through an index on
+    /// exactly those columns if the table has one.
+    ///
+    /// # Arguments
+    ///
+    /// * `sm` - Storage manager holding the table.
+    /// * `container_id` - Container of the table.
+    /// * `tid` - Transaction looking for the rows.
+    /// * `columns` - Columns of the key.
+    /// * `key` - Values of the columns.
+    pub fn find_rows<S: StorageTrait>(
+        &self,
+        sm: &S,
+        container_id: ContainerId,
+        tid: TransactionId,
+        columns: &[usize],
+        key: &[Field],
+    ) -> Result<Vec<(ValueId, Tuple)>, CrustyError> {
+        let index = match self.secondary_indexes.iter().find(|i| i.columns == columns) {
+            Some(index) => index,
+            None => return self.scan_rows(sm, container_id, tid, columns, key),
+        };
+        let bound = Bound::Included(key);
+        let mut rows = Vec::new();
+        for id in sm.index_scan(index.container_id, bound, bound, tid)? {
+            let bytes = sm.get_value(id, tid, Permissions::ReadOnly)?;
+            rows.push((id, self.decode(&bytes)));
+        }
+        Ok(rows)
+    }
+
+    /// Finds the rows the transaction can see whose columns hold the key by reading them all.
+    fn scan_rows<S: StorageTrait>(
+        &self,
+        sm: &S,
+        container_id: ContainerId,
+        tid: TransactionId,
+        columns: &[usize],
+        key: &[Field],
+    ) -> Result<Vec<(ValueId, Tuple)>, CrustyError> {
+        let mut rows = Vec::new();
+        let mut iter = sm.get_iterator(container_id, tid, Permissions::ReadOnly);
+        while let Some(bytes) = iter.next() {
+            if self.decode_fields(&bytes, columns) == key {
+                rows.push((iter.last_value_id().unwrap(), self.decode(&bytes)));
+            }
+        }
+        Ok(rows)
+    }
+
+    fn duplicate_msg(&self, columns: &[usize], key: &[Field]) -> String {
+        let names: Vec<&str> = columns
+            .iter()
+            .map(|i| self.schema.get_attribute(*i).unwrap().name())
+            .collect();
+        let values: Vec<String> = key.iter().map(|f| f.to_string()).collect();
+        let unique_index = self
+            .secondary_indexes
+            .iter()
+            .find(|i| i.unique && !i.constraint && i.columns == columns);
+        let kind = match unique_index {
+            Some(i) if !self.unique_keys().iter().any(|k| k == columns) => {
+                format!("unique index {}", i.name)
+            }
+            _ => match self.schema.get_attribute(columns[0]).unwrap().constraint {
+                Constraint::PrimaryKey => "primary key".to_string(),
+                _ => "unique column".to_string(),
+            },
+        };
+        format!(
+            "Duplicate value ({}) for {} ({}) of table {}",
+            values.join(", "),
+            kind,
+            names.join(", "),
+            self.name
+        )
+    }
+
+    /// Describes the values of columns, as in `(a, b)=(1, 2)`.
+    pub fn describe_key(&self, columns: &[usize], key: &[Field]) -> String {
+        let names: Vec<&str> = columns
+            .iter()
+            .map(|i| self.schema.get_attribute(*i).unwrap().name())
+            .collect();
+        let values: Vec<String> = key.iter().map(|f| f.to_string()).collect();
+        format!("({})=({})", names.join(", "), values.join(", "))
+    }
+
+    /// Reads a row of a CSV file into a tuple of the table. Empty cells are NULL.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - Values of the row, in the order of the columns.
+    pub fn tuple_from_csv(&self, record: &csv::StringRecord) -> Result<Tuple, CrustyError> {
+        if record.len() != self.schema.size() {
+            return Err(CrustyError::ValidationError(format!(
+                "Expected {} values but got {} in CSV row {:?}",
+                self.schema.size(),
+                record.len(),
+                record
+            )));
+        }
+        let mut fields = Vec::new();
+        for (value, attr) in record.iter().zip(self.schema.attributes()) {
+            let field = if value.is_empty() {
+                Field::Null
+            } else {
+                Field::parse(value, attr.dtype()).ok_or_else(|| {
+                    CrustyError::ValidationError(format!(
+                        "Value {:?} does not match type {} of column {}",
+                        value,
+                        attr.dtype(),
+                        attr.name()
+                    ))
+                })?
+            };
+            fields.push(field);
+        }
+        Ok(Tuple::new(fields))
+    }
+
+    /// Gets the bytes to store a tuple laid out in the current schema.
+    pub fn encode(&self, tuple: &Tuple) -> Vec<u8> {
+        let version = self.schema_version();
+        self.layout(version).encode(tuple, version)
+    }
+
+    /// Reads a stored tuple, upgrading it to the current schema.
+    ///
+    /// Rows written before the binary layout are CBOR, and are read as such until they are
+    /// next written.
+    pub fn decode(&self, bytes: &[u8]) -> Tuple {
+        let (version, mut tuple) = match row::version(bytes) {
+            Some(version) => (version, self.layout(version).decode(bytes)),
+            None => Tuple::from_versioned_bytes(bytes),
+        };
+        for migration in &self.migrations[version as usize..] {
+            migration.apply(&mut tuple);
+        }
+        tuple
+    }
+
+    /// Reads columns of a stored tuple in the current schema, without decoding the other
+    /// columns if the row was written at the current version.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Stored tuple.
+    /// * `columns` - Columns to read.
+    pub fn decode_fields(&self, bytes: &[u8], columns: &[usize]) -> Vec<Field> {
+        let version = self.schema_version();
+        if row::version(bytes) == Some(version) {
+            let layout = self.layout(version);
+            return columns
+                .iter()
+                .map(|i| layout.read_field(bytes, *i))
+                .collect();
+        }
+        let tuple = self.decode(bytes);
+        columns
+            .iter()
+            .map(|i| tuple.field_vals[*i].clone())
+            .collect()
+    }
+
+    /// Reads a stored tuple in the current schema with only some of its columns, leaving the
+    /// others NULL.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Stored tuple.
+    /// * `columns` - Columns to read.
+    pub fn decode_columns(&self, bytes: &[u8], columns: &[usize]) -> Tuple {
+        let mut fields = vec![Field::Null; self.schema.size()];
+        for (i, field) in columns.iter().zip(self.decode_fields(bytes, columns)) {
+            fields[*i] = field;
+        }
+        Tuple::new(fields)
+    }
+
+    /// Layout of the rows written at a version of the schema.
+    fn layout(&self, version: u32) -> &RowLayout {
+        let layouts = self.layouts.get_or_init(|| {
+            let mut layouts: BTreeMap<_, _> = self
+                .old_schemas
+                .iter()
+                .map(|(version, schema)| (*version, RowLayout::new(schema)))
+                .collect();
+            layouts.insert(self.schema_version(), RowLayout::new(&self.schema));
+            layouts
+        });
+        layouts
+            .get(&version)
+            .unwrap_or_else(|| &layouts[&self.schema_version()])
+    }
+}
+
+/// Values of the columns of a key in a tuple.
+fn key_of(tuple: &Tuple, columns: &[usize]) -> Vec<Field> {
+    columns.iter().map(|i| tuple.field_vals[*i].clone()).collect()
+}
+
+/// Checks that a field fits the length of its column.
+///
+/// # Arguments
+///
+/// * `attr` - Column the field is written to.
+/// * `field` - Field to check.
+fn check_length(attr: &Attribute, field: &Field) -> Result<(), CrustyError> {
+    if attr.dtype().fits(field) {
+        return Ok(());
+    }
+    Err(CrustyError::ValidationError(format!(
+        "Value too long for type {} of column {}",
+        attr.dtype(),
+        attr.name()
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::IndexMethod;
+    use crate::foreign_key::ReferentialAction;
+    use crate::testutil::*;
+    use crate::{DataType, Double};
+
+    #[test]
+    fn test_lazy_upgrade() -> Result<(), CrustyError> {
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::Int),
+        ]);
+        let mut table = Table::new("t".to_string(), schema);
+        let v0 = table.encode(&int_vec_to_tuple(vec![1, 2]));
+        // Rows written before the binary layout
+        let cbor = int_vec_to_tuple(vec![9, 10]).get_bytes();
+
+        let c = Attribute::new("c".to_string(), DataType::Int);
+        table.add_column(c, Field::IntField(7))?;
+        let v1 = table.encode(&int_vec_to_tuple(vec![3, 4, 5]));
+        table.drop_column("b")?;
+        table.rename_column("c", "d")?;
+        let v2 = table.encode(&int_vec_to_tuple(vec![6, 8]));
+
+        assert_eq!(2, table.schema_version());
+        assert_eq!(Some(&1), table.schema.get_field_index("d"));
+        assert_eq!(int_vec_to_tuple(vec![1, 7]), table.decode(&v0));
+        assert_eq!(int_vec_to_tuple(vec![3, 5]), table.decode(&v1));
+        assert_eq!(int_vec_to_tuple(vec![6, 8]), table.decode(&v2));
+        assert_eq!(int_vec_to_tuple(vec![9, 7]), table.decode(&cbor));
+        assert_eq!(vec![Field::IntField(7)], table.decode_fields(&v0, &[1]));
+        assert_eq!(vec![Field::IntField(8)], table.decode_fields(&v2, &[1]));
+        let only_d = Tuple::new(vec![Field::Null, Field::IntField(8)]);
+        assert_eq!(only_d, table.decode_columns(&v2, &[1]));
+        assert_eq!(
+            int_vec_to_tuple(vec![6, 8]),
+            table.clone().decode_columns(&v2, &[0, 1])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_changes() {
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::Int),
+        ]);
+        let mut table = Table::new("t".to_string(), schema);
+        let b = Attribute::new("b".to_string(), DataType::Int);
+        assert!(table.add_column(b, Field::IntField(0)).is_err());
+        assert!(table.drop_column("a").is_err());
+        assert!(table.drop_column("c").is_err());
+        assert!(table.rename_column("b", "a").is_err());
+        let c = Attribute::new("c".to_string(), DataType::Varchar(2));
+        let default = Field::StringField("abc".to_string());
+        assert!(table.add_column(c, default).is_err());
+        assert_eq!(0, table.schema_version());
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::Int),
+            Attribute::new_with_constraint("c".to_string(), DataType::Int, Constraint::NotNull),
+        ]);
+        let mut table = Table::new("t".to_string(), schema);
+        table.add_foreign_key(ForeignKey {
+            name: "t_c_fkey".to_string(),
+            columns: vec![2],
+            parent: 7,
+            parent_columns: vec![1],
+            on_delete: ReferentialAction::Restrict,
+        });
+        let constraint = |t: &Table, i| t.schema.get_attribute(i).unwrap().constraint.clone();
+        assert_eq!(Constraint::NotNullFKey(7), constraint(&table, 2));
+        assert!(table.drop_column("c").is_err());
+        // Dropping a column before it moves the key's column
+        table.drop_column("b").unwrap();
+        assert_eq!(vec![1], table.foreign_keys()[0].columns);
+        table.parent_column_dropped(7, 0);
+        assert_eq!(vec![0], table.foreign_keys()[0].parent_columns);
+        assert_eq!(vec!["t_c_fkey".to_string()], table.remove_foreign_keys_to(7));
+        assert!(table.foreign_keys().is_empty());
+        assert_eq!(Constraint::NotNull, constraint(&table, 1));
+    }
+
+    #[test]
+    fn test_secondary_indexes() {
+        let schema = TableSchema::from_vecs(
+            vec!["a", "b", "c"],
+            vec![DataType::Int, DataType::Int, DataType::String],
+        );
+        let mut table = Table::new("t".to_string(), schema);
+        table.add_secondary_index(SecondaryIndex {
+            name: "t_c".to_string(),
+            container_id: 4,
+            columns: vec![2],
+            unique: false,
+            method: IndexMethod::BTree,
+            constraint: false,
+        });
+        assert!(table.drop_column("c").is_err());
+        table.drop_column("a").unwrap();
+        assert_eq!(vec![1], table.secondary_index(4).unwrap().columns);
+        assert!(table.remove_secondary_index(4).is_some());
+        assert!(table.secondary_indexes().is_empty());
+        assert!(table.remove_secondary_index(4).is_none());
+
+        // A unique index is checked like a unique column
+        table.add_secondary_index(SecondaryIndex {
+            name: "t_c_b".to_string(),
+            container_id: 5,
+            columns: vec![1, 0],
+            unique: true,
+            method: IndexMethod::BTree,
+            constraint: false,
+        });
+        assert!(table.unique_keys().is_empty());
+        assert_eq!(vec![vec![1, 0]], table.unique_index_keys());
+        assert!(table.drop_column("c").is_err());
+        table.remove_secondary_index(5);
+
+        // The index of a unique column goes away with the column
+        let key = SecondaryIndex {
+            name: "t_c_key".to_string(),
+            container_id: 6,
+            columns: vec![1],
+            unique: true,
+            method: IndexMethod::BTree,
+            constraint: true,
+        };
+        table.add_secondary_index(key.clone());
+        assert_eq!(vec![key], table.drop_column("c").unwrap());
+        assert!(table.secondary_indexes().is_empty());
+    }
+
+    #[test]
+    fn test_tuple_from_csv() {
+        let schema = TableSchema::new(vec![
+            Attribute::new("a".to_string(), DataType::BigInt),
+            Attribute::new("b".to_string(), DataType::Double),
+            Attribute::new("c".to_string(), DataType::Boolean),
+            Attribute::new("d".to_string(), DataType::Decimal(4, 1)),
+        ]);
+        let table = Table::new("t".to_string(), schema);
+        let record = csv::StringRecord::from(vec!["-9000000000", "2.5", "f", "12.34"]);
+        let expected = Tuple::new(vec![
+            Field::BigIntField(-9000000000),
+            Field::DoubleField(Double(2.5)),
+            Field::BoolField(false),
+            Field::DecimalField(123, 1),
+        ]);
+        assert_eq!(expected, table.tuple_from_csv(&record).unwrap());
+        let record = csv::StringRecord::from(vec!["1", "", "true", "1000"]);
+        assert!(table.tuple_from_csv(&record).is_err());
     }
 }
@@ -0,0 +1,647 @@
+//! Dates, timestamps and intervals, and the calendar arithmetic on them.
+//!
+//! A date is stored as days since 1970-01-01 and a timestamp as microseconds since
+//! 1970-01-01 00:00:00, both on the proleptic Gregorian calendar without time zones.
+use crate::logical_plan::ArithOp;
+use crate::{CrustyError, Field};
+use sqlparser::ast::DateTimeField;
+use std::cmp::Ordering;
+use std::fmt;
+
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+pub const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+pub const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+pub const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// Span of time in months, days and microseconds. Months and days are kept apart from the
+/// microseconds since their length depends on the date they are added to.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+impl Interval {
+    pub fn new(months: i32, days: i32, micros: i64) -> Self {
+        Self {
+            months,
+            days,
+            micros,
+        }
+    }
+
+    /// Returns the length of the interval in microseconds, counting months as 30 days.
+    /// Intervals compare by their span, so `1 mon` equals `30 days` in predicates.
+    pub fn span(&self) -> i128 {
+        (self.months as i128 * 30 + self.days as i128) * MICROS_PER_DAY as i128
+            + self.micros as i128
+    }
+
+    fn checked_add(&self, other: &Interval) -> Option<Interval> {
+        Some(Interval::new(
+            self.months.checked_add(other.months)?,
+            self.days.checked_add(other.days)?,
+            self.micros.checked_add(other.micros)?,
+        ))
+    }
+
+    fn checked_mul(&self, factor: i32) -> Option<Interval> {
+        Some(Interval::new(
+            self.months.checked_mul(factor)?,
+            self.days.checked_mul(factor)?,
+            self.micros.checked_mul(factor as i64)?,
+        ))
+    }
+
+    fn checked_neg(&self) -> Option<Interval> {
+        self.checked_mul(-1)
+    }
+
+    /// Parses an interval written as quantities and units, like `1 year 2 mons 3 days` or
+    /// `-2 hours 30 minutes`, optionally ending in a time of `[-]HH:MM[:SS[.ffffff]]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Interval to parse.
+    pub fn parse(value: &str) -> Option<Interval> {
+        let mut interval = Interval::default();
+        let mut words = value.split_whitespace().peekable();
+        words.peek()?;
+        while let Some(word) = words.next() {
+            if word.contains(':') {
+                let micros = parse_time(word.trim_start_matches('-'))?;
+                let micros = if word.starts_with('-') {
+                    -micros
+                } else {
+                    micros
+                };
+                interval.micros = interval.micros.checked_add(micros)?;
+                continue;
+            }
+            let quantity: f64 = word.parse().ok()?;
+            let unit = TimeUnit::parse(words.next()?)?;
+            interval = interval.checked_add(&Interval::of(quantity, unit)?)?;
+        }
+        Some(interval)
+    }
+
+    /// Returns an interval of a quantity of a unit. Fractions of months and days carry over
+    /// to the smaller parts.
+    ///
+    /// # Arguments
+    ///
+    /// * `quantity` - How many of the unit.
+    /// * `unit` - Unit of the quantity.
+    pub fn of(quantity: f64, unit: TimeUnit) -> Option<Interval> {
+        let (months, days, micros) = match unit {
+            TimeUnit::Year => (quantity * 12.0, 0.0, 0.0),
+            TimeUnit::Quarter => (quantity * 3.0, 0.0, 0.0),
+            TimeUnit::Month => (quantity, 0.0, 0.0),
+            TimeUnit::Week => (0.0, quantity * 7.0, 0.0),
+            TimeUnit::Day => (0.0, quantity, 0.0),
+            TimeUnit::Hour => (0.0, 0.0, quantity * MICROS_PER_HOUR as f64),
+            TimeUnit::Minute => (0.0, 0.0, quantity * MICROS_PER_MINUTE as f64),
+            TimeUnit::Second => (0.0, 0.0, quantity * MICROS_PER_SECOND as f64),
+        };
+        let days = days + months.fract() * 30.0;
+        let micros = micros + days.fract() * MICROS_PER_DAY as f64;
+        let in_range = |x: f64, max: f64| x.is_finite() && x.abs() <= max;
+        if !in_range(months, i32::MAX as f64)
+            || !in_range(days, i32::MAX as f64)
+            || !in_range(micros, i64::MAX as f64)
+        {
+            return None;
+        }
+        Some(Interval::new(
+            months.trunc() as i32,
+            days.trunc() as i32,
+            micros.round() as i64,
+        ))
+    }
+}
+
+impl Ord for Interval {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.span().cmp(&other.span()).then_with(|| {
+            (self.months, self.days, self.micros).cmp(&(other.months, other.days, other.micros))
+        })
+    }
+}
+
+impl PartialOrd for Interval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        let plural = |n: i32, unit: &str| {
+            if n.abs() == 1 {
+                format!("{} {}", n, unit)
+            } else {
+                format!("{} {}s", n, unit)
+            }
+        };
+        if self.months / 12 != 0 {
+            parts.push(plural(self.months / 12, "year"));
+        }
+        if self.months % 12 != 0 {
+            parts.push(plural(self.months % 12, "mon"));
+        }
+        if self.days != 0 {
+            parts.push(plural(self.days, "day"));
+        }
+        if self.micros != 0 || parts.is_empty() {
+            let sign = if self.micros < 0 { "-" } else { "" };
+            parts.push(format!(
+                "{}{}",
+                sign,
+                format_time(self.micros.unsigned_abs() as i64)
+            ));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// Units of dates and times that can be extracted from or truncated to.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Year,
+    Quarter,
+    Month,
+    Week,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl TimeUnit {
+    /// Parses the name of a unit, singular, plural or abbreviated as in `3 mons`.
+    pub fn parse(name: &str) -> Option<TimeUnit> {
+        match &name.to_lowercase()[..] {
+            "year" | "years" | "y" => Some(TimeUnit::Year),
+            "quarter" | "quarters" => Some(TimeUnit::Quarter),
+            "month" | "months" | "mon" | "mons" => Some(TimeUnit::Month),
+            "week" | "weeks" | "w" => Some(TimeUnit::Week),
+            "day" | "days" | "d" => Some(TimeUnit::Day),
+            "hour" | "hours" | "h" => Some(TimeUnit::Hour),
+            "minute" | "minutes" | "min" | "mins" | "m" => Some(TimeUnit::Minute),
+            "second" | "seconds" | "sec" | "secs" | "s" => Some(TimeUnit::Second),
+            _ => None,
+        }
+    }
+}
+
+impl From<&DateTimeField> for TimeUnit {
+    fn from(field: &DateTimeField) -> Self {
+        match field {
+            DateTimeField::Year => TimeUnit::Year,
+            DateTimeField::Month => TimeUnit::Month,
+            DateTimeField::Day => TimeUnit::Day,
+            DateTimeField::Hour => TimeUnit::Hour,
+            DateTimeField::Minute => TimeUnit::Minute,
+            DateTimeField::Second => TimeUnit::Second,
+        }
+    }
+}
+
+impl fmt::Display for TimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+/// Returns the days since 1970-01-01 of a date, from Howard Hinnant's `days_from_civil`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Returns the year, month and day of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses a date written as `YYYY-MM-DD` to days since 1970-01-01.
+pub fn parse_date(value: &str) -> Option<i32> {
+    let mut parts = value.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(1..=9999).contains(&year) || !(1..=12).contains(&month) {
+        return None;
+    }
+    if day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    i32::try_from(days_from_civil(year, month, day)).ok()
+}
+
+/// Parses a time of day written as `HH:MM[:SS[.ffffff]]` to microseconds.
+fn parse_time(value: &str) -> Option<i64> {
+    let mut parts = value.splitn(3, ':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next()?.parse().ok()?;
+    let (seconds, fraction) = match parts.next() {
+        Some(seconds) => seconds.split_once('.').unwrap_or((seconds, "")),
+        None => ("0", ""),
+    };
+    let seconds: i64 = seconds.parse().ok()?;
+    if !(0..60).contains(&minutes) || !(0..60).contains(&seconds) || hours < 0 {
+        return None;
+    }
+    if fraction.len() > 6 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let micros = format!("{:0<6}", fraction).parse::<i64>().ok()?;
+    Some(
+        hours * MICROS_PER_HOUR
+            + minutes * MICROS_PER_MINUTE
+            + seconds * MICROS_PER_SECOND
+            + micros,
+    )
+}
+
+/// Parses a timestamp written as `YYYY-MM-DD[( |T)HH:MM[:SS[.ffffff]]]` to microseconds
+/// since 1970-01-01 00:00:00.
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (date, time) = match value.split_once([' ', 'T']) {
+        Some((date, time)) => (date, parse_time(time.trim())?),
+        None => (value, 0),
+    };
+    if time >= MICROS_PER_DAY {
+        return None;
+    }
+    Some(parse_date(date)? as i64 * MICROS_PER_DAY + time)
+}
+
+/// Formats days since 1970-01-01 as `YYYY-MM-DD`.
+pub fn format_date(days: i32) -> String {
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Formats microseconds as `HH:MM:SS`, with as many digits of fractional seconds as needed.
+fn format_time(micros: i64) -> String {
+    let seconds = micros / MICROS_PER_SECOND;
+    let mut time = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    let fraction = micros % MICROS_PER_SECOND;
+    if fraction != 0 {
+        let digits = format!("{:06}", fraction);
+        time.push('.');
+        time.push_str(digits.trim_end_matches('0'));
+    }
+    time
+}
+
+/// Formats microseconds since 1970-01-01 00:00:00 as `YYYY-MM-DD HH:MM:SS[.ffffff]`.
+pub fn format_timestamp(micros: i64) -> String {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {}",
+        year,
+        month,
+        day,
+        format_time(micros.rem_euclid(MICROS_PER_DAY))
+    )
+}
+
+/// Adds months to a timestamp, moving days past the end of the new month back to its last
+/// day, so that 2021-01-31 plus 1 mon is 2021-02-28.
+fn add_months(micros: i64, months: i32) -> Option<i64> {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let total = year * 12 + month as i64 - 1 + months as i64;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+    let day = day.min(days_in_month(year, month));
+    let days = days_from_civil(year, month, day);
+    days.checked_mul(MICROS_PER_DAY)?
+        .checked_add(micros.rem_euclid(MICROS_PER_DAY))
+}
+
+/// Adds an interval to a timestamp: first its months, then its days and time.
+fn add_interval(micros: i64, interval: &Interval) -> Option<i64> {
+    add_months(micros, interval.months)?
+        .checked_add((interval.days as i64).checked_mul(MICROS_PER_DAY)?)?
+        .checked_add(interval.micros)
+}
+
+/// Returns the microseconds since 1970-01-01 00:00:00 of a date or timestamp.
+pub fn to_micros(field: &Field) -> Option<i64> {
+    match field {
+        Field::DateField(days) => (*days as i64).checked_mul(MICROS_PER_DAY),
+        Field::TimestampField(micros) => Some(*micros),
+        _ => None,
+    }
+}
+
+/// Applies arithmetic to dates, timestamps and intervals:
+///
+/// * date ± integer days is a date, and date - date the integer days between them.
+/// * date or timestamp ± interval is a timestamp, and timestamp - timestamp an interval.
+/// * interval ± interval is an interval, as is an interval times an integer.
+///
+/// # Arguments
+///
+/// * `left` - Left operand.
+/// * `op` - Operation to apply.
+/// * `right` - Right operand.
+pub fn arith(left: &Field, op: ArithOp, right: &Field) -> Result<Field, CrustyError> {
+    let out_of_range =
+        || CrustyError::ExecutionError(format!("{} {} {} is out of range", left, op, right));
+    let result = match (left, op, right) {
+        (Field::DateField(d), ArithOp::Plus, Field::IntField(n))
+        | (Field::IntField(n), ArithOp::Plus, Field::DateField(d)) => {
+            d.checked_add(*n).map(Field::DateField)
+        }
+        (Field::DateField(d), ArithOp::Minus, Field::IntField(n)) => {
+            d.checked_sub(*n).map(Field::DateField)
+        }
+        (Field::DateField(l), ArithOp::Minus, Field::DateField(r)) => {
+            l.checked_sub(*r).map(Field::IntField)
+        }
+        (Field::DateField(_), _, Field::IntervalField(i))
+        | (Field::TimestampField(_), _, Field::IntervalField(i)) => {
+            let interval = match op {
+                ArithOp::Plus => Some(*i),
+                ArithOp::Minus => i.checked_neg(),
+                _ => return Err(unsupported(left, op, right)),
+            };
+            let micros = to_micros(left).ok_or_else(out_of_range)?;
+            interval
+                .and_then(|i| add_interval(micros, &i))
+                .map(Field::TimestampField)
+        }
+        (Field::IntervalField(i), ArithOp::Plus, Field::DateField(_))
+        | (Field::IntervalField(i), ArithOp::Plus, Field::TimestampField(_)) => {
+            let micros = to_micros(right).ok_or_else(out_of_range)?;
+            add_interval(micros, i).map(Field::TimestampField)
+        }
+        (Field::TimestampField(l), ArithOp::Minus, Field::TimestampField(r)) => {
+            l.checked_sub(*r).and_then(|diff| {
+                let days = i32::try_from(diff / MICROS_PER_DAY).ok()?;
+                Some(Field::IntervalField(Interval::new(
+                    0,
+                    days,
+                    diff % MICROS_PER_DAY,
+                )))
+            })
+        }
+        (Field::IntervalField(l), ArithOp::Plus, Field::IntervalField(r)) => {
+            l.checked_add(r).map(Field::IntervalField)
+        }
+        (Field::IntervalField(l), ArithOp::Minus, Field::IntervalField(r)) => r
+            .checked_neg()
+            .and_then(|r| l.checked_add(&r))
+            .map(Field::IntervalField),
+        (Field::IntervalField(i), ArithOp::Multiply, Field::IntField(n))
+        | (Field::IntField(n), ArithOp::Multiply, Field::IntervalField(i)) => {
+            i.checked_mul(*n).map(Field::IntervalField)
+        }
+        _ => return Err(unsupported(left, op, right)),
+    };
+    result.ok_or_else(out_of_range)
+}
+
+fn unsupported(left: &Field, op: ArithOp, right: &Field) -> CrustyError {
+    CrustyError::ExecutionError(format!("Cannot apply {} to {} and {}", op, left, right))
+}
+
+/// Returns a part of a date, timestamp or interval as an integer, like EXTRACT. Quarters
+/// and weeks are not parts of intervals.
+///
+/// # Arguments
+///
+/// * `unit` - Part to extract.
+/// * `field` - Value to extract it from.
+pub fn extract(unit: TimeUnit, field: &Field) -> Result<Field, CrustyError> {
+    let part = match field {
+        Field::Null => return Ok(Field::Null),
+        Field::IntervalField(i) => match unit {
+            TimeUnit::Year => i.months as i64 / 12,
+            TimeUnit::Month => i.months as i64 % 12,
+            TimeUnit::Day => i.days as i64,
+            TimeUnit::Hour => i.micros / MICROS_PER_HOUR,
+            TimeUnit::Minute => i.micros / MICROS_PER_MINUTE % 60,
+            TimeUnit::Second => i.micros / MICROS_PER_SECOND % 60,
+            TimeUnit::Quarter | TimeUnit::Week => {
+                return Err(CrustyError::ExecutionError(format!(
+                    "Cannot extract {} from an interval",
+                    unit
+                )))
+            }
+        },
+        _ => {
+            let micros = to_micros(field).ok_or_else(|| {
+                CrustyError::ExecutionError(format!("Cannot extract {} from {}", unit, field))
+            })?;
+            let (year, month, day) = civil_from_days(micros.div_euclid(MICROS_PER_DAY));
+            let time = micros.rem_euclid(MICROS_PER_DAY);
+            match unit {
+                TimeUnit::Year => year,
+                TimeUnit::Quarter => (month as i64 - 1) / 3 + 1,
+                TimeUnit::Month => month as i64,
+                TimeUnit::Week => iso_week(micros.div_euclid(MICROS_PER_DAY)),
+                TimeUnit::Day => day as i64,
+                TimeUnit::Hour => time / MICROS_PER_HOUR,
+                TimeUnit::Minute => time / MICROS_PER_MINUTE % 60,
+                TimeUnit::Second => time / MICROS_PER_SECOND % 60,
+            }
+        }
+    };
+    Ok(Field::IntField(part as i32))
+}
+
+/// Returns the days since 1970-01-01 of the Monday starting the week of a day.
+fn monday_of(days: i64) -> i64 {
+    // 1970-01-01 was a Thursday, three days after a Monday.
+    days - (days + 3).rem_euclid(7)
+}
+
+/// Returns the ISO 8601 week number of a day. Week 1 is the week with the year's first
+/// Thursday.
+fn iso_week(days: i64) -> i64 {
+    let thursday = monday_of(days) + 3;
+    let (year, _, _) = civil_from_days(thursday);
+    (thursday - days_from_civil(year, 1, 1)) / 7 + 1
+}
+
+/// Truncates a date or timestamp to the start of its unit, like `date_trunc`. Dates stay
+/// dates, so truncating them to a unit shorter than a day leaves them as they are.
+///
+/// # Arguments
+///
+/// * `unit` - Unit to truncate to.
+/// * `field` - Date or timestamp to truncate.
+pub fn truncate(unit: TimeUnit, field: &Field) -> Result<Field, CrustyError> {
+    let micros = match field {
+        Field::Null => return Ok(Field::Null),
+        Field::DateField(_) | Field::TimestampField(_) => to_micros(field).unwrap_or_default(),
+        _ => {
+            return Err(CrustyError::ExecutionError(format!(
+                "Cannot truncate {} to a {}",
+                field, unit
+            )))
+        }
+    };
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let (year, month, _) = civil_from_days(days);
+    let truncated = match unit {
+        TimeUnit::Year => days_from_civil(year, 1, 1) * MICROS_PER_DAY,
+        TimeUnit::Quarter => days_from_civil(year, (month - 1) / 3 * 3 + 1, 1) * MICROS_PER_DAY,
+        TimeUnit::Month => days_from_civil(year, month, 1) * MICROS_PER_DAY,
+        TimeUnit::Week => monday_of(days) * MICROS_PER_DAY,
+        TimeUnit::Day => days * MICROS_PER_DAY,
+        TimeUnit::Hour => micros - micros.rem_euclid(MICROS_PER_HOUR),
+        TimeUnit::Minute => micros - micros.rem_euclid(MICROS_PER_MINUTE),
+        TimeUnit::Second => micros - micros.rem_euclid(MICROS_PER_SECOND),
+    };
+    Ok(match field {
+        Field::DateField(_) => Field::DateField(truncated.div_euclid(MICROS_PER_DAY) as i32),
+        _ => Field::TimestampField(truncated),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ts(value: &str) -> Field {
+        Field::TimestampField(parse_timestamp(value).unwrap())
+    }
+
+    fn date(value: &str) -> Field {
+        Field::DateField(parse_date(value).unwrap())
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(Some(0), parse_date("1970-01-01"));
+        assert_eq!(Some(-1), parse_date("1969-12-31"));
+        assert_eq!(None, parse_date("2021-02-29"));
+        assert_eq!(None, parse_date("2021-13-01"));
+        assert_eq!("2020-02-29", format_date(parse_date("2020-02-29").unwrap()));
+        let t = parse_timestamp("2021-03-04T05:06:07.25").unwrap();
+        assert_eq!("2021-03-04 05:06:07.25", format_timestamp(t));
+        assert_eq!(None, parse_timestamp("2021-03-04 24:00:00"));
+        assert_eq!("1969-12-31 23:59:59", format_timestamp(-MICROS_PER_SECOND));
+
+        let i = Interval::parse("1 year 2 mons -3 days 04:05:06.5").unwrap();
+        assert_eq!(Interval::new(14, -3, 14_706_500_000), i);
+        assert_eq!("1 year 2 mons -3 days 04:05:06.5", i.to_string());
+        assert_eq!(Some(i), Interval::parse(&i.to_string()));
+        assert_eq!(
+            Some(Interval::new(0, 1, 12 * MICROS_PER_HOUR)),
+            Interval::parse("1.5 days")
+        );
+        assert_eq!("00:00:00", Interval::default().to_string());
+        assert_eq!(None, Interval::parse("3 fortnights"));
+        assert_eq!(None, Interval::parse(""));
+        assert_eq!(
+            Ordering::Equal,
+            Interval::new(1, 0, 0)
+                .span()
+                .cmp(&Interval::new(0, 30, 0).span())
+        );
+    }
+
+    #[test]
+    fn test_arith() -> Result<(), CrustyError> {
+        let month = Field::IntervalField(Interval::new(1, 0, 0));
+        assert_eq!(
+            ts("2021-02-28"),
+            arith(&date("2021-01-31"), ArithOp::Plus, &month)?
+        );
+        assert_eq!(
+            ts("2020-12-31 12:00"),
+            arith(&ts("2021-01-31 12:00"), ArithOp::Minus, &month)?
+        );
+        assert_eq!(
+            date("2021-03-01"),
+            arith(&date("2021-02-28"), ArithOp::Plus, &Field::IntField(1))?
+        );
+        assert_eq!(
+            Field::IntField(365),
+            arith(&date("2022-01-01"), ArithOp::Minus, &date("2021-01-01"))?
+        );
+        assert_eq!(
+            Field::IntervalField(Interval::new(0, 1, MICROS_PER_HOUR)),
+            arith(&ts("2021-01-02 01:00"), ArithOp::Minus, &ts("2021-01-01"))?
+        );
+        assert_eq!(
+            Field::IntervalField(Interval::new(3, 0, 0)),
+            arith(&month, ArithOp::Multiply, &Field::IntField(3))?
+        );
+        assert!(arith(&date("2021-01-01"), ArithOp::Multiply, &month).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_and_truncate() -> Result<(), CrustyError> {
+        let t = ts("2021-08-15 13:45:30.5");
+        let part = |unit| extract(unit, &t).unwrap();
+        assert_eq!(Field::IntField(2021), part(TimeUnit::Year));
+        assert_eq!(Field::IntField(3), part(TimeUnit::Quarter));
+        assert_eq!(Field::IntField(8), part(TimeUnit::Month));
+        assert_eq!(Field::IntField(32), part(TimeUnit::Week));
+        assert_eq!(Field::IntField(15), part(TimeUnit::Day));
+        assert_eq!(Field::IntField(13), part(TimeUnit::Hour));
+        assert_eq!(Field::IntField(45), part(TimeUnit::Minute));
+        assert_eq!(Field::IntField(30), part(TimeUnit::Second));
+        assert_eq!(
+            Field::IntField(53),
+            extract(TimeUnit::Week, &date("2021-01-01"))?
+        );
+        assert_eq!(Field::Null, extract(TimeUnit::Year, &Field::Null)?);
+
+        assert_eq!(ts("2021-01-01"), truncate(TimeUnit::Year, &t)?);
+        assert_eq!(ts("2021-07-01"), truncate(TimeUnit::Quarter, &t)?);
+        assert_eq!(ts("2021-08-01"), truncate(TimeUnit::Month, &t)?);
+        assert_eq!(ts("2021-08-09"), truncate(TimeUnit::Week, &t)?);
+        assert_eq!(ts("2021-08-15 13:00"), truncate(TimeUnit::Hour, &t)?);
+        assert_eq!(ts("2021-08-15 13:45:30"), truncate(TimeUnit::Second, &t)?);
+        assert_eq!(
+            date("2021-08-01"),
+            truncate(TimeUnit::Month, &date("2021-08-15"))?
+        );
+        assert_eq!(
+            ts("1969-12-01"),
+            truncate(TimeUnit::Month, &ts("1969-12-31 23:00"))?
+        );
+        Ok(())
+    }
+}
@@ -81,6 +81,7 @@ pub fn gen_table_for_test_tuples(table_name: String) -> Table {
         name: String::from("id"),
         dtype: DataType::Int,
         constraint: Constraint::PrimaryKey,
+        default: None,
     };
     attributes.push(pk_attr);
 
@@ -89,6 +90,7 @@ pub fn gen_table_for_test_tuples(table_name: String) -> Table {
             name: format!("ia{}", n.to_string()),
             dtype: DataType::Int,
             constraint: Constraint::None,
+            default: None,
         };
         attributes.push(attr);
     }
@@ -97,6 +99,7 @@ pub fn gen_table_for_test_tuples(table_name: String) -> Table {
             name: format!("sa{}", n.to_string()),
             dtype: DataType::String,
             constraint: Constraint::None,
+            default: None,
         };
         attributes.push(attr);
     }
@@ -0,0 +1,830 @@
+use crate::buffer_pool::BufferPool;
+use crate::heapfile::HeapFile;
+use crate::page::MAX_VALUE_SIZE;
+use common::ids::{ContainerId, PageId, SlotId, ValueId};
+use common::index::{SecondaryIndex, MAX_KEY_SIZE};
+use common::row::RowLayout;
+use common::table::Table;
+use common::{CrustyError, DataType, Field, Tuple};
+use std::cmp::Ordering;
+use std::convert::TryInto;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+/// Page recording the root and the first free page.
+const META_PAGE: PageId = 0;
+/// Marks the end of a chain of pages.
+const NO_PAGE: PageId = PageId::MAX;
+/// Nodes smaller than this take entries from a sibling or are merged with it.
+const MIN_NODE_SIZE: usize = MAX_VALUE_SIZE / 4;
+
+const LEAF: u8 = 0;
+const INTERNAL: u8 = 1;
+const FREE: u8 = 2;
+
+/// An entry of the tree: a key and where the row holding it is stored. Entries with equal
+/// keys are ordered by their rows, so no two entries are equal.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    key: Vec<Field>,
+    page_id: PageId,
+    slot_id: SlotId,
+}
+
+impl Entry {
+    fn encode(&self, layout: &RowLayout, bytes: &mut Vec<u8>) {
+        let key = layout.encode(&Tuple::new(self.key.clone()), 0);
+        bytes.extend_from_slice(&(key.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&key);
+        bytes.extend_from_slice(&self.page_id.to_le_bytes());
+        bytes.extend_from_slice(&self.slot_id.to_le_bytes());
+    }
+
+    /// Reads an entry from the start of bytes, returning it and the bytes it took.
+    fn decode(layout: &RowLayout, bytes: &[u8]) -> (Self, usize) {
+        let len = u16::from_le_bytes(bytes[..2].try_into().unwrap()) as usize;
+        let key = layout.decode(&bytes[2..2 + len]).field_vals;
+        let rest = &bytes[2 + len..];
+        let entry = Entry {
+            key,
+            page_id: PageId::from_le_bytes(rest[..2].try_into().unwrap()),
+            slot_id: SlotId::from_le_bytes(rest[2..4].try_into().unwrap()),
+        };
+        (entry, len + 6)
+    }
+}
+
+/// A node of the tree, stored as the only value of its page.
+#[derive(Debug, PartialEq)]
+enum Node {
+    /// Entries in order, and the leaf after this one.
+    Leaf { entries: Vec<Entry>, next: PageId },
+    /// The subtree of each child holds the entries from the separator before it, up to but
+    /// not including the one after it.
+    Internal {
+        separators: Vec<Entry>,
+        children: Vec<PageId>,
+    },
+    /// A page freed by a merge, and the next free page.
+    Free { next: PageId },
+}
+
+impl Node {
+    fn encode(&self, layout: &RowLayout) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Node::Leaf { entries, next } => {
+                bytes.push(LEAF);
+                bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(&next.to_le_bytes());
+                for entry in entries {
+                    entry.encode(layout, &mut bytes);
+                }
+            }
+            Node::Internal {
+                separators,
+                children,
+            } => {
+                bytes.push(INTERNAL);
+                bytes.extend_from_slice(&(separators.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(&children[0].to_le_bytes());
+                for (separator, child) in separators.iter().zip(&children[1..]) {
+                    separator.encode(layout, &mut bytes);
+                    bytes.extend_from_slice(&child.to_le_bytes());
+                }
+            }
+            Node::Free { next } => {
+                bytes.push(FREE);
+                bytes.extend_from_slice(&next.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn decode(layout: &RowLayout, bytes: &[u8]) -> Self {
+        let read_u16 = |at: usize| u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap());
+        if bytes[0] == FREE {
+            return Node::Free { next: read_u16(1) };
+        }
+        let count = read_u16(1) as usize;
+        let mut at = 5;
+        let mut entries = Vec::with_capacity(count);
+        let mut children = vec![read_u16(3)];
+        for _ in 0..count {
+            let (entry, len) = Entry::decode(layout, &bytes[at..]);
+            entries.push(entry);
+            at += len;
+            if bytes[0] == INTERNAL {
+                children.push(read_u16(at));
+                at += 2;
+            }
+        }
+        match bytes[0] {
+            LEAF => Node::Leaf {
+                entries,
+                next: children[0],
+            },
+            _ => Node::Internal {
+                separators: entries,
+                children,
+            },
+        }
+    }
+
+    /// Puts two siblings and the separator between them in one node.
+    fn join(left: Node, separator: Entry, right: Node) -> Node {
+        match (left, right) {
+            (
+                Node::Leaf { mut entries, .. },
+                Node::Leaf {
+                    entries: more,
+                    next,
+                },
+            ) => {
+                entries.extend(more);
+                Node::Leaf { entries, next }
+            }
+            (
+                Node::Internal {
+                    mut separators,
+                    mut children,
+                },
+                Node::Internal {
+                    separators: more_separators,
+                    children: more_children,
+                },
+            ) => {
+                separators.push(separator);
+                separators.extend(more_separators);
+                children.extend(more_children);
+                Node::Internal {
+                    separators,
+                    children,
+                }
+            }
+            _ => panic!("Cannot join a leaf with an internal node"),
+        }
+    }
+
+    /// Splits the node in two of about the same size, returning the left node, the separator
+    /// between them and the right node, which is to be stored at right_id.
+    fn split(self, right_id: PageId, layout: &RowLayout) -> (Node, Entry, Node) {
+        match self {
+            Node::Leaf { mut entries, next } => {
+                let at = Self::middle(&entries, layout).max(1);
+                let right = entries.split_off(at);
+                let separator = right[0].clone();
+                let left = Node::Leaf {
+                    entries,
+                    next: right_id,
+                };
+                (
+                    left,
+                    separator,
+                    Node::Leaf {
+                        entries: right,
+                        next,
+                    },
+                )
+            }
+            Node::Internal {
+                mut separators,
+                mut children,
+            } => {
+                let at = Self::middle(&separators, layout).min(separators.len() - 1);
+                let right_separators = separators.split_off(at + 1);
+                let separator = separators.pop().unwrap();
+                let right_children = children.split_off(at + 1);
+                let left = Node::Internal {
+                    separators,
+                    children,
+                };
+                (
+                    left,
+                    separator,
+                    Node::Internal {
+                        separators: right_separators,
+                        children: right_children,
+                    },
+                )
+            }
+            Node::Free { .. } => panic!("Cannot split a free page"),
+        }
+    }
+
+    /// Index of the first entry in the second half of the entries' bytes.
+    fn middle(entries: &[Entry], layout: &RowLayout) -> usize {
+        let sizes: Vec<usize> = entries
+            .iter()
+            .map(|entry| {
+                let mut bytes = Vec::new();
+                entry.encode(layout, &mut bytes);
+                bytes.len()
+            })
+            .collect();
+        let half = sizes.iter().sum::<usize>() / 2;
+        let mut taken = 0;
+        sizes
+            .iter()
+            .position(|size| {
+                taken += size;
+                taken > half
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// Compares a key with a bound on its first columns.
+fn cmp_prefix(key: &[Field], bound: &[Field]) -> Ordering {
+    key[..bound.len().min(key.len())].cmp(bound)
+}
+
+/// Whether a key comes before the lower bound of a range.
+fn below(key: &[Field], lower: &Bound<Vec<Field>>) -> bool {
+    match lower {
+        Bound::Included(bound) => cmp_prefix(key, bound) == Ordering::Less,
+        Bound::Excluded(bound) => cmp_prefix(key, bound) != Ordering::Greater,
+        Bound::Unbounded => false,
+    }
+}
+
+/// Whether a key comes after the upper bound of a range.
+fn above(key: &[Field], upper: &Bound<Vec<Field>>) -> bool {
+    match upper {
+        Bound::Included(bound) => cmp_prefix(key, bound) == Ordering::Greater,
+        Bound::Excluded(bound) => cmp_prefix(key, bound) != Ordering::Less,
+        Bound::Unbounded => false,
+    }
+}
+
+/// A B+ tree from the keys of a secondary index to the rows of the table holding them. The
+/// tree is stored in a heap file of its own, through the buffer pool, with a node per page
+/// after the first, which records where the root is. Nodes are split when they no longer fit
+/// in their page, and take entries from a sibling, or are merged with it, when they are less
+/// than a quarter full. Pages freed by merges are reused.
+///
+/// The tree holds an entry for every version of a row that is stored, so the rows it finds
+/// still have to be checked to be visible and to hold the key. It is latched as a whole:
+/// scans share the latch and changes take it alone. Changes are not logged, so the tree is
+/// rebuilt after a crash.
+pub(crate) struct BTree {
+    /// Container of the tree.
+    pub(crate) index_id: ContainerId,
+    /// Container of the table.
+    pub(crate) table_id: ContainerId,
+    table: Arc<RwLock<Table>>,
+    hf: Arc<HeapFile>,
+    buffer_pool: Arc<BufferPool>,
+    latch: RwLock<()>,
+}
+
+impl BTree {
+    /// Opens the tree stored in a heap file, creating an empty tree if the file is empty.
+    /// Returns the tree and whether it was created.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_id` - Container of the tree.
+    /// * `table_id` - Container of the table.
+    /// * `table` - The table, whose secondary index stored in index_id gives the key.
+    /// * `hf` - Heap file of the tree.
+    /// * `buffer_pool` - Buffer pool to read and write the pages through.
+    pub(crate) fn open(
+        index_id: ContainerId,
+        table_id: ContainerId,
+        table: Arc<RwLock<Table>>,
+        hf: Arc<HeapFile>,
+        buffer_pool: Arc<BufferPool>,
+    ) -> Result<(Self, bool), CrustyError> {
+        let tree = BTree {
+            index_id,
+            table_id,
+            table,
+            hf,
+            buffer_pool,
+            latch: RwLock::new(()),
+        };
+        let (layout, _) = tree.key_format()?;
+        if tree.hf.num_pages() > 0 {
+            return Ok((tree, false));
+        }
+        tree.hf.allocate_page()?;
+        let root = tree.hf.allocate_page()?;
+        let leaf = Node::Leaf {
+            entries: Vec::new(),
+            next: NO_PAGE,
+        };
+        tree.write(root, &leaf, &layout)?;
+        tree.set_meta(root, NO_PAGE)?;
+        Ok((tree, true))
+    }
+
+    fn index(&self, table: &Table) -> Result<SecondaryIndex, CrustyError> {
+        table
+            .secondary_index(self.index_id)
+            .cloned()
+            .ok_or_else(|| {
+                CrustyError::CrustyError(format!(
+                    "Table {} has no index in container {}",
+                    table.name, self.index_id
+                ))
+            })
+    }
+
+    /// Layout the keys are stored in, and the types of their columns.
+    fn key_format(&self) -> Result<(RowLayout, Vec<DataType>), CrustyError> {
+        let table = self.table.read().unwrap();
+        let index = self.index(&table)?;
+        let dtypes = index
+            .columns
+            .iter()
+            .map(|i| table.schema.get_attribute(*i).unwrap().dtype().clone())
+            .collect();
+        Ok((index.key_layout(&table.schema), dtypes))
+    }
+
+    /// Reads the key of a stored row.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Row as stored in the table.
+    pub(crate) fn key(&self, row: &[u8]) -> Result<Vec<Field>, CrustyError> {
+        let table = self.table.read().unwrap();
+        let index = self.index(&table)?;
+        Ok(table.decode_fields(row, &index.columns))
+    }
+
+    fn read(&self, page_id: PageId, layout: &RowLayout) -> Result<Node, CrustyError> {
+        let page = self
+            .buffer_pool
+            .get_page(self.index_id, page_id, &self.hf, false)?;
+        match page.get_value(0) {
+            Some(bytes) => Ok(Node::decode(layout, &bytes)),
+            None => Err(CrustyError::CrustyError(format!(
+                "Page {} of index {} holds no node",
+                page_id, self.index_id
+            ))),
+        }
+    }
+
+    fn write(&self, page_id: PageId, node: &Node, layout: &RowLayout) -> Result<(), CrustyError> {
+        self.write_bytes(page_id, &node.encode(layout))
+    }
+
+    fn write_bytes(&self, page_id: PageId, bytes: &[u8]) -> Result<(), CrustyError> {
+        self.buffer_pool
+            .with_page_mut(self.index_id, page_id, &self.hf, |page| {
+                page.put_value_at(0, bytes)
+            })?
+            .ok_or_else(|| {
+                CrustyError::CrustyError(format!(
+                    "Node of {} bytes does not fit in page {} of index {}",
+                    bytes.len(),
+                    page_id,
+                    self.index_id
+                ))
+            })
+    }
+
+    /// The root and the first free page.
+    fn meta(&self) -> Result<(PageId, PageId), CrustyError> {
+        let page = self
+            .buffer_pool
+            .get_page(self.index_id, META_PAGE, &self.hf, false)?;
+        let bytes = page.get_value(0).ok_or_else(|| {
+            CrustyError::CrustyError(format!("Index {} has no root", self.index_id))
+        })?;
+        let read_u16 = |at: usize| u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap());
+        Ok((read_u16(0), read_u16(2)))
+    }
+
+    fn set_meta(&self, root: PageId, free: PageId) -> Result<(), CrustyError> {
+        let mut bytes = root.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&free.to_le_bytes());
+        self.write_bytes(META_PAGE, &bytes)
+    }
+
+    /// Takes a page for a new node, reusing a freed page if there is one.
+    fn allocate(&self, layout: &RowLayout) -> Result<PageId, CrustyError> {
+        let (root, free) = self.meta()?;
+        if free == NO_PAGE {
+            return self.hf.allocate_page();
+        }
+        match self.read(free, layout)? {
+            Node::Free { next } => self.set_meta(root, next)?,
+            _ => {
+                return Err(CrustyError::CrustyError(format!(
+                    "Free page {} of index {} is in use",
+                    free, self.index_id
+                )))
+            }
+        }
+        Ok(free)
+    }
+
+    /// Adds a page that no longer holds a node to the free pages.
+    fn release(&self, page_id: PageId, layout: &RowLayout) -> Result<(), CrustyError> {
+        let (root, free) = self.meta()?;
+        self.write(page_id, &Node::Free { next: free }, layout)?;
+        self.set_meta(root, page_id)
+    }
+
+    /// Writes a node to its page, splitting it first if it does not fit. Returns the
+    /// separator and the page of the new right node if it was split.
+    fn store(
+        &self,
+        page_id: PageId,
+        node: Node,
+        layout: &RowLayout,
+    ) -> Result<Option<(Entry, PageId)>, CrustyError> {
+        if node.encode(layout).len() <= MAX_VALUE_SIZE {
+            self.write(page_id, &node, layout)?;
+            return Ok(None);
+        }
+        let right_id = self.allocate(layout)?;
+        let (left, separator, right) = node.split(right_id, layout);
+        self.write(page_id, &left, layout)?;
+        self.write(right_id, &right, layout)?;
+        Ok(Some((separator, right_id)))
+    }
+
+    /// Adds an entry for a row. Adding an entry the tree already has does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key the row holds.
+    /// * `id` - Where the row is stored.
+    pub(crate) fn insert(&self, key: Vec<Field>, id: ValueId) -> Result<(), CrustyError> {
+        let _latch = self.latch.write().unwrap();
+        let (layout, dtypes) = self.key_format()?;
+        let entry = Entry {
+            key: widen(&key, &dtypes),
+            page_id: id.page_id.unwrap(),
+            slot_id: id.slot_id.unwrap(),
+        };
+        if layout.encode(&Tuple::new(entry.key.clone()), 0).len() > MAX_KEY_SIZE {
+            return Err(CrustyError::CrustyError(format!(
+                "Key of index {} is longer than {} bytes",
+                self.index_id, MAX_KEY_SIZE
+            )));
+        }
+        let (root, _) = self.meta()?;
+        if let Some((separator, right)) = self.insert_into(root, entry, &layout)? {
+            let new_root = self.allocate(&layout)?;
+            let node = Node::Internal {
+                separators: vec![separator],
+                children: vec![root, right],
+            };
+            self.write(new_root, &node, &layout)?;
+            let (_, free) = self.meta()?;
+            self.set_meta(new_root, free)?;
+        }
+        Ok(())
+    }
+
+    fn insert_into(
+        &self,
+        page_id: PageId,
+        entry: Entry,
+        layout: &RowLayout,
+    ) -> Result<Option<(Entry, PageId)>, CrustyError> {
+        let node = match self.read(page_id, layout)? {
+            Node::Leaf { mut entries, next } => match entries.binary_search(&entry) {
+                Ok(_) => return Ok(None),
+                Err(i) => {
+                    entries.insert(i, entry);
+                    Node::Leaf { entries, next }
+                }
+            },
+            Node::Internal {
+                mut separators,
+                mut children,
+            } => {
+                let i = separators.partition_point(|s| *s <= entry);
+                match self.insert_into(children[i], entry, layout)? {
+                    None => return Ok(None),
+                    Some((separator, right)) => {
+                        separators.insert(i, separator);
+                        children.insert(i + 1, right);
+                        Node::Internal {
+                            separators,
+                            children,
+                        }
+                    }
+                }
+            }
+            Node::Free { .. } => return Err(self.free_in_tree(page_id)),
+        };
+        self.store(page_id, node, layout)
+    }
+
+    /// Removes the entry for a row, returning whether the tree had it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key the row holds.
+    /// * `id` - Where the row is stored.
+    pub(crate) fn delete(&self, key: Vec<Field>, id: ValueId) -> Result<bool, CrustyError> {
+        let _latch = self.latch.write().unwrap();
+        let (layout, dtypes) = self.key_format()?;
+        let entry = Entry {
+            key: widen(&key, &dtypes),
+            page_id: id.page_id.unwrap(),
+            slot_id: id.slot_id.unwrap(),
+        };
+        let (root, _) = self.meta()?;
+        if !self.delete_from(root, &entry, &layout)? {
+            return Ok(false);
+        }
+        // A root left with one child is replaced by the child
+        if let Node::Internal {
+            separators,
+            children,
+        } = self.read(root, &layout)?
+        {
+            if separators.is_empty() {
+                let (_, free) = self.meta()?;
+                self.set_meta(children[0], free)?;
+                self.release(root, &layout)?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn delete_from(
+        &self,
+        page_id: PageId,
+        entry: &Entry,
+        layout: &RowLayout,
+    ) -> Result<bool, CrustyError> {
+        match self.read(page_id, layout)? {
+            Node::Leaf { mut entries, next } => match entries.binary_search(entry) {
+                Ok(i) => {
+                    entries.remove(i);
+                    self.write(page_id, &Node::Leaf { entries, next }, layout)?;
+                    Ok(true)
+                }
+                Err(_) => Ok(false),
+            },
+            Node::Internal {
+                mut separators,
+                mut children,
+            } => {
+                let i = separators.partition_point(|s| s <= entry);
+                if !self.delete_from(children[i], entry, layout)? {
+                    return Ok(false);
+                }
+                let child = self.read(children[i], layout)?;
+                if child.encode(layout).len() < MIN_NODE_SIZE {
+                    self.rebalance(&mut separators, &mut children, i, layout)?;
+                    let node = Node::Internal {
+                        separators,
+                        children,
+                    };
+                    self.write(page_id, &node, layout)?;
+                }
+                Ok(true)
+            }
+            Node::Free { .. } => Err(self.free_in_tree(page_id)),
+        }
+    }
+
+    /// Merges the ith child of a node with a sibling if they fit in a page together, or
+    /// else shares their entries evenly between them.
+    fn rebalance(
+        &self,
+        separators: &mut Vec<Entry>,
+        children: &mut Vec<PageId>,
+        i: usize,
+        layout: &RowLayout,
+    ) -> Result<(), CrustyError> {
+        let l = if i + 1 < children.len() { i } else { i - 1 };
+        let (left_id, right_id) = (children[l], children[l + 1]);
+        let left = self.read(left_id, layout)?;
+        let right = self.read(right_id, layout)?;
+        let joined = Node::join(left, separators[l].clone(), right);
+        if joined.encode(layout).len() <= MAX_VALUE_SIZE {
+            self.write(left_id, &joined, layout)?;
+            self.release(right_id, layout)?;
+            separators.remove(l);
+            children.remove(l + 1);
+        } else {
+            let (left, separator, right) = joined.split(right_id, layout);
+            self.write(left_id, &left, layout)?;
+            self.write(right_id, &right, layout)?;
+            separators[l] = separator;
+        }
+        Ok(())
+    }
+
+    /// Finds the entries whose keys are between the bounds, in order. A bound with fewer
+    /// values than the key has columns is compared with the first columns of the key.
+    ///
+    /// # Arguments
+    ///
+    /// * `lower` - Lowest key to return.
+    /// * `upper` - Highest key to return.
+    pub(crate) fn range(
+        &self,
+        lower: Bound<&[Field]>,
+        upper: Bound<&[Field]>,
+    ) -> Result<Vec<(Vec<Field>, ValueId)>, CrustyError> {
+        let _latch = self.latch.read().unwrap();
+        let (layout, dtypes) = self.key_format()?;
+        let lower = lower.map(|key| widen(key, &dtypes));
+        let upper = upper.map(|key| widen(key, &dtypes));
+        let (mut page_id, _) = self.meta()?;
+        let mut found = Vec::new();
+        loop {
+            match self.read(page_id, &layout)? {
+                Node::Internal {
+                    separators,
+                    children,
+                } => {
+                    page_id = children[separators.partition_point(|s| below(&s.key, &lower))];
+                }
+                Node::Leaf { entries, next } => {
+                    for entry in entries {
+                        if below(&entry.key, &lower) {
+                            continue;
+                        }
+                        if above(&entry.key, &upper) {
+                            return Ok(found);
+                        }
+                        let id = ValueId::new_slot(self.table_id, entry.page_id, entry.slot_id);
+                        found.push((entry.key, id));
+                    }
+                    if next == NO_PAGE {
+                        return Ok(found);
+                    }
+                    page_id = next;
+                }
+                Node::Free { .. } => return Err(self.free_in_tree(page_id)),
+            }
+        }
+    }
+
+    fn free_in_tree(&self, page_id: PageId) -> CrustyError {
+        CrustyError::CrustyError(format!(
+            "Free page {} of index {} is in the tree",
+            page_id, self.index_id
+        ))
+    }
+}
+
+/// Gives the values of a key the types of their columns, where they differ but compare alike.
+pub(crate) fn widen(key: &[Field], dtypes: &[DataType]) -> Vec<Field> {
+    key.iter()
+        .zip(dtypes)
+        .map(|(field, dtype)| match (field, dtype) {
+            (Field::IntField(x), DataType::BigInt) => Field::BigIntField(*x as i64),
+            _ => field.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::index::{IndexMethod, SecondaryIndex};
+    use common::testutil::*;
+    use common::TableSchema;
+    use temp_testdir::TempDir;
+
+    fn new_tree(tdir: &TempDir) -> BTree {
+        let mut path = tdir.to_path_buf();
+        path.push("1.idx");
+        let hf = Arc::new(HeapFile::new(path).unwrap());
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::String]);
+        let mut table = Table::new("t".to_string(), schema);
+        table.add_secondary_index(SecondaryIndex {
+            name: "t_b_a".to_string(),
+            container_id: 1,
+            columns: vec![1, 0],
+            unique: false,
+            method: IndexMethod::BTree,
+            constraint: false,
+        });
+        let table = Arc::new(RwLock::new(table));
+        let bp = Arc::new(BufferPool::new(16));
+        let (tree, created) = BTree::open(1, 0, table, hf, bp).unwrap();
+        assert!(created);
+        tree
+    }
+
+    /// Keys long enough for a node to hold about twenty, two to each value of the first
+    /// column.
+    fn key(i: u16) -> Vec<Field> {
+        let b = format!("{:04}{}", i / 2, "x".repeat(200));
+        vec![Field::StringField(b), Field::IntField(i as i32)]
+    }
+
+    fn id(i: u16) -> ValueId {
+        ValueId::new_slot(0, i / 10, i % 10)
+    }
+
+    /// Numbers below 1000 in a scrambled order.
+    fn scrambled() -> impl Iterator<Item = u16> {
+        (0..1000u32).map(|i| (i * 7919 % 1000) as u16)
+    }
+
+    /// Number of levels of the tree.
+    fn height(tree: &BTree) -> usize {
+        let (layout, _) = tree.key_format().unwrap();
+        let (mut page_id, _) = tree.meta().unwrap();
+        let mut height = 1;
+        while let Node::Internal { children, .. } = tree.read(page_id, &layout).unwrap() {
+            page_id = children[0];
+            height += 1;
+        }
+        height
+    }
+
+    fn all(tree: &BTree) -> Vec<(Vec<Field>, ValueId)> {
+        tree.range(Bound::Unbounded, Bound::Unbounded).unwrap()
+    }
+
+    #[test]
+    fn hs_bt_insert_range() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let tree = new_tree(&tdir);
+        for i in scrambled() {
+            tree.insert(key(i), id(i)).unwrap();
+        }
+        // Adding an entry twice keeps one
+        tree.insert(key(3), id(3)).unwrap();
+        let expected: Vec<(Vec<Field>, ValueId)> = (0..1000).map(|i| (key(i), id(i))).collect();
+        assert_eq!(expected, all(&tree));
+        // Internal nodes have split too
+        assert_eq!(3, height(&tree));
+
+        let point = key(7);
+        let found = tree
+            .range(Bound::Included(&point), Bound::Included(&point))
+            .unwrap();
+        assert_eq!(vec![(key(7), id(7))], found);
+        // Bounds on the first column only
+        let (low, high) = (key(200)[..1].to_vec(), key(210)[..1].to_vec());
+        let found = tree
+            .range(Bound::Included(&low), Bound::Excluded(&high))
+            .unwrap();
+        assert_eq!(expected[200..210].to_vec(), found);
+        let found = tree
+            .range(Bound::Excluded(&low), Bound::Included(&high))
+            .unwrap();
+        assert_eq!(expected[202..212].to_vec(), found);
+        let found = tree
+            .range(Bound::Excluded(&high), Bound::Unbounded)
+            .unwrap();
+        assert_eq!(expected[212..].to_vec(), found);
+    }
+
+    #[test]
+    fn hs_bt_delete_merges() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let tree = new_tree(&tdir);
+        for i in scrambled() {
+            tree.insert(key(i), id(i)).unwrap();
+        }
+        let num_pages = tree.hf.num_pages();
+        for i in scrambled().filter(|i| i % 2 == 0) {
+            assert!(tree.delete(key(i), id(i)).unwrap());
+        }
+        assert!(!tree.delete(key(0), id(0)).unwrap());
+        // The key has to match as well as the row
+        assert!(!tree.delete(key(2), id(3)).unwrap());
+        let expected: Vec<(Vec<Field>, ValueId)> = (0..1000)
+            .filter(|i| i % 2 == 1)
+            .map(|i| (key(i), id(i)))
+            .collect();
+        assert_eq!(expected, all(&tree));
+        assert_eq!(3, height(&tree));
+
+        for i in scrambled().filter(|i| i % 2 == 1) {
+            assert!(tree.delete(key(i), id(i)).unwrap());
+        }
+        assert!(all(&tree).is_empty());
+        assert_eq!(1, height(&tree));
+
+        // The pages freed by merges are reused
+        for i in scrambled() {
+            tree.insert(key(i), id(i)).unwrap();
+        }
+        assert_eq!(1000, all(&tree).len());
+        assert_eq!(num_pages, tree.hf.num_pages());
+    }
+
+    #[test]
+    fn hs_bt_long_key() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let tree = new_tree(&tdir);
+        let key = vec![
+            Field::StringField("x".repeat(MAX_KEY_SIZE)),
+            Field::IntField(0),
+        ];
+        assert!(tree.insert(key, id(0)).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+use crate::heapfile::HeapFile;
+use crate::page::Page;
+use crate::wal::Wal;
+use common::ids::{ContainerId, PageId};
+use common::CrustyError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A single slot in the buffer pool holding one page of one container.
+struct Frame {
+    container_id: ContainerId,
+    page_id: PageId,
+    /// The heap file the page belongs to, used to write the page back on eviction.
+    hf: Arc<HeapFile>,
+    page: Page,
+    pin_count: usize,
+    dirty: bool,
+    /// Reference bit for the clock eviction policy.
+    referenced: bool,
+}
+
+/// State guarded by the buffer pool latch.
+struct PoolState {
+    frames: Vec<Frame>,
+    lookup: HashMap<(ContainerId, PageId), usize>,
+    /// Position of the clock hand in frames.
+    hand: usize,
+}
+
+/// A bounded cache of pages shared by every container of a storage manager.
+///
+/// Pages are handed out as copies; changes come back either through `write_page`
+/// or by running a closure against the cached page with `with_page_mut`. Dirty pages
+/// are only written to their heap file when they are evicted or flushed.
+/// Victims are chosen with the clock policy and pinned frames are never evicted.
+/// If the pool has a write-ahead log it is flushed before any page is written.
+pub(crate) struct BufferPool {
+    capacity: usize,
+    wal: Option<Arc<Wal>>,
+    state: Mutex<PoolState>,
+    /// Number of page requests served, hits and misses included.
+    requests: AtomicU64,
+}
+
+impl BufferPool {
+    /// Create a new buffer pool that can hold up to capacity pages.
+    #[allow(dead_code)]
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self::build(capacity, None)
+    }
+
+    /// Create a buffer pool that forces wal before writing back a dirty page.
+    pub(crate) fn with_wal(capacity: usize, wal: Arc<Wal>) -> Self {
+        Self::build(capacity, Some(wal))
+    }
+
+    fn build(capacity: usize, wal: Option<Arc<Wal>>) -> Self {
+        assert!(capacity > 0, "A buffer pool needs at least one frame");
+        BufferPool {
+            capacity,
+            wal,
+            state: Mutex::new(PoolState {
+                frames: Vec::with_capacity(capacity),
+                lookup: HashMap::new(),
+                hand: 0,
+            }),
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// The maximum number of pages the pool holds.
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of pages currently cached.
+    #[allow(dead_code)]
+    pub(crate) fn len(&self) -> usize {
+        self.state.lock().unwrap().frames.len()
+    }
+
+    /// Number of page requests made against the pool.
+    pub(crate) fn request_count(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Get a copy of a page, reading it from the heap file on a miss.
+    /// If pin is set the page cannot be evicted until `unpin` is called.
+    pub(crate) fn get_page(
+        &self,
+        container_id: ContainerId,
+        page_id: PageId,
+        hf: &Arc<HeapFile>,
+        pin: bool,
+    ) -> Result<Page, CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        let idx = self.fetch(&mut state, container_id, page_id, hf)?;
+        let frame = &mut state.frames[idx];
+        if pin {
+            frame.pin_count += 1;
+        }
+        Ok(frame.page.clone())
+    }
+
+    /// Run f against the cached page. The page is marked dirty if f returns Some.
+    /// The pool latch is held while f runs, so a read-modify-write of a page is atomic
+    /// with respect to other callers.
+    pub(crate) fn with_page_mut<T>(
+        &self,
+        container_id: ContainerId,
+        page_id: PageId,
+        hf: &Arc<HeapFile>,
+        f: impl FnOnce(&mut Page) -> Option<T>,
+    ) -> Result<Option<T>, CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        let idx = self.fetch(&mut state, container_id, page_id, hf)?;
+        let frame = &mut state.frames[idx];
+        let res = f(&mut frame.page);
+        if res.is_some() {
+            frame.dirty = true;
+        }
+        Ok(res)
+    }
+
+    /// Replace the cached copy of a page, marking it dirty.
+    pub(crate) fn write_page(
+        &self,
+        container_id: ContainerId,
+        page: Page,
+        hf: &Arc<HeapFile>,
+    ) -> Result<(), CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        let page_id = page.get_page_id();
+        let idx = match state.lookup.get(&(container_id, page_id)) {
+            Some(idx) => *idx,
+            None => {
+                let idx = self.free_frame(&mut state)?;
+                let frame = Frame {
+                    container_id,
+                    page_id,
+                    hf: hf.clone(),
+                    page,
+                    pin_count: 0,
+                    dirty: true,
+                    referenced: true,
+                };
+                Self::install(&mut state, idx, frame);
+                return Ok(());
+            }
+        };
+        let frame = &mut state.frames[idx];
+        frame.page = page;
+        frame.dirty = true;
+        frame.referenced = true;
+        Ok(())
+    }
+
+    /// Release one pin on a page. Unpinning a page that is not pinned is a no-op.
+    pub(crate) fn unpin(&self, container_id: ContainerId, page_id: PageId) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(idx) = state.lookup.get(&(container_id, page_id)).copied() {
+            let frame = &mut state.frames[idx];
+            frame.pin_count = frame.pin_count.saturating_sub(1);
+        }
+    }
+
+    /// Write every dirty page back to its heap file. Pages stay cached.
+    pub(crate) fn flush_all(&self) -> Result<(), CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        for frame in state.frames.iter_mut() {
+            self.write_back(frame)?;
+        }
+        Ok(())
+    }
+
+    /// Flush every dirty page and then empty the pool, dropping any pins.
+    pub(crate) fn clear(&self) -> Result<(), CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        for frame in state.frames.iter_mut() {
+            self.write_back(frame)?;
+        }
+        state.frames.clear();
+        state.lookup.clear();
+        state.hand = 0;
+        Ok(())
+    }
+
+    /// Drop all pages of a container without writing them back.
+    pub(crate) fn discard_container(&self, container_id: ContainerId) {
+        let mut state = self.state.lock().unwrap();
+        state.frames.retain(|f| f.container_id != container_id);
+        Self::rebuild_lookup(&mut state);
+    }
+
+    /// Drop every page without writing it back.
+    pub(crate) fn discard_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.frames.clear();
+        state.lookup.clear();
+        state.hand = 0;
+    }
+
+    /// Find the frame for a page, loading it from the heap file on a miss.
+    fn fetch(
+        &self,
+        state: &mut PoolState,
+        container_id: ContainerId,
+        page_id: PageId,
+        hf: &Arc<HeapFile>,
+    ) -> Result<usize, CrustyError> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(idx) = state.lookup.get(&(container_id, page_id)).copied() {
+            state.frames[idx].referenced = true;
+            return Ok(idx);
+        }
+        if page_id >= hf.num_pages() {
+            return Err(CrustyError::CrustyError(format!(
+                "Page {} does not exist in container {}",
+                page_id, container_id
+            )));
+        }
+        let idx = self.free_frame(state)?;
+        let page = hf.read_page_from_file(page_id)?;
+        let frame = Frame {
+            container_id,
+            page_id,
+            hf: hf.clone(),
+            page,
+            pin_count: 0,
+            dirty: false,
+            referenced: true,
+        };
+        Self::install(state, idx, frame);
+        Ok(idx)
+    }
+
+    /// Return the index of a frame that can be (re)used, evicting a page if the pool is full.
+    /// An index equal to frames.len() means a fresh frame should be pushed.
+    fn free_frame(&self, state: &mut PoolState) -> Result<usize, CrustyError> {
+        if state.frames.len() < self.capacity {
+            return Ok(state.frames.len());
+        }
+        // Two sweeps of the clock: the first may only clear reference bits.
+        for _ in 0..2 * state.frames.len() {
+            let idx = state.hand;
+            state.hand = (state.hand + 1) % state.frames.len();
+            let frame = &mut state.frames[idx];
+            if frame.pin_count > 0 {
+                continue;
+            }
+            if frame.referenced {
+                frame.referenced = false;
+                continue;
+            }
+            self.write_back(frame)?;
+            let key = (frame.container_id, frame.page_id);
+            state.lookup.remove(&key);
+            return Ok(idx);
+        }
+        Err(CrustyError::ExecutionError(String::from(
+            "Buffer pool is full and every page is pinned",
+        )))
+    }
+
+    fn install(state: &mut PoolState, idx: usize, frame: Frame) {
+        state
+            .lookup
+            .insert((frame.container_id, frame.page_id), idx);
+        if idx == state.frames.len() {
+            state.frames.push(frame);
+        } else {
+            state.frames[idx] = frame;
+        }
+    }
+
+    fn write_back(&self, frame: &mut Frame) -> Result<(), CrustyError> {
+        if frame.dirty {
+            if let Some(wal) = &self.wal {
+                wal.flush()?;
+            }
+            frame.hf.write_page_to_file(frame.page.clone())?;
+            frame.dirty = false;
+        }
+        Ok(())
+    }
+
+    fn rebuild_lookup(state: &mut PoolState) {
+        state.lookup = state
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(idx, f)| ((f.container_id, f.page_id), idx))
+            .collect();
+        state.hand = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::get_random_page;
+    use common::testutil::*;
+    use temp_testdir::TempDir;
+
+    fn new_hf(tdir: &TempDir, num_pages: PageId) -> Arc<HeapFile> {
+        let mut f = tdir.to_path_buf();
+        f.push(gen_rand_string(4));
+        f.set_extension("hf");
+        let hf = Arc::new(HeapFile::new(f).unwrap());
+        for i in 0..num_pages {
+            let (p, _) = get_random_page(i, 5, 10, 20);
+            hf.write_page_to_file(p).unwrap();
+        }
+        hf
+    }
+
+    #[test]
+    fn hs_bp_hits_do_not_read() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let hf = new_hf(&tdir, 4);
+        let bp = BufferPool::new(4);
+        for _ in 0..3 {
+            for pid in 0..4 {
+                let p = bp.get_page(0, pid, &hf, false).unwrap();
+                assert_eq!(pid, p.get_page_id());
+            }
+        }
+        assert_eq!(12, bp.request_count());
+        #[cfg(feature = "profile")]
+        assert_eq!(4, hf.read_count.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn hs_bp_evict_writes_back_dirty() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let hf = new_hf(&tdir, 3);
+        let bp = BufferPool::new(2);
+        let bytes = get_random_byte_vec(30);
+        let slot = bp
+            .with_page_mut(0, 0, &hf, |p| p.add_value(&bytes))
+            .unwrap()
+            .unwrap();
+        // Touch the other pages so page 0 is evicted
+        bp.get_page(0, 1, &hf, false).unwrap();
+        bp.get_page(0, 2, &hf, false).unwrap();
+        assert_eq!(2, bp.len());
+        let on_disk = hf.read_page_from_file(0).unwrap();
+        assert_eq!(Some(bytes), on_disk.get_value(slot));
+    }
+
+    #[test]
+    fn hs_bp_pinned_not_evicted() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let hf = new_hf(&tdir, 3);
+        let bp = BufferPool::new(2);
+        bp.get_page(0, 0, &hf, true).unwrap();
+        bp.get_page(0, 1, &hf, true).unwrap();
+        assert!(bp.get_page(0, 2, &hf, false).is_err());
+
+        bp.unpin(0, 1);
+        bp.get_page(0, 2, &hf, false).unwrap();
+        // Page 0 is still pinned so it must still be cached, and page 1 was evicted
+        let cached = |pid| bp.state.lock().unwrap().lookup.contains_key(&(0, pid));
+        assert!(cached(0));
+        assert!(!cached(1));
+        #[cfg(feature = "profile")]
+        let reads = hf.read_count.load(Ordering::Relaxed);
+        bp.get_page(0, 0, &hf, false).unwrap();
+        #[cfg(feature = "profile")]
+        assert_eq!(reads, hf.read_count.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn hs_bp_clear_flushes() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let hf = new_hf(&tdir, 1);
+        let bp = BufferPool::new(2);
+        let (p, _) = get_random_page(0, 3, 10, 20);
+        let bytes = p.get_bytes();
+        bp.write_page(0, p, &hf).unwrap();
+        bp.clear().unwrap();
+        assert_eq!(0, bp.len());
+        assert_eq!(bytes, hf.read_page_from_file(0).unwrap().get_bytes());
+    }
+}
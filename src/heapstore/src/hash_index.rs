@@ -0,0 +1,762 @@
+use crate::btree::widen;
+use crate::buffer_pool::BufferPool;
+use crate::heapfile::HeapFile;
+use crate::page::MAX_VALUE_SIZE;
+use common::ids::{ContainerId, PageId, SlotId, ValueId};
+use common::index::{SecondaryIndex, MAX_KEY_SIZE};
+use common::row::RowLayout;
+use common::table::Table;
+use common::{CrustyError, DataType, Field, Tuple};
+use std::convert::TryInto;
+use std::sync::{Arc, RwLock};
+
+/// Page recording the depth of the directory, the first free page and the pages of the
+/// directory.
+const META_PAGE: PageId = 0;
+/// Marks the end of a chain of pages.
+const NO_PAGE: PageId = PageId::MAX;
+/// Number of slots of the directory each of its pages holds.
+const DIRECTORY_PAGE_SLOTS: usize = 1024;
+/// Deepest the directory grows, when it has a slot for every page a container can have.
+const MAX_DEPTH: u8 = 16;
+/// Bytes a bucket page takes before its entries.
+const BUCKET_HEADER_SIZE: usize = 6;
+
+const DIRECTORY: u8 = 0;
+const BUCKET: u8 = 1;
+const FREE: u8 = 2;
+
+/// Hashes the stored form of a key with FNV-1a, which, unlike the hashers of the standard
+/// library, is sure to give a key the same hash after a restart.
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The slot of the directory a hash falls in, at a depth.
+fn slot(hash: u64, depth: u8) -> usize {
+    (hash & ((1u64 << depth) - 1)) as usize
+}
+
+/// An entry of the index: the hash of a key, the key and where the row holding it is stored.
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    hash: u64,
+    key: Vec<Field>,
+    page_id: PageId,
+    slot_id: SlotId,
+}
+
+impl Entry {
+    fn new(key: Vec<Field>, id: ValueId, layout: &RowLayout) -> Self {
+        Entry {
+            hash: hash(&layout.encode(&Tuple::new(key.clone()), 0)),
+            key,
+            page_id: id.page_id.unwrap(),
+            slot_id: id.slot_id.unwrap(),
+        }
+    }
+
+    fn encode(&self, layout: &RowLayout, bytes: &mut Vec<u8>) {
+        let key = layout.encode(&Tuple::new(self.key.clone()), 0);
+        bytes.extend_from_slice(&self.hash.to_le_bytes());
+        bytes.extend_from_slice(&(key.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&key);
+        bytes.extend_from_slice(&self.page_id.to_le_bytes());
+        bytes.extend_from_slice(&self.slot_id.to_le_bytes());
+    }
+
+    /// Reads an entry from the start of bytes, returning it and the bytes it took.
+    fn decode(layout: &RowLayout, bytes: &[u8]) -> (Self, usize) {
+        let len = u16::from_le_bytes(bytes[8..10].try_into().unwrap()) as usize;
+        let rest = &bytes[10 + len..];
+        let entry = Entry {
+            hash: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            key: layout.decode(&bytes[10..10 + len]).field_vals,
+            page_id: PageId::from_le_bytes(rest[..2].try_into().unwrap()),
+            slot_id: SlotId::from_le_bytes(rest[2..4].try_into().unwrap()),
+        };
+        (entry, len + 14)
+    }
+
+    fn size(&self, layout: &RowLayout) -> usize {
+        let mut bytes = Vec::new();
+        self.encode(layout, &mut bytes);
+        bytes.len()
+    }
+}
+
+/// A page of the index, stored as its only value.
+#[derive(Debug, PartialEq)]
+enum Node {
+    /// Part of the directory: the first page of the bucket of each of its slots.
+    Directory { buckets: Vec<PageId> },
+    /// Entries whose hashes end with the same depth bits, and the overflow page holding more
+    /// of them.
+    Bucket {
+        depth: u8,
+        entries: Vec<Entry>,
+        overflow: PageId,
+    },
+    /// A page no longer used, and the next free page.
+    Free { next: PageId },
+}
+
+impl Node {
+    fn encode(&self, layout: &RowLayout) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Node::Directory { buckets } => {
+                bytes.push(DIRECTORY);
+                bytes.extend_from_slice(&(buckets.len() as u16).to_le_bytes());
+                for bucket in buckets {
+                    bytes.extend_from_slice(&bucket.to_le_bytes());
+                }
+            }
+            Node::Bucket {
+                depth,
+                entries,
+                overflow,
+            } => {
+                bytes.push(BUCKET);
+                bytes.push(*depth);
+                bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(&overflow.to_le_bytes());
+                for entry in entries {
+                    entry.encode(layout, &mut bytes);
+                }
+            }
+            Node::Free { next } => {
+                bytes.push(FREE);
+                bytes.extend_from_slice(&next.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn decode(layout: &RowLayout, bytes: &[u8]) -> Self {
+        let read_u16 = |at: usize| u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap());
+        match bytes[0] {
+            DIRECTORY => Node::Directory {
+                buckets: (0..read_u16(1) as usize)
+                    .map(|i| read_u16(3 + 2 * i))
+                    .collect(),
+            },
+            BUCKET => {
+                let mut at = BUCKET_HEADER_SIZE;
+                let mut entries = Vec::new();
+                for _ in 0..read_u16(2) {
+                    let (entry, len) = Entry::decode(layout, &bytes[at..]);
+                    entries.push(entry);
+                    at += len;
+                }
+                Node::Bucket {
+                    depth: bytes[1],
+                    entries,
+                    overflow: read_u16(4),
+                }
+            }
+            _ => Node::Free { next: read_u16(1) },
+        }
+    }
+}
+
+/// The contents of the meta page.
+struct Meta {
+    /// Number of bits of the hashes the directory tells apart.
+    depth: u8,
+    /// First free page.
+    free: PageId,
+    /// Pages of the directory, in order.
+    directory: Vec<PageId>,
+}
+
+/// A page of a bucket: its first page or one of its overflow pages.
+struct BucketPage {
+    page_id: PageId,
+    entries: Vec<Entry>,
+    overflow: PageId,
+}
+
+/// An extendible hash table from the keys of a secondary index to the rows of the table
+/// holding them, which only finds the rows holding a given key. The table is stored in a
+/// heap file of its own, through the buffer pool. A directory, indexed by the last bits of
+/// the hashes of the keys, gives the bucket holding each key. A bucket that no longer fits in
+/// its page is split in two by one more bit of the hashes, doubling the directory when it
+/// does not tell that bit apart yet. Entries that splitting cannot separate, such as those
+/// of rows sharing a key, go in overflow pages chained to their bucket. Buckets are not
+/// merged, but overflow pages left empty are freed and reused.
+///
+/// As with `BTree`, the index holds an entry for every version of a row that is stored, is
+/// latched as a whole, and is rebuilt after a crash.
+pub(crate) struct HashIndex {
+    /// Container of the index.
+    pub(crate) index_id: ContainerId,
+    /// Container of the table.
+    pub(crate) table_id: ContainerId,
+    table: Arc<RwLock<Table>>,
+    hf: Arc<HeapFile>,
+    buffer_pool: Arc<BufferPool>,
+    latch: RwLock<()>,
+}
+
+impl HashIndex {
+    /// Opens the index stored in a heap file, creating an empty index if the file is empty.
+    /// Returns the index and whether it was created.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_id` - Container of the index.
+    /// * `table_id` - Container of the table.
+    /// * `table` - The table, whose secondary index stored in index_id gives the key.
+    /// * `hf` - Heap file of the index.
+    /// * `buffer_pool` - Buffer pool to read and write the pages through.
+    pub(crate) fn open(
+        index_id: ContainerId,
+        table_id: ContainerId,
+        table: Arc<RwLock<Table>>,
+        hf: Arc<HeapFile>,
+        buffer_pool: Arc<BufferPool>,
+    ) -> Result<(Self, bool), CrustyError> {
+        let index = HashIndex {
+            index_id,
+            table_id,
+            table,
+            hf,
+            buffer_pool,
+            latch: RwLock::new(()),
+        };
+        let (layout, _) = index.key_format()?;
+        if index.hf.num_pages() > 0 {
+            return Ok((index, false));
+        }
+        index.hf.allocate_page()?;
+        let directory = index.hf.allocate_page()?;
+        let bucket = index.hf.allocate_page()?;
+        let empty = Node::Bucket {
+            depth: 0,
+            entries: Vec::new(),
+            overflow: NO_PAGE,
+        };
+        index.write(bucket, &empty, &layout)?;
+        let buckets = vec![bucket];
+        index.write(directory, &Node::Directory { buckets }, &layout)?;
+        index.set_meta(&Meta {
+            depth: 0,
+            free: NO_PAGE,
+            directory: vec![directory],
+        })?;
+        Ok((index, true))
+    }
+
+    fn index(&self, table: &Table) -> Result<SecondaryIndex, CrustyError> {
+        table
+            .secondary_index(self.index_id)
+            .cloned()
+            .ok_or_else(|| {
+                CrustyError::CrustyError(format!(
+                    "Table {} has no index in container {}",
+                    table.name, self.index_id
+                ))
+            })
+    }
+
+    /// Layout the keys are stored in, and the types of their columns.
+    fn key_format(&self) -> Result<(RowLayout, Vec<DataType>), CrustyError> {
+        let table = self.table.read().unwrap();
+        let index = self.index(&table)?;
+        let dtypes = index
+            .columns
+            .iter()
+            .map(|i| table.schema.get_attribute(*i).unwrap().dtype().clone())
+            .collect();
+        Ok((index.key_layout(&table.schema), dtypes))
+    }
+
+    /// Reads the key of a stored row.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Row as stored in the table.
+    pub(crate) fn key(&self, row: &[u8]) -> Result<Vec<Field>, CrustyError> {
+        let table = self.table.read().unwrap();
+        let index = self.index(&table)?;
+        Ok(table.decode_fields(row, &index.columns))
+    }
+
+    fn read(&self, page_id: PageId, layout: &RowLayout) -> Result<Node, CrustyError> {
+        let page = self
+            .buffer_pool
+            .get_page(self.index_id, page_id, &self.hf, false)?;
+        match page.get_value(0) {
+            Some(bytes) => Ok(Node::decode(layout, &bytes)),
+            None => Err(self.unexpected(page_id, "no node")),
+        }
+    }
+
+    fn write(&self, page_id: PageId, node: &Node, layout: &RowLayout) -> Result<(), CrustyError> {
+        self.write_bytes(page_id, &node.encode(layout))
+    }
+
+    fn write_bytes(&self, page_id: PageId, bytes: &[u8]) -> Result<(), CrustyError> {
+        self.buffer_pool
+            .with_page_mut(self.index_id, page_id, &self.hf, |page| {
+                page.put_value_at(0, bytes)
+            })?
+            .ok_or_else(|| {
+                CrustyError::CrustyError(format!(
+                    "Node of {} bytes does not fit in page {} of index {}",
+                    bytes.len(),
+                    page_id,
+                    self.index_id
+                ))
+            })
+    }
+
+    fn meta(&self) -> Result<Meta, CrustyError> {
+        let page = self
+            .buffer_pool
+            .get_page(self.index_id, META_PAGE, &self.hf, false)?;
+        let bytes = page
+            .get_value(0)
+            .ok_or_else(|| self.unexpected(META_PAGE, "no directory"))?;
+        let read_u16 = |at: usize| u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap());
+        Ok(Meta {
+            depth: bytes[0],
+            free: read_u16(1),
+            directory: (0..read_u16(3) as usize)
+                .map(|i| read_u16(5 + 2 * i))
+                .collect(),
+        })
+    }
+
+    fn set_meta(&self, meta: &Meta) -> Result<(), CrustyError> {
+        let mut bytes = vec![meta.depth];
+        bytes.extend_from_slice(&meta.free.to_le_bytes());
+        bytes.extend_from_slice(&(meta.directory.len() as u16).to_le_bytes());
+        for page_id in &meta.directory {
+            bytes.extend_from_slice(&page_id.to_le_bytes());
+        }
+        self.write_bytes(META_PAGE, &bytes)
+    }
+
+    /// Takes a page, reusing a free page if there is one. The caller saves the meta.
+    fn allocate(&self, meta: &mut Meta, layout: &RowLayout) -> Result<PageId, CrustyError> {
+        if meta.free == NO_PAGE {
+            return self.hf.allocate_page();
+        }
+        let page_id = meta.free;
+        match self.read(page_id, layout)? {
+            Node::Free { next } => meta.free = next,
+            _ => return Err(self.unexpected(page_id, "a node, but is free")),
+        }
+        Ok(page_id)
+    }
+
+    /// Adds a page that is no longer used to the free pages. The caller saves the meta.
+    fn release(
+        &self,
+        meta: &mut Meta,
+        page_id: PageId,
+        layout: &RowLayout,
+    ) -> Result<(), CrustyError> {
+        self.write(page_id, &Node::Free { next: meta.free }, layout)?;
+        meta.free = page_id;
+        Ok(())
+    }
+
+    /// The first page of the bucket a hash falls in.
+    fn bucket(&self, meta: &Meta, hash: u64, layout: &RowLayout) -> Result<PageId, CrustyError> {
+        let slot = slot(hash, meta.depth);
+        let page_id = meta.directory[slot / DIRECTORY_PAGE_SLOTS];
+        match self.read(page_id, layout)? {
+            Node::Directory { buckets } => Ok(buckets[slot % DIRECTORY_PAGE_SLOTS]),
+            _ => Err(self.unexpected(page_id, "no directory")),
+        }
+    }
+
+    /// The first page of the bucket of every slot of the directory.
+    fn directory(&self, meta: &Meta, layout: &RowLayout) -> Result<Vec<PageId>, CrustyError> {
+        let mut directory = Vec::with_capacity(1 << meta.depth);
+        for page_id in &meta.directory {
+            match self.read(*page_id, layout)? {
+                Node::Directory { buckets } => directory.extend(buckets),
+                _ => return Err(self.unexpected(*page_id, "no directory")),
+            }
+        }
+        Ok(directory)
+    }
+
+    /// Writes the directory, taking pages for it as it grows. The caller saves the meta.
+    fn set_directory(
+        &self,
+        meta: &mut Meta,
+        directory: &[PageId],
+        layout: &RowLayout,
+    ) -> Result<(), CrustyError> {
+        for (i, slots) in directory.chunks(DIRECTORY_PAGE_SLOTS).enumerate() {
+            if i == meta.directory.len() {
+                let page_id = self.allocate(meta, layout)?;
+                meta.directory.push(page_id);
+            }
+            let buckets = slots.to_vec();
+            self.write(meta.directory[i], &Node::Directory { buckets }, layout)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the pages of a bucket, returning its depth and its pages in order.
+    fn chain(
+        &self,
+        first: PageId,
+        layout: &RowLayout,
+    ) -> Result<(u8, Vec<BucketPage>), CrustyError> {
+        let mut pages = Vec::new();
+        let mut bucket_depth = 0;
+        let mut page_id = first;
+        while page_id != NO_PAGE {
+            match self.read(page_id, layout)? {
+                Node::Bucket {
+                    depth,
+                    entries,
+                    overflow,
+                } => {
+                    bucket_depth = depth;
+                    pages.push(BucketPage {
+                        page_id,
+                        entries,
+                        overflow,
+                    });
+                    page_id = overflow;
+                }
+                _ => return Err(self.unexpected(page_id, "no bucket")),
+            }
+        }
+        Ok((bucket_depth, pages))
+    }
+
+    fn write_page(
+        &self,
+        depth: u8,
+        page: &BucketPage,
+        layout: &RowLayout,
+    ) -> Result<(), CrustyError> {
+        let node = Node::Bucket {
+            depth,
+            entries: page.entries.clone(),
+            overflow: page.overflow,
+        };
+        self.write(page.page_id, &node, layout)
+    }
+
+    /// Writes the entries of a bucket to as few pages as they fit in, taking the given pages
+    /// first, in order, and freeing those left over. The caller saves the meta.
+    fn store_bucket(
+        &self,
+        meta: &mut Meta,
+        depth: u8,
+        mut page_ids: Vec<PageId>,
+        entries: Vec<Entry>,
+        layout: &RowLayout,
+    ) -> Result<(), CrustyError> {
+        let mut pages: Vec<Vec<Entry>> = vec![Vec::new()];
+        let mut size = BUCKET_HEADER_SIZE;
+        for entry in entries {
+            let len = entry.size(layout);
+            if size + len > MAX_VALUE_SIZE {
+                pages.push(Vec::new());
+                size = BUCKET_HEADER_SIZE;
+            }
+            size += len;
+            pages.last_mut().unwrap().push(entry);
+        }
+        while page_ids.len() < pages.len() {
+            page_ids.push(self.allocate(meta, layout)?);
+        }
+        for page_id in page_ids.split_off(pages.len()) {
+            self.release(meta, page_id, layout)?;
+        }
+        for (i, entries) in pages.into_iter().enumerate() {
+            let page = BucketPage {
+                page_id: page_ids[i],
+                entries,
+                overflow: page_ids.get(i + 1).copied().unwrap_or(NO_PAGE),
+            };
+            self.write_page(depth, &page, layout)?;
+        }
+        Ok(())
+    }
+
+    /// Splits a bucket in two by the bit of the hashes after its depth, doubling the
+    /// directory first if it does not tell that bit apart. The caller saves the meta.
+    fn split(
+        &self,
+        meta: &mut Meta,
+        first: PageId,
+        depth: u8,
+        pages: Vec<BucketPage>,
+        layout: &RowLayout,
+    ) -> Result<(), CrustyError> {
+        let mut directory = self.directory(meta, layout)?;
+        if depth == meta.depth {
+            directory.extend_from_within(..);
+            meta.depth += 1;
+        }
+        let bit = 1 << depth;
+        let new_first = self.allocate(meta, layout)?;
+        for (slot, bucket) in directory.iter_mut().enumerate() {
+            if *bucket == first && slot & bit != 0 {
+                *bucket = new_first;
+            }
+        }
+        let page_ids = pages.iter().map(|page| page.page_id).collect();
+        let (moved, kept): (Vec<Entry>, Vec<Entry>) = pages
+            .into_iter()
+            .flat_map(|page| page.entries)
+            .partition(|entry| entry.hash & bit as u64 != 0);
+        self.store_bucket(meta, depth + 1, page_ids, kept, layout)?;
+        self.store_bucket(meta, depth + 1, vec![new_first], moved, layout)?;
+        self.set_directory(meta, &directory, layout)
+    }
+
+    /// Adds an entry for a row. Adding an entry the index already has does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key the row holds.
+    /// * `id` - Where the row is stored.
+    pub(crate) fn insert(&self, key: Vec<Field>, id: ValueId) -> Result<(), CrustyError> {
+        let _latch = self.latch.write().unwrap();
+        let (layout, dtypes) = self.key_format()?;
+        let entry = Entry::new(widen(&key, &dtypes), id, &layout);
+        if layout.encode(&Tuple::new(entry.key.clone()), 0).len() > MAX_KEY_SIZE {
+            return Err(CrustyError::CrustyError(format!(
+                "Key of index {} is longer than {} bytes",
+                self.index_id, MAX_KEY_SIZE
+            )));
+        }
+        let len = entry.size(&layout);
+        loop {
+            let mut meta = self.meta()?;
+            let first = self.bucket(&meta, entry.hash, &layout)?;
+            let (depth, mut pages) = self.chain(first, &layout)?;
+            if pages.iter().any(|page| page.entries.contains(&entry)) {
+                return Ok(());
+            }
+            let free_page = pages.iter_mut().find(|page| {
+                let size: usize = page.entries.iter().map(|e| e.size(&layout)).sum();
+                BUCKET_HEADER_SIZE + size + len <= MAX_VALUE_SIZE
+            });
+            if let Some(page) = free_page {
+                page.entries.push(entry);
+                return self.write_page(depth, page, &layout);
+            }
+            // Splitting only makes room if the entries do not all share the new one's hash
+            let separable = pages
+                .iter()
+                .flat_map(|page| &page.entries)
+                .any(|e| e.hash != entry.hash);
+            if separable && depth < MAX_DEPTH {
+                self.split(&mut meta, first, depth, pages, &layout)?;
+                self.set_meta(&meta)?;
+                continue;
+            }
+            let page = BucketPage {
+                page_id: self.allocate(&mut meta, &layout)?,
+                entries: vec![entry],
+                overflow: NO_PAGE,
+            };
+            self.write_page(depth, &page, &layout)?;
+            let last = pages.last_mut().unwrap();
+            last.overflow = page.page_id;
+            self.write_page(depth, last, &layout)?;
+            return self.set_meta(&meta);
+        }
+    }
+
+    /// Removes the entry for a row, returning whether the index had it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key the row holds.
+    /// * `id` - Where the row is stored.
+    pub(crate) fn delete(&self, key: Vec<Field>, id: ValueId) -> Result<bool, CrustyError> {
+        let _latch = self.latch.write().unwrap();
+        let (layout, dtypes) = self.key_format()?;
+        let entry = Entry::new(widen(&key, &dtypes), id, &layout);
+        let mut meta = self.meta()?;
+        let first = self.bucket(&meta, entry.hash, &layout)?;
+        let (depth, mut pages) = self.chain(first, &layout)?;
+        let i = match pages.iter().position(|page| page.entries.contains(&entry)) {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+        pages[i].entries.retain(|e| *e != entry);
+        if !pages[i].entries.is_empty() || pages.len() == 1 {
+            self.write_page(depth, &pages[i], &layout)?;
+            return Ok(true);
+        }
+        // The overflow pages are packed again to free the one left empty
+        let page_ids = pages.iter().map(|page| page.page_id).collect();
+        let entries = pages.into_iter().flat_map(|page| page.entries).collect();
+        self.store_bucket(&mut meta, depth, page_ids, entries, &layout)?;
+        self.set_meta(&meta)?;
+        Ok(true)
+    }
+
+    /// Finds the rows holding a key, in the order they are stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Values of every column of the key.
+    pub(crate) fn find(&self, key: &[Field]) -> Result<Vec<(Vec<Field>, ValueId)>, CrustyError> {
+        let _latch = self.latch.read().unwrap();
+        let (layout, dtypes) = self.key_format()?;
+        if key.len() != dtypes.len() {
+            return Err(CrustyError::CrustyError(format!(
+                "Hash index {} only finds keys with a value for each of its {} columns",
+                self.index_id,
+                dtypes.len()
+            )));
+        }
+        let key = widen(key, &dtypes);
+        let hash = hash(&layout.encode(&Tuple::new(key.clone()), 0));
+        let meta = self.meta()?;
+        let first = self.bucket(&meta, hash, &layout)?;
+        let (_, pages) = self.chain(first, &layout)?;
+        let mut found: Vec<(Vec<Field>, ValueId)> = pages
+            .into_iter()
+            .flat_map(|page| page.entries)
+            .filter(|entry| entry.hash == hash && entry.key == key)
+            .map(|entry| {
+                let id = ValueId::new_slot(self.table_id, entry.page_id, entry.slot_id);
+                (entry.key, id)
+            })
+            .collect();
+        found.sort_by_key(|(_, id)| (id.page_id, id.slot_id));
+        Ok(found)
+    }
+
+    fn unexpected(&self, page_id: PageId, holds: &str) -> CrustyError {
+        CrustyError::CrustyError(format!(
+            "Page {} of index {} holds {}",
+            page_id, self.index_id, holds
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::index::IndexMethod;
+    use common::testutil::*;
+    use common::TableSchema;
+    use temp_testdir::TempDir;
+
+    fn open_index(path: &std::path::Path, bp: Arc<BufferPool>) -> (HashIndex, bool) {
+        let hf = Arc::new(HeapFile::new(path.to_path_buf()).unwrap());
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::String]);
+        let mut table = Table::new("t".to_string(), schema);
+        table.add_secondary_index(SecondaryIndex {
+            name: "t_a".to_string(),
+            container_id: 1,
+            columns: vec![0],
+            unique: false,
+            method: IndexMethod::Hash,
+            constraint: false,
+        });
+        let table = Arc::new(RwLock::new(table));
+        HashIndex::open(1, 0, table, hf, bp).unwrap()
+    }
+
+    fn key(i: i32) -> Vec<Field> {
+        vec![Field::IntField(i)]
+    }
+
+    fn id(i: u32) -> ValueId {
+        ValueId::new_slot(0, (i / 100) as PageId, (i % 100) as SlotId)
+    }
+
+    fn find(index: &HashIndex, i: i32) -> Vec<ValueId> {
+        let found = index.find(&key(i)).unwrap();
+        found.into_iter().map(|(_, id)| id).collect()
+    }
+
+    #[test]
+    fn hs_hash_insert_find() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let path = tdir.to_path_buf().join("1.idx");
+        let bp = Arc::new(BufferPool::new(16));
+        let (index, created) = open_index(&path, bp.clone());
+        assert!(created);
+        for i in 0..5000 {
+            index.insert(key(i), id(i as u32)).unwrap();
+        }
+        // Adding an entry twice keeps one
+        index.insert(key(3), id(3)).unwrap();
+        assert_eq!(vec![id(3)], find(&index, 3));
+        assert!(find(&index, 5000).is_empty());
+        // Buckets were split rather than overflowing
+        let meta = index.meta().unwrap();
+        assert!(meta.depth >= 4);
+        let (layout, _) = index.key_format().unwrap();
+        for first in index.directory(&meta, &layout).unwrap() {
+            assert_eq!(1, index.chain(first, &layout).unwrap().1.len());
+        }
+
+        // The index is found again when reopened
+        drop(index);
+        let (index, created) = open_index(&path, bp);
+        assert!(!created);
+        for i in (0..5000).step_by(7) {
+            assert_eq!(vec![id(i as u32)], find(&index, i));
+        }
+        assert!(index.find(&[]).is_err());
+    }
+
+    #[test]
+    fn hs_hash_overflow_delete() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let bp = Arc::new(BufferPool::new(16));
+        let (index, _) = open_index(&tdir.to_path_buf().join("1.idx"), bp);
+        // Rows sharing a key cannot be split apart, so they overflow
+        let ids: Vec<ValueId> = (0..2000).map(id).collect();
+        for id in &ids {
+            index.insert(key(7), *id).unwrap();
+        }
+        index.insert(key(8), id(0)).unwrap();
+        assert_eq!(ids, find(&index, 7));
+        assert_eq!(vec![id(0)], find(&index, 8));
+        let (layout, _) = index.key_format().unwrap();
+        let meta = index.meta().unwrap();
+        let first = index.bucket(&meta, Entry::new(key(7), id(0), &layout).hash, &layout);
+        let pages = || {
+            index
+                .chain(first.clone().unwrap(), &layout)
+                .unwrap()
+                .1
+                .len()
+        };
+        assert!(pages() > 2);
+
+        // Deleting frees the overflow pages left empty
+        assert!(!index.delete(key(7), id(2000)).unwrap());
+        for id in &ids[..1990] {
+            assert!(index.delete(key(7), *id).unwrap());
+        }
+        assert_eq!(1, pages());
+        assert_ne!(NO_PAGE, index.meta().unwrap().free);
+        assert_eq!(ids[1990..].to_vec(), find(&index, 7));
+        assert_eq!(vec![id(0)], find(&index, 8));
+
+        // Freed pages are reused
+        let num_pages = index.hf.num_pages();
+        for id in &ids[..1000] {
+            index.insert(key(7), *id).unwrap();
+        }
+        assert_eq!(num_pages, index.hf.num_pages());
+    }
+}
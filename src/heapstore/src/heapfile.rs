@@ -2,15 +2,11 @@ use crate::page::Page;
 use common::ids::PageId;
 use common::{CrustyError, PAGE_SIZE};
 use std::fs::{File, OpenOptions};
-use std::io::prelude::*;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU16, Ordering};
 use std::sync::{Arc, RwLock, Mutex};
 use std::os::unix::fs::FileExt;
 
-use std::io::BufWriter;
-use std::io::{Seek, SeekFrom};
-
 /// The struct for a heap file.  
 ///
 /// HINT: You likely will want to design for interior mutability for concurrent accesses.
@@ -26,6 +22,8 @@ pub(crate) struct HeapFile {
     // TODO milestone hs (add new fields)
     pub heap: Arc<RwLock<File>>,
     pub num_of_pages: Arc<Mutex<u16>>,
+    /// Where the heap file lives, so the storage manager can persist and reopen it.
+    pub file_path: PathBuf,
     //pub insertion: u8,
 
     // The following are for profiling/ correctness checks
@@ -55,13 +53,19 @@ impl HeapFile {
             }
         };
 
-        // TODO milestone hs
+        //an existing file already holds some pages
+        let num_of_pages = match file.metadata()
+        {
+            Ok(metadata) => (metadata.len() / PAGE_SIZE as u64) as u16,
+            Err(error) => return Err(CrustyError::IOError(error.to_string())),
+        };
 
         Ok(HeapFile {
             // TODO milestone hs init your new field(s)
             //heap: Arc::RwLock::new(file),
             heap: Arc::new(RwLock::new(file)),
-            num_of_pages: Arc::new(Mutex::new(0)),
+            num_of_pages: Arc::new(Mutex::new(num_of_pages)),
+            file_path,
             //required for correctness checks
             read_count: AtomicU16::new(0),
             write_count: AtomicU16::new(0),
@@ -114,8 +118,6 @@ impl HeapFile {
         //calculate the offset of the page being added
         let offset = page.header.p_id as u64 * PAGE_SIZE as u64;
 
-        println!("offset for insertion: {}", offset);
-
         //creating the buffer of bytes to be added to the file
         //let _page = my::Page::new(Page);
         //let buffer = Page::get_bytes(&_page);
@@ -134,20 +136,38 @@ impl HeapFile {
         //checking to see if we need to increase the number of pages
         let pid = page.header.p_id;
         let mut page_count = self.num_of_pages.lock().unwrap();
-        
-        println!("pagecount : {}, pageid : {}", *page_count, pid);
 
         if pid >= *page_count 
         {
             //self.num_of_pages  = page_count + 1;
             *page_count += 1;
-            println!("made it into pid >= pagecount");
-            //println!("page count: {}", self.num_of_pages.lock().unwrap());
         }
 
         return Ok(());
         //panic!("TODO milestone hs");
     }
+
+    /// Append a new empty page to the end of the file and return its id.
+    /// The page count lock is held for the whole write so two callers never get the same id.
+    pub(crate) fn allocate_page(&self) -> Result<PageId, CrustyError> {
+        #[cfg(feature = "profile")]
+        {
+            self.write_count.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut page_count = self.num_of_pages.lock().unwrap();
+        let pid = *page_count;
+        if pid == PageId::MAX {
+            return Err(CrustyError::CrustyError(format!(
+                "Heap file {} is full",
+                self.file_path.to_string_lossy()
+            )));
+        }
+        let offset = pid as u64 * PAGE_SIZE as u64;
+        let f = self.heap.read().unwrap();
+        f.write_at(&Page::new(pid).get_bytes(), offset)?;
+        *page_count += 1;
+        Ok(pid)
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,10 @@
+use crate::buffer_pool::BufferPool;
 use crate::heapfile::HeapFile;
+use crate::mvcc::{RecordKind, Snapshot, Version};
+use crate::overflow;
 use crate::page::PageIter;
-use common::ids::{ContainerId, PageId, TransactionId};
+use common::ids::{ContainerId, PageId, TransactionId, ValueId};
+use common::storage_trait::ValueIdIterator;
 use std::sync::Arc;
 
 #[allow(dead_code)]
@@ -9,18 +13,54 @@ use std::sync::Arc;
 /// standard way of Rust's IntoIter for simplicity (avoiding lifetime issues).
 /// This should store the state/metadata required to iterate through the file.
 ///
-/// HINT: This will need an Arc<HeapFile>
+/// Pages are fetched through the storage manager's buffer pool, so scanning a
+/// table again (e.g. on a rewind) is served from memory when the pages still fit.
+/// Only the record versions visible to the transaction's snapshot are returned, with
+/// values stored in overflow pages put back together.
 pub struct HeapFileIterator {
-    //TODO milestone hs
-     
+    container_id: ContainerId,
+    tid: TransactionId,
+    hf: Arc<HeapFile>,
+    buffer_pool: Arc<BufferPool>,
+    snapshot: Arc<Snapshot>,
+    /// The xid of the transaction's own writes, if it had made any when the scan started.
+    own: Option<u64>,
+    /// The next page to load once the current one is exhausted.
+    next_page: PageId,
+    current: Option<PageIter>,
+    /// Where the record last returned is stored.
+    last: Option<ValueId>,
 }
 
 /// Required HeapFileIterator functions
 impl HeapFileIterator {
     /// Create a new HeapFileIterator that stores the container_id, tid, and heapFile pointer.
     /// This should initialize the state required to iterate through the heap file.
-    pub(crate) fn new(container_id: ContainerId, tid: TransactionId, hf: Arc<HeapFile>) -> Self {
-        panic!("TODO milestone hs");
+    pub(crate) fn new(
+        container_id: ContainerId,
+        tid: TransactionId,
+        hf: Arc<HeapFile>,
+        buffer_pool: Arc<BufferPool>,
+        snapshot: Arc<Snapshot>,
+        own: Option<u64>,
+    ) -> Self {
+        HeapFileIterator {
+            container_id,
+            tid,
+            hf,
+            buffer_pool,
+            snapshot,
+            own,
+            next_page: 0,
+            current: None,
+            last: None,
+        }
+    }
+
+    /// Turn this into an iterator that returns nothing.
+    pub(crate) fn exhausted(mut self) -> Self {
+        self.next_page = PageId::MAX;
+        self
     }
 }
 
@@ -29,6 +69,59 @@ impl HeapFileIterator {
 impl Iterator for HeapFileIterator {
     type Item = Vec<u8>;
     fn next(&mut self) -> Option<Self::Item> {
-        panic!("TODO milestone hs");
+        loop {
+            if let Some(iter) = self.current.as_mut() {
+                if let Some(record) = iter.next() {
+                    let (version, payload) = Version::decode(&record);
+                    if version.kind == RecordKind::Chunk
+                        || !self.snapshot.visible(version, self.own)
+                    {
+                        continue;
+                    }
+                    let value = match version.kind {
+                        RecordKind::Overflow => {
+                            let (buffer_pool, hf) = (&self.buffer_pool, &self.hf);
+                            match overflow::read(buffer_pool, self.container_id, hf, payload) {
+                                Ok(value) => value,
+                                Err(e) => {
+                                    error!("Unable to read overflowed value: {:?}", e);
+                                    continue;
+                                }
+                            }
+                        }
+                        _ => payload.to_vec(),
+                    };
+                    self.last = Some(ValueId::new_slot(
+                        self.container_id,
+                        self.next_page - 1,
+                        iter.last_slot().unwrap(),
+                    ));
+                    return Some(value);
+                }
+            }
+            if self.next_page >= self.hf.num_pages() {
+                self.current = None;
+                self.last = None;
+                return None;
+            }
+            let page = match self
+                .buffer_pool
+                .get_page(self.container_id, self.next_page, &self.hf, false)
+            {
+                Ok(page) => page,
+                Err(e) => {
+                    error!("Unable to read page {}: {:?}", self.next_page, e);
+                    return None;
+                }
+            };
+            self.next_page += 1;
+            self.current = Some(page.into_iter());
+        }
+    }
+}
+
+impl ValueIdIterator for HeapFileIterator {
+    fn last_value_id(&self) -> Option<ValueId> {
+        self.last
     }
 }
@@ -0,0 +1,119 @@
+use crate::btree::BTree;
+use crate::buffer_pool::BufferPool;
+use crate::hash_index::HashIndex;
+use crate::heapfile::HeapFile;
+use common::ids::{ContainerId, ValueId};
+use common::index::IndexMethod;
+use common::table::Table;
+use common::{CrustyError, Field};
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+/// A secondary index open in the storage manager, stored as its method says.
+pub(crate) enum Index {
+    BTree(BTree),
+    Hash(HashIndex),
+}
+
+impl Index {
+    /// Opens the index stored in a heap file, creating an empty index if the file is empty.
+    /// Returns the index and whether it was created.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_id` - Container of the index.
+    /// * `table_id` - Container of the table.
+    /// * `table` - The table, whose secondary index stored in index_id gives the key and method.
+    /// * `hf` - Heap file of the index.
+    /// * `buffer_pool` - Buffer pool to read and write the pages through.
+    pub(crate) fn open(
+        index_id: ContainerId,
+        table_id: ContainerId,
+        table: Arc<RwLock<Table>>,
+        hf: Arc<HeapFile>,
+        buffer_pool: Arc<BufferPool>,
+    ) -> Result<(Self, bool), CrustyError> {
+        let method = table
+            .read()
+            .unwrap()
+            .secondary_index(index_id)
+            .map(|index| index.method)
+            .unwrap_or_default();
+        Ok(match method {
+            IndexMethod::BTree => {
+                let (tree, created) = BTree::open(index_id, table_id, table, hf, buffer_pool)?;
+                (Index::BTree(tree), created)
+            }
+            IndexMethod::Hash => {
+                let (hash, created) = HashIndex::open(index_id, table_id, table, hf, buffer_pool)?;
+                (Index::Hash(hash), created)
+            }
+        })
+    }
+
+    /// Container of the index.
+    pub(crate) fn index_id(&self) -> ContainerId {
+        match self {
+            Index::BTree(tree) => tree.index_id,
+            Index::Hash(hash) => hash.index_id,
+        }
+    }
+
+    /// Container of the table.
+    pub(crate) fn table_id(&self) -> ContainerId {
+        match self {
+            Index::BTree(tree) => tree.table_id,
+            Index::Hash(hash) => hash.table_id,
+        }
+    }
+
+    /// Reads the key of a stored row.
+    pub(crate) fn key(&self, row: &[u8]) -> Result<Vec<Field>, CrustyError> {
+        match self {
+            Index::BTree(tree) => tree.key(row),
+            Index::Hash(hash) => hash.key(row),
+        }
+    }
+
+    /// Adds an entry for a row.
+    pub(crate) fn insert(&self, key: Vec<Field>, id: ValueId) -> Result<(), CrustyError> {
+        match self {
+            Index::BTree(tree) => tree.insert(key, id),
+            Index::Hash(hash) => hash.insert(key, id),
+        }
+    }
+
+    /// Removes the entry for a row, returning whether the index had it.
+    pub(crate) fn delete(&self, key: Vec<Field>, id: ValueId) -> Result<bool, CrustyError> {
+        match self {
+            Index::BTree(tree) => tree.delete(key, id),
+            Index::Hash(hash) => hash.delete(key, id),
+        }
+    }
+
+    /// Finds the entries whose keys are between the bounds. A hash index only finds the keys
+    /// equal to both bounds, which must then hold a value for each column of the key.
+    ///
+    /// # Arguments
+    ///
+    /// * `lower` - Lowest key to return.
+    /// * `upper` - Highest key to return.
+    pub(crate) fn find(
+        &self,
+        lower: Bound<&[Field]>,
+        upper: Bound<&[Field]>,
+    ) -> Result<Vec<(Vec<Field>, ValueId)>, CrustyError> {
+        match (self, lower, upper) {
+            (Index::BTree(tree), lower, upper) => tree.range(lower, upper),
+            (Index::Hash(hash), Bound::Included(lower), Bound::Included(upper))
+                if lower == upper =>
+            {
+                hash.find(lower)
+            }
+            (Index::Hash(hash), _, _) => Err(CrustyError::CrustyError(format!(
+                "Hash index {} only finds keys equal to a value",
+                hash.index_id
+            ))),
+        }
+    }
+}
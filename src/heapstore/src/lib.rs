@@ -2,8 +2,15 @@
 extern crate log;
 #[macro_use]
 extern crate serde;
+mod btree;
+mod buffer_pool;
+mod hash_index;
 mod heapfile;
 mod heapfileiter;
+mod index;
+mod mvcc;
+mod overflow;
 mod page;
+mod wal;
 pub mod storage_manager;
 pub mod testutil;
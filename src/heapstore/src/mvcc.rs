@@ -0,0 +1,308 @@
+use common::ids::{IsolationLevel, TransactionId};
+use common::CrustyError;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Bytes in front of every record in a heap file.
+pub(crate) const VERSION_HEADER_SIZE: usize = 17;
+/// The xmax of a version nobody has deleted.
+pub(crate) const LIVE: u64 = u64::MAX;
+/// How many version numbers are reserved on disk at once.
+const XID_BATCH: u64 = 1024;
+
+/// What the payload of a stored record holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RecordKind {
+    /// The value itself.
+    Inline,
+    /// A pointer to the chain of overflow chunks holding a value too large for a page.
+    Overflow,
+    /// Part of a value in an overflow chain. Only read through the pointer to the chain.
+    Chunk,
+}
+
+/// The header of a stored record version: the number of the transaction that created it
+/// and of the one that deleted it, or `LIVE`, and what kind of record it is.
+///
+/// Version numbers (xids) are handed out by the storage manager the first time a transaction
+/// writes, rather than reusing `TransactionId`s, which start over when the process restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Version {
+    pub(crate) xmin: u64,
+    pub(crate) xmax: u64,
+    pub(crate) kind: RecordKind,
+}
+
+impl Version {
+    /// A live version created by xid.
+    pub(crate) fn created_by(xid: u64) -> Self {
+        Version {
+            xmin: xid,
+            xmax: LIVE,
+            kind: RecordKind::Inline,
+        }
+    }
+
+    /// Prefix payload with this header.
+    pub(crate) fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut record = Vec::with_capacity(VERSION_HEADER_SIZE + payload.len());
+        record.extend_from_slice(&self.xmin.to_le_bytes());
+        record.extend_from_slice(&self.xmax.to_le_bytes());
+        record.push(self.kind as u8);
+        record.extend_from_slice(payload);
+        record
+    }
+
+    /// Split a stored record into its header and payload.
+    pub(crate) fn decode(record: &[u8]) -> (Self, &[u8]) {
+        let mut xmin = [0; 8];
+        let mut xmax = [0; 8];
+        xmin.copy_from_slice(&record[0..8]);
+        xmax.copy_from_slice(&record[8..16]);
+        let kind = match record[16] {
+            0 => RecordKind::Inline,
+            1 => RecordKind::Overflow,
+            _ => RecordKind::Chunk,
+        };
+        let version = Version {
+            xmin: u64::from_le_bytes(xmin),
+            xmax: u64::from_le_bytes(xmax),
+            kind,
+        };
+        (version, &record[VERSION_HEADER_SIZE..])
+    }
+}
+
+/// The transactions whose changes a reader sees: everything committed before the snapshot
+/// was taken, i.e. with an xid below `xmax` that was not still running.
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+    xmax: u64,
+    running: HashSet<u64>,
+}
+
+impl Snapshot {
+    /// Whether the changes of xid are in the snapshot.
+    pub(crate) fn sees(&self, xid: u64) -> bool {
+        xid < self.xmax && !self.running.contains(&xid)
+    }
+
+    /// Whether a reader using this snapshot, whose own writes were made as own, sees version.
+    pub(crate) fn visible(&self, version: Version, own: Option<u64>) -> bool {
+        let seen = |xid: u64| Some(xid) == own || self.sees(xid);
+        seen(version.xmin) && (version.xmax == LIVE || !seen(version.xmax))
+    }
+}
+
+#[derive(Default)]
+struct TxnState {
+    xid: Option<u64>,
+    isolation: IsolationLevel,
+    snapshot: Option<Arc<Snapshot>>,
+}
+
+struct MvccState {
+    next_xid: u64,
+    /// Xids below this may already be on disk, so a restart resumes from here.
+    reserved: u64,
+    txns: HashMap<TransactionId, TxnState>,
+}
+
+/// Tracks the version number and snapshot of each running transaction.
+///
+/// A transaction keeps its snapshot for every statement under `Snapshot` isolation and takes
+/// a new one at each `begin_statement` under `ReadCommitted`. One that never calls
+/// `begin_statement` keeps the snapshot of its first read.
+pub(crate) struct Mvcc {
+    path: PathBuf,
+    state: Mutex<MvccState>,
+}
+
+impl Mvcc {
+    /// Open the xid reservation file at path, creating it if needed.
+    pub(crate) fn open(path: PathBuf) -> Result<Self, CrustyError> {
+        let reserved = if path.exists() {
+            let bytes = fs::read(&path)?;
+            if bytes.len() != 8 {
+                return Err(CrustyError::IOError(format!("Bad xid file {:?}", path)));
+            }
+            let mut buf = [0; 8];
+            buf.copy_from_slice(&bytes);
+            u64::from_le_bytes(buf)
+        } else {
+            0
+        };
+        Ok(Mvcc {
+            path,
+            state: Mutex::new(MvccState {
+                next_xid: reserved,
+                reserved,
+                txns: HashMap::new(),
+            }),
+        })
+    }
+
+    /// The xid tid writes versions as, assigning one on its first write.
+    pub(crate) fn xid(&self, tid: TransactionId) -> Result<u64, CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(xid) = state.txns.get(&tid).and_then(|t| t.xid) {
+            return Ok(xid);
+        }
+        let xid = state.next_xid;
+        if xid >= state.reserved {
+            let reserved = xid + XID_BATCH;
+            let mut file = File::create(&self.path)?;
+            file.write_all(&reserved.to_le_bytes())?;
+            file.sync_all()?;
+            state.reserved = reserved;
+        }
+        state.next_xid += 1;
+        state.txns.entry(tid).or_default().xid = Some(xid);
+        Ok(xid)
+    }
+
+    /// The xid of tid if it has written anything.
+    pub(crate) fn own_xid(&self, tid: TransactionId) -> Option<u64> {
+        self.state.lock().unwrap().txns.get(&tid).and_then(|t| t.xid)
+    }
+
+    /// Whether the transaction that writes as xid is still running.
+    pub(crate) fn running(&self, xid: u64) -> bool {
+        let state = self.state.lock().unwrap();
+        state.txns.values().any(|t| t.xid == Some(xid))
+    }
+
+    /// Note that tid is starting a statement, taking a new snapshot if its isolation level calls for one.
+    pub(crate) fn begin_statement(&self, tid: TransactionId, isolation: IsolationLevel) {
+        let mut state = self.state.lock().unwrap();
+        let txn = state.txns.entry(tid).or_default();
+        txn.isolation = isolation;
+        if isolation == IsolationLevel::ReadCommitted {
+            txn.snapshot = None;
+        }
+    }
+
+    /// The isolation level tid runs at.
+    pub(crate) fn isolation(&self, tid: TransactionId) -> IsolationLevel {
+        let state = self.state.lock().unwrap();
+        state.txns.get(&tid).map(|t| t.isolation).unwrap_or_default()
+    }
+
+    /// The snapshot tid reads with, taken now if it has none.
+    pub(crate) fn snapshot(&self, tid: TransactionId) -> Arc<Snapshot> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(snapshot) = state.txns.get(&tid).and_then(|t| t.snapshot.clone()) {
+            return snapshot;
+        }
+        let snapshot = Arc::new(Snapshot {
+            xmax: state.next_xid,
+            running: state.txns.values().filter_map(|t| t.xid).collect(),
+        });
+        state.txns.entry(tid).or_default().snapshot = Some(snapshot.clone());
+        snapshot
+    }
+
+    /// Forget tid. Its writes count as committed from now on, so an aborted transaction
+    /// must be rolled back first.
+    pub(crate) fn finish(&self, tid: TransactionId) {
+        self.state.lock().unwrap().txns.remove(&tid);
+    }
+
+    /// Whether version is deleted and no running or future transaction can see it.
+    pub(crate) fn reclaimable(&self, version: Version) -> bool {
+        if version.xmax == LIVE {
+            return false;
+        }
+        let state = self.state.lock().unwrap();
+        version.xmax < state.next_xid
+            && state.txns.values().all(|t| {
+                t.xid != Some(version.xmax)
+                    && t.snapshot.as_ref().is_none_or(|s| s.sees(version.xmax))
+            })
+    }
+
+    /// Forget every transaction.
+    pub(crate) fn reset(&self) {
+        self.state.lock().unwrap().txns.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::testutil::*;
+    use temp_testdir::TempDir;
+
+    fn open_temp(tdir: &TempDir) -> Mvcc {
+        let mut path = tdir.to_path_buf();
+        path.push("xids");
+        Mvcc::open(path).unwrap()
+    }
+
+    #[test]
+    fn hs_mvcc_version_round_trip() {
+        let payload = get_random_byte_vec(30);
+        for kind in [RecordKind::Inline, RecordKind::Overflow, RecordKind::Chunk] {
+            let version = Version {
+                xmin: 3,
+                xmax: 9,
+                kind,
+            };
+            let record = version.encode(&payload);
+            assert_eq!(VERSION_HEADER_SIZE + payload.len(), record.len());
+            assert_eq!((version, &payload[..]), Version::decode(&record));
+        }
+    }
+
+    #[test]
+    fn hs_mvcc_visibility() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let mvcc = open_temp(&tdir);
+        let (t1, t2, reader) = (TransactionId::new(), TransactionId::new(), TransactionId::new());
+        let x1 = mvcc.xid(t1).unwrap();
+        mvcc.finish(t1);
+        let x2 = mvcc.xid(t2).unwrap();
+
+        let snapshot = mvcc.snapshot(reader);
+        assert!(snapshot.visible(Version::created_by(x1), None));
+        // Running when the snapshot was taken, so not seen even once it commits
+        assert!(!snapshot.visible(Version::created_by(x2), None));
+        mvcc.finish(t2);
+        assert!(!snapshot.visible(Version::created_by(x2), None));
+        let deleted = Version {
+            xmax: x2,
+            ..Version::created_by(x1)
+        };
+        assert!(snapshot.visible(deleted, None));
+        let deleted_by_creator = Version { xmax: x1, ..deleted };
+        assert!(!snapshot.visible(deleted_by_creator, None));
+        // A transaction sees its own writes
+        assert!(snapshot.visible(Version::created_by(x2), Some(x2)));
+        assert!(!snapshot.visible(deleted, Some(x2)));
+
+        // The reader still sees the version x2 deleted, until it moves on
+        assert!(!mvcc.reclaimable(deleted));
+        mvcc.begin_statement(reader, IsolationLevel::ReadCommitted);
+        assert!(!mvcc.snapshot(reader).visible(deleted, None));
+        assert!(mvcc.reclaimable(deleted));
+        assert!(!mvcc.reclaimable(Version::created_by(x1)));
+    }
+
+    #[test]
+    fn hs_mvcc_xids_survive_restart() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let first = open_temp(&tdir).xid(TransactionId::new()).unwrap();
+        let mvcc = open_temp(&tdir);
+        let second = mvcc.xid(TransactionId::new()).unwrap();
+        assert!(second > first);
+        // Versions written before the restart are committed
+        let snapshot = mvcc.snapshot(TransactionId::new());
+        assert!(snapshot.sees(first));
+        assert!(!snapshot.sees(second));
+    }
+}
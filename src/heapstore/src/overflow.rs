@@ -0,0 +1,131 @@
+use crate::buffer_pool::BufferPool;
+use crate::heapfile::HeapFile;
+use crate::mvcc::{RecordKind, Version, VERSION_HEADER_SIZE};
+use crate::page::MAX_VALUE_SIZE;
+use common::ids::{ContainerId, PageId, SlotId, ValueId};
+use common::CrustyError;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+/// Bytes a chunk uses to point to the next chunk of its chain.
+const LINK_SIZE: usize = 4;
+/// Bytes of a value a chunk holds when it fills an overflow page.
+pub(crate) const CHUNK_SIZE: usize = MAX_VALUE_SIZE - VERSION_HEADER_SIZE - LINK_SIZE;
+
+/// Whether a value is small enough to be stored in its record instead of overflowing.
+pub(crate) fn fits_inline(len: usize) -> bool {
+    VERSION_HEADER_SIZE + len <= MAX_VALUE_SIZE
+}
+
+/// The payload of an overflow pointer: the length of the value and where its first chunk is.
+///
+/// # Arguments
+///
+/// * `len` - Length of the value.
+/// * `first` - First chunk of the chain holding the value.
+pub(crate) fn pointer(len: usize, first: ValueId) -> Vec<u8> {
+    let mut payload = (len as u32).to_le_bytes().to_vec();
+    payload.extend_from_slice(&encode_link(Some(first)));
+    payload
+}
+
+/// The payload of a chunk: where the next chunk is, then the chunk's part of the value.
+///
+/// # Arguments
+///
+/// * `data` - Part of the value the chunk holds.
+/// * `next` - Next chunk of the chain, if this is not the last.
+pub(crate) fn chunk(data: &[u8], next: Option<ValueId>) -> Vec<u8> {
+    let mut payload = encode_link(next).to_vec();
+    payload.extend_from_slice(data);
+    payload
+}
+
+fn encode_link(link: Option<ValueId>) -> [u8; LINK_SIZE] {
+    let (page_id, slot_id) = match link {
+        Some(id) => (id.page_id.unwrap(), id.slot_id.unwrap()),
+        None => (PageId::MAX, SlotId::MAX),
+    };
+    let mut bytes = [0; LINK_SIZE];
+    bytes[..2].copy_from_slice(&page_id.to_le_bytes());
+    bytes[2..].copy_from_slice(&slot_id.to_le_bytes());
+    bytes
+}
+
+fn decode_link(container_id: ContainerId, bytes: &[u8]) -> Option<ValueId> {
+    let page_id = PageId::from_le_bytes(bytes[..2].try_into().unwrap());
+    let slot_id = SlotId::from_le_bytes(bytes[2..LINK_SIZE].try_into().unwrap());
+    if page_id == PageId::MAX {
+        return None;
+    }
+    Some(ValueId::new_slot(container_id, page_id, slot_id))
+}
+
+/// Follows the chain an overflow pointer refers to, calling visit with the id and the part
+/// of the value of each chunk in order.
+fn walk(
+    buffer_pool: &BufferPool,
+    container_id: ContainerId,
+    hf: &Arc<HeapFile>,
+    pointer: &[u8],
+    mut visit: impl FnMut(ValueId, &[u8]),
+) -> Result<usize, CrustyError> {
+    let len = u32::from_le_bytes(pointer[..4].try_into().unwrap()) as usize;
+    let mut next = decode_link(container_id, &pointer[4..]);
+    while let Some(id) = next {
+        let page = buffer_pool.get_page(container_id, id.page_id.unwrap(), hf, false)?;
+        let record = page.get_value(id.slot_id.unwrap()).ok_or_else(|| {
+            CrustyError::CrustyError(format!("Overflow chunk {:?} is missing", id))
+        })?;
+        let (version, payload) = Version::decode(&record);
+        if version.kind != RecordKind::Chunk {
+            return Err(CrustyError::CrustyError(format!(
+                "Value {:?} is not an overflow chunk",
+                id
+            )));
+        }
+        visit(id, &payload[LINK_SIZE..]);
+        next = decode_link(container_id, &payload[..LINK_SIZE]);
+    }
+    Ok(len)
+}
+
+/// Reassembles the value an overflow pointer refers to.
+///
+/// # Arguments
+///
+/// * `buffer_pool` - Buffer pool to read the chunks through.
+/// * `container_id` - Container of the value.
+/// * `hf` - Heap file of the container.
+/// * `pointer` - Payload of the overflow pointer.
+pub(crate) fn read(
+    buffer_pool: &BufferPool,
+    container_id: ContainerId,
+    hf: &Arc<HeapFile>,
+    pointer: &[u8],
+) -> Result<Vec<u8>, CrustyError> {
+    let mut value = Vec::new();
+    let len = walk(buffer_pool, container_id, hf, pointer, |_, data| {
+        value.extend_from_slice(data)
+    })?;
+    if value.len() != len {
+        return Err(CrustyError::CrustyError(format!(
+            "Overflowed value has {} of its {} bytes",
+            value.len(),
+            len
+        )));
+    }
+    Ok(value)
+}
+
+/// Returns the ids of the chunks of the chain an overflow pointer refers to.
+pub(crate) fn chunk_ids(
+    buffer_pool: &BufferPool,
+    container_id: ContainerId,
+    hf: &Arc<HeapFile>,
+    pointer: &[u8],
+) -> Result<Vec<ValueId>, CrustyError> {
+    let mut ids = Vec::new();
+    walk(buffer_pool, container_id, hf, pointer, |id, _| ids.push(id))?;
+    Ok(ids)
+}
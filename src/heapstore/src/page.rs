@@ -3,6 +3,9 @@ use common::PAGE_SIZE;
 use std::convert::TryInto;
 use std::mem;
 
+/// The largest value an empty page can hold: the page takes 8 bytes of metadata and 4 for
+/// the value's slot.
+pub(crate) const MAX_VALUE_SIZE: usize = PAGE_SIZE - 12;
 
 /// The struct for a page. Note this can hold more elements/meta data when created,
 /// but it must be able to be packed/serialized/marshalled into the data array of size
@@ -12,6 +15,7 @@ use std::mem;
 /// You do not need reclaim header information for a value inserted (eg 6 bytes per value ever inserted)
 /// The rest must filled as much as possible to hold values.
 
+#[derive(Clone)]
 pub(crate) struct Page {
     /// The data for data
     pub data: [u8; PAGE_SIZE],
@@ -20,6 +24,7 @@ pub(crate) struct Page {
 }
 
 //currently occupying 6 bytes of 8 total available bytes
+#[derive(Clone)]
 pub struct GeneralMetadataHeader
 {
     pub p_id: PageId, //u16, 2bytes which represents over 65000 possible values
@@ -29,6 +34,7 @@ pub struct GeneralMetadataHeader
     pub free_spaces_from_deleted_record: Vec<u16>, //2byte, keeps track of the indeces for the deleted records
 }
 
+#[derive(Clone)]
 pub struct Metadata
 {
     pub record_size: u16,
@@ -83,77 +89,59 @@ impl Page {
     //function to overwrite if there is at least one unused SlotId from deleting a record
     {
         //if all slotids are currently in use
-        if self.header.free_spaces_from_deleted_record.len() == 0
+        if self.header.free_spaces_from_deleted_record.is_empty()
         {
             return None;
         }
-        //if there is a free slotid to use
-        else
+
+        //loop through the free slotids, looking for an old record big enough to reuse in place
+        let mut reuse = None;
+        for (index, slot) in self.header.free_spaces_from_deleted_record.iter().enumerate()
         {
-            //loop through the slotids
-            for i in &self.header.free_spaces_from_deleted_record
-            {   
-                let i: usize = *i as usize;
-                println!("{}", i);
-                let old_metadata = &self.metadata[i];
-                let index = 0;
-                //check to see if there is enough free space in the given/current slotid
-                if old_metadata.record_size >= bytes.len() as u16
-                //proceed to reassign the metadata header
-                {
-                    let start_index = old_metadata.offset_value;
-                    let end_index = start_index + bytes.len() as u16 - 1;
+            if self.metadata[*slot as usize].record_size as usize >= bytes.len()
+            {
+                reuse = Some(index);
+                break;
+            }
+        }
 
-                    let remove_bytes = old_metadata.offset_value - bytes.len() as u16; 
-                    //create a new metadata object for mapping
-                    let new_metadata = Metadata 
-                    {
-                        record_size: bytes.len() as u16,
-                        offset_value: old_metadata.offset_value,
-                    };
-                    //set metadata at index of the given slot id = new_metadata
-                    self.metadata[i] = new_metadata;
-                    //overwrite the data in the data array
-                    self.data[start_index as usize..=end_index as usize].clone_from_slice(&bytes);
-                    //remove slot_id from the "free space from deleted records" array
-                    self.header.free_spaces_from_deleted_record.remove(index); // I THINK THIS MIGHT CAUSE MY CODE TO NOT WORK
-                    //create slot id to prepare for return
-                    let slot_id: SlotId = i as u16;
-                    //reduce number of free spaces indicated by n_deleted
-                    self.header.n_deletes = self.header.n_deletes - 1;
-                    //return new slot id
-                    return Some(slot_id);
+        let index = match reuse
+        {
+            Some(index) => index,
+            None =>
+            {
+                //no deleted record is big enough, so the bytes go into the free space like a
+                //normal add, but still reuse the slot id. reusing the slot frees 2 header bytes.
+                if self.get_largest_free_contiguous_space() + 2 < bytes.len() || bytes.is_empty()
+                {
+                    return None;
                 }
+                0
             }
+        };
 
-            //if we make it out of the previous loop, this means that there was no available space to insert the new data into
-            //the previously existing spaces... must add new space for it in data array, but update the same slot_id association
-            //aka mapping in the metadata
-
-            //acting as if we are adding "normally" to the data array
-            //the only thing that will be different will be the metadata that will be mapped to it
-            let end_index: u16 = self.header.position_last_record;
-            let start_index: u16 = (end_index - bytes.len() as u16) + 1;
-
-            self.data[start_index as usize..=end_index as usize].clone_from_slice(&bytes);
-
-            let replacing_slot_id = self.header.free_spaces_from_deleted_record[0 as usize];
+        let slot_id: SlotId = self.header.free_spaces_from_deleted_record.remove(index);
+        self.header.n_deletes -= 1;
 
-            let new_metadata = Metadata
+        let start_index = match reuse
+        {
+            Some(_) => self.metadata[slot_id as usize].offset_value,
+            None =>
             {
-                record_size: bytes.len() as u16,
-                offset_value: start_index,
-            };
+                let start_index = self.header.position_last_record + 1 - bytes.len() as u16;
+                self.header.position_last_record = start_index - 1;
+                start_index
+            }
+        };
 
-            //placing the new metadata object where the old one used to be
-            self.metadata[replacing_slot_id as usize] = new_metadata;
-            //removing the slot_id from the free space record
-            let slot_id: SlotId = self.header.free_spaces_from_deleted_record[0 as usize];
-            self.header.free_spaces_from_deleted_record.remove(0);
-            //reduce number of free spaces indicated by n_deleted
-            self.header.n_deletes = self.header.n_deletes - 1;
-            return Some(slot_id);
-        }
+        //overwrite the data in the data array and remap the slot
+        self.data[start_index as usize..start_index as usize + bytes.len()].clone_from_slice(bytes);
+        self.metadata[slot_id as usize] = Metadata
+        {
+            record_size: bytes.len() as u16,
+            offset_value: start_index,
+        };
+        Some(slot_id)
     }
 
     pub fn add_value(&mut self, bytes: &[u8]) -> Option<SlotId> 
@@ -173,7 +161,8 @@ impl Page {
         //if there are no free spots from deletion, do things normally
         {
             //make sure there is enough free space on the page
-            if self.get_largest_free_contiguous_space() > bytes.len()
+            //the new record also needs room for its own metadata entry
+            if self.get_largest_free_contiguous_space() >= bytes.len() + 4 && !bytes.is_empty()
             {
                 let end_index: u16 = self.header.position_last_record;
                 let start_index: u16 = (end_index - bytes.len() as u16) + 1;
@@ -219,11 +208,93 @@ impl Page {
         }
     }
 
+    /// Store bytes under a specific slotId, replacing whatever the slot held.
+    /// Slots past the end of the slot array are created (as deleted) so the id exists.
+    /// Used to replay the write-ahead log, so calling it twice with the same bytes is harmless.
+    /// Returns None if the page cannot hold the value even after compacting.
+    pub(crate) fn put_value_at(&mut self, slot_id: SlotId, bytes: &[u8]) -> Option<()>
+    {
+        if self.get_value(slot_id).as_deref() == Some(bytes)
+        {
+            return Some(());
+        }
+        while self.metadata.len() <= slot_id as usize
+        {
+            self.metadata.push(Metadata { record_size: 0, offset_value: 0 });
+            self.header.free_spaces_from_deleted_record.push(self.metadata.len() as u16 - 1);
+            self.header.n_records += 1;
+            self.header.n_deletes += 1;
+        }
+        if let Some(index) = self.header.free_spaces_from_deleted_record.iter().position(|s| *s == slot_id)
+        {
+            self.header.free_spaces_from_deleted_record.remove(index);
+            self.header.n_deletes -= 1;
+        }
+
+        //reuse the slot's old space if it is big enough, otherwise take new space
+        let old = self.metadata[slot_id as usize].clone();
+        let start_index = if old.record_size as usize >= bytes.len() && old.offset_value != 0
+        {
+            old.offset_value as usize
+        }
+        else
+        {
+            self.metadata[slot_id as usize] = Metadata { record_size: 0, offset_value: 0 };
+            if self.get_largest_free_contiguous_space() < bytes.len()
+            {
+                self.compact();
+            }
+            if self.get_largest_free_contiguous_space() < bytes.len()
+            {
+                //put the slot back the way it was
+                self.metadata[slot_id as usize] = old;
+                self.header.free_spaces_from_deleted_record.push(slot_id);
+                self.header.n_deletes += 1;
+                return None;
+            }
+            let start_index = self.header.position_last_record as usize + 1 - bytes.len();
+            self.header.position_last_record = start_index as u16 - 1;
+            start_index
+        };
+        self.data[start_index..start_index + bytes.len()].clone_from_slice(bytes);
+        self.metadata[slot_id as usize] = Metadata
+        {
+            record_size: bytes.len() as u16,
+            offset_value: start_index as u16,
+        };
+        Some(())
+    }
+
+    /// Move every live record to the end of the page so all free space is contiguous.
+    /// Slot ids do not change.
+    fn compact(&mut self)
+    {
+        let live: Vec<(usize, Vec<u8>)> = (0..self.metadata.len())
+            .filter_map(|slot| self.get_value(slot as SlotId).map(|bytes| (slot, bytes)))
+            .collect();
+        self.header.position_last_record = PAGE_SIZE as u16 - 1;
+        for metadata in self.metadata.iter_mut()
+        {
+            *metadata = Metadata { record_size: 0, offset_value: 0 };
+        }
+        for (slot, bytes) in live
+        {
+            let start_index = self.header.position_last_record as usize + 1 - bytes.len();
+            self.data[start_index..start_index + bytes.len()].clone_from_slice(&bytes);
+            self.header.position_last_record = (start_index as u16).wrapping_sub(1);
+            self.metadata[slot] = Metadata
+            {
+                record_size: bytes.len() as u16,
+                offset_value: start_index as u16,
+            };
+        }
+    }
+
     /// Return the bytes for the slotId. If the slotId is not valid then return None
     pub fn get_value(&self, slot_id: SlotId) -> Option<Vec<u8>> 
     {
 
-        if slot_id > self.metadata.len() as u16 - 1
+        if slot_id as usize >= self.metadata.len()
         {
             return None;
         }
@@ -249,7 +320,8 @@ impl Page {
     /// HINT: Return Some(()) for a valid delete
     pub fn delete_value(&mut self, slot_id: SlotId) -> Option<()> {
         //panic!("TODO milestone pg");
-        if slot_id > self.metadata.len() as u16 - 1
+        if slot_id as usize >= self.metadata.len()
+            || self.header.free_spaces_from_deleted_record.contains(&slot_id)
         {
             return None;
         }
@@ -257,6 +329,11 @@ impl Page {
         {
             self.header.free_spaces_from_deleted_record.push(slot_id);
             self.header.n_deletes = self.header.n_deletes + 1;
+            //on a full page the longer free list can run into the data, so reclaim deleted space
+            if self.get_header_size() > self.header.position_last_record as usize + 1
+            {
+                self.compact();
+            }
             return Some(());
         }
     }
@@ -391,6 +468,11 @@ impl Page {
         return byte_vector;
     }
 
+    /// Number of slots on the page, including deleted ones.
+    pub(crate) fn num_slots(&self) -> SlotId {
+        self.metadata.len() as SlotId
+    }
+
     /// A utility function to determine the size of the header in the page
     /// when serialized/to_bytes.
     /// Will be used by tests. Optional for you to use in your code
@@ -415,7 +497,7 @@ impl Page {
     #[allow(dead_code)]
     pub(crate) fn get_largest_free_contiguous_space(&self) -> usize {
         //panic!("TODO milestone pg");
-        return self.header.position_last_record as usize - self.get_header_size() + 1;
+        return (self.header.position_last_record as usize + 1).saturating_sub(self.get_header_size());
     }
 }
 
@@ -428,62 +510,29 @@ pub struct PageIter
     page_index:usize,
 }
 
+impl PageIter {
+    /// The slot of the value last returned by `next`.
+    pub(crate) fn last_slot(&self) -> Option<SlotId> {
+        self.page_index.checked_sub(1).map(|i| i as SlotId)
+    }
+}
+
 /// The implementation of the (consuming) page iterator.
 impl Iterator for PageIter {
     type Item = Vec<u8>;
 
     fn next(&mut self) -> Option<Self::Item> {
-
-        let mut index = self.page_index;
-
-        //panic!("TODO milestone pg");
-        //check to make sure the index is within the valid bounds of the page
-        if self.page.header.n_records <= index as u16
-        {
-            return None;
-        }
-        //if there are no deleted spaces
-        else if self.page.header.n_deletes == 0
-        {
-            self.page_index = self.page_index + 1;
-
-            let begin = self.page.metadata[index].offset_value as usize;
-	        let end = begin + self.page.metadata[index].record_size as usize;
-
-	        let size_of_record = self.page.metadata[index].record_size as usize;
-	        
-	        return Some(self.page.data[begin..end].to_vec());
-        }
-        //if there are currently deleted records to be skipped over
-        else
+        //skip over any slots that have been deleted
+        while self.page_index < self.page.metadata.len()
         {
-            let mut i = 0;
-            while i < self.page.header.n_deletes
+            let slot_id = self.page_index as SlotId;
+            self.page_index += 1;
+            if let Some(bytes) = self.page.get_value(slot_id)
             {
-                //if we hit a slot that has been deleted, skip it and set i back to 0!
-                if index == self.page.header.free_spaces_from_deleted_record[i as usize] as usize
-                {
-                    i = 0;
-                    index = index + 1;
-                }
-                else if index >= self.page.metadata.len()
-                {
-                    return None;
-                }
-                else 
-                {
-                    i = i + 1;
-                }            
+                return Some(bytes);
             }
-
-            self.page_index = index + 1;
-
-	        let size = self.page.metadata[index].record_size as usize;
-	        let begin = self.page.metadata[index].offset_value as usize;
-	        let end = begin + size;
-	        return Some(self.page.data[begin..end].to_vec());
-
         }
+        None
     }
 }
 
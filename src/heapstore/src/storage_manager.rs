@@ -1,29 +1,73 @@
+use crate::buffer_pool::BufferPool;
 use crate::heapfile::HeapFile;
 use crate::heapfileiter::HeapFileIterator;
-use crate::page::Page;
+use crate::index::Index;
+use crate::mvcc::{Mvcc, RecordKind, Version, LIVE};
+use crate::overflow::{self, CHUNK_SIZE};
+use crate::page::{Page, MAX_VALUE_SIZE};
+use crate::wal::{LogRecord, Wal};
 use common::prelude::*;
+use common::foreign_key::ForeignKeys;
 use common::storage_trait::StorageTrait;
 use common::testutil::gen_random_dir;
-use common::PAGE_SIZE;
+use common::PAGE_SLOTS;
 use std::collections::HashMap;
 use std::fs;
-use std::path::{PathBuf, Path};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use txn_manager::lock_manager::{LockId, LockManager, LockMode};
 
-
-/// This is the basic data structure a container that maps a value ID to bytes
-type ContainerMap = Arc<RwLock<HashMap<PageId, Vec<u8>>>>;
-
+/// File in storage_path that records which heap file backs each container.
+const CONTAINER_MAP_FILE: &str = "containers.json";
+/// File in storage_path holding the write-ahead log.
+const WAL_FILE: &str = "wal.log";
+/// File in storage_path recording how many version numbers may be in use.
+const XID_FILE: &str = "xids";
+/// Extension of the files holding the rows of tables.
+const HEAP_EXTENSION: &str = "hf";
+/// Extension of the files holding secondary indexes.
+const INDEX_EXTENSION: &str = "idx";
 
 /// The StorageManager struct
 /// A map for storing containers
-#[derive(Serialize, Deserialize)]
+///
+/// Every change to a record is written to the write-ahead log before the page holding
+/// it can reach disk, and the log is forced when a transaction finishes. Opening a
+/// storage manager on an existing path replays the log: all logged changes are redone,
+/// changes of transactions that never finished are undone, and the result is checkpointed.
+///
+/// Records are stored as versions stamped with the transactions that created and deleted
+/// them. Reads return the versions visible to their transaction's snapshot and only lock
+/// when they intend to write, so long scans neither block nor wait for writers. Writes take
+/// two-phase locks held until `transaction_finished`. A transaction that cannot get a lock
+/// in time, or that under snapshot isolation would overwrite a change it cannot see, is
+/// aborted; its changes are rolled back when it finishes instead of being committed.
+/// Deleted versions stay in place until `vacuum` finds that nothing can see them.
+///
+/// Secondary indexes are B+ trees or hash tables stored in containers of their own. Each
+/// has an entry for every version of a row that is stored, added when the version is written
+/// and removed when it is vacuumed or rolled back. The indexes are not logged: recovering
+/// from a crash empties them, and they are rebuilt from their tables when next opened.
+#[derive(Serialize)]
 pub struct StorageManager 
 {
-    //containers: Arc<RwLock<HashMap<ContainerId, ContainerMap>>>,
     #[serde(skip)]
-    containers: Arc<RwLock<HashMap<ContainerId, HeapFile>>>,
+    containers: Arc<RwLock<HashMap<ContainerId, Arc<HeapFile>>>>,
+    /// Pages cached for every container, shared with the iterators.
+    #[serde(skip)]
+    buffer_pool: Arc<BufferPool>,
+    #[serde(skip)]
+    wal: Arc<Wal>,
+    #[serde(skip)]
+    lock_manager: Arc<LockManager>,
+    #[serde(skip)]
+    mvcc: Arc<Mvcc>,
+    /// Open secondary indexes, by their container.
+    #[serde(skip)]
+    indexes: Arc<RwLock<HashMap<ContainerId, Arc<Index>>>>,
     /// Path to database metadata files.
     pub storage_path: String,
     is_temp: bool,
@@ -31,39 +75,59 @@ pub struct StorageManager
 
 /// The required functions in HeapStore's StorageManager that are specific for HeapFiles
 impl StorageManager {
+    /// Create a storage manager whose buffer pool holds up to frames pages.
+    /// Any containers persisted by an earlier shutdown are reopened.
+    pub fn new_with_frames(storage_path: String, frames: usize) -> Self {
+        if !Path::new(&storage_path).exists() {
+            fs::create_dir_all(&storage_path).expect("Unable to create storage path");
+        }
+        let containers = Self::load_container_map(&storage_path)
+            .expect("Unable to reload containers from storage path");
+        let mut wal_path = PathBuf::from(&storage_path);
+        wal_path.push(WAL_FILE);
+        let wal = Arc::new(Wal::open(wal_path).expect("Unable to open write-ahead log"));
+        let mut xid_path = PathBuf::from(&storage_path);
+        xid_path.push(XID_FILE);
+        let mvcc = Arc::new(Mvcc::open(xid_path).expect("Unable to open version numbers"));
+        let sm = StorageManager {
+            containers: Arc::new(RwLock::new(containers)),
+            buffer_pool: Arc::new(BufferPool::with_wal(frames, wal.clone())),
+            wal,
+            lock_manager: Arc::new(LockManager::default()),
+            mvcc,
+            indexes: Arc::new(RwLock::new(HashMap::new())),
+            storage_path,
+            is_temp: false,
+        };
+        sm.recover().expect("Unable to recover from write-ahead log");
+        sm
+    }
+
     /// Get a page if exists for a given container.
+    /// If pin is set the page stays in the buffer pool until `unpin_page` is called.
     pub(crate) fn get_page(
         &self,
         container_id: ContainerId,
         page_id: PageId,
-        _tid: TransactionId,
-        _perm: Permissions,
-        _pin: bool,
-    ) -> Option<Page> 
+        tid: TransactionId,
+        perm: Permissions,
+        pin: bool,
+    ) -> Result<Page, CrustyError> 
     {
-        let containers = self.containers.read().unwrap();
-
-        match containers.get(&container_id) 
-        {
-            Some (heapfile) => return Some(HeapFile::read_page_from_file(heapfile, page_id).unwrap()),
-            None => return None
-        }
-
-        // if containers.contains_key(&container_id)
-        // {
-        //     let heap = containers.get(&container_id);
-        //     let page = HeapFile::read_page_from_file(&heap, page_id);
-        //     return page;
-        // }
-        // else
-        // {
-        //     return None;
-        // }
+        let hf = self.get_heapfile(container_id)?;
+        self.lock_manager.lock_page(tid, container_id, page_id, perm)?;
+        self.buffer_pool.get_page(container_id, page_id, &hf, pin)
+    }
 
-        //panic!("TODO milestone hs");
+    /// Release a pin taken by `get_page`.
+    #[allow(dead_code)]
+    pub(crate) fn unpin_page(&self, container_id: ContainerId, page_id: PageId) {
+        self.buffer_pool.unpin(container_id, page_id);
     }
 
     /// Write a page
+    /// Pages already in the heap file are written to the buffer pool and flushed later;
+    /// a page past the end of the file is written through so the file grows.
     pub(crate) fn write_page(
         &self,
         container_id: ContainerId,
@@ -71,23 +135,12 @@ impl StorageManager {
         _tid: TransactionId,
     ) -> Result<(), CrustyError> 
     {
-        //consider changing this to a match statement
-        let containers = self.containers.read().unwrap();
-        if containers.contains_key(&container_id)
-        {
-            let heap = containers.get(&container_id).unwrap();
-            //let number_of_pages = heap.num_of_pages;
-            HeapFile::write_page_to_file(heap, page);
-            //Some(heapfile) => return Some(HeapFile::write_page_to_file(heapfile, Page).unwrap()),
-            //Some(heapfile) => HeapFile::write_page_to_file(heapfile, page).unwrap()
-            return Ok(());
+        let hf = self.get_heapfile(container_id)?;
+        if page.get_page_id() >= hf.num_pages() {
+            hf.write_page_to_file(page)
+        } else {
+            self.buffer_pool.write_page(container_id, page, &hf)
         }
-        else
-        {
-            return Err(CrustyError::CrustyError(format!("Container does not exist")));
-        }
-        //return Some(HeapFile::write_page_to_file(containers, page: Page));
-        //panic!("TODO milestone hs");
     }
 
     /// Get the number of pages for a container
@@ -97,47 +150,639 @@ impl StorageManager {
         let heap = containers.get(&container_id).unwrap();
         let number_of_pages = heap.num_of_pages.lock().unwrap();
         return *number_of_pages as PageId;
-        // if containers.contains_key(&container_id)
-        // {
-        //     let heap = containers.get(&container_id).unwrap();
-        //     let number_of_pages = heap.num_of_pages.lock().unwrap();
-        //     return *number_of_pages as PageId;
-        // }
-        // else
-        // {
-        //     return Err(CrustyError::CrustyError(format!("Container does not exist")));
-        // }
-        //panic!("TODO milestone hs");
     }
 
-
     /// Test utility function for counting reads and writes served by the heap file.
     /// Can return 0,0 for invalid container_ids
     #[allow(dead_code)]
     pub(crate) fn get_hf_read_write_count(&self, container_id: ContainerId) -> (u16, u16) 
     {
         let containers = self.containers.read().unwrap();
-
-        // if containers.contains_key(&container_id)
-        // {
-        //     let heap = containers.get(&container_id);
-        //     return ((heap.read_count).into_inner, (heap.write_count).into_inner());
-        // }
-        // else
-        // {
-        //     return (0, 0);
-        // }
-
         match containers.get(&container_id)
         {
-            //Some(heapfile) => return((heapfile.read_count).into_inner().clone(), (heapfile.write_count).into_inner().clone()),
-            //how to read a value from the atomic16
-            //check how to do this in rust
-            //must more direct way to do this
             Some(heapfile) => return(heapfile.read_count.load(Ordering::Relaxed), heapfile.write_count.load(Ordering::Relaxed)),
             None => return (0, 0),
         }
     }
+
+    /// Fraction of page requests served by the buffer pool without reading a heap file.
+    /// Misses are taken from the heap files' read counts, so this needs the profile feature.
+    pub fn get_buffer_pool_hit_ratio(&self) -> f64 {
+        let requests = self.buffer_pool.request_count();
+        if requests == 0 {
+            return 0.0;
+        }
+        let containers = self.containers.read().unwrap();
+        let misses: u64 = containers
+            .values()
+            .map(|hf| hf.read_count.load(Ordering::Relaxed) as u64)
+            .sum();
+        1.0 - (misses.min(requests) as f64 / requests as f64)
+    }
+
+    /// Change how long a request waits for a lock before its transaction is aborted.
+    pub fn set_lock_timeout(&self, timeout: Duration) {
+        self.lock_manager.set_timeout(timeout);
+    }
+
+    /// Number of pages the buffer pool can hold.
+    pub fn get_buffer_pool_size(&self) -> usize {
+        self.buffer_pool.capacity()
+    }
+
+    fn get_heapfile(&self, container_id: ContainerId) -> Result<Arc<HeapFile>, CrustyError> {
+        let containers = self.containers.read().unwrap();
+        match containers.get(&container_id) {
+            Some(hf) => Ok(hf.clone()),
+            None => Err(CrustyError::CrustyError(format!(
+                "Container {} does not exist",
+                container_id
+            ))),
+        }
+    }
+
+    /// Whether a heap file holds a secondary index rather than rows.
+    fn is_index(hf: &HeapFile) -> bool {
+        hf.file_path
+            .extension()
+            .is_some_and(|ext| ext == INDEX_EXTENSION)
+    }
+
+    /// Open secondary indexes of a table.
+    fn table_indexes(&self, table_id: ContainerId) -> Vec<Arc<Index>> {
+        self.indexes
+            .read()
+            .unwrap()
+            .values()
+            .filter(|index| index.table_id() == table_id)
+            .cloned()
+            .collect()
+    }
+
+    fn get_index(&self, index_id: ContainerId) -> Result<Arc<Index>, CrustyError> {
+        match self.indexes.read().unwrap().get(&index_id) {
+            Some(index) => Ok(index.clone()),
+            None => Err(CrustyError::CrustyError(format!(
+                "Index {} is not open",
+                index_id
+            ))),
+        }
+    }
+
+    /// The value stored at id unless it was deleted, whether or not the snapshot of tid sees
+    /// it. A version another transaction is still writing or deleting is read once that
+    /// transaction finishes, which tid waits for by locking the value.
+    fn latest_value(
+        &self,
+        id: ValueId,
+        tid: TransactionId,
+    ) -> Result<Option<Vec<u8>>, CrustyError> {
+        let (page_id, slot_id) = match (id.page_id, id.slot_id) {
+            (Some(page_id), Some(slot_id)) => (page_id, slot_id),
+            _ => return Err(CrustyError::CrustyError(format!("Invalid value id {:?}", id))),
+        };
+        let hf = self.get_heapfile(id.container_id)?;
+        let own = self.mvcc.own_xid(tid);
+        let pending = |xid: u64| xid != LIVE && Some(xid) != own && self.mvcc.running(xid);
+        let mut waited = false;
+        loop {
+            let record = self
+                .buffer_pool
+                .get_page(id.container_id, page_id, &hf, false)?
+                .get_value(slot_id);
+            let record = match record {
+                Some(record) => record,
+                None => return Ok(None),
+            };
+            let (version, payload) = Version::decode(&record);
+            if !waited && (pending(version.xmin) || pending(version.xmax)) {
+                // The page is not held while waiting, as the writer may need it to roll back
+                self.lock_manager.lock_value(tid, id, Permissions::ReadOnly)?;
+                waited = true;
+                continue;
+            }
+            return match version.kind {
+                _ if version.xmax != LIVE => Ok(None),
+                RecordKind::Chunk => Ok(None),
+                RecordKind::Overflow => {
+                    overflow::read(&self.buffer_pool, id.container_id, &hf, payload).map(Some)
+                }
+                RecordKind::Inline => Ok(Some(payload.to_vec())),
+            };
+        }
+    }
+
+    /// Add a version of a row written at id to the secondary indexes of its table.
+    fn index_value(&self, id: ValueId, value: &[u8]) -> Result<(), CrustyError> {
+        for index in self.table_indexes(id.container_id) {
+            index.insert(index.key(value)?, id)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the version of a row stored at id in record from the secondary indexes of its
+    /// table. A value in overflow pages is read from its chunks, which must still be stored.
+    fn unindex_record(
+        &self,
+        id: ValueId,
+        record: &[u8],
+        hf: &Arc<HeapFile>,
+    ) -> Result<(), CrustyError> {
+        let indexes = self.table_indexes(id.container_id);
+        if indexes.is_empty() {
+            return Ok(());
+        }
+        let (version, payload) = Version::decode(record);
+        let value = match version.kind {
+            RecordKind::Inline => payload.to_vec(),
+            RecordKind::Overflow => {
+                overflow::read(&self.buffer_pool, id.container_id, hf, payload)?
+            }
+            RecordKind::Chunk => return Ok(()),
+        };
+        for index in indexes {
+            index.delete(index.key(&value)?, id)?;
+        }
+        Ok(())
+    }
+
+    /// Add every version of a row stored in a table to a secondary index.
+    fn build_index(&self, index: &Index) -> Result<(), CrustyError> {
+        let hf = self.get_heapfile(index.table_id())?;
+        for page_id in 0..hf.num_pages() {
+            let page = self
+                .buffer_pool
+                .get_page(index.table_id(), page_id, &hf, false)?;
+            for slot_id in 0..page.num_slots() {
+                let record = match page.get_value(slot_id) {
+                    Some(record) => record,
+                    None => continue,
+                };
+                let (version, payload) = Version::decode(&record);
+                let value = match version.kind {
+                    RecordKind::Inline => payload.to_vec(),
+                    RecordKind::Overflow => {
+                        overflow::read(&self.buffer_pool, index.table_id(), &hf, payload)?
+                    }
+                    RecordKind::Chunk => continue,
+                };
+                let id = ValueId::new_slot(index.table_id(), page_id, slot_id);
+                index.insert(index.key(&value)?, id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Try to place a value in the given page, returning its slot if it fit.
+    /// The insert is logged while the page is still latched. If locking, pages that other
+    /// transactions have locked are skipped, as is a reused slot another transaction still
+    /// has locked (it deleted the value there and has not finished), so inserts never wait.
+    fn insert_into_page(
+        &self,
+        container_id: ContainerId,
+        page_id: PageId,
+        hf: &Arc<HeapFile>,
+        value: &[u8],
+        tid: TransactionId,
+        lock: bool,
+    ) -> Result<Option<SlotId>, CrustyError> {
+        if lock
+            && !self.lock_manager.try_acquire(
+                tid,
+                LockId::Page(container_id, page_id),
+                LockMode::IntentionExclusive,
+            )
+        {
+            return Ok(None);
+        }
+        self.buffer_pool.with_page_mut(container_id, page_id, hf, |page| {
+            let before = if lock { Some(page.clone()) } else { None };
+            let slot_id = page.add_value(value)?;
+            let id = ValueId::new_slot(container_id, page_id, slot_id);
+            if let Some(before) = before {
+                if !self
+                    .lock_manager
+                    .try_acquire(tid, LockId::Value(id), LockMode::Exclusive)
+                {
+                    *page = before;
+                    return None;
+                }
+            }
+            self.log(LogRecord::Insert {
+                tid: tid.id(),
+                id,
+                after: value.to_vec(),
+            });
+            Some(slot_id)
+        })
+    }
+
+    /// Insert a value into the first page with room, adding a page if none has any.
+    fn place_value(
+        &self,
+        container_id: ContainerId,
+        value: &[u8],
+        tid: TransactionId,
+        lock: bool,
+    ) -> Result<ValueId, CrustyError> {
+        if value.len() > MAX_VALUE_SIZE {
+            panic!("Cannot handle inserting a value larger than the page size");
+        }
+        let hf = self.get_heapfile(container_id)?;
+        if lock {
+            self.lock_manager.acquire(
+                tid,
+                LockId::Container(container_id),
+                LockMode::IntentionExclusive,
+            )?;
+        }
+        let num_pages = hf.num_pages();
+
+        // The last page is the most likely to have room, then any space freed by deletes
+        let candidates = num_pages.checked_sub(1).into_iter().chain(0..num_pages.saturating_sub(1));
+        for page_id in candidates {
+            if let Some(slot_id) = self.insert_into_page(container_id, page_id, &hf, value, tid, lock)? {
+                return Ok(ValueId::new_slot(container_id, page_id, slot_id));
+            }
+        }
+        loop {
+            let page_id = hf.allocate_page()?;
+            if let Some(slot_id) = self.insert_into_page(container_id, page_id, &hf, value, tid, lock)? {
+                return Ok(ValueId::new_slot(container_id, page_id, slot_id));
+            }
+            if hf.num_pages() > page_id + 1 {
+                // Another insert raced us onto the new page; allocate again
+                continue;
+            }
+            panic!("Value of {} bytes does not fit in an empty page", value.len());
+        }
+    }
+
+    /// Build the record for a new version of value written by tid. A value too large for a
+    /// page is first stored in a chain of chunks, each filling an overflow page except the
+    /// last, and the record points to the chain.
+    fn new_record(
+        &self,
+        container_id: ContainerId,
+        value: &[u8],
+        tid: TransactionId,
+    ) -> Result<Vec<u8>, CrustyError> {
+        let version = Version::created_by(self.mvcc.xid(tid)?);
+        if overflow::fits_inline(value.len()) {
+            return Ok(version.encode(value));
+        }
+        let with_kind = |kind| Version { kind, ..version };
+        // Chunks are written last to first so each knows where the next one is
+        let mut next = None;
+        for data in value.chunks(CHUNK_SIZE).rev() {
+            let chunk = with_kind(RecordKind::Chunk).encode(&overflow::chunk(data, next));
+            next = Some(self.place_value(container_id, &chunk, tid, true)?);
+        }
+        let pointer = overflow::pointer(value.len(), next.unwrap());
+        Ok(with_kind(RecordKind::Overflow).encode(&pointer))
+    }
+
+    /// Mark the version at id deleted by tid, returning false if there is none tid can delete:
+    /// the slot is empty, tid deleted it already, or it is not the version tid's snapshot sees
+    /// because another transaction changed it. That last case is a write conflict, which
+    /// aborts tid under snapshot isolation.
+    fn delete_version(&self, id: ValueId, tid: TransactionId) -> Result<bool, CrustyError> {
+        let (page_id, slot_id) = match (id.page_id, id.slot_id) {
+            (Some(page_id), Some(slot_id)) => (page_id, slot_id),
+            _ => return Ok(false),
+        };
+        let hf = self.get_heapfile(id.container_id)?;
+        if page_id >= hf.num_pages() {
+            return Ok(false);
+        }
+        self.lock_manager.lock_value(tid, id, Permissions::ReadWrite)?;
+        let snapshot = self.mvcc.snapshot(tid);
+        let xid = self.mvcc.xid(tid)?;
+        let before = match self
+            .buffer_pool
+            .get_page(id.container_id, page_id, &hf, false)?
+            .get_value(slot_id)
+        {
+            Some(before) => before,
+            None => return Ok(false),
+        };
+        let (version, payload) = Version::decode(&before);
+        if version.xmax == xid || version.kind == RecordKind::Chunk {
+            return Ok(false);
+        }
+        if version.xmax != LIVE || !snapshot.visible(version, Some(xid)) {
+            if self.mvcc.isolation(tid) == IsolationLevel::Snapshot {
+                debug!("Write conflict on {:?} aborts {:?}", id, tid);
+                self.lock_manager.abort(tid);
+                return Err(CrustyError::TransactionAbortedError);
+            }
+            return Ok(false);
+        }
+        let after = Version { xmax: xid, ..version }.encode(payload);
+        self.buffer_pool.with_page_mut(id.container_id, page_id, &hf, |page| {
+            page.put_value_at(slot_id, &after)?;
+            self.log(LogRecord::Update {
+                tid: tid.id(),
+                id,
+                before: before.clone(),
+                after: after.clone(),
+            });
+            Some(())
+        })?;
+        Ok(true)
+    }
+
+    /// Remove the versions on a page that no transaction can see any more, along with the
+    /// overflow chunks of their values, logging the removals as tid. Values another
+    /// transaction has locked are left alone.
+    fn vacuum_page(
+        &self,
+        container_id: ContainerId,
+        page_id: PageId,
+        hf: &Arc<HeapFile>,
+        tid: TransactionId,
+    ) -> Result<usize, CrustyError> {
+        let page = self.buffer_pool.get_page(container_id, page_id, hf, false)?;
+        let dead: Vec<SlotId> = (0..page.num_slots())
+            .filter(|slot_id| match page.get_value(*slot_id) {
+                Some(record) => self.mvcc.reclaimable(Version::decode(&record).0),
+                None => false,
+            })
+            .filter(|slot_id| {
+                let id = ValueId::new_slot(container_id, page_id, *slot_id);
+                self.lock_manager
+                    .try_acquire(tid, LockId::Value(id), LockMode::Exclusive)
+            })
+            .collect();
+        if dead.is_empty() {
+            return Ok(0);
+        }
+        let mut chunks = Vec::new();
+        for slot_id in &dead {
+            let record = page.get_value(*slot_id).unwrap();
+            let id = ValueId::new_slot(container_id, page_id, *slot_id);
+            self.unindex_record(id, &record, hf)?;
+            let (version, payload) = Version::decode(&record);
+            if version.kind == RecordKind::Overflow {
+                chunks.extend(overflow::chunk_ids(&self.buffer_pool, container_id, hf, payload)?);
+            }
+        }
+        let removed = self.buffer_pool.with_page_mut(container_id, page_id, hf, |page| {
+            for slot_id in &dead {
+                let id = ValueId::new_slot(container_id, page_id, *slot_id);
+                self.remove_slot(page, id, tid)?;
+            }
+            Some(dead.len())
+        })?;
+        for id in chunks {
+            self.buffer_pool.with_page_mut(container_id, id.page_id.unwrap(), hf, |page| {
+                self.remove_slot(page, id, tid)
+            })?;
+        }
+        Ok(removed.unwrap_or(0))
+    }
+
+    /// Delete the value at id from its page, logging the removal as tid.
+    fn remove_slot(&self, page: &mut Page, id: ValueId, tid: TransactionId) -> Option<()> {
+        let before = page.get_value(id.slot_id.unwrap())?;
+        page.delete_value(id.slot_id.unwrap())?;
+        self.log(LogRecord::Delete {
+            tid: tid.id(),
+            id,
+            before,
+        });
+        Some(())
+    }
+
+    /// Append to the log. A record that cannot be logged must not be applied,
+    /// so failing to log is fatal.
+    fn log(&self, record: LogRecord) {
+        self.wal.append(record).expect("Unable to append to write-ahead log");
+    }
+
+    /// Open the heap file for a container, optionally discarding what it held. The file is
+    /// named after the container, with the extension given.
+    fn open_container(
+        &self,
+        container_id: ContainerId,
+        fresh: bool,
+        extension: &str,
+    ) -> Result<(), CrustyError> {
+        let mut containers = self.containers.write().unwrap();
+        if containers.contains_key(&container_id) {
+            return Err(CrustyError::IOError(format!("Container {} already exists", container_id)));
+        }
+        //each container gets its own heap file in the storage path
+        let mut path = PathBuf::from(&self.storage_path);
+        path.push(format!("{}.{}", container_id, extension));
+        if fresh && path.exists() {
+            fs::remove_file(&path)?;
+        }
+        containers.insert(container_id, Arc::new(HeapFile::new(path)?));
+        Ok(())
+    }
+
+    /// Drop a container and delete its heap file.
+    fn drop_container(&self, container_id: ContainerId) -> Result<(), CrustyError> {
+        let hf = match self.containers.write().unwrap().remove(&container_id) {
+            Some(hf) => hf,
+            None => {
+                return Err(CrustyError::CrustyError(format!(
+                    "Container {} does not exist",
+                    container_id
+                )))
+            }
+        };
+        self.buffer_pool.discard_container(container_id);
+        if hf.file_path.exists() {
+            fs::remove_file(&hf.file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Make the state in the buffer pool durable and empty the log.
+    /// Transactions that have not finished are rolled back first, as the log is all that
+    /// tells their changes apart from committed ones.
+    fn checkpoint(&self) -> Result<(), CrustyError> {
+        for tid in self.wal.active_transactions() {
+            debug!("Rolling back unfinished transaction {}", tid);
+            self.rollback(tid)?;
+        }
+        self.wal.flush()?;
+        self.buffer_pool.flush_all()?;
+        self.save_container_map()?;
+        self.wal.truncate()
+    }
+
+    /// Bring the heap files up to date with the log after a crash.
+    fn recover(&self) -> Result<(), CrustyError> {
+        let records = self.wal.read_all()?;
+        if records.is_empty() {
+            return Ok(());
+        }
+        info!("Recovering {} log records in {}", records.len(), self.storage_path);
+        // Redo: repeat history, including changes made by transactions that never finished
+        for record in records {
+            self.redo(&record)?;
+            self.wal.track(record);
+        }
+        // Undo: roll back everything that did not commit or abort before the crash
+        for tid in self.wal.active_transactions() {
+            debug!("Rolling back unfinished transaction {}", tid);
+            self.rollback(tid)?;
+        }
+        // Changes to the indexes are not logged, so they are rebuilt when next opened
+        let index_ids: Vec<ContainerId> = self
+            .containers
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, hf)| Self::is_index(hf))
+            .map(|(container_id, _)| *container_id)
+            .collect();
+        for container_id in index_ids {
+            self.drop_container(container_id)?;
+            self.open_container(container_id, true, INDEX_EXTENSION)?;
+        }
+        self.checkpoint()
+    }
+
+    /// Reapply one logged change. Each change sets or clears a slot, so replaying a
+    /// change that already reached disk does no harm.
+    fn redo(&self, record: &LogRecord) -> Result<(), CrustyError> {
+        match record {
+            LogRecord::CreateContainer { container_id } => {
+                // Everything the container ever held follows in the log
+                if self.containers.read().unwrap().contains_key(container_id) {
+                    self.drop_container(*container_id)?;
+                }
+                self.open_container(*container_id, true, HEAP_EXTENSION)
+            }
+            LogRecord::RemoveContainer { container_id } => {
+                if self.containers.read().unwrap().contains_key(container_id) {
+                    self.drop_container(*container_id)?;
+                }
+                Ok(())
+            }
+            LogRecord::Insert { id, after, .. } => self.put_slot(*id, Some(after)),
+            LogRecord::Delete { id, .. } => self.put_slot(*id, None),
+            LogRecord::Update { id, after, .. } => self.put_slot(*id, Some(after)),
+            LogRecord::Commit { .. } | LogRecord::Abort { .. } => Ok(()),
+        }
+    }
+
+    /// Set the slot for id to bytes, or clear it if bytes is None, creating pages as needed.
+    fn put_slot(&self, id: ValueId, bytes: Option<&Vec<u8>>) -> Result<(), CrustyError> {
+        let (page_id, slot_id) = match (id.page_id, id.slot_id) {
+            (Some(page_id), Some(slot_id)) => (page_id, slot_id),
+            _ => return Err(CrustyError::CrustyError(format!("Invalid value id {:?}", id))),
+        };
+        // The container may have been removed later in the log
+        let hf = match self.get_heapfile(id.container_id) {
+            Ok(hf) => hf,
+            Err(_) => return Ok(()),
+        };
+        while hf.num_pages() <= page_id {
+            if bytes.is_none() {
+                return Ok(());
+            }
+            hf.allocate_page()?;
+        }
+        let applied = self.buffer_pool.with_page_mut(id.container_id, page_id, &hf, |page| {
+            match bytes {
+                Some(bytes) => page.put_value_at(slot_id, bytes).map(|_| true),
+                None => Some(page.delete_value(slot_id).is_some()),
+            }
+        })?;
+        match applied {
+            Some(_) => Ok(()),
+            None => Err(CrustyError::CrustyError(format!(
+                "Unable to restore value {:?}, page is full",
+                id
+            ))),
+        }
+    }
+
+    /// Undo every change tid has made, newest first, logging each compensation,
+    /// and then log the abort.
+    pub(crate) fn rollback(&self, tid: u64) -> Result<(), CrustyError> {
+        let txn = TransactionId::from_raw(tid);
+        if !self.wal.is_active(txn) {
+            return Ok(());
+        }
+        for record in self.wal.active_records(tid).into_iter().rev() {
+            match record {
+                LogRecord::Insert { id, after, .. } => {
+                    if let Ok(hf) = self.get_heapfile(id.container_id) {
+                        self.unindex_record(id, &after, &hf)?;
+                    }
+                    self.put_slot(id, None)?;
+                    self.log(LogRecord::Delete { tid, id, before: after });
+                }
+                LogRecord::Update { id, before, after, .. } => {
+                    self.put_slot(id, Some(&before))?;
+                    self.log(LogRecord::Update { tid, id, before: after, after: before });
+                }
+                LogRecord::Delete { id, before, .. } => {
+                    // Put the value back where it was unless the slot was reused since
+                    let (page_id, slot_id) = (id.page_id.unwrap(), id.slot_id.unwrap());
+                    let hf = match self.get_heapfile(id.container_id) {
+                        Ok(hf) => hf,
+                        Err(_) => continue,
+                    };
+                    let slot_free = page_id >= hf.num_pages()
+                        || self.buffer_pool.get_page(id.container_id, page_id, &hf, false)?
+                            .get_value(slot_id)
+                            .is_none();
+                    if slot_free {
+                        self.put_slot(id, Some(&before))?;
+                        self.log(LogRecord::Insert { tid, id, after: before });
+                    } else {
+                        self.place_value(id.container_id, &before, txn, false)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.log(LogRecord::Abort { tid });
+        self.wal.flush()
+    }
+
+    fn container_map_path(storage_path: &str) -> PathBuf {
+        let mut path = PathBuf::from(storage_path);
+        path.push(CONTAINER_MAP_FILE);
+        path
+    }
+
+    /// Reopen the heap files listed in the container map, if one was written.
+    fn load_container_map(
+        storage_path: &str,
+    ) -> Result<HashMap<ContainerId, Arc<HeapFile>>, CrustyError> {
+        let mut containers = HashMap::new();
+        let map_path = Self::container_map_path(storage_path);
+        if !map_path.exists() {
+            return Ok(containers);
+        }
+        let file = fs::File::open(map_path)?;
+        let paths: HashMap<ContainerId, PathBuf> = serde_json::from_reader(file)
+            .map_err(|e| CrustyError::IOError(format!("Bad container map: {}", e)))?;
+        for (container_id, path) in paths {
+            containers.insert(container_id, Arc::new(HeapFile::new(path)?));
+        }
+        Ok(containers)
+    }
+
+    /// Write out which file backs each container so `new` can reopen them.
+    fn save_container_map(&self) -> Result<(), CrustyError> {
+        let containers = self.containers.read().unwrap();
+        let paths: HashMap<ContainerId, PathBuf> = containers
+            .iter()
+            .map(|(container_id, hf)| (*container_id, hf.file_path.clone()))
+            .collect();
+        let file = fs::File::create(Self::container_map_path(&self.storage_path))?;
+        serde_json::to_writer(file, &paths)
+            .map_err(|e| CrustyError::IOError(format!("Unable to save container map: {}", e)))
+    }
 }
 
 /// Implementation of storage trait
@@ -148,31 +793,7 @@ impl StorageTrait for StorageManager {
     /// (if the storage manager persists records on disk)
     fn new(storage_path: String) -> Self 
     {
-        //#[serde(skip)]
-
-        if Path::new(&storage_path).exists()
-        {
-            let sm = StorageManager
-            {
-            containers: Arc::new(RwLock::new(HashMap::new())),
-            /// Path to database metadata files.
-            storage_path: storage_path,
-            is_temp: false,
-            };
-            return sm;
-        }
-        else
-        {
-            fs::create_dir_all(storage_path.clone());
-            let sm = StorageManager
-            {
-            containers: Arc::new(RwLock::new(HashMap::new())),
-            /// Path to database metadata files.
-            storage_path: storage_path,
-            is_temp: false,
-            };
-            return sm;
-        }
+        StorageManager::new_with_frames(storage_path, PAGE_SLOTS)
     }
 
     /// Create a new storage manager for testing. If this creates a temporary directory it should be cleaned up
@@ -180,15 +801,9 @@ impl StorageTrait for StorageManager {
     fn new_test_sm() -> Self {
         let storage_path = gen_random_dir().to_string_lossy().to_string();
         debug!("Making new temp storage_manager {}", storage_path);
-        let sm = StorageManager
-        {
-            containers: Arc::new(RwLock::new(HashMap::new())),
-            /// Path to database metadata files.
-            storage_path: storage_path,
-            is_temp: true,
-        };
-
-        return sm;
+        let mut sm = StorageManager::new_with_frames(storage_path, PAGE_SLOTS);
+        sm.is_temp = true;
+        sm
     }
 
     fn get_simple_config() -> common::ContainerConfig {
@@ -200,6 +815,7 @@ impl StorageTrait for StorageManager {
     /// Returns the value id associated with the stored value.
     /// Function will need to find the first page that can hold the value.
     /// A new page may need to be created if no space on existing pages can be found.
+    /// A value too large for a page is stored in a chain of overflow pages.
     fn insert_value(
         &self,
         container_id: ContainerId,
@@ -207,42 +823,19 @@ impl StorageTrait for StorageManager {
         tid: TransactionId,
     ) -> ValueId 
     {
-        if value.len() > PAGE_SIZE {
-            panic!("Cannot handle inserting a value larger than the page size");
+        let placed = self
+            .new_record(container_id, &value, tid)
+            .and_then(|record| self.place_value(container_id, &record, tid, true))
+            .and_then(|id| self.index_value(id, &value).map(|_| id));
+        match placed {
+            Ok(id) => id,
+            Err(CrustyError::TransactionAbortedError) => {
+                // Nothing was inserted; the caller finds out from check_transaction
+                debug!("Insert into container {} aborted transaction {:?}", container_id, tid);
+                ValueId::new(container_id)
+            }
+            Err(e) => panic!("Unable to insert into container {}: {:?}", container_id, e),
         }
-
-        panic!("TODO")
-        
-        // let containers = self.containers.read().unwrap();
-
-        // if containers.contains_key(&container_id)
-        // {
-        //     let mut heapfile = containers.get(&container_id).unwrap();
-        //     let mut page_id = 0;
-        //     let mut num_pages = heapfile.num_of_pages.lock().unwrap();
-        //     while page_id < num_pages
-        //     {
-        //         //HeapFile::read_page_from_file(heapfile, page_id).unwrap()
-        //         let mut page = HeapFile::read_page_from_file(heapfile, page_id).unwrap();
-        //         let mut slot_id_add = page.add_value(&value).unwrap();
-        //         //HeapFile::read_page_from_file(heapfile, page_id).unwrap()
-        //         HeapFile::write_page_to_file(heapfile, page);
-        //         if slot_id_add.is_none()
-        //         {
-        //             page_id += 1;
-        //         }
-        //         else
-        //         {
-        //             return slot_id_add;
-        //         }
-        //     }
-
-        //     let mut page = Page::new(num_of_pages - 1);
-        //     let slot_id = page.add_value(&value);
-        //     num_of_pages += 1;
-        //     HeapFile::write_page_to_file(heapfile, page);
-        //     return slot_id;
-        // }
     }
 
     /// Insert some bytes into a container for vector of values (e.g. record).
@@ -254,12 +847,18 @@ impl StorageTrait for StorageManager {
         values: Vec<Vec<u8>>,
         tid: TransactionId,
     ) -> Vec<ValueId> {
-        panic!("TODO milestone hs");
+        values
+            .into_iter()
+            .map(|value| self.insert_value(container_id, value, tid))
+            .collect()
     }
 
     /// Delete the data for a value. If the valueID is not found it returns Ok() still.
+    /// The version stays on its page, marked deleted, until vacuumed. So do its entries in
+    /// the secondary indexes, as older snapshots may still find it through them.
     fn delete_value(&self, id: ValueId, tid: TransactionId) -> Result<(), CrustyError> {
-        panic!("TODO milestone hs");
+        self.delete_version(id, tid)?;
+        Ok(())
     }
 
     /// Updates a value. Returns valueID on update (which may have changed). Error on failure
@@ -269,9 +868,21 @@ impl StorageTrait for StorageManager {
         &self,
         value: Vec<u8>,
         id: ValueId,
-        _tid: TransactionId,
+        tid: TransactionId,
     ) -> Result<ValueId, CrustyError> {
-        panic!("TODO milestone hs");
+        if !self.delete_version(id, tid)? {
+            return Err(CrustyError::CrustyError(format!("Value {:?} does not exist", id)));
+        }
+        // The new version goes on the same page as the old one when it fits
+        let record = self.new_record(id.container_id, &value, tid)?;
+        let hf = self.get_heapfile(id.container_id)?;
+        let page_id = id.page_id.unwrap();
+        let new_id = match self.insert_into_page(id.container_id, page_id, &hf, &record, tid, true)? {
+            Some(slot_id) => ValueId::new_slot(id.container_id, page_id, slot_id),
+            None => self.place_value(id.container_id, &record, tid, true)?,
+        };
+        self.index_value(new_id, &value)?;
+        Ok(new_id)
     }
 
     /// Create a new container to be stored. 
@@ -293,23 +904,10 @@ impl StorageTrait for StorageManager {
         _dependencies: Option<Vec<ContainerId>>,
     ) -> Result<(), CrustyError> 
     {
-        let mut containers = self.containers.write().unwrap();
-
-        if containers.contains_key(&container_id)
-        {
-            return Err(CrustyError::IOError("ContainerId key already exists, you fool!".to_string()));
-        }
-        else
-        {
-            //get the storage path to add the new container to
-            let pathbuf = PathBuf::from(&self.storage_path);
-            //create a heapfile from the given path
-            let heap = HeapFile::new(pathbuf);
-            //add the container id as the key and the new heap as value
-            containers.insert(container_id, heap.unwrap());
-            //return OK(())
-            return Ok(());
-        }
+        //a file left behind by a container that was never persisted is stale
+        self.open_container(container_id, true, HEAP_EXTENSION)?;
+        self.log(LogRecord::CreateContainer { container_id });
+        Ok(())
     }
 
     /// A wrapper function to call create container
@@ -325,56 +923,231 @@ impl StorageTrait for StorageManager {
 
     /// Remove the container and all stored values in the container.
     /// If the container is persisted remove the underlying files
+    /// The secondary indexes of a table are removed with it.
     fn remove_container(&self, container_id: ContainerId) -> Result<(), CrustyError> {
-        panic!("TODO milestone hs");
+        for index in self.table_indexes(container_id) {
+            self.drop_index(index.index_id())?;
+        }
+        self.drop_container(container_id)?;
+        // The file is already gone, so the removal has to be durable too
+        self.log(LogRecord::RemoveContainer { container_id });
+        self.wal.flush()
     }
 
     /// Get an iterator that returns all valid records
+    /// The records are those visible to the transaction's snapshot. Reading takes no locks;
+    /// for writing the whole container is locked. If the lock cannot be had the iterator is
+    /// empty and the transaction is aborted, which check_transaction reports.
     fn get_iterator(
         &self,
         container_id: ContainerId,
         tid: TransactionId,
-        _perm: Permissions,
+        perm: Permissions,
     ) -> Self::ValIterator {
-
-        if Path::new(&self.storage_path).exists()
-        {
-            
+        let hf = self.get_heapfile(container_id).unwrap();
+        let locked = match perm {
+            Permissions::ReadOnly => Ok(()),
+            Permissions::ReadWrite => self.lock_manager.lock_container(tid, container_id, perm),
+        };
+        let iter = HeapFileIterator::new(
+            container_id,
+            tid,
+            hf,
+            self.buffer_pool.clone(),
+            self.mvcc.snapshot(tid),
+            self.mvcc.own_xid(tid),
+        );
+        match locked {
+            Ok(()) => iter,
+            Err(_) => iter.exhausted(),
         }
-        //call iterator on each page
-        // let containers = self.containers.read().unwrap();
-        // let heapfile = containers.get(&container_id).unwrap();
-        // let page_count = heapfile.num_pages();
-
-        // let mut i = 0;
-
-        // while i < page_count
-        panic!("arg");
     }
 
     /// Get the data for a particular ValueId. Error if does not exists
+    /// A value stored in overflow pages is put back together.
     fn get_value(
         &self,
         id: ValueId,
         tid: TransactionId,
         perm: Permissions,
     ) -> Result<Vec<u8>, CrustyError> {
-        panic!("TODO milestone hs");
+        let (page_id, slot_id) = match (id.page_id, id.slot_id) {
+            (Some(page_id), Some(slot_id)) => (page_id, slot_id),
+            _ => return Err(CrustyError::CrustyError(format!("Invalid value id {:?}", id))),
+        };
+        let hf = self.get_heapfile(id.container_id)?;
+        if perm == Permissions::ReadWrite {
+            self.lock_manager.lock_value(tid, id, perm)?;
+        }
+        let snapshot = self.mvcc.snapshot(tid);
+        let page = self.buffer_pool.get_page(id.container_id, page_id, &hf, false)?;
+        if let Some(record) = page.get_value(slot_id) {
+            let (version, payload) = Version::decode(&record);
+            match version.kind {
+                RecordKind::Chunk => {}
+                _ if !snapshot.visible(version, self.mvcc.own_xid(tid)) => {}
+                RecordKind::Overflow => {
+                    return overflow::read(&self.buffer_pool, id.container_id, &hf, payload)
+                }
+                RecordKind::Inline => return Ok(payload.to_vec()),
+            }
+        }
+        Err(CrustyError::CrustyError(format!("Value {:?} does not exist", id)))
     }
 
     /// Notify the storage manager that the transaction is finished so that any held resources can be released.
+    /// The transaction's changes are durable once this returns, unless it was aborted,
+    /// in which case they are rolled back. Either way its locks are released.
     fn transaction_finished(&self, tid: TransactionId) {
-        panic!("TODO milestone tm");
+        if self.lock_manager.is_aborted(tid) {
+            self.rollback(tid.id()).expect("Unable to roll back aborted transaction");
+        } else {
+            self.wal.commit(tid).expect("Unable to force write-ahead log on commit");
+        }
+        self.mvcc.finish(tid);
+        self.lock_manager.release_all(tid);
+    }
+
+    fn begin_statement(&self, tid: TransactionId, isolation: IsolationLevel) {
+        self.mvcc.begin_statement(tid, isolation);
+    }
+
+    /// Remove deleted versions that no running transaction's snapshot still sees, freeing
+    /// their space on the page. The removals are logged and committed as a transaction of their own.
+    fn vacuum(&self) -> Result<usize, CrustyError> {
+        let tid = TransactionId::new();
+        let containers: Vec<(ContainerId, Arc<HeapFile>)> = self
+            .containers
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, hf)| !Self::is_index(hf))
+            .map(|(container_id, hf)| (*container_id, hf.clone()))
+            .collect();
+        let mut removed = 0;
+        for (container_id, hf) in containers {
+            for page_id in 0..hf.num_pages() {
+                removed += self.vacuum_page(container_id, page_id, &hf, tid)?;
+            }
+        }
+        self.wal.commit(tid)?;
+        self.lock_manager.release_all(tid);
+        debug!("Vacuum removed {} versions", removed);
+        Ok(removed)
+    }
+
+    fn abort_transaction(&self, tid: TransactionId) {
+        self.lock_manager.abort(tid);
+        self.transaction_finished(tid);
+    }
+
+    fn check_transaction(&self, tid: TransactionId) -> Result<(), CrustyError> {
+        if self.lock_manager.is_aborted(tid) {
+            Err(CrustyError::TransactionAbortedError)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn detect_deadlocks(&self) -> Vec<TransactionId> {
+        self.lock_manager.detect_deadlocks()
+    }
+
+    /// The index is stored in a heap file of its own, named after the container with the
+    /// extension idx.
+    fn open_index(
+        &self,
+        index_id: ContainerId,
+        table_id: ContainerId,
+        table: Arc<RwLock<Table>>,
+    ) -> Result<(), CrustyError> {
+        if self.indexes.read().unwrap().contains_key(&index_id) {
+            return Ok(());
+        }
+        if self.get_heapfile(index_id).is_err() {
+            self.open_container(index_id, true, INDEX_EXTENSION)?;
+        }
+        let hf = self.get_heapfile(index_id)?;
+        let (index, created) = Index::open(index_id, table_id, table, hf, self.buffer_pool.clone())?;
+        let index = Arc::new(index);
+        // Rows written while the index is built are added by their writers as well
+        self.indexes.write().unwrap().insert(index_id, index.clone());
+        if created {
+            if let Err(e) = self.build_index(&index) {
+                self.drop_index(index_id)?;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn drop_index(&self, index_id: ContainerId) -> Result<(), CrustyError> {
+        self.indexes.write().unwrap().remove(&index_id);
+        self.drop_container(index_id)
+    }
+
+    /// Rows whose key changed since the entry was added are skipped, as are the versions
+    /// the transaction cannot see.
+    fn index_scan(
+        &self,
+        index_id: ContainerId,
+        lower: Bound<&[Field]>,
+        upper: Bound<&[Field]>,
+        tid: TransactionId,
+    ) -> Result<Vec<ValueId>, CrustyError> {
+        let index = self.get_index(index_id)?;
+        let mut ids = Vec::new();
+        for (key, id) in index.find(lower, upper)? {
+            if let Ok(value) = self.get_value(id, tid, Permissions::ReadOnly) {
+                if index.key(&value)? == key {
+                    ids.push(id);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Unlike `index_scan`, rows other transactions wrote but did not commit yet count too,
+    /// after waiting for them to finish, and so do rows committed after the snapshot of tid.
+    fn find_key_conflicts(
+        &self,
+        index_id: ContainerId,
+        key: &[Field],
+        tid: TransactionId,
+    ) -> Result<Vec<ValueId>, CrustyError> {
+        let index = self.get_index(index_id)?;
+        let mut ids = Vec::new();
+        for (_, id) in index.find(Bound::Included(key), Bound::Included(key))? {
+            if let Some(value) = self.latest_value(id, tid)? {
+                if index.key(&value)? == key {
+                    ids.push(id);
+                }
+            }
+        }
+        Ok(ids)
     }
 
     /// Testing utility to reset all state associated the storage manager.
     fn reset(&self) -> Result<(), CrustyError> {
-        panic!("TODO milestone hs");
+        self.buffer_pool.discard_all();
+        self.wal.truncate()?;
+        self.lock_manager.reset();
+        self.mvcc.reset();
+        self.indexes.write().unwrap().clear();
+        let mut containers = self.containers.write().unwrap();
+        for (_, hf) in containers.drain() {
+            fs::remove_file(&hf.file_path)?;
+        }
+        let map_path = Self::container_map_path(&self.storage_path);
+        if map_path.exists() {
+            fs::remove_file(map_path)?;
+        }
+        Ok(())
     }
 
     /// If there is a buffer pool or cache it should be cleared/reset.
     fn clear_cache(&self) {
-        panic!("TODO milestone hs");
+        self.buffer_pool.clear().unwrap();
     }
 
     /// Shutdown the storage manager. Can call drop. Should be safe to call multiple times.
@@ -383,15 +1156,22 @@ impl StorageTrait for StorageManager {
     /// HINT: Heapfile won't be serializable/deserializable. You'll want to serialize information
     /// that can be used to create a HeapFile object pointing to the same data. You don't need to
     /// worry about recreating read_count or write_count.
+    ///
+    /// Shutdown checkpoints, so changes of transactions that have not finished are rolled back.
     fn shutdown(&self) {
-        panic!("TODO milestone hs");
+        if self.is_temp {
+            self.buffer_pool.flush_all().unwrap();
+        } else {
+            self.checkpoint().unwrap();
+        }
     }
 
     fn import_csv(
         &self,
         table: &Table,
+        foreign_keys: &ForeignKeys,
         path: String,
-        _tid: TransactionId,
+        tid: TransactionId,
         container_id: ContainerId,
         _timestamp: LogicalTimeStamp,
     ) -> Result<(), CrustyError> {
@@ -405,34 +1185,11 @@ impl StorageTrait for StorageManager {
             .from_reader(file);
 
         // Iterate through csv records.
-        let mut inserted_records = 0;
+        let mut tuples = Vec::new();
         for result in rdr.records() {
             #[allow(clippy::single_match)]
             match result {
-                Ok(rec) => {
-                    // Build tuple and infer types from schema.
-                    let mut tuple = Tuple::new(Vec::new());
-                    for (field, attr) in rec.iter().zip(table.schema.attributes()) {
-                        // TODO: Type mismatch between attributes and record data>
-                        match &attr.dtype() {
-                            DataType::Int => {
-                                let value: i32 = field.parse::<i32>().unwrap();
-                                tuple.field_vals.push(Field::IntField(value));
-                            }
-                            DataType::String => {
-                                let value: String = field.to_string().clone();
-                                tuple.field_vals.push(Field::StringField(value));
-                            }
-                        }
-                    }
-                    //TODO: How should individual row insertion errors be handled?
-                    debug!(
-                        "server::csv_utils about to insert tuple into container_id: {:?}",
-                        &container_id
-                    );
-                    self.insert_value(container_id, tuple.get_bytes(), _tid);
-                    inserted_records += 1;
-                }
+                Ok(rec) => tuples.push(table.tuple_from_csv(&rec)?),
                 _ => {
                     // FIXME: get error from csv reader
                     error!("Could not read row from CSV");
@@ -440,24 +1197,43 @@ impl StorageTrait for StorageManager {
                 }
             }
         }
+        // Check every row before inserting any, so a bad file imports nothing.
+        let rows: Vec<(Option<ValueId>, &Tuple)> = tuples.iter().map(|t| (None, t)).collect();
+        table.check_rows(self, container_id, tid, &rows)?;
+        let refs: Vec<&Tuple> = tuples.iter().collect();
+        foreign_keys.check_references(self, tid, table, container_id, &refs)?;
+        debug!(
+            "server::csv_utils about to insert tuples into container_id: {:?}",
+            &container_id
+        );
+        let values = tuples.iter().map(|t| table.encode(t)).collect();
+        let ids = self.insert_values(container_id, values, tid);
+        self.check_transaction(tid)?;
+        let written: Vec<(ValueId, &Tuple)> = ids.into_iter().zip(tuples.iter()).collect();
+        table.check_written(self, tid, &written)?;
+        let inserted_records = tuples.len();
         info!("Num records imported: {:?}", inserted_records);
         Ok(())
     }
 }
 
 /// Trait Impl for Drop
-/// bro i dont know if this works but i feel like my brain is fully shutting down.
-/// future abby, if you are debugging this, you got this. sorry for doing you so dirty and ignoring this functionality
 impl Drop for StorageManager {
     /// Shutdown the storage manager. Can call be called by shutdown. Should be safe to call multiple times.
     /// If temp, this should remove all stored files.
+    /// Otherwise dirty pages are written back; the container map is only saved by shutdown.
     fn drop(&mut self) 
     {
         if self.is_temp
         {
-            fs::remove_dir_all(&self.storage_path.clone());
+            if let Err(e) = fs::remove_dir_all(&self.storage_path) {
+                debug!("Unable to remove temp storage {}: {}", self.storage_path, e);
+            }
+        }
+        else if let Err(e) = self.buffer_pool.flush_all()
+        {
+            error!("Unable to flush buffer pool on drop: {:?}", e);
         }
-        //panic!("TODO milestone hs");
     }
 }
 
@@ -468,6 +1244,7 @@ mod test {
     use crate::storage_manager::StorageManager;
     use common::storage_trait::StorageTrait;
     use common::testutil::*;
+    use common::PAGE_SIZE;
 
     #[test]
     fn hs_sm_a_insert() {
@@ -575,4 +1352,394 @@ mod test {
         }
         assert_eq!(1000, count);
     }
+
+    #[test]
+    fn hs_sm_c_buffer_pool_rescan() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let tid = TransactionId::new();
+
+        let vals = get_random_vec_of_byte_vec(100, 100, 200);
+        sm.insert_values(cid, vals.clone(), tid);
+        sm.clear_cache();
+        let num_pages = sm.get_num_pages(cid);
+        assert!((num_pages as usize) < sm.get_buffer_pool_size());
+
+        let (reads_before, _) = sm.get_hf_read_write_count(cid);
+        for _ in 0..3 {
+            let check: Vec<Vec<u8>> = sm.get_iterator(cid, tid, Permissions::ReadOnly).collect();
+            assert!(compare_unordered_byte_vecs(&vals, check));
+        }
+        // Only the first scan should go to the heap file
+        let (reads_after, _) = sm.get_hf_read_write_count(cid);
+        if cfg!(feature = "profile") {
+            assert_eq!(num_pages, reads_after - reads_before);
+            assert!(sm.get_buffer_pool_hit_ratio() > 0.5);
+        }
+    }
+
+    #[test]
+    fn hs_sm_c_small_pool_evicts() {
+        init();
+        let sm = StorageManager::new_with_frames(gen_random_dir().to_string_lossy().to_string(), 3);
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let tid = TransactionId::new();
+
+        let vals = get_random_vec_of_byte_vec(200, 100, 200);
+        let ids = sm.insert_values(cid, vals.clone(), tid);
+        assert!(sm.get_num_pages(cid) > 3);
+        let check: Vec<Vec<u8>> = sm.get_iterator(cid, tid, Permissions::ReadOnly).collect();
+        assert!(compare_unordered_byte_vecs(&vals, check));
+        for (id, val) in ids.iter().zip(vals.iter()) {
+            assert_eq!(val, &sm.get_value(*id, tid, Permissions::ReadOnly).unwrap());
+        }
+        sm.reset().unwrap();
+        fs::remove_dir_all(&sm.storage_path).unwrap();
+    }
+
+    #[test]
+    fn hs_sm_d_lock_conflict_aborts() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        sm.set_lock_timeout(Duration::from_millis(50));
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let t1 = TransactionId::new();
+        let vals = get_random_vec_of_byte_vec(10, 20, 40);
+        let ids = sm.insert_values(cid, vals.clone(), t1);
+        sm.transaction_finished(t1);
+
+        // Readers don't hold the writer up
+        let t2 = TransactionId::new();
+        let t3 = TransactionId::new();
+        assert_eq!(vals[0], sm.get_value(ids[0], t2, Permissions::ReadOnly).unwrap());
+        sm.delete_value(ids[0], t3).unwrap();
+        assert_eq!(vals[0], sm.get_value(ids[0], t2, Permissions::ReadOnly).unwrap());
+        assert_eq!(10, sm.get_iterator(cid, t2, Permissions::ReadOnly).count());
+
+        // A second writer times out and is aborted
+        let t4 = TransactionId::new();
+        assert_eq!(
+            Err(CrustyError::TransactionAbortedError),
+            sm.delete_value(ids[0], t4)
+        );
+        assert!(sm.check_transaction(t4).is_err());
+        sm.transaction_finished(t4);
+        sm.transaction_finished(t3);
+        sm.transaction_finished(t2);
+
+        let t5 = TransactionId::new();
+        assert_eq!(9, sm.get_iterator(cid, t5, Permissions::ReadOnly).count());
+        assert!(sm.check_transaction(t5).is_ok());
+    }
+
+    #[test]
+    fn hs_sm_d_aborted_writes_roll_back() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        sm.set_lock_timeout(Duration::from_millis(50));
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let t1 = TransactionId::new();
+        let vals = get_random_vec_of_byte_vec(10, 20, 40);
+        let ids = sm.insert_values(cid, vals.clone(), t1);
+        sm.transaction_finished(t1);
+
+        let blocker = TransactionId::new();
+        sm.delete_value(ids[9], blocker).unwrap();
+
+        let t2 = TransactionId::new();
+        sm.insert_values(cid, get_random_vec_of_byte_vec(5, 20, 40), t2);
+        sm.delete_value(ids[0], t2).unwrap();
+        sm.update_value(get_random_byte_vec(10), ids[1], t2).unwrap();
+        // Blocked by the other writer, so t2 is aborted
+        assert!(sm.update_value(get_random_byte_vec(10), ids[9], t2).is_err());
+        sm.transaction_finished(t2);
+        sm.abort_transaction(blocker);
+
+        let t3 = TransactionId::new();
+        let check: Vec<Vec<u8>> = sm.get_iterator(cid, t3, Permissions::ReadOnly).collect();
+        assert!(compare_unordered_byte_vecs(&vals, check));
+    }
+
+    #[test]
+    fn hs_sm_d_abort_transaction() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let t1 = TransactionId::new();
+        let vals = get_random_vec_of_byte_vec(10, 20, 40);
+        let ids = sm.insert_values(cid, vals.clone(), t1);
+        sm.transaction_finished(t1);
+
+        let t2 = TransactionId::new();
+        sm.insert_values(cid, get_random_vec_of_byte_vec(5, 20, 40), t2);
+        sm.delete_value(ids[3], t2).unwrap();
+        sm.update_value(get_random_byte_vec(10), ids[4], t2).unwrap();
+        sm.abort_transaction(t2);
+        assert!(sm.check_transaction(t2).is_ok());
+
+        let t3 = TransactionId::new();
+        let check: Vec<Vec<u8>> = sm.get_iterator(cid, t3, Permissions::ReadOnly).collect();
+        assert!(compare_unordered_byte_vecs(&vals, check));
+    }
+
+    #[test]
+    fn hs_sm_e_snapshot_isolation() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let t1 = TransactionId::new();
+        let vals = get_random_vec_of_byte_vec(10, 20, 40);
+        let ids = sm.insert_values(cid, vals.clone(), t1);
+        sm.transaction_finished(t1);
+
+        let snap = TransactionId::new();
+        let committed = TransactionId::new();
+        sm.begin_statement(snap, IsolationLevel::Snapshot);
+        sm.begin_statement(committed, IsolationLevel::ReadCommitted);
+        assert_eq!(10, sm.get_iterator(cid, snap, Permissions::ReadOnly).count());
+        assert_eq!(10, sm.get_iterator(cid, committed, Permissions::ReadOnly).count());
+
+        let writer = TransactionId::new();
+        let new_val = get_random_byte_vec(15);
+        let new_id = sm.update_value(new_val.clone(), ids[0], writer).unwrap();
+        sm.delete_value(ids[1], writer).unwrap();
+        sm.insert_value(cid, get_random_byte_vec(15), writer);
+        // The writer sees its own changes
+        assert_eq!(new_val, sm.get_value(new_id, writer, Permissions::ReadOnly).unwrap());
+        assert!(sm.get_value(ids[0], writer, Permissions::ReadOnly).is_err());
+        assert_eq!(10, sm.get_iterator(cid, writer, Permissions::ReadOnly).count());
+        sm.transaction_finished(writer);
+
+        // Read committed picks the commit up at its next statement, snapshot never does
+        assert_eq!(vals[0], sm.get_value(ids[0], committed, Permissions::ReadOnly).unwrap());
+        sm.begin_statement(committed, IsolationLevel::ReadCommitted);
+        assert!(sm.get_value(ids[0], committed, Permissions::ReadOnly).is_err());
+        assert_eq!(new_val, sm.get_value(new_id, committed, Permissions::ReadOnly).unwrap());
+        sm.begin_statement(snap, IsolationLevel::Snapshot);
+        let check: Vec<Vec<u8>> = sm.get_iterator(cid, snap, Permissions::ReadOnly).collect();
+        assert!(compare_unordered_byte_vecs(&vals, check));
+
+        // Under snapshot isolation, changing what someone else changed since is a conflict
+        assert_eq!(
+            Err(CrustyError::TransactionAbortedError),
+            sm.delete_value(ids[0], snap)
+        );
+        assert!(sm.check_transaction(snap).is_err());
+        sm.transaction_finished(snap);
+        // while read committed finds nothing left to change
+        assert!(sm.update_value(get_random_byte_vec(10), ids[1], committed).is_err());
+        sm.delete_value(ids[1], committed).unwrap();
+        assert!(sm.check_transaction(committed).is_ok());
+        sm.transaction_finished(committed);
+    }
+
+    #[test]
+    fn hs_sm_e_vacuum_reclaims_dead_versions() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let t1 = TransactionId::new();
+        let vals = get_random_vec_of_byte_vec(10, 20, 40);
+        let ids = sm.insert_values(cid, vals.clone(), t1);
+        sm.transaction_finished(t1);
+
+        let reader = TransactionId::new();
+        sm.begin_statement(reader, IsolationLevel::Snapshot);
+        assert_eq!(10, sm.get_iterator(cid, reader, Permissions::ReadOnly).count());
+
+        let t2 = TransactionId::new();
+        for id in &ids[..4] {
+            sm.delete_value(*id, t2).unwrap();
+        }
+        // Not committed yet
+        assert_eq!(0, sm.vacuum().unwrap());
+        sm.transaction_finished(t2);
+        // The reader can still see them
+        assert_eq!(0, sm.vacuum().unwrap());
+        assert_eq!(10, sm.get_iterator(cid, reader, Permissions::ReadOnly).count());
+        sm.transaction_finished(reader);
+
+        assert_eq!(4, sm.vacuum().unwrap());
+        assert_eq!(0, sm.vacuum().unwrap());
+        let t3 = TransactionId::new();
+        let check: Vec<Vec<u8>> = sm.get_iterator(cid, t3, Permissions::ReadOnly).collect();
+        assert!(compare_unordered_byte_vecs(&vals[4..], check));
+        // The freed slots are reused
+        let id = sm.insert_value(cid, get_random_byte_vec(20), t3);
+        assert!(ids[..4].contains(&id));
+        sm.transaction_finished(t3);
+    }
+
+    #[test]
+    fn hs_sm_f_overflow_values() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let t1 = TransactionId::new();
+        let small = get_random_byte_vec(40);
+        let large = get_random_byte_vec(PAGE_SIZE * 3 + 100);
+        sm.insert_value(cid, small.clone(), t1);
+        let id = sm.insert_value(cid, large.clone(), t1);
+        assert_eq!(large, sm.get_value(id, t1, Permissions::ReadOnly).unwrap());
+        sm.transaction_finished(t1);
+
+        // The chunks are read through the value pointing to them, never on their own
+        let t2 = TransactionId::new();
+        let check: Vec<Vec<u8>> = sm.get_iterator(cid, t2, Permissions::ReadOnly).collect();
+        assert!(compare_unordered_byte_vecs(&[small.clone(), large.clone()], check));
+        let num_pages = sm.get_num_pages(cid);
+        assert!(num_pages >= 4);
+
+        // Aborted overflowing writes leave nothing behind
+        let larger = get_random_byte_vec(PAGE_SIZE * 5);
+        sm.insert_value(cid, larger.clone(), t2);
+        let new_id = sm.update_value(larger.clone(), id, t2).unwrap();
+        assert_eq!(larger, sm.get_value(new_id, t2, Permissions::ReadOnly).unwrap());
+        sm.abort_transaction(t2);
+        let t3 = TransactionId::new();
+        let check: Vec<Vec<u8>> = sm.get_iterator(cid, t3, Permissions::ReadOnly).collect();
+        assert!(compare_unordered_byte_vecs(&[small.clone(), large], check));
+
+        // Vacuuming a deleted value frees its overflow pages for new values
+        sm.delete_value(id, t3).unwrap();
+        sm.transaction_finished(t3);
+        sm.vacuum().unwrap();
+        let num_pages = sm.get_num_pages(cid);
+        let t4 = TransactionId::new();
+        let id = sm.insert_value(cid, larger.clone(), t4);
+        let other = sm.insert_value(cid, larger.clone(), t4);
+        assert_eq!(larger, sm.get_value(id, t4, Permissions::ReadOnly).unwrap());
+        assert_eq!(larger, sm.get_value(other, t4, Permissions::ReadOnly).unwrap());
+        let check: Vec<Vec<u8>> = sm.get_iterator(cid, t4, Permissions::ReadOnly).collect();
+        assert!(compare_unordered_byte_vecs(&[small, larger.clone(), larger], check));
+        assert_eq!(num_pages, sm.get_num_pages(cid));
+        sm.transaction_finished(t4);
+    }
+
+    #[test]
+    fn hs_sm_g_secondary_index() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        let (cid, index_id) = (1, 2);
+        sm.create_table(cid).unwrap();
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::Int]);
+        let mut table = Table::new("t".to_string(), schema);
+        table.add_secondary_index(common::index::SecondaryIndex {
+            name: "t_b".to_string(),
+            container_id: index_id,
+            columns: vec![1],
+            unique: false,
+            method: common::index::IndexMethod::BTree,
+            constraint: false,
+        });
+        let row = |a, b| table.encode(&int_vec_to_tuple(vec![a, b]));
+        let t1 = TransactionId::new();
+        let ids: Vec<ValueId> = (0..10)
+            .map(|i| sm.insert_value(cid, row(i, i % 5), t1))
+            .collect();
+        sm.transaction_finished(t1);
+
+        // Rows stored before the index is opened are added to it
+        let table = Arc::new(RwLock::new(table.clone()));
+        sm.open_index(index_id, cid, table).unwrap();
+        let key = [Field::IntField(3)];
+        let scan = |lower, upper, tid| sm.index_scan(index_id, lower, upper, tid).unwrap();
+        let t2 = TransactionId::new();
+        let found = scan(Bound::Included(&key[..]), Bound::Included(&key[..]), t2);
+        assert_eq!(vec![ids[3], ids[8]], found);
+        let found = scan(Bound::Excluded(&key[..]), Bound::Unbounded, t2);
+        assert_eq!(vec![ids[4], ids[9]], found);
+
+        // Writes keep the index up to date; rows the transaction cannot see are skipped
+        let new_id = sm.update_value(row(3, 7), ids[3], t2).unwrap();
+        let added = sm.insert_value(cid, row(10, 3), t2);
+        let t3 = TransactionId::new();
+        sm.begin_statement(t3, IsolationLevel::Snapshot);
+        let found = scan(Bound::Included(&key[..]), Bound::Included(&key[..]), t2);
+        assert_eq!(vec![ids[8], added], found);
+        let found = scan(Bound::Included(&key[..]), Bound::Included(&key[..]), t3);
+        assert_eq!(vec![ids[3], ids[8]], found);
+        assert!(scan(Bound::Excluded(&key[..]), Bound::Unbounded, t2).contains(&new_id));
+        sm.abort_transaction(t2);
+        sm.transaction_finished(t3);
+
+        // Rolling back and vacuuming remove the entries of the versions they remove
+        let index = sm.indexes.read().unwrap().get(&index_id).unwrap().clone();
+        let entries = || {
+            index.find(Bound::Unbounded, Bound::Unbounded)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(10, entries());
+        let t4 = TransactionId::new();
+        sm.delete_value(ids[8], t4).unwrap();
+        assert_eq!(10, entries());
+        sm.transaction_finished(t4);
+        sm.vacuum().unwrap();
+        assert_eq!(9, entries());
+        let t5 = TransactionId::new();
+        let found = scan(Bound::Included(&key[..]), Bound::Included(&key[..]), t5);
+        assert_eq!(vec![ids[3]], found);
+        sm.transaction_finished(t5);
+
+        sm.drop_index(index_id).unwrap();
+        assert!(sm
+            .index_scan(index_id, Bound::Unbounded, Bound::Unbounded, t5)
+            .is_err());
+    }
+
+    #[test]
+    fn hs_sm_g_hash_index() {
+        init();
+        let sm = StorageManager::new_test_sm();
+        let (cid, index_id) = (1, 2);
+        sm.create_table(cid).unwrap();
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::Int]);
+        let mut table = Table::new("t".to_string(), schema);
+        table.add_secondary_index(common::index::SecondaryIndex {
+            name: "t_b".to_string(),
+            container_id: index_id,
+            columns: vec![1],
+            unique: false,
+            method: common::index::IndexMethod::Hash,
+            constraint: false,
+        });
+        let row = |a, b| table.encode(&int_vec_to_tuple(vec![a, b]));
+        let t1 = TransactionId::new();
+        let ids: Vec<ValueId> = (0..1000)
+            .map(|i| sm.insert_value(cid, row(i, i % 100), t1))
+            .collect();
+        sm.transaction_finished(t1);
+
+        let table = Arc::new(RwLock::new(table.clone()));
+        sm.open_index(index_id, cid, table).unwrap();
+        let key = [Field::IntField(42)];
+        let t2 = TransactionId::new();
+        let found = sm
+            .index_scan(index_id, Bound::Included(&key), Bound::Included(&key), t2)
+            .unwrap();
+        let expected: Vec<ValueId> = (0..10).map(|i| ids[42 + 100 * i]).collect();
+        assert_eq!(expected, found);
+        let new_id = sm.update_value(row(7, 42), ids[7], t2).unwrap();
+        let found = sm
+            .index_scan(index_id, Bound::Included(&key), Bound::Included(&key), t2)
+            .unwrap();
+        assert_eq!(11, found.len());
+        assert!(found.contains(&new_id));
+
+        // Only equal keys can be found
+        assert!(sm
+            .index_scan(index_id, Bound::Included(&key), Bound::Unbounded, t2)
+            .is_err());
+        sm.transaction_finished(t2);
+    }
 }
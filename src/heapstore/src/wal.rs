@@ -0,0 +1,264 @@
+use common::ids::{ContainerId, TransactionId, ValueId};
+use common::CrustyError;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// One entry in the write-ahead log.
+///
+/// Record changes carry full images so that redo and undo are both
+/// "set this slot to these bytes" or "clear this slot", which can be
+/// replayed any number of times. Overwriting a record in place (e.g. marking a
+/// version deleted) is logged as an update. Transaction ids are stored as raw u64s.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) enum LogRecord {
+    CreateContainer {
+        container_id: ContainerId,
+    },
+    RemoveContainer {
+        container_id: ContainerId,
+    },
+    Insert {
+        tid: u64,
+        id: ValueId,
+        after: Vec<u8>,
+    },
+    Delete {
+        tid: u64,
+        id: ValueId,
+        before: Vec<u8>,
+    },
+    Update {
+        tid: u64,
+        id: ValueId,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+    Commit {
+        tid: u64,
+    },
+    Abort {
+        tid: u64,
+    },
+}
+
+struct WalState {
+    file: File,
+    /// Encoded records that have not been written to the file yet.
+    buffer: Vec<u8>,
+    /// Record changes made by transactions that have not committed or aborted,
+    /// in the order they were logged. Used to roll a transaction back.
+    active: HashMap<u64, Vec<LogRecord>>,
+}
+
+/// The write-ahead log for a storage manager.
+///
+/// Records are appended to an in-memory buffer and only reach the file on `flush`,
+/// which also syncs it. The storage manager must flush before writing a dirty page
+/// and on commit. Each record is stored as a little endian u32 length followed by
+/// the CBOR encoding of the record.
+pub(crate) struct Wal {
+    path: PathBuf,
+    state: Mutex<WalState>,
+}
+
+impl Wal {
+    /// Open (or create) the log file at path.
+    pub(crate) fn open(path: PathBuf) -> Result<Self, CrustyError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        Ok(Wal {
+            path,
+            state: Mutex::new(WalState {
+                file,
+                buffer: Vec::new(),
+                active: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Add a record to the log buffer.
+    pub(crate) fn append(&self, record: LogRecord) -> Result<(), CrustyError> {
+        let bytes = serde_cbor::to_vec(&record)
+            .map_err(|e| CrustyError::IOError(format!("Unable to encode log record: {}", e)))?;
+        let mut state = self.state.lock().unwrap();
+        state
+            .buffer
+            .extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        state.buffer.extend_from_slice(&bytes);
+        Self::track_locked(&mut state, record);
+        Ok(())
+    }
+
+    /// Follow a record that is already in the log file, so that an unfinished
+    /// transaction it belongs to can be rolled back. Used while recovering.
+    pub(crate) fn track(&self, record: LogRecord) {
+        let mut state = self.state.lock().unwrap();
+        Self::track_locked(&mut state, record);
+    }
+
+    fn track_locked(state: &mut WalState, record: LogRecord) {
+        match &record {
+            LogRecord::Insert { tid, .. }
+            | LogRecord::Delete { tid, .. }
+            | LogRecord::Update { tid, .. } => {
+                state.active.entry(*tid).or_default().push(record);
+            }
+            LogRecord::Commit { tid } | LogRecord::Abort { tid } => {
+                state.active.remove(tid);
+            }
+            _ => {}
+        }
+    }
+
+    /// Transactions with logged changes that have not committed or aborted.
+    pub(crate) fn active_transactions(&self) -> Vec<u64> {
+        let state = self.state.lock().unwrap();
+        let mut tids: Vec<u64> = state.active.keys().copied().collect();
+        tids.sort_unstable();
+        tids
+    }
+
+    /// Write the buffered records to the end of the log file and sync it.
+    pub(crate) fn flush(&self) -> Result<(), CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        if state.buffer.is_empty() {
+            return Ok(());
+        }
+        let buffer = std::mem::take(&mut state.buffer);
+        state.file.seek(SeekFrom::End(0))?;
+        state.file.write_all(&buffer)?;
+        state.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Log the commit of tid and force the log.
+    /// A transaction that never logged a change has nothing to make durable.
+    pub(crate) fn commit(&self, tid: TransactionId) -> Result<(), CrustyError> {
+        if !self.is_active(tid) {
+            return Ok(());
+        }
+        self.append(LogRecord::Commit { tid: tid.id() })?;
+        self.flush()
+    }
+
+    /// The changes tid has logged so far, oldest first. Empty if it has not changed anything.
+    pub(crate) fn active_records(&self, tid: u64) -> Vec<LogRecord> {
+        let state = self.state.lock().unwrap();
+        state.active.get(&tid).cloned().unwrap_or_default()
+    }
+
+    /// Whether tid has logged changes and not yet committed or aborted.
+    pub(crate) fn is_active(&self, tid: TransactionId) -> bool {
+        self.state.lock().unwrap().active.contains_key(&tid.id())
+    }
+
+    /// Read every complete record in the log file. A torn record at the end
+    /// (from a crash in the middle of a write) is ignored.
+    pub(crate) fn read_all(&self) -> Result<Vec<LogRecord>, CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        let mut bytes = Vec::new();
+        state.file.seek(SeekFrom::Start(0))?;
+        state.file.read_to_end(&mut bytes)?;
+        let mut records = Vec::new();
+        let mut pos = 0;
+        while pos + 4 <= bytes.len() {
+            let mut len = [0; 4];
+            len.copy_from_slice(&bytes[pos..pos + 4]);
+            let len = u32::from_le_bytes(len) as usize;
+            if pos + 4 + len > bytes.len() {
+                warn!("Ignoring torn record at the end of {:?}", self.path);
+                break;
+            }
+            match serde_cbor::from_slice(&bytes[pos + 4..pos + 4 + len]) {
+                Ok(record) => records.push(record),
+                Err(e) => {
+                    warn!("Ignoring unreadable log record in {:?}: {}", self.path, e);
+                    break;
+                }
+            }
+            pos += 4 + len;
+        }
+        Ok(records)
+    }
+
+    /// Empty the log. Only safe once every page it covers is on disk and no
+    /// transaction needs it for rollback.
+    pub(crate) fn truncate(&self) -> Result<(), CrustyError> {
+        let mut state = self.state.lock().unwrap();
+        state.buffer.clear();
+        state.active.clear();
+        state.file.set_len(0)?;
+        state.file.seek(SeekFrom::Start(0))?;
+        state.file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::testutil::*;
+    use temp_testdir::TempDir;
+
+    #[test]
+    fn hs_wal_round_trip() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let mut path = tdir.to_path_buf();
+        path.push("wal.log");
+        let wal = Wal::open(path.clone()).unwrap();
+        let tid = TransactionId::new();
+        let records = vec![
+            LogRecord::CreateContainer { container_id: 1 },
+            LogRecord::Insert {
+                tid: tid.id(),
+                id: ValueId::new_slot(1, 0, 0),
+                after: get_random_byte_vec(20),
+            },
+            LogRecord::Delete {
+                tid: tid.id(),
+                id: ValueId::new_slot(1, 0, 0),
+                before: get_random_byte_vec(20),
+            },
+        ];
+        for r in &records {
+            wal.append(r.clone()).unwrap();
+        }
+        assert!(wal.is_active(tid));
+        assert_eq!(2, wal.active_records(tid.id()).len());
+        // Nothing is on disk until a flush
+        assert!(wal.read_all().unwrap().is_empty());
+        wal.commit(tid).unwrap();
+        assert!(!wal.is_active(tid));
+
+        let reopened = Wal::open(path).unwrap();
+        let mut expected = records;
+        expected.push(LogRecord::Commit { tid: tid.id() });
+        assert_eq!(expected, reopened.read_all().unwrap());
+    }
+
+    #[test]
+    fn hs_wal_torn_tail() {
+        init();
+        let tdir = TempDir::new(gen_random_dir(), true);
+        let mut path = tdir.to_path_buf();
+        path.push("wal.log");
+        let wal = Wal::open(path.clone()).unwrap();
+        wal.append(LogRecord::Commit { tid: 7 }).unwrap();
+        wal.flush().unwrap();
+        // Simulate a crash part way through writing a record
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        f.write_all(&[200, 0, 0, 0, 1, 2]).unwrap();
+
+        assert_eq!(vec![LogRecord::Commit { tid: 7 }], wal.read_all().unwrap());
+        wal.truncate().unwrap();
+        assert!(wal.read_all().unwrap().is_empty());
+    }
+}
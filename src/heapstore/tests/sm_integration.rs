@@ -2,10 +2,15 @@ extern crate common;
 extern crate heapstore as sm;
 use common::ids::Permissions;
 use common::ids::{ContainerId, TransactionId};
+use common::index::{IndexMethod, SecondaryIndex};
+use common::table::Table;
+use common::{DataType, Field, TableSchema};
 use common::storage_trait::StorageTrait;
 use common::testutil::*;
 use rand::{thread_rng, Rng};
 use sm::storage_manager::StorageManager;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
 
 const RO: Permissions = Permissions::ReadOnly;
 
@@ -82,7 +87,7 @@ fn sm_no_container() {
 
 #[test]
 fn sm_test_shutdown() {
-    let path = String::from("tmp");
+    let path = gen_random_dir().to_string_lossy().to_string();
     let sm = StorageManager::new(path.clone());
     let t = TransactionId::new();
 
@@ -90,6 +95,7 @@ fn sm_test_shutdown() {
     let cid = 1;
     sm.create_table(cid).unwrap();
     let _val_ids = sm.insert_values(cid, vals1.clone(), t);
+    sm.transaction_finished(t);
     sm.shutdown();
 
     let sm2 = StorageManager::new(path);
@@ -97,3 +103,154 @@ fn sm_test_shutdown() {
     assert!(compare_unordered_byte_vecs(&vals1, check_vals));
     sm2.reset().unwrap();
 }
+
+#[test]
+fn sm_shutdown_rolls_back_unfinished() {
+    let path = gen_random_dir().to_string_lossy().to_string();
+    let sm = StorageManager::new(path.clone());
+    let cid = 1;
+    sm.create_table(cid).unwrap();
+    let t1 = TransactionId::new();
+    let committed = get_random_vec_of_byte_vec(50, 50, 100);
+    let ids = sm.insert_values(cid, committed.clone(), t1);
+    sm.transaction_finished(t1);
+
+    // Still running at shutdown: an insert, a delete and an update
+    let t2 = TransactionId::new();
+    sm.insert_values(cid, get_random_vec_of_byte_vec(50, 50, 100), t2);
+    sm.delete_value(ids[0], t2).unwrap();
+    sm.update_value(get_random_byte_vec(30), ids[1], t2).unwrap();
+    sm.shutdown();
+    drop(sm);
+
+    let sm2 = StorageManager::new(path.clone());
+    let t3 = TransactionId::new();
+    let check_vals: Vec<Vec<u8>> = sm2.get_iterator(cid, t3, RO).collect();
+    assert!(compare_unordered_byte_vecs(&committed, check_vals));
+    sm2.reset().unwrap();
+    drop(sm2);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn sm_crash_recovery() {
+    let path = gen_random_dir().to_string_lossy().to_string();
+    // A tiny buffer pool forces uncommitted pages out to disk before the crash
+    let sm = StorageManager::new_with_frames(path.clone(), 3);
+    let cid = 1;
+    sm.create_table(cid).unwrap();
+
+    // Committed: inserts, then a delete and an update of some of them
+    let t1 = TransactionId::new();
+    let mut committed = get_random_vec_of_byte_vec(200, 50, 100);
+    let mut ids = sm.insert_values(cid, committed.clone(), t1);
+    sm.transaction_finished(t1);
+
+    let t2 = TransactionId::new();
+    sm.delete_value(ids[0], t2).unwrap();
+    committed.swap_remove(0);
+    ids.swap_remove(0);
+    let new_bytes = get_random_byte_vec(30);
+    ids[5] = sm.update_value(new_bytes.clone(), ids[5], t2).unwrap();
+    committed[5] = new_bytes;
+    sm.transaction_finished(t2);
+
+    // Uncommitted: more inserts, deletes and updates of committed rows
+    let t3 = TransactionId::new();
+    sm.insert_values(cid, get_random_vec_of_byte_vec(200, 50, 100), t3);
+    for id in ids.iter().take(20) {
+        sm.delete_value(*id, t3).unwrap();
+    }
+    for id in ids.iter().skip(20).take(20) {
+        sm.update_value(get_random_byte_vec(90), *id, t3).unwrap();
+    }
+
+    // Crash: nothing is flushed, nothing is cleaned up
+    std::mem::forget(sm);
+
+    let sm2 = StorageManager::new(path.clone());
+    let check_vals: Vec<Vec<u8>> = sm2.get_iterator(cid, t1, RO).collect();
+    assert!(compare_unordered_byte_vecs(&committed, check_vals));
+
+    // Recovery checkpoints, so a clean restart sees the same rows
+    drop(sm2);
+    let sm3 = StorageManager::new(path.clone());
+    let check_vals: Vec<Vec<u8>> = sm3.get_iterator(cid, t1, RO).collect();
+    assert!(compare_unordered_byte_vecs(&committed, check_vals));
+    sm3.reset().unwrap();
+    drop(sm3);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn sm_crash_before_checkpoint_keeps_containers() {
+    let path = gen_random_dir().to_string_lossy().to_string();
+    let sm = StorageManager::new(path.clone());
+    let t = TransactionId::new();
+    let vals = get_random_vec_of_byte_vec(50, 50, 100);
+    sm.create_table(1).unwrap();
+    sm.create_table(2).unwrap();
+    sm.insert_values(2, vals.clone(), t);
+    sm.remove_container(1).unwrap();
+    sm.transaction_finished(t);
+    std::mem::forget(sm);
+
+    // The container map was never saved, so it comes from the log
+    let sm2 = StorageManager::new(path.clone());
+    let check_vals: Vec<Vec<u8>> = sm2.get_iterator(2, t, RO).collect();
+    assert!(compare_unordered_byte_vecs(&vals, check_vals));
+    assert!(sm2.remove_container(1).is_err());
+    sm2.reset().unwrap();
+    drop(sm2);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn sm_crash_rebuilds_indexes() {
+    let path = gen_random_dir().to_string_lossy().to_string();
+    let sm = StorageManager::new_with_frames(path.clone(), 3);
+    let (cid, index_id) = (1, 2);
+    sm.create_table(cid).unwrap();
+    let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::Int]);
+    let mut table = Table::new("t".to_string(), schema);
+    table.add_secondary_index(SecondaryIndex {
+        name: "t_b".to_string(),
+        container_id: index_id,
+        columns: vec![1],
+        unique: false,
+        method: IndexMethod::BTree,
+        constraint: false,
+    });
+    let table = Arc::new(RwLock::new(table));
+    sm.open_index(index_id, cid, table.clone()).unwrap();
+    let row = |a, b| table.read().unwrap().encode(&int_vec_to_tuple(vec![a, b]));
+
+    let t1 = TransactionId::new();
+    for i in 0..100 {
+        sm.insert_value(cid, row(i, i % 10), t1);
+    }
+    sm.transaction_finished(t1);
+    sm.shutdown();
+    // Uncommitted rows reach the tree, and some of its pages reach disk
+    let t2 = TransactionId::new();
+    for i in 100..300 {
+        sm.insert_value(cid, row(i, i % 10), t2);
+    }
+    std::mem::forget(sm);
+
+    let sm2 = StorageManager::new(path.clone());
+    let key = [Field::IntField(4)];
+    let (lower, upper) = (Bound::Included(&key[..]), Bound::Included(&key[..]));
+    assert!(sm2.index_scan(index_id, lower, upper, t1).is_err());
+    sm2.open_index(index_id, cid, table.clone()).unwrap();
+    let ids = sm2.index_scan(index_id, lower, upper, t1).unwrap();
+    let expected: Vec<Vec<u8>> = (0..10).map(|i| row(i * 10 + 4, 4)).collect();
+    let found: Vec<Vec<u8>> = ids
+        .into_iter()
+        .map(|id| sm2.get_value(id, t1, RO).unwrap())
+        .collect();
+    assert!(compare_unordered_byte_vecs(&expected, found));
+    sm2.reset().unwrap();
+    drop(sm2);
+    std::fs::remove_dir_all(path).unwrap();
+}
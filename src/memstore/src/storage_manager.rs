@@ -1,5 +1,6 @@
+use common::foreign_key::ForeignKeys;
 use common::prelude::*;
-use common::storage_trait::StorageTrait;
+use common::storage_trait::{StorageTrait, ValueIdIterator};
 use common::{ContainerConfig, CrustyError};
 
 use std::collections::HashMap;
@@ -7,18 +8,28 @@ use std::fs::OpenOptions;
 use std::fs::{self, File};
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use txn_manager::lock_manager::{LockId, LockManager, LockMode};
 
 /// This is the basic data structure a container that maps a value ID to bytes
 type ContainerMap = Arc<RwLock<HashMap<ValueId, Vec<u8>>>>;
 
+/// A transaction's changes, oldest first. Each is the value ID with the bytes it held
+/// before, or None if the transaction inserted it. Value IDs are never reused, so undoing
+/// a change can always put the old bytes back under the same ID.
+type UndoLog = Vec<(ValueId, Option<Vec<u8>>)>;
+
 /// The MemStore StorageManager. A map for storing containers, a map for tracking the next insert ID,
 /// and where to persist on shutdown/startup
+/// Reads and writes take two-phase locks that are released by transaction_finished,
+/// and the writes of an aborted transaction are undone in memory.
 pub struct StorageManager {
     containers: Arc<RwLock<HashMap<ContainerId, ContainerMap>>>,
     last_insert: Arc<RwLock<HashMap<ContainerId, ValueId>>>,
     persist_path: PathBuf,
     container_names: Arc<RwLock<HashMap<String, ContainerId>>>,
+    lock_manager: LockManager,
+    undo: Mutex<HashMap<TransactionId, UndoLog>>,
 }
 
 impl Drop for StorageManager {
@@ -48,6 +59,8 @@ impl StorageTrait for StorageManager {
                 last_insert: Arc::new(RwLock::new(HashMap::new())),
                 persist_path: PathBuf::from(storage_path),
                 container_names: Arc::new(RwLock::new(HashMap::new())),
+                lock_manager: LockManager::default(),
+                undo: Mutex::new(HashMap::new()),
             }
         }
     }
@@ -66,8 +79,16 @@ impl StorageTrait for StorageManager {
         &self,
         container_id: ContainerId,
         value: Vec<u8>,
-        _tid: TransactionId,
+        tid: TransactionId,
     ) -> ValueId {
+        let container_lock = LockId::Container(container_id);
+        if let Err(e) = self
+            .lock_manager
+            .acquire(tid, container_lock, LockMode::IntentionExclusive)
+        {
+            debug!("memstore: insert into {:?} failed: {:?}", container_id, e);
+            return ValueId::new(container_id);
+        }
         // Get the container
         let mut containers = self.containers.write().unwrap();
         // Find key to insert
@@ -95,6 +116,9 @@ impl StorageTrait for StorageManager {
         );
         vals.insert(rid, value);
         last_insert.insert(container_id, rid);
+        self.log_change(tid, rid, None);
+        self.lock_manager
+            .try_acquire(tid, LockId::Value(rid), LockMode::Exclusive);
         rid
     }
 
@@ -113,12 +137,14 @@ impl StorageTrait for StorageManager {
     }
 
     /// Remove the value from the container
-    fn delete_value(&self, id: ValueId, _tid: TransactionId) -> Result<(), CrustyError> {
+    fn delete_value(&self, id: ValueId, tid: TransactionId) -> Result<(), CrustyError> {
+        self.lock_manager
+            .lock_value(tid, id, Permissions::ReadWrite)?;
         let containers = self.containers.write().unwrap();
         if containers.contains_key(&id.container_id) {
             let mut table_map = containers.get(&id.container_id).unwrap().write().unwrap();
-            if table_map.contains_key(&id) {
-                table_map.remove(&id);
+            if let Some(before) = table_map.remove(&id) {
+                self.log_change(tid, id, Some(before));
                 Ok(())
             } else {
                 //Key not found, no need to delete.
@@ -215,8 +241,8 @@ impl StorageTrait for StorageManager {
     fn get_iterator(
         &self,
         container_id: ContainerId,
-        _tid: TransactionId,
-        _perm: Permissions,
+        tid: TransactionId,
+        perm: Permissions,
     ) -> ValueIterator {
         let table_map = self
             .containers
@@ -232,16 +258,20 @@ impl StorageTrait for StorageManager {
             .unwrap_or(&ValueId::new(container_id))
             .slot_id
             .unwrap_or(0);
-        ValueIterator::new(table_map, container_id, max)
+        match self.lock_manager.lock_container(tid, container_id, perm) {
+            Ok(()) => ValueIterator::new(table_map, container_id, max),
+            Err(_) => ValueIterator::empty(table_map, container_id),
+        }
     }
 
     /// Get the bytes for a given value if found
     fn get_value(
         &self,
         id: ValueId,
-        _tid: TransactionId,
-        _perm: Permissions,
+        tid: TransactionId,
+        perm: Permissions,
     ) -> Result<Vec<u8>, CrustyError> {
+        self.lock_manager.lock_value(tid, id, perm)?;
         let containers = self.containers.read().unwrap();
         if containers.contains_key(&id.container_id) {
             let map = containers.get(&id.container_id).unwrap().read().unwrap();
@@ -261,8 +291,29 @@ impl StorageTrait for StorageManager {
         }
     }
 
-    fn transaction_finished(&self, _tid: TransactionId) {
-        panic!("Not implemented");
+    fn transaction_finished(&self, tid: TransactionId) {
+        let changes = self.undo.lock().unwrap().remove(&tid);
+        if self.lock_manager.is_aborted(tid) {
+            self.undo_changes(changes.unwrap_or_default());
+        }
+        self.lock_manager.release_all(tid);
+    }
+
+    fn abort_transaction(&self, tid: TransactionId) {
+        self.lock_manager.abort(tid);
+        self.transaction_finished(tid);
+    }
+
+    fn check_transaction(&self, tid: TransactionId) -> Result<(), CrustyError> {
+        if self.lock_manager.is_aborted(tid) {
+            Err(CrustyError::TransactionAbortedError)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn detect_deadlocks(&self) -> Vec<TransactionId> {
+        self.lock_manager.detect_deadlocks()
     }
 
     fn reset(&self) -> Result<(), CrustyError> {
@@ -272,6 +323,8 @@ impl StorageTrait for StorageManager {
         containers.clear();
         last_inserts.clear();
         container_names.clear();
+        self.lock_manager.reset();
+        self.undo.lock().unwrap().clear();
         Ok(())
     }
 
@@ -305,6 +358,7 @@ impl StorageTrait for StorageManager {
     fn import_csv(
         &self,
         table: &Table,
+        foreign_keys: &ForeignKeys,
         path: String,
         tid: TransactionId,
         container_id: ContainerId,
@@ -320,46 +374,63 @@ impl StorageTrait for StorageManager {
             .from_reader(file);
 
         // Iterate through csv records.
-        let mut inserted_records = 0;
+        let mut tuples = Vec::new();
         for result in rdr.records() {
             #[allow(clippy::single_match)]
             match result {
-                Ok(rec) => {
-                    // Build tuple and infer types from schema.
-                    let mut tuple = Tuple::new(Vec::new());
-                    for (field, attr) in rec.iter().zip(table.schema.attributes()) {
-                        // TODO: Type mismatch between attributes and record data>
-                        match &attr.dtype() {
-                            DataType::Int => {
-                                let value: i32 = field.parse::<i32>().unwrap();
-                                tuple.field_vals.push(Field::IntField(value));
-                            }
-                            DataType::String => {
-                                let value: String = field.to_string().clone();
-                                tuple.field_vals.push(Field::StringField(value));
-                            }
-                        }
-                    }
-                    //TODO: How should individual row insertion errors be handled?
-                    debug!(
-                        "server::csv_utils about to insert tuple into container_id: {:?}",
-                        &container_id
-                    );
-                    self.insert_value(container_id, tuple.get_bytes(), tid);
-                    inserted_records += 1;
-                }
+                Ok(rec) => tuples.push(table.tuple_from_csv(&rec)?),
                 _ => {
                     // FIXME: get error from csv reader
                     error!("Could not read row from CSV");
                 }
             }
         }
+        // Check every row before inserting any, so a bad file imports nothing.
+        let rows: Vec<(Option<ValueId>, &Tuple)> = tuples.iter().map(|t| (None, t)).collect();
+        table.check_rows(self, container_id, tid, &rows)?;
+        let refs: Vec<&Tuple> = tuples.iter().collect();
+        foreign_keys.check_references(self, tid, table, container_id, &refs)?;
+        debug!(
+            "server::csv_utils about to insert tuples into container_id: {:?}",
+            &container_id
+        );
+        let values = tuples.iter().map(|t| table.encode(t)).collect();
+        let ids = self.insert_values(container_id, values, tid);
+        self.check_transaction(tid)?;
+        let written: Vec<(ValueId, &Tuple)> = ids.into_iter().zip(tuples.iter()).collect();
+        table.check_written(self, tid, &written)?;
+        let inserted_records = tuples.len();
         info!("Num records imported: {:?}", inserted_records);
         Ok(())
     }
 }
 
 impl StorageManager {
+    /// Remember a change tid made so it can be undone if tid aborts.
+    fn log_change(&self, tid: TransactionId, id: ValueId, before: Option<Vec<u8>>) {
+        self.undo
+            .lock()
+            .unwrap()
+            .entry(tid)
+            .or_default()
+            .push((id, before));
+    }
+
+    /// Undo changes, newest first.
+    fn undo_changes(&self, changes: UndoLog) {
+        let containers = self.containers.read().unwrap();
+        for (id, before) in changes.into_iter().rev() {
+            // A removed container takes the change with it
+            if let Some(table) = containers.get(&id.container_id) {
+                let mut table_map = table.write().unwrap();
+                match before {
+                    Some(bytes) => table_map.insert(id, bytes),
+                    None => table_map.remove(&id),
+                };
+            }
+        }
+    }
+
     /// Create a Memstore SM from a file path and populate from the files
     fn load(path: String) -> Self {
         let mut container_map = HashMap::new();
@@ -414,6 +485,8 @@ impl StorageManager {
             last_insert: Arc::new(RwLock::new(last_ins)),
             persist_path: PathBuf::from(path),
             container_names: Arc::new(RwLock::new(HashMap::new())),
+            lock_manager: LockManager::default(),
+            undo: Mutex::new(HashMap::new()),
         }
     }
 }
@@ -424,6 +497,7 @@ pub struct ValueIterator {
     max: u16,
     table_map: ContainerMap,
     current: u16,
+    last: Option<ValueId>,
 }
 
 impl ValueIterator {
@@ -437,8 +511,16 @@ impl ValueIterator {
             max,
             table_map,
             current: 0,
+            last: None,
         }
     }
+
+    //An iterator that returns nothing, for a transaction that could not lock the container
+    fn empty(table_map: ContainerMap, container_id: ContainerId) -> Self {
+        let mut iter = ValueIterator::new(table_map, container_id, 0);
+        iter.current = 1;
+        iter
+    }
 }
 
 impl Iterator for ValueIterator {
@@ -447,6 +529,7 @@ impl Iterator for ValueIterator {
         while self.current <= self.max {
             match self.table_map.read().unwrap().get(&self.tracker) {
                 Some(res) => {
+                    self.last = Some(self.tracker);
                     self.tracker.slot_id = Some(self.tracker.slot_id.unwrap() + 1);
                     self.current += 1;
                     return Some(res.clone());
@@ -457,10 +540,17 @@ impl Iterator for ValueIterator {
                 }
             }
         }
+        self.last = None;
         None
     }
 }
 
+impl ValueIdIterator for ValueIterator {
+    fn last_value_id(&self) -> Option<ValueId> {
+        self.last
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -533,6 +623,30 @@ mod tests {
         assert_eq!(tuple2, check_tuple);
     }
 
+    #[test]
+    fn test_abort_undoes_writes() {
+        let sm = StorageManager::new_test_sm();
+        let container_id = 1;
+        sm.create_table(container_id).unwrap();
+        let tid = TransactionId::new();
+        let bytes1 = get_random_byte_vec(50);
+        let kept = sm.insert_value(container_id, bytes1.clone(), tid);
+        sm.transaction_finished(tid);
+
+        let tid2 = TransactionId::new();
+        let inserted = sm.insert_value(container_id, get_random_byte_vec(50), tid2);
+        sm.update_value(get_random_byte_vec(50), kept, tid2).unwrap();
+        sm.abort_transaction(tid2);
+
+        let tid3 = TransactionId::new();
+        assert_eq!(bytes1, sm.get_value(kept, tid3, Permissions::ReadOnly).unwrap());
+        assert!(sm.get_value(inserted, tid3, Permissions::ReadOnly).is_err());
+        let count = sm
+            .get_iterator(container_id, tid3, Permissions::ReadOnly)
+            .count();
+        assert_eq!(1, count);
+    }
+
     #[test]
     fn test_delete1() {
         let tuple = int_vec_to_tuple(vec![0, 1, 2]);
@@ -1,11 +1,18 @@
 use common::catalog::Catalog;
-use common::ids::StateType;
+use common::ids::{ContainerId, StateType};
+use common::index::IndexMethod;
 use common::logical_plan::*;
 use common::physical_plan::*;
-use common::CrustyError;
+use common::table::Table;
+use common::{CrustyError, DataType, Field};
+use std::collections::HashMap;
+use std::ops::Bound;
 
 pub struct Optimizer {}
 
+/// A secondary index, and the lowest and highest keys to read from it.
+type IndexRange = (ContainerId, Bound<Vec<Field>>, Bound<Vec<Field>>);
+
 #[allow(clippy::new_without_default)]
 impl Optimizer {
     #[allow(clippy::let_and_return)]
@@ -19,11 +26,15 @@ impl Optimizer {
     /// # Arguments
     ///
     /// * `logical_op` - the logical operator to convert to a physical operator
+    /// * `parent` - the operator consuming the output of the logical operator, if any
+    /// * `children` - the inputs of the logical operator, left first
     /// * `physical_plan` - the physical plan to which the converted logical op will be added
     /// * `catalog` - the catalog in which containers can be created during this conversion
     fn logical_op_to_physical_op<T: Catalog>(
         &self,
         logical_op: LogicalOp,
+        parent: Option<&LogicalOp>,
+        children: &[&LogicalOp],
         physical_plan: &mut PhysicalPlan,
         catalog: &T,
     ) -> Result<PhysicalOp, CrustyError> {
@@ -33,10 +44,28 @@ impl Optimizer {
                 container_id,
             }) => {
                 physical_plan.add_base_table(container_id);
-                Ok(PhysicalOp::Scan(PhysicalScanNode {
-                    alias,
-                    container_id,
-                }))
+                // A filter above the scan still checks the rows read through an index
+                let index = match parent {
+                    Some(LogicalOp::Filter(FilterNode { predicate, .. })) => {
+                        Self::filter_index(catalog, container_id, &alias, predicate)
+                    }
+                    _ => None,
+                };
+                match index {
+                    Some((index_id, lower, upper)) => {
+                        Ok(PhysicalOp::IndexScan(PhysicalIndexScanNode {
+                            alias,
+                            container_id,
+                            index_id,
+                            lower,
+                            upper,
+                        }))
+                    }
+                    None => Ok(PhysicalOp::Scan(PhysicalScanNode {
+                        alias,
+                        container_id,
+                    })),
+                }
             }
             LogicalOp::Project(ProjectNode { identifiers }) => {
                 Ok(PhysicalOp::Project(PhysicalProjectNode { identifiers }))
@@ -62,6 +91,32 @@ impl Optimizer {
                 left_table,
                 right_table,
             }) => {
+                match Self::join_index(catalog, &left, &right, op, children) {
+                    Some((node, IndexMethod::BTree)) => {
+                        return Ok(PhysicalOp::IndexNestedLoopJoin(node));
+                    }
+                    Some((node, IndexMethod::Hash)) => {
+                        let hash_table_state_id =
+                            catalog.get_new_container_id(StateType::HashTable, None)?;
+                        physical_plan.add_hash_table(hash_table_state_id);
+                        let (left_table, right_table) = if node.outer.column() == left.column() {
+                            (left_table, right_table)
+                        } else {
+                            (right_table, left_table)
+                        };
+                        return Ok(PhysicalOp::HashJoin(PhysicalHashJoinNode {
+                            hash_table_key: node.outer.clone(),
+                            left: node.outer,
+                            right: node.inner,
+                            op: node.op,
+                            left_table,
+                            right_table,
+                            hash_table_state_id,
+                            index_id: Some(node.index_id),
+                        }));
+                    }
+                    None => {}
+                }
                 Ok(PhysicalOp::NestedLoopJoin(PhysicalNestedLoopJoinNode {
                     left,
                     right,
@@ -73,11 +128,268 @@ impl Optimizer {
             LogicalOp::Filter(FilterNode { table, predicate }) => {
                 Ok(PhysicalOp::Filter(PhysicalFilterNode { table, predicate }))
             }
+            LogicalOp::Map(MapNode { columns }) => {
+                Ok(PhysicalOp::Map(PhysicalMapNode { columns }))
+            }
+            LogicalOp::Sort(SortNode { fields }) => match parent {
+                // A limit above the sort still skips the offset, so the sort keeps it too
+                Some(LogicalOp::Limit(LimitNode {
+                    limit: Some(limit),
+                    offset,
+                })) => Ok(PhysicalOp::TopN(PhysicalTopNNode {
+                    fields,
+                    limit: limit.saturating_add(*offset),
+                })),
+                _ => Ok(PhysicalOp::Sort(PhysicalSortNode { fields })),
+            },
+            LogicalOp::Limit(LimitNode { limit, offset }) => {
+                Ok(PhysicalOp::Limit(PhysicalLimitNode { limit, offset }))
+            }
+            LogicalOp::Distinct(DistinctNode {}) => {
+                Ok(PhysicalOp::Distinct(PhysicalDistinctNode {}))
+            }
+            LogicalOp::Values(ValuesNode { schema, rows }) => {
+                Ok(PhysicalOp::Values(PhysicalValuesNode { schema, rows }))
+            }
+            LogicalOp::Insert(InsertNode {
+                table,
+                container_id,
+                columns,
+            }) => {
+                physical_plan.add_base_table(container_id);
+                Ok(PhysicalOp::Insert(PhysicalInsertNode {
+                    table,
+                    container_id,
+                    columns,
+                }))
+            }
+            LogicalOp::Update(UpdateNode {
+                table,
+                container_id,
+                assignments,
+            }) => {
+                physical_plan.add_base_table(container_id);
+                Ok(PhysicalOp::Update(PhysicalUpdateNode {
+                    table,
+                    container_id,
+                    assignments,
+                }))
+            }
+            LogicalOp::Delete(DeleteNode {
+                table,
+                container_id,
+            }) => {
+                physical_plan.add_base_table(container_id);
+                Ok(PhysicalOp::Delete(PhysicalDeleteNode {
+                    table,
+                    container_id,
+                }))
+            }
             //not currently covering read delta and write delta logical ops
             _ => todo!(),
         }
     }
 
+    /// Finds the secondary index of a table that narrows a filter of its rows the most, and
+    /// the bounds of the keys the filter reads. Comparisons of the first column of a key with
+    /// literals bound it; equalities with the first columns let the next one bound it too. A
+    /// hash index is only used when every column of its key is equal to a literal, and is
+    /// preferred to a B+ tree bounding as many columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `catalog` - the catalog holding the table
+    /// * `container_id` - the container of the table
+    /// * `alias` - the name the filter gives the table
+    /// * `predicate` - the predicate of the filter
+    fn filter_index<T: Catalog>(
+        catalog: &T,
+        container_id: ContainerId,
+        alias: &str,
+        predicate: &Predicate,
+    ) -> Option<IndexRange> {
+        let table = catalog.get_table_ptr(container_id).ok()?;
+        let table = table.read().unwrap();
+        let predicates = match predicate {
+            Predicate::SimplePredicate(p) => std::slice::from_ref(p),
+            Predicate::CompoundPredicate(CompoundPredicate {
+                op: CompoundPredicateOp::And,
+                simple_predicates,
+            }) => &simple_predicates[..],
+            _ => return None,
+        };
+        // Comparisons of columns with literals, as (column, op, literal)
+        let comparisons: Vec<(usize, SimplePredicateOp, &Field)> = predicates
+            .iter()
+            .filter_map(|p| {
+                let (id, op, value) = match (&p.left, &p.right) {
+                    (PredExpr::Ident(id), PredExpr::Literal(value)) => (id, p.op, value),
+                    (PredExpr::Literal(value), PredExpr::Ident(id)) => (id, p.op.flip(), value),
+                    _ => return None,
+                };
+                let column = Self::column_index(&table, alias, id)?;
+                let dtype = table.schema.get_attribute(column)?.dtype();
+                Some((column, op, value)).filter(|_| Self::indexable(value, dtype))
+            })
+            .collect();
+
+        // The best index found, by the number of columns it bounds
+        let mut best: Option<((usize, usize, bool), IndexRange)> = None;
+        for index in table.secondary_indexes() {
+            let mut prefix = Vec::new();
+            let (mut lower, mut upper) = (None, None);
+            for column in &index.columns {
+                let equal = comparisons
+                    .iter()
+                    .find(|(c, op, _)| c == column && matches!(op, SimplePredicateOp::Equals));
+                if let Some((_, _, value)) = equal {
+                    prefix.push((*value).clone());
+                    continue;
+                }
+                for (_, op, value) in comparisons.iter().filter(|(c, _, _)| c == column) {
+                    match op {
+                        SimplePredicateOp::GreaterThan => lower = Some((*value, false)),
+                        SimplePredicateOp::GreaterThanOrEq => lower = Some((*value, true)),
+                        SimplePredicateOp::LessThan => upper = Some((*value, false)),
+                        SimplePredicateOp::LessThanOrEq => upper = Some((*value, true)),
+                        _ => {}
+                    }
+                }
+                break;
+            }
+            let hash = index.method == IndexMethod::Hash;
+            if hash && prefix.len() < index.columns.len() {
+                continue;
+            }
+            let score = (
+                prefix.len(),
+                lower.is_some() as usize + upper.is_some() as usize,
+                hash,
+            );
+            if score == (0, 0, false) || best.as_ref().is_some_and(|(s, _)| *s >= score) {
+                continue;
+            }
+            let bound = |range: Option<(&Field, bool)>| match range {
+                Some((value, inclusive)) => {
+                    let mut key = prefix.clone();
+                    key.push(value.clone());
+                    if inclusive {
+                        Bound::Included(key)
+                    } else {
+                        Bound::Excluded(key)
+                    }
+                }
+                None if prefix.is_empty() => Bound::Unbounded,
+                None => Bound::Included(prefix.clone()),
+            };
+            best = Some((score, (index.container_id, bound(lower), bound(upper))));
+        }
+        best.map(|(_, range)| range)
+    }
+
+    /// Finds a secondary index to join the rows of the left child of a join with the rows of
+    /// its right child through, which it can when the right child scans a table with a B+ tree
+    /// whose key starts with the right column of the predicate, or, for an equality, a hash
+    /// index whose key is that column. A hash index is preferred, and probed by a hash join.
+    ///
+    /// # Arguments
+    ///
+    /// * `catalog` - the catalog holding the tables
+    /// * `left` - the left column of the predicate
+    /// * `right` - the right column of the predicate
+    /// * `op` - the operator of the predicate
+    /// * `children` - the inputs of the join, left first
+    fn join_index<T: Catalog>(
+        catalog: &T,
+        left: &FieldIdentifier,
+        right: &FieldIdentifier,
+        op: SimplePredicateOp,
+        children: &[&LogicalOp],
+    ) -> Option<(PhysicalIndexNestedLoopJoinNode, IndexMethod)> {
+        let (alias, container_id) = match children {
+            [_, LogicalOp::Scan(ScanNode {
+                alias,
+                container_id,
+            })] => (alias, *container_id),
+            _ => return None,
+        };
+        // The join condition may be written in reverse of the join tables order
+        let (outer, inner, op) = match (left.table() == alias, right.table() == alias) {
+            (false, true) => (left, right, op),
+            (true, false) => (right, left, op.flip()),
+            _ => return None,
+        };
+        if !matches!(
+            op,
+            SimplePredicateOp::Equals
+                | SimplePredicateOp::LessThan
+                | SimplePredicateOp::LessThanOrEq
+                | SimplePredicateOp::GreaterThan
+                | SimplePredicateOp::GreaterThanOrEq
+        ) {
+            return None;
+        }
+        let outer_table = catalog
+            .get_table_ptr(catalog.get_table_id(outer.table())?)
+            .ok()?;
+        let outer_table = outer_table.read().unwrap();
+        let outer_column = Self::column_index(&outer_table, outer.table(), outer)?;
+        let outer_dtype = outer_table.schema.get_attribute(outer_column)?.dtype();
+        let table = catalog.get_table_ptr(container_id).ok()?;
+        let table = table.read().unwrap();
+        let column = Self::column_index(&table, alias, inner)?;
+        let dtype = table.schema.get_attribute(column)?.dtype();
+        if !Self::indexable_type(outer_dtype, dtype) {
+            return None;
+        }
+        let index = table
+            .secondary_indexes()
+            .iter()
+            .filter(|index| match index.method {
+                IndexMethod::BTree => index.columns[0] == column,
+                IndexMethod::Hash => {
+                    index.columns == [column] && matches!(op, SimplePredicateOp::Equals)
+                }
+            })
+            .min_by_key(|index| index.method == IndexMethod::BTree)?;
+        let node = PhysicalIndexNestedLoopJoinNode {
+            outer: outer.clone(),
+            inner: inner.clone(),
+            op,
+            container_id,
+            index_id: index.container_id,
+        };
+        Some((node, index.method))
+    }
+
+    /// Returns the index in a table's schema of a column the plan names.
+    fn column_index(table: &Table, alias: &str, id: &FieldIdentifier) -> Option<usize> {
+        let name = id.column().strip_prefix(alias)?.strip_prefix('.')?;
+        table.schema.get_field_index(name).copied()
+    }
+
+    /// Returns whether an index on a column finds the values equal to a literal, which it
+    /// does when the literal orders among the values of the column as SQL compares them.
+    /// Doubles order in an index by their bits, which tell -0 from 0, and intervals by their
+    /// fields rather than their lengths.
+    fn indexable(value: &Field, dtype: &DataType) -> bool {
+        match (value, dtype) {
+            (Field::Null | Field::DoubleField(_) | Field::IntervalField(_), _) => false,
+            (Field::IntField(_), DataType::BigInt) => true,
+            _ => dtype.matches(value),
+        }
+    }
+
+    /// Returns whether an index on a column finds the values equal to values of a type, as
+    /// `indexable` does for literals.
+    fn indexable_type(value: &DataType, dtype: &DataType) -> bool {
+        match (value, dtype) {
+            (DataType::Double | DataType::Interval, _) => false,
+            (DataType::Int, DataType::BigInt) => true,
+            _ => dtype.accepts(value),
+        }
+    }
+
     /// Converts a logical plan into a physical plan
     ///
     /// # Arguments
@@ -91,10 +403,26 @@ impl Optimizer {
         is_mat_view: bool,
     ) -> Result<PhysicalPlan, CrustyError> {
         let mut physical_plan = PhysicalPlan::new();
+        let parents: HashMap<OpIndex, OpIndex> = logical_plan
+            .edge_references()
+            .map(|edge| (edge.target(), edge.source()))
+            .collect();
         for (idx, node) in logical_plan.node_references() {
             let logical_op = node.data();
-            let physical_op =
-                self.logical_op_to_physical_op(logical_op.clone(), &mut physical_plan, catalog)?;
+            let parent = parents
+                .get(&idx)
+                .and_then(|parent| logical_plan.get_operator(*parent));
+            let children: Vec<&LogicalOp> = logical_plan
+                .edges(idx)
+                .filter_map(|child| logical_plan.get_operator(child))
+                .collect();
+            let physical_op = self.logical_op_to_physical_op(
+                logical_op.clone(),
+                parent,
+                &children,
+                &mut physical_plan,
+                catalog,
+            )?;
             physical_plan.add_node(physical_op);
             if !is_mat_view {
                 logical_plan.root();
@@ -140,6 +468,9 @@ impl Optimizer {
 mod test {
     use super::*;
     use common::database::Database;
+    use common::index::SecondaryIndex;
+    use common::TableSchema;
+    use std::sync::{Arc, RwLock};
 
     fn logical_plan1() -> LogicalPlan {
         LogicalPlan::from_json("{\"edges\":{\"1\":[\"0\"]},\"nodes\":{\"0\":{\"Scan\":{\"alias\":\"test\",\"container_id\":0,\"timestamp\":0}},\"1\":{\"Project\":{\"identifiers\":\"Wildcard\"}}},\"root\":\"1\"}").unwrap()
@@ -208,4 +539,301 @@ mod test {
         assert_eq!(physical_plan.base_tables().len(), 1);
         assert_eq!(physical_plan.hash_tables().len(), 0);
     }
+
+    /// Adds a table with columns a and b to the catalog, with secondary indexes on the columns
+    /// given, and returns its container.
+    fn add_table(db: &Database, name: &str, indexes: &[Vec<usize>]) -> ContainerId {
+        let id = db
+            .get_new_container_id(StateType::BaseTable, Some(name.to_string()))
+            .unwrap();
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::Int]);
+        let mut table = Table::new(name.to_string(), schema);
+        for columns in indexes {
+            table.add_secondary_index(SecondaryIndex {
+                name: format!("{}_{:?}", name, columns),
+                container_id: db.get_new_container_id(StateType::Index, None).unwrap(),
+                columns: columns.clone(),
+                unique: false,
+                method: IndexMethod::BTree,
+                constraint: false,
+            });
+        }
+        let table = Arc::new(RwLock::new(table));
+        db.tables.write().unwrap().insert(id, table);
+        id
+    }
+
+    /// Adds a hash index on columns of a table in the catalog, and returns its container.
+    fn add_hash_index(db: &Database, table_id: ContainerId, columns: Vec<usize>) -> ContainerId {
+        let index_id = db.get_new_container_id(StateType::Index, None).unwrap();
+        let table = db.get_table_ptr(table_id).unwrap();
+        let mut table = table.write().unwrap();
+        let name = format!("{}_hash_{:?}", table.name, columns);
+        table.add_secondary_index(SecondaryIndex {
+            name,
+            container_id: index_id,
+            columns,
+            unique: false,
+            method: IndexMethod::Hash,
+            constraint: false,
+        });
+        index_id
+    }
+
+    fn compare(table: &str, column: &str, op: SimplePredicateOp, value: i32) -> SimplePredicate {
+        SimplePredicate {
+            left: PredExpr::Ident(FieldIdentifier::new(
+                table,
+                &format!("{}.{}", table, column),
+            )),
+            op,
+            right: PredExpr::Literal(Field::IntField(value)),
+        }
+    }
+
+    /// Converts a plan filtering a scan of a table, and returns the operator of the scan.
+    fn filtered_scan(db: &Database, table: &str, predicates: Vec<SimplePredicate>) -> PhysicalOp {
+        let predicate = match predicates.len() {
+            1 => Predicate::SimplePredicate(predicates.into_iter().next().unwrap()),
+            _ => Predicate::CompoundPredicate(CompoundPredicate {
+                op: CompoundPredicateOp::And,
+                simple_predicates: predicates,
+            }),
+        };
+        let mut plan = LogicalPlan::new();
+        let scan = plan.add_node(LogicalOp::Scan(ScanNode {
+            alias: table.to_string(),
+            container_id: db.get_table_id(table).unwrap(),
+        }));
+        let filter = plan.add_node(LogicalOp::Filter(FilterNode {
+            table: table.to_string(),
+            predicate,
+        }));
+        plan.add_edge(filter, scan);
+        let physical_plan = Optimizer::new()
+            .logical_plan_to_physical_plan(plan, db, false)
+            .unwrap();
+        physical_plan.get_operator(scan).unwrap().clone()
+    }
+
+    #[test]
+    fn test_index_scan() {
+        let db = Database::new(String::from("test"));
+        add_table(&db, "t", &[vec![1], vec![0, 1]]);
+        let index_ids: Vec<ContainerId> = db
+            .tables
+            .read()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap()
+            .read()
+            .unwrap()
+            .secondary_indexes()
+            .iter()
+            .map(|index| index.container_id)
+            .collect();
+        let key = |values: &[i32]| {
+            values
+                .iter()
+                .map(|v| Field::IntField(*v))
+                .collect::<Vec<_>>()
+        };
+
+        // The index on a alone bounds only a
+        match filtered_scan(
+            &db,
+            "t",
+            vec![compare("t", "b", SimplePredicateOp::GreaterThan, 3)],
+        ) {
+            PhysicalOp::IndexScan(node) => {
+                assert_eq!(index_ids[0], node.index_id);
+                assert_eq!(Bound::Excluded(key(&[3])), node.lower);
+                assert_eq!(Bound::Unbounded, node.upper);
+            }
+            op => panic!("Expected an index scan, got {:?}", op),
+        }
+
+        // An equality on a lets the index on (a, b) bound b too
+        let predicates = vec![
+            compare("t", "b", SimplePredicateOp::LessThanOrEq, 3),
+            compare("t", "a", SimplePredicateOp::Equals, 1),
+        ];
+        match filtered_scan(&db, "t", predicates) {
+            PhysicalOp::IndexScan(node) => {
+                assert_eq!(index_ids[1], node.index_id);
+                assert_eq!(Bound::Included(key(&[1])), node.lower);
+                assert_eq!(Bound::Included(key(&[1, 3])), node.upper);
+            }
+            op => panic!("Expected an index scan, got {:?}", op),
+        }
+
+        // No index starts with a column the filter compares with a value
+        let predicates = vec![compare("t", "a", SimplePredicateOp::NotEq, 1)];
+        assert!(matches!(
+            filtered_scan(&db, "t", predicates),
+            PhysicalOp::Scan(_)
+        ));
+        let mut null = compare("t", "b", SimplePredicateOp::Equals, 1);
+        null.right = PredExpr::Literal(Field::Null);
+        assert!(matches!(
+            filtered_scan(&db, "t", vec![null]),
+            PhysicalOp::Scan(_)
+        ));
+    }
+
+    /// Converts a plan sorting a scan of a table under a limit, and returns the operator of the
+    /// sort.
+    fn limited_sort(db: &Database, table: &str, limit: Option<usize>, offset: usize) -> PhysicalOp {
+        let mut plan = LogicalPlan::new();
+        let scan = plan.add_node(LogicalOp::Scan(ScanNode {
+            alias: table.to_string(),
+            container_id: db.get_table_id(table).unwrap(),
+        }));
+        let sort = plan.add_node(LogicalOp::Sort(SortNode {
+            fields: vec![OrderByField {
+                field: FieldIdentifier::new(table, &format!("{}.a", table)),
+                descending: false,
+                nulls_first: false,
+            }],
+        }));
+        let limit = plan.add_node(LogicalOp::Limit(LimitNode { limit, offset }));
+        plan.add_edge(sort, scan);
+        plan.add_edge(limit, sort);
+        let physical_plan = Optimizer::new()
+            .logical_plan_to_physical_plan(plan, db, false)
+            .unwrap();
+        assert!(matches!(
+            physical_plan.get_operator(limit),
+            Some(PhysicalOp::Limit(_))
+        ));
+        physical_plan.get_operator(sort).unwrap().clone()
+    }
+
+    #[test]
+    fn test_top_n() {
+        let db = Database::new(String::from("test"));
+        add_table(&db, "t", &[]);
+
+        // The top-N keeps the tuples the limit skips as well as those it produces
+        match limited_sort(&db, "t", Some(20), 5) {
+            PhysicalOp::TopN(node) => {
+                assert_eq!(25, node.limit);
+                assert_eq!("t.a", node.fields[0].field.column());
+            }
+            op => panic!("Expected a top-N, got {:?}", op),
+        }
+
+        // An offset alone needs all the tuples sorted
+        assert!(matches!(
+            limited_sort(&db, "t", None, 5),
+            PhysicalOp::Sort(_)
+        ));
+    }
+
+    #[test]
+    fn test_hash_index_scan() {
+        let db = Database::new(String::from("test"));
+        let id = add_table(&db, "t", &[vec![0]]);
+        let hash_id = add_hash_index(&db, id, vec![0, 1]);
+        let equal = |column| compare("t", column, SimplePredicateOp::Equals, 1);
+        let key = vec![Field::IntField(1), Field::IntField(1)];
+
+        // The hash index is used when its whole key is compared, and preferred then
+        match filtered_scan(&db, "t", vec![equal("a"), equal("b")]) {
+            PhysicalOp::IndexScan(node) => {
+                assert_eq!(hash_id, node.index_id);
+                assert_eq!(Bound::Included(key.clone()), node.lower);
+                assert_eq!(Bound::Included(key), node.upper);
+            }
+            op => panic!("Expected an index scan, got {:?}", op),
+        }
+        match filtered_scan(&db, "t", vec![equal("a")]) {
+            PhysicalOp::IndexScan(node) => assert_ne!(hash_id, node.index_id),
+            op => panic!("Expected an index scan, got {:?}", op),
+        }
+        let predicates = vec![
+            equal("a"),
+            compare("t", "b", SimplePredicateOp::LessThan, 1),
+        ];
+        match filtered_scan(&db, "t", predicates) {
+            PhysicalOp::IndexScan(node) => assert_ne!(hash_id, node.index_id),
+            op => panic!("Expected an index scan, got {:?}", op),
+        }
+        let predicates = vec![equal("b")];
+        assert!(matches!(
+            filtered_scan(&db, "t", predicates),
+            PhysicalOp::Scan(_)
+        ));
+    }
+
+    #[test]
+    fn test_index_nested_loop_join() {
+        let db = Database::new(String::from("test"));
+        add_table(&db, "r", &[]);
+        let s_id = add_table(&db, "s", &[vec![1]]);
+        let join = |left: &str, right: &str, op, inner_indexed: bool| {
+            let mut plan = LogicalPlan::new();
+            let (outer, inner) = if inner_indexed {
+                ("r", "s")
+            } else {
+                ("s", "r")
+            };
+            let scan = |plan: &mut LogicalPlan, table: &str| {
+                plan.add_node(LogicalOp::Scan(ScanNode {
+                    alias: table.to_string(),
+                    container_id: db.get_table_id(table).unwrap(),
+                }))
+            };
+            let outer_scan = scan(&mut plan, outer);
+            let inner_scan = scan(&mut plan, inner);
+            let column = |id: &str| {
+                let table = id.split('.').next().unwrap();
+                FieldIdentifier::new(table, id)
+            };
+            let join = plan.add_node(LogicalOp::Join(JoinNode {
+                left: column(left),
+                right: column(right),
+                op,
+                left_table: None,
+                right_table: None,
+            }));
+            plan.add_edge(join, inner_scan);
+            plan.add_edge(join, outer_scan);
+            let physical_plan = Optimizer::new()
+                .logical_plan_to_physical_plan(plan, &db, false)
+                .unwrap();
+            physical_plan.get_operator(join).unwrap().clone()
+        };
+
+        // The condition is turned around to put the outer column on the left
+        match join("s.b", "r.a", SimplePredicateOp::LessThan, true) {
+            PhysicalOp::IndexNestedLoopJoin(node) => {
+                assert_eq!("r.a", node.outer.column());
+                assert_eq!("s.b", node.inner.column());
+                assert!(matches!(node.op, SimplePredicateOp::GreaterThan));
+                assert_eq!(s_id, node.container_id);
+            }
+            op => panic!("Expected an index nested loop join, got {:?}", op),
+        }
+        let op = join("r.a", "s.a", SimplePredicateOp::Equals, true);
+        assert!(matches!(op, PhysicalOp::NestedLoopJoin(_)));
+        let op = join("r.a", "s.b", SimplePredicateOp::NotEq, true);
+        assert!(matches!(op, PhysicalOp::NestedLoopJoin(_)));
+        let op = join("s.b", "r.a", SimplePredicateOp::Equals, false);
+        assert!(matches!(op, PhysicalOp::NestedLoopJoin(_)));
+
+        // An equality probes a hash index on the inner column, preferred to the B+ tree
+        let hash_id = add_hash_index(&db, s_id, vec![1]);
+        match join("s.b", "r.a", SimplePredicateOp::Equals, true) {
+            PhysicalOp::HashJoin(node) => {
+                assert_eq!("r.a", node.left.column());
+                assert_eq!("s.b", node.right.column());
+                assert_eq!(Some(hash_id), node.index_id);
+            }
+            op => panic!("Expected a hash join, got {:?}", op),
+        }
+        let op = join("s.b", "r.a", SimplePredicateOp::LessThan, true);
+        assert!(matches!(op, PhysicalOp::IndexNestedLoopJoin(_)));
+    }
 }
@@ -1,15 +1,134 @@
 use super::{OpIterator, TupleIterator};
-use common::{AggOp, Attribute, CrustyError, DataType, Field, TableSchema, Tuple};
-use std::cmp::{max, min};
-
+use common::{
+    AggOp, Attribute, CrustyError, DataType, Double, Field, TableSchema, Tuple,
+    MAX_DECIMAL_PRECISION,
+};
+use std::collections::{HashMap, HashSet};
 
 /// Contains the index of the field to aggregate and the operator to apply to the column of each group.
 #[derive(Clone)]
 pub struct AggregateField {
+    /// Index of field being aggregated.
+    pub field: usize,
+    /// Aggregate operation to aggregate the column with.
+    pub op: AggOp,
+}
+
+/// Running value of an aggregate over the tuples of a group merged so far.
+#[derive(Clone)]
+struct Accumulator {
+    /// Minimum or maximum of the values merged, NULL until a value is merged.
+    value: Field,
+    /// Sum of the exact numbers merged, as unscaled values, wide enough not to overflow.
+    sum: i128,
+    /// Sum of the doubles merged.
+    double_sum: f64,
+    /// Number of values merged. Only COUNT(*) merges NULLs.
+    count: i32,
+    /// Values merged by an aggregate over distinct values, which skips them after.
+    distinct: HashSet<Field>,
+}
+
+impl Accumulator {
+    fn new() -> Self {
+        Self {
+            value: Field::Null,
+            sum: 0,
+            double_sum: 0.0,
+            count: 0,
+            distinct: HashSet::new(),
+        }
+    }
+
+    /// Merges a value into the aggregate. Every aggregate but COUNT(*) skips NULLs, and those
+    /// over distinct values skip the values already merged.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Aggregate operation.
+    /// * `field` - Value to merge.
+    fn merge(&mut self, op: AggOp, field: &Field) {
+        if field.is_null() && op != AggOp::CountAll {
+            return;
+        }
+        if op.is_distinct() && !self.distinct.insert(field.clone()) {
+            return;
+        }
+        self.count += 1;
+        match op.without_distinct() {
+            AggOp::Count | AggOp::CountAll => {}
+            AggOp::Max if self.value.is_null() || *field > self.value => self.value = field.clone(),
+            AggOp::Min if self.value.is_null() || *field < self.value => self.value = field.clone(),
+            AggOp::Max | AggOp::Min => {}
+            _ => match field {
+                Field::DoubleField(x) => self.double_sum += x.0,
+                field => self.sum += field.to_decimal().map_or(0, |(x, _)| x as i128),
+            },
+        }
+    }
+
+    /// Returns the result of the aggregate as a value of its output type. Aggregates over no
+    /// values other than the counts are NULL.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Aggregate operation.
+    /// * `dtype` - Output type of the aggregate.
+    fn result(&self, op: AggOp, dtype: &DataType) -> Result<Field, CrustyError> {
+        let sum = match op.without_distinct() {
+            AggOp::Count | AggOp::CountAll => return Ok(Field::IntField(self.count)),
+            AggOp::Max | AggOp::Min => return Ok(self.value.clone()),
+            _ if self.count == 0 => return Ok(Field::Null),
+            AggOp::Sum => self.sum,
+            _ => match dtype {
+                // Decimals round to their scale, while integers truncate like integer division.
+                DataType::Decimal(_, _) => {
+                    let count = self.count as i128;
+                    (self.sum + self.sum.signum() * count / 2) / count
+                }
+                _ => self.sum / self.count as i128,
+            },
+        };
+        let out_of_range =
+            || CrustyError::ExecutionError(format!("{} {} is out of range for {}", op, sum, dtype));
+        match dtype {
+            DataType::Int => i32::try_from(sum)
+                .map(Field::IntField)
+                .map_err(|_| out_of_range()),
+            DataType::BigInt => i64::try_from(sum)
+                .map(Field::BigIntField)
+                .map_err(|_| out_of_range()),
+            DataType::Decimal(precision, scale) => {
+                if sum.abs() >= 10i128.pow(*precision as u32) {
+                    return Err(out_of_range());
+                }
+                Ok(Field::DecimalField(sum as i64, *scale))
+            }
+            DataType::Double => {
+                let sum = match op.without_distinct() {
+                    AggOp::Avg => self.double_sum / self.count as f64,
+                    _ => self.double_sum,
+                };
+                Ok(Field::DoubleField(Double(sum)))
+            }
+            _ => Err(CrustyError::ExecutionError(format!(
+                "Cannot compute {} as {}",
+                op, dtype
+            ))),
+        }
+    }
 }
 
 /// Computes an aggregation function over multiple columns and grouped by multiple fields.
 struct Aggregator {
+    /// Aggregated fields.
+    agg_fields: Vec<AggregateField>,
+    /// Group by fields
+    groupby_fields: Vec<usize>,
+    /// Schema of the output.
+    schema: TableSchema,
+    /// Running aggregates of each group, keyed by the group by values. NULLs form one group.
+    groups: HashMap<Vec<Field>, Vec<Accumulator>>,
 }
 
 impl Aggregator {
@@ -22,12 +141,18 @@ impl Aggregator {
     /// * `groupby_fields` - Indices of the fields to groupby over.
     /// * `schema` - TableSchema of the form [groupby_field attributes ..., agg_field attributes ...]).
     fn new(
+        agg_fields: Vec<AggregateField>,
+        groupby_fields: Vec<usize>,
+        schema: &TableSchema,
     ) -> Self {
         Self {
+            agg_fields,
+            groupby_fields,
+            schema: schema.clone(),
+            groups: HashMap::new(),
         }
     }
 
-
     /// Handles the creation of groups for aggregation.
     ///
     /// If a group exists, then merge the tuple into the group's accumulated value.
@@ -37,24 +162,70 @@ impl Aggregator {
     ///
     /// * `tuple` - Tuple to add to a group.
     pub fn merge_tuple_into_group(&mut self, tuple: &Tuple) {
-        panic!("TODO milestone op");
+        let key: Vec<Field> = self
+            .groupby_fields
+            .iter()
+            .map(|i| tuple.get_field(*i).unwrap().clone())
+            .collect();
+        let accumulators = self
+            .groups
+            .entry(key)
+            .or_insert_with(|| vec![Accumulator::new(); self.agg_fields.len()]);
+        for (acc, agg) in accumulators.iter_mut().zip(self.agg_fields.iter()) {
+            acc.merge(agg.op, tuple.get_field(agg.field).unwrap());
+        }
     }
 
     // TODO: Create check for schema.
     /// Returns a `TupleIterator` over the results.
     ///
     /// Resulting tuples must be of the form: (group by fields ..., aggregate fields ...)
-    pub fn iterator(&self) -> TupleIterator {
-        panic!("TODO milestone op");
+    ///
+    /// Without group by fields there is always one result, even if no tuples were merged.
+    /// Fails if a result is out of range of its type.
+    pub fn iterator(&self) -> Result<TupleIterator, CrustyError> {
+        let mut tuples: Vec<Tuple> = self
+            .groups
+            .iter()
+            .map(|(key, accumulators)| self.result(key, accumulators))
+            .collect::<Result<_, _>>()?;
+        if tuples.is_empty() && self.groupby_fields.is_empty() {
+            let accumulators = vec![Accumulator::new(); self.agg_fields.len()];
+            tuples.push(self.result(&[], &accumulators)?);
+        }
+        Ok(TupleIterator::new(tuples, self.schema.clone()))
+    }
+
+    /// Returns the result tuple of a group.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Group by values of the group.
+    /// * `accumulators` - Running aggregates of the group.
+    fn result(&self, key: &[Field], accumulators: &[Accumulator]) -> Result<Tuple, CrustyError> {
+        let mut fields = key.to_vec();
+        for (i, (acc, agg)) in accumulators.iter().zip(self.agg_fields.iter()).enumerate() {
+            let attr = self.schema.get_attribute(key.len() + i).unwrap();
+            fields.push(acc.result(agg.op, attr.dtype())?);
+        }
+        Ok(Tuple::new(fields))
     }
 }
 
 /// Aggregate operator.
 pub struct Aggregate {
+    /// Fields to groupby over.
+    groupby_fields: Vec<usize>,
+    /// Aggregation fields and corresponding aggregation functions.
+    agg_fields: Vec<AggregateField>,
+    /// Aggregation iterators for results.
+    agg_iter: Option<TupleIterator>,
     /// Resulting schema.
     schema: TableSchema,
     /// Boolean if the iterator is open.
     open: bool,
+    /// Child operator to get the data from.
+    child: Box<dyn OpIterator>,
 }
 
 impl Aggregate {
@@ -67,29 +238,83 @@ impl Aggregate {
     /// * `agg_indices` - the indices of the aggregate fields
     /// * `agg_names` - the names of the aggreagte fields in the final aggregation
     /// * `ops` - Aggregate operations, 1:1 correspondence with the indices in agg_indices
-    /// * `schema` - Input schema.
+    /// * `child` - child operator to get the input data from.
     pub fn new(
+        groupby_indices: Vec<usize>,
+        groupby_names: Vec<&str>,
+        agg_indices: Vec<usize>,
+        agg_names: Vec<&str>,
+        ops: Vec<AggOp>,
+        child: Box<dyn OpIterator>,
     ) -> Self {
-        panic!("TODO milestone op");
+        let child_schema = child.get_schema();
+        let mut attrs = Vec::new();
+        for (i, name) in groupby_indices.iter().zip(groupby_names) {
+            let dtype = child_schema.get_attribute(*i).unwrap().dtype().clone();
+            attrs.push(Attribute::new(name.to_string(), dtype));
+        }
+        let mut agg_fields = Vec::new();
+        for ((i, name), op) in agg_indices.iter().zip(agg_names).zip(ops) {
+            let child_dtype = child_schema.get_attribute(*i).unwrap().dtype().clone();
+            let dtype = match (op.without_distinct(), child_dtype) {
+                (AggOp::Count, _) | (AggOp::CountAll, _) => DataType::Int,
+                // Sums of decimals keep their scale but may use every digit of precision.
+                (AggOp::Sum, DataType::Decimal(_, scale)) => {
+                    DataType::Decimal(MAX_DECIMAL_PRECISION, scale)
+                }
+                (_, dtype) => dtype,
+            };
+            attrs.push(Attribute::new(name.to_string(), dtype));
+            agg_fields.push(AggregateField { field: *i, op });
+        }
+        Self {
+            groupby_fields: groupby_indices,
+            agg_fields,
+            agg_iter: None,
+            schema: TableSchema::new(attrs),
+            open: false,
+            child,
+        }
     }
-
 }
 
 impl OpIterator for Aggregate {
     fn open(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        self.child.open()?;
+        let mut agg = Aggregator::new(
+            self.agg_fields.clone(),
+            self.groupby_fields.clone(),
+            &self.schema,
+        );
+        while let Some(t) = self.child.next()? {
+            agg.merge_tuple_into_group(&t);
+        }
+        let mut agg_iter = agg.iterator()?;
+        agg_iter.open()?;
+        self.agg_iter = Some(agg_iter);
+        self.open = true;
+        Ok(())
     }
 
     fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
-        panic!("TODO milestone op");
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.agg_iter.as_mut().unwrap().next()
     }
 
     fn close(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        self.child.close()?;
+        self.agg_iter = None;
+        self.open = false;
+        Ok(())
     }
 
     fn rewind(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.agg_iter.as_mut().unwrap().rewind()
     }
 
     fn get_schema(&self) -> &TableSchema {
@@ -165,21 +390,29 @@ mod test {
         /// * `expected` - The expected result.
         fn test_no_group(op: AggOp, field: usize, expected: i32) -> Result<(), CrustyError> {
             let schema = TableSchema::new(vec![Attribute::new("agg".to_string(), DataType::Int)]);
-            let mut agg = Aggregator::new(
-            );
+            let mut agg = Aggregator::new(vec![AggregateField { field, op }], Vec::new(), &schema);
 
             let ti = tuples();
             for t in &ti {
                 agg.merge_tuple_into_group(t);
             }
 
-            let expected = vec![Field::IntField(6), Field::IntField(6)];
-            let mut ai = agg.iterator();
+            let expected = vec![Field::IntField(expected)];
+            let mut ai = agg.iterator()?;
             ai.open()?;
             assert_eq!(Tuple::new(expected), ai.next()?.unwrap());
             Ok(())
         }
 
+        #[test]
+        fn test_merge_tuples_no_group() -> Result<(), CrustyError> {
+            test_no_group(AggOp::Count, 0, 6)?;
+            test_no_group(AggOp::Sum, 2, 24)?;
+            test_no_group(AggOp::Max, 0, 6)?;
+            test_no_group(AggOp::Min, 2, 3)?;
+            test_no_group(AggOp::Avg, 0, 3)
+        }
+
         #[test]
         fn test_merge_tuples_one_group() -> Result<(), CrustyError> {
             let schema = TableSchema::new(vec![
@@ -187,6 +420,12 @@ mod test {
                 Attribute::new("agg".to_string(), DataType::Int),
             ]);
             let mut agg = Aggregator::new(
+                vec![AggregateField {
+                    field: 0,
+                    op: AggOp::Count,
+                }],
+                vec![2],
+                &schema,
             );
 
             let ti = tuples();
@@ -194,7 +433,7 @@ mod test {
                 agg.merge_tuple_into_group(t);
             }
 
-            let mut ai = agg.iterator();
+            let mut ai = agg.iterator()?;
             ai.open()?;
             let rows = num_tuples(&mut ai)?;
             assert_eq!(3, rows);
@@ -225,6 +464,12 @@ mod test {
             ]);
 
             let mut agg = Aggregator::new(
+                vec![AggregateField {
+                    field: 0,
+                    op: AggOp::Count,
+                }],
+                vec![1, 2],
+                &schema,
             );
 
             let ti = tuples();
@@ -232,7 +477,7 @@ mod test {
                 agg.merge_tuple_into_group(t);
             }
 
-            let mut ai = agg.iterator();
+            let mut ai = agg.iterator()?;
             ai.open()?;
             let rows = num_tuples(&mut ai)?;
             assert_eq!(4, rows);
@@ -262,6 +507,12 @@ mod test {
         fn test_open() -> Result<(), CrustyError> {
             let ti = tuple_iterator();
             let mut ai = Aggregate::new(
+                Vec::new(),
+                Vec::new(),
+                vec![0],
+                vec!["count"],
+                vec![AggOp::Count],
+                Box::new(ti),
             );
             assert!(!ai.open);
             ai.open()?;
@@ -276,6 +527,12 @@ mod test {
         ) -> Result<(), CrustyError> {
             let ti = tuple_iterator();
             let mut ai = Aggregate::new(
+                Vec::new(),
+                Vec::new(),
+                vec![col],
+                vec!["agg"],
+                vec![op],
+                Box::new(ti),
             );
             ai.open()?;
             assert_eq!(
@@ -300,6 +557,12 @@ mod test {
         fn test_multiple_aggs() -> Result<(), CrustyError> {
             let ti = tuple_iterator();
             let mut ai = Aggregate::new(
+                Vec::new(),
+                Vec::new(),
+                vec![3, 0, 0],
+                vec!["count", "avg", "max"],
+                vec![AggOp::Count, AggOp::Avg, AggOp::Max],
+                Box::new(ti),
             );
             ai.open()?;
             let first_row: Vec<Field> = ai.next()?.unwrap().field_vals().cloned().collect();
@@ -325,6 +588,12 @@ mod test {
         fn test_multiple_aggs_groups() -> Result<(), CrustyError> {
             let ti = tuple_iterator();
             let mut ai = Aggregate::new(
+                vec![1, 2],
+                vec!["group1", "group2"],
+                vec![3, 0],
+                vec!["count", "max"],
+                vec![AggOp::Count, AggOp::Max],
+                Box::new(ti),
             );
             let mut result = iter_to_vec(&mut ai)?;
             result.sort();
@@ -347,6 +616,12 @@ mod test {
         fn test_next_not_open() {
             let ti = tuple_iterator();
             let mut ai = Aggregate::new(
+                Vec::new(),
+                Vec::new(),
+                vec![0],
+                vec!["count"],
+                vec![AggOp::Count],
+                Box::new(ti),
             );
             ai.next().unwrap();
         }
@@ -355,6 +630,12 @@ mod test {
         fn test_close() -> Result<(), CrustyError> {
             let ti = tuple_iterator();
             let mut ai = Aggregate::new(
+                Vec::new(),
+                Vec::new(),
+                vec![0],
+                vec!["count"],
+                vec![AggOp::Count],
+                Box::new(ti),
             );
             ai.open()?;
             assert!(ai.open);
@@ -368,6 +649,12 @@ mod test {
         fn test_rewind_not_open() {
             let ti = tuple_iterator();
             let mut ai = Aggregate::new(
+                Vec::new(),
+                Vec::new(),
+                vec![0],
+                vec!["count"],
+                vec![AggOp::Count],
+                Box::new(ti),
             );
             ai.rewind().unwrap();
         }
@@ -376,6 +663,12 @@ mod test {
         fn test_rewind() -> Result<(), CrustyError> {
             let ti = tuple_iterator();
             let mut ai = Aggregate::new(
+                Vec::new(),
+                Vec::new(),
+                vec![0],
+                vec!["count"],
+                vec![AggOp::Count],
+                Box::new(ti),
             );
             ai.open()?;
             let count_before = num_tuples(&mut ai);
@@ -392,6 +685,12 @@ mod test {
             let mut groupby_names = vec!["group1", "group2"];
             let ti = tuple_iterator();
             let ai = Aggregate::new(
+                vec![1, 2],
+                groupby_names.clone(),
+                vec![3, 0, 0],
+                agg_names.clone(),
+                vec![AggOp::Count, AggOp::Avg, AggOp::Max],
+                Box::new(ti),
             );
             groupby_names.append(&mut agg_names);
             let expected_names = groupby_names;
@@ -401,5 +700,218 @@ mod test {
                 assert_eq!(DataType::Int, *attr.dtype());
             }
         }
+
+        /// Rows of (group, value) where some values are NULL and group 3 has only NULLs.
+        fn nullable_iterator() -> TupleIterator {
+            let rows = vec![
+                (1, Field::IntField(4)),
+                (1, Field::Null),
+                (1, Field::IntField(2)),
+                (2, Field::Null),
+                (2, Field::IntField(7)),
+                (3, Field::Null),
+            ];
+            let tuples = rows
+                .into_iter()
+                .map(|(g, v)| Tuple::new(vec![Field::IntField(g), v]))
+                .collect();
+            let schema = TableSchema::from_vecs(vec!["g", "v"], vec![DataType::Int, DataType::Int]);
+            TupleIterator::new(tuples, schema)
+        }
+
+        #[test]
+        fn test_nulls() -> Result<(), CrustyError> {
+            let mut ai = Aggregate::new(
+                vec![0],
+                vec!["g"],
+                vec![1, 1, 1, 1, 1, 1],
+                vec!["count", "count_all", "sum", "min", "max", "avg"],
+                vec![
+                    AggOp::Count,
+                    AggOp::CountAll,
+                    AggOp::Sum,
+                    AggOp::Min,
+                    AggOp::Max,
+                    AggOp::Avg,
+                ],
+                Box::new(nullable_iterator()),
+            );
+            let mut result = iter_to_vec(&mut ai)?;
+            result.sort();
+            let int = Field::IntField;
+            let expected = vec![
+                vec![int(1), int(2), int(3), int(6), int(2), int(4), int(3)],
+                vec![int(2), int(1), int(2), int(7), int(7), int(7), int(7)],
+                vec![
+                    int(3),
+                    int(0),
+                    int(1),
+                    Field::Null,
+                    Field::Null,
+                    Field::Null,
+                    Field::Null,
+                ],
+            ];
+            assert_eq!(expected, result);
+            Ok(())
+        }
+
+        #[test]
+        fn test_distinct() -> Result<(), CrustyError> {
+            // Values 3, 3, 4 in group 1 and 4, 5, 5 in group 2, plus NULLs which are skipped
+            let mut tuples = tuples();
+            tuples.push(Tuple::new(vec![
+                Field::IntField(7),
+                Field::IntField(1),
+                Field::Null,
+                Field::Null,
+            ]));
+            let schema = tuple_iterator().get_schema().clone();
+            let mut ai = Aggregate::new(
+                vec![1],
+                vec!["group"],
+                vec![2, 2, 2, 2, 3],
+                vec![
+                    "count_distinct",
+                    "sum_distinct",
+                    "avg_distinct",
+                    "count",
+                    "letters",
+                ],
+                vec![
+                    AggOp::CountDistinct,
+                    AggOp::SumDistinct,
+                    AggOp::AvgDistinct,
+                    AggOp::Count,
+                    AggOp::CountDistinct,
+                ],
+                Box::new(TupleIterator::new(tuples, schema)),
+            );
+            let mut result = iter_to_vec(&mut ai)?;
+            result.sort();
+            let int = Field::IntField;
+            let expected = vec![
+                vec![int(1), int(2), int(7), int(3), int(3), int(3)],
+                vec![int(2), int(2), int(9), int(4), int(3), int(1)],
+            ];
+            assert_eq!(expected, result);
+            Ok(())
+        }
+
+        #[test]
+        fn test_null_groups() -> Result<(), CrustyError> {
+            // Grouping by the value puts the NULLs in one group, which sorts last
+            let mut ai = Aggregate::new(
+                vec![1],
+                vec!["v"],
+                vec![0],
+                vec!["count"],
+                vec![AggOp::Count],
+                Box::new(nullable_iterator()),
+            );
+            let mut result = iter_to_vec(&mut ai)?;
+            result.sort();
+            assert_eq!(vec![Field::Null, Field::IntField(3)], result[3]);
+            assert_eq!(4, result.len());
+            Ok(())
+        }
+
+        #[test]
+        fn test_empty_input() -> Result<(), CrustyError> {
+            let schema = TableSchema::from_vecs(vec!["v"], vec![DataType::Int]);
+            let ti = TupleIterator::new(Vec::new(), schema.clone());
+            let mut ai = Aggregate::new(
+                Vec::new(),
+                Vec::new(),
+                vec![0, 0],
+                vec!["count", "sum"],
+                vec![AggOp::CountAll, AggOp::Sum],
+                Box::new(ti),
+            );
+            // Without a group by there is one row, with a count of 0 and a NULL sum
+            assert_eq!(
+                vec![vec![Field::IntField(0), Field::Null]],
+                iter_to_vec(&mut ai)?
+            );
+            let ti = TupleIterator::new(Vec::new(), schema);
+            let mut ai = Aggregate::new(
+                vec![0],
+                vec!["v"],
+                vec![0],
+                vec!["count"],
+                vec![AggOp::Count],
+                Box::new(ti),
+            );
+            assert!(iter_to_vec(&mut ai)?.is_empty());
+            Ok(())
+        }
+
+        #[test]
+        fn test_typed_sums() -> Result<(), CrustyError> {
+            let schema = TableSchema::from_vecs(
+                vec!["i", "b", "d", "x"],
+                vec![
+                    DataType::Int,
+                    DataType::BigInt,
+                    DataType::Double,
+                    DataType::Decimal(4, 2),
+                ],
+            );
+            let row = |i, b, d, x| {
+                Tuple::new(vec![
+                    Field::IntField(i),
+                    Field::BigIntField(b),
+                    Field::DoubleField(Double(d)),
+                    Field::DecimalField(x, 2),
+                ])
+            };
+            let tuples = vec![
+                row(i32::MAX, i64::MAX, 0.5, 9999),
+                row(i32::MAX, 1, 0.25, 9999),
+                row(1, 1, 0.0, 1),
+            ];
+            let new_agg = |indices, ops| {
+                let ti = TupleIterator::new(tuples.clone(), schema.clone());
+                Aggregate::new(
+                    Vec::new(),
+                    Vec::new(),
+                    indices,
+                    vec!["a", "b", "c"],
+                    ops,
+                    Box::new(ti),
+                )
+            };
+
+            // Sums accumulate past the range of their inputs
+            let mut ai = new_agg(vec![1, 2, 3], vec![AggOp::Avg, AggOp::Sum, AggOp::Sum]);
+            assert_eq!(
+                vec![vec![
+                    Field::BigIntField(3074457345618258603),
+                    Field::DoubleField(Double(0.75)),
+                    Field::DecimalField(19999, 2),
+                ]],
+                iter_to_vec(&mut ai)?
+            );
+            assert_eq!(
+                &DataType::Decimal(MAX_DECIMAL_PRECISION, 2),
+                ai.get_schema().get_attribute(2).unwrap().dtype()
+            );
+            let mut ai = new_agg(vec![0, 2, 3], vec![AggOp::Avg, AggOp::Avg, AggOp::Avg]);
+            assert_eq!(
+                vec![vec![
+                    Field::IntField(1431655765),
+                    Field::DoubleField(Double(0.25)),
+                    Field::DecimalField(6666, 2),
+                ]],
+                iter_to_vec(&mut ai)?
+            );
+
+            // But fail if the sum does not fit the output type
+            let mut ai = new_agg(vec![0], vec![AggOp::Sum]);
+            assert!(ai.open().is_err());
+            let mut ai = new_agg(vec![1], vec![AggOp::Sum]);
+            assert!(ai.open().is_err());
+            Ok(())
+        }
     }
 }
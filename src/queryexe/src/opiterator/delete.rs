@@ -0,0 +1,328 @@
+use super::OpIterator;
+use crate::StorageManager;
+use common::foreign_key::ForeignKeys;
+use common::ids::{ContainerId, TransactionId, ValueId};
+use common::storage_trait::StorageTrait;
+use common::{Attribute, CrustyError, DataType, Field, TableSchema, Tuple};
+
+/// Delete operator. Removes every record its child reads from a table and returns a
+/// single tuple holding the number of rows deleted.
+pub struct Delete {
+    storage_manager: &'static StorageManager,
+    container_id: ContainerId,
+    /// Foreign keys that may refer to the deleted rows.
+    foreign_keys: ForeignKeys,
+    transaction_id: TransactionId,
+    /// Number of rows deleted, once the child has been drained.
+    count: Option<i32>,
+    /// Whether the count has been returned since opening or rewinding.
+    returned: bool,
+    /// Schema of the output.
+    schema: TableSchema,
+    open: bool,
+    /// Source of the tuples to delete, which must come from a scan of the table.
+    child: Box<dyn OpIterator>,
+}
+
+impl Delete {
+    /// Constructor for the delete operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_id` - Container of the table.
+    /// * `foreign_keys` - Foreign keys of the database.
+    /// * `tid` - Transaction to delete in.
+    /// * `child` - Tuples to delete, read from the table.
+    pub fn new(
+        storage_manager: &'static StorageManager,
+        container_id: ContainerId,
+        foreign_keys: ForeignKeys,
+        tid: TransactionId,
+        child: Box<dyn OpIterator>,
+    ) -> Self {
+        Self {
+            storage_manager,
+            container_id,
+            foreign_keys,
+            transaction_id: tid,
+            count: None,
+            returned: false,
+            schema: TableSchema::new(vec![Attribute::new("count".to_string(), DataType::Int)]),
+            open: false,
+            child,
+        }
+    }
+
+    /// Drains the child and deletes its records, along with the rows of other tables that
+    /// cascade from them, and sets to NULL the columns of the rows that refer to them with
+    /// ON DELETE SET NULL. Returns how many records of the table there were.
+    fn delete_all(&mut self) -> Result<i32, CrustyError> {
+        let mut rows: Vec<(ValueId, Tuple)> = Vec::new();
+        while let Some(tuple) = self.child.next()? {
+            let id = self.child.last_value_id().ok_or_else(|| {
+                CrustyError::ExecutionError(String::from(
+                    "Cannot delete tuples not read from a table",
+                ))
+            })?;
+            rows.push((id, tuple));
+        }
+        let cascade = self.foreign_keys.cascade_delete(
+            self.storage_manager,
+            self.transaction_id,
+            self.container_id,
+            &rows,
+        )?;
+        for (id, _) in &rows {
+            debug_assert_eq!(id.container_id, self.container_id);
+            self.storage_manager
+                .delete_value(*id, self.transaction_id)?;
+        }
+        for id in cascade.deleted {
+            self.storage_manager.delete_value(id, self.transaction_id)?;
+        }
+        for (id, value) in cascade.updated {
+            self.storage_manager
+                .update_value(value, id, self.transaction_id)?;
+        }
+        self.storage_manager
+            .check_transaction(self.transaction_id)?;
+        Ok(rows.len() as i32)
+    }
+}
+
+impl OpIterator for Delete {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.child.open()?;
+        self.open = true;
+        self.returned = false;
+        Ok(())
+    }
+
+    /// Deletes the child's records on the first call and returns their count.
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        if self.returned {
+            return Ok(None);
+        }
+        let count = match self.count {
+            Some(count) => count,
+            None => {
+                let count = self.delete_all()?;
+                self.count = Some(count);
+                count
+            }
+        };
+        self.returned = true;
+        Ok(Some(Tuple::new(vec![Field::IntField(count)])))
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.child.close()?;
+        self.open = false;
+        Ok(())
+    }
+
+    /// Returns the count again without deleting anything else.
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.returned = false;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod test {
+    use super::*;
+    use crate::opiterator::{Filter, SeqScan};
+    use common::foreign_key::{ForeignKey, ReferentialAction};
+    use common::ids::Permissions;
+    use common::logical_plan::CompoundPredicateOp;
+    use common::table::Table;
+    use common::testutil::*;
+    use common::SimplePredicateOp;
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    const TABLE: &str = "Delete";
+
+    fn setup() -> (&'static StorageManager, TransactionId) {
+        let smb = Box::new(StorageManager::new_test_sm());
+        let sm: &'static StorageManager = Box::leak(smb);
+        sm.create_table(0).unwrap();
+        let tid = TransactionId::new();
+        let rows = (0..10).map(|i| int_vec_to_tuple(vec![i, i % 2]).get_bytes());
+        sm.insert_values(0, rows.collect(), tid);
+        (sm, tid)
+    }
+
+    fn scan(sm: &'static StorageManager, tid: TransactionId) -> Box<dyn OpIterator> {
+        let table = Table::new(TABLE.to_string(), get_int_table_schema(2));
+        Box::new(SeqScan::new(
+            sm,
+            Arc::new(RwLock::new(table)),
+            TABLE,
+            &0,
+            tid,
+        ))
+    }
+
+    fn remaining(sm: &'static StorageManager, tid: TransactionId) -> Vec<i32> {
+        let mut scan = scan(sm, tid);
+        scan.open().unwrap();
+        let mut res = Vec::new();
+        while let Some(t) = scan.next().unwrap() {
+            res.push(t.get_field(0).unwrap().unwrap_int_field());
+        }
+        res.sort_unstable();
+        res
+    }
+
+    #[test]
+    fn test_delete_where() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        // WHERE a < 6 AND b = 1
+        let predicates = vec![
+            (SimplePredicateOp::LessThan, 0, Field::IntField(6)),
+            (SimplePredicateOp::Equals, 1, Field::IntField(1)),
+        ];
+        let child = Filter::new_compound(CompoundPredicateOp::And, predicates, scan(sm, tid));
+        let mut delete = Delete::new(sm, 0, ForeignKeys::default(), tid, Box::new(child));
+        delete.open()?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(3)])), delete.next()?);
+        // Rewinding does not delete again
+        delete.rewind()?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(3)])), delete.next()?);
+        delete.close()?;
+        assert_eq!(vec![0, 2, 4, 6, 7, 8, 9], remaining(sm, tid));
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_all() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        let mut delete = Delete::new(sm, 0, ForeignKeys::default(), tid, scan(sm, tid));
+        delete.open()?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(10)])), delete.next()?);
+        assert!(remaining(sm, tid).is_empty());
+        Ok(())
+    }
+
+    /// Creates a table p in container 1 with rows 1 and 2, a table c in container 2 whose rows
+    /// 10 and 11 refer to row 1 of p, and a table g in container 3 whose row 100 refers to
+    /// row 10 of c.
+    fn family(
+        sm: &'static StorageManager,
+        tid: TransactionId,
+        on_delete: ReferentialAction,
+    ) -> (Arc<RwLock<Table>>, ForeignKeys) {
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("id".to_string(), DataType::Int),
+            Attribute::new("ref".to_string(), DataType::Int),
+        ]);
+        let mut tables = HashMap::new();
+        for (i, (name, rows)) in [
+            ("p", vec![vec![1, 0], vec![2, 0]]),
+            ("c", vec![vec![10, 1], vec![11, 1]]),
+            ("g", vec![vec![100, 10]]),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let container_id = i as ContainerId + 1;
+            let mut table = Table::new(name.to_string(), schema.clone());
+            if container_id > 1 {
+                table.add_foreign_key(ForeignKey {
+                    name: format!("{}_ref_fkey", name),
+                    columns: vec![1],
+                    parent: container_id - 1,
+                    parent_columns: vec![0],
+                    on_delete,
+                });
+            }
+            sm.create_table(container_id).unwrap();
+            let rows = rows.into_iter().map(|r| int_vec_to_tuple(r).get_bytes());
+            sm.insert_values(container_id, rows.collect(), tid);
+            tables.insert(container_id, Arc::new(RwLock::new(table)));
+        }
+        let parent = tables[&1].clone();
+        (parent, ForeignKeys::new(Arc::new(RwLock::new(tables))))
+    }
+
+    fn count(sm: &'static StorageManager, tid: TransactionId, container_id: ContainerId) -> usize {
+        sm.get_iterator(container_id, tid, Permissions::ReadOnly).count()
+    }
+
+    fn delete_parent(
+        sm: &'static StorageManager,
+        tid: TransactionId,
+        parent: &Arc<RwLock<Table>>,
+        foreign_keys: &ForeignKeys,
+        id: i32,
+    ) -> Result<Option<Tuple>, CrustyError> {
+        let scan = Box::new(SeqScan::new(sm, parent.clone(), "p", &1, tid));
+        let child = Filter::new(SimplePredicateOp::Equals, 0, Field::IntField(id), scan);
+        let mut delete = Delete::new(sm, 1, foreign_keys.clone(), tid, Box::new(child));
+        delete.open()?;
+        delete.next()
+    }
+
+    #[test]
+    fn test_delete_restrict() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        let (parent, foreign_keys) = family(sm, tid, ReferentialAction::Restrict);
+        let res = delete_parent(sm, tid, &parent, &foreign_keys, 1);
+        assert!(matches!(res, Err(CrustyError::ValidationError(_))));
+        assert_eq!((2, 2, 1), (count(sm, tid, 1), count(sm, tid, 2), count(sm, tid, 3)));
+        // Rows nothing refers to can go
+        let res = delete_parent(sm, tid, &parent, &foreign_keys, 2)?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(1)])), res);
+        assert_eq!(1, count(sm, tid, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_cascade() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        let (parent, foreign_keys) = family(sm, tid, ReferentialAction::Cascade);
+        let res = delete_parent(sm, tid, &parent, &foreign_keys, 1)?;
+        // Only the rows of the table itself are counted
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(1)])), res);
+        assert_eq!((1, 0, 0), (count(sm, tid, 1), count(sm, tid, 2), count(sm, tid, 3)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_set_null() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        let (parent, foreign_keys) = family(sm, tid, ReferentialAction::SetNull);
+        let res = delete_parent(sm, tid, &parent, &foreign_keys, 1)?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(1)])), res);
+        // The rows of c stay, referring to nothing, so the row of g still has its parent
+        assert_eq!((1, 2, 1), (count(sm, tid, 1), count(sm, tid, 2), count(sm, tid, 3)));
+        let c = Table::new("c".to_string(), parent.read().unwrap().schema.clone());
+        let mut refs: Vec<Field> = sm
+            .get_iterator(2, tid, Permissions::ReadOnly)
+            .map(|bytes| c.decode(&bytes).field_vals[1].clone())
+            .collect();
+        refs.dedup();
+        assert_eq!(vec![Field::Null], refs);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        let (sm, tid) = setup();
+        let mut delete = Delete::new(sm, 0, ForeignKeys::default(), tid, scan(sm, tid));
+        delete.next();
+    }
+}
@@ -0,0 +1,275 @@
+use super::spill::{memory_size, SpillFile, SpillReader, SpillWriter};
+use super::OpIterator;
+use common::{CrustyError, Field, TableSchema, Tuple};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// Number of partitions the tuples that do not fit in memory are spilled to.
+const PARTITIONS: usize = 16;
+
+/// Partitions of the tuples spilled by a pass, which each make up a later pass.
+struct Partitions {
+    /// Writers of the partitions, created once a tuple goes in them.
+    writers: Vec<Option<SpillWriter>>,
+    /// Pass the tuples were spilled by, which seeds the hash partitioning them.
+    pass: usize,
+}
+
+impl Partitions {
+    fn new(pass: usize) -> Self {
+        let writers = (0..PARTITIONS).map(|_| None).collect();
+        Self { writers, pass }
+    }
+
+    /// Writes a tuple to its partition. Equal tuples go to the same partition.
+    fn write(&mut self, tuple: &Tuple) -> Result<(), CrustyError> {
+        let mut hasher = DefaultHasher::new();
+        self.pass.hash(&mut hasher);
+        tuple.field_vals.hash(&mut hasher);
+        let writer = &mut self.writers[hasher.finish() as usize % PARTITIONS];
+        if writer.is_none() {
+            *writer = Some(SpillWriter::new("distinct")?);
+        }
+        writer.as_mut().unwrap().write(tuple)
+    }
+}
+
+/// Distinct operator, which produces each tuple of its child once. Tuples are produced as
+/// they are first seen while those seen so far fit in the memory budget. Past the budget, the
+/// tuples not seen yet are spilled to disk in partitions by their hash, and once the child is
+/// read each partition is read in turn the same way, so that equal tuples meet in the same
+/// pass however many distinct tuples there are.
+pub struct Distinct {
+    /// Bytes of tuples held in memory before the others are spilled.
+    budget: usize,
+    open: bool,
+    child: Box<dyn OpIterator>,
+    /// Tuples produced by the pass being read.
+    seen: HashSet<Vec<Field>>,
+    /// Bytes of the tuples produced by the pass being read.
+    size: usize,
+    /// Number of the pass being read, the child being the first.
+    pass: usize,
+    /// Partition being read, or None while the child is.
+    reading: Option<SpillReader>,
+    /// Partitions the pass being read spills to once past the budget.
+    spilling: Option<Partitions>,
+    /// Partitions left to read, and the passes that spilled them.
+    pending: Vec<(SpillFile, usize)>,
+    /// The partition being read.
+    partition: Option<SpillFile>,
+}
+
+impl Distinct {
+    /// Distinct constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - Bytes of tuples to hold in memory before spilling the others to disk.
+    /// * `child` - Child operator to get the tuples from.
+    pub fn new(budget: usize, child: Box<dyn OpIterator>) -> Self {
+        Self {
+            budget,
+            open: false,
+            child,
+            seen: HashSet::new(),
+            size: 0,
+            pass: 0,
+            reading: None,
+            spilling: None,
+            pending: Vec::new(),
+            partition: None,
+        }
+    }
+
+    /// Forgets the tuples seen and spilled, to read the child again.
+    fn reset(&mut self) {
+        self.seen.clear();
+        self.size = 0;
+        self.pass = 0;
+        self.reading = None;
+        self.spilling = None;
+        self.pending.clear();
+        self.partition = None;
+    }
+
+    /// Ends the pass being read, and starts the pass of the next partition spilled. Returns
+    /// whether there is one.
+    fn next_pass(&mut self) -> Result<bool, CrustyError> {
+        self.seen.clear();
+        self.size = 0;
+        if let Some(partitions) = self.spilling.take() {
+            for writer in partitions.writers.into_iter().flatten() {
+                self.pending.push((writer.finish()?, self.pass));
+            }
+        }
+        match self.pending.pop() {
+            Some((file, pass)) => {
+                self.reading = Some(file.reader()?);
+                self.partition = Some(file);
+                // Later passes partition by another hash, to split what the spilling pass
+                // partitioned together
+                self.pass = pass + 1;
+                Ok(true)
+            }
+            None => {
+                self.reading = None;
+                self.partition = None;
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl OpIterator for Distinct {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.child.open()?;
+        self.reset();
+        self.open = true;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        loop {
+            let tuple = match &mut self.reading {
+                Some(reader) => reader.next()?,
+                None if self.pass == 0 => self.child.next()?,
+                None => return Ok(None),
+            };
+            let tuple = match tuple {
+                Some(tuple) => tuple,
+                None if self.next_pass()? => continue,
+                None => return Ok(None),
+            };
+            if self.seen.contains(&tuple.field_vals) {
+                continue;
+            }
+            if let Some(partitions) = &mut self.spilling {
+                partitions.write(&tuple)?;
+                continue;
+            }
+            self.size += memory_size(&tuple);
+            self.seen.insert(tuple.field_vals.clone());
+            if self.size > self.budget {
+                self.spilling = Some(Partitions::new(self.pass));
+            }
+            return Ok(Some(tuple));
+        }
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.child.close()?;
+        self.reset();
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.child.rewind()?;
+        self.reset();
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        self.child.get_schema()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::TupleIterator;
+    use super::*;
+    use common::DataType;
+
+    /// Tuples of (a % m, b) for a below n, where b is NULL for every third value of a % m, so
+    /// that each distinct tuple comes n / m times.
+    fn child(n: i32, m: i32) -> Box<dyn OpIterator> {
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::Int]);
+        let tuples = (0..n)
+            .map(|a| {
+                let b = match a % m % 3 {
+                    0 => Field::Null,
+                    _ => Field::IntField(1),
+                };
+                Tuple::new(vec![Field::IntField(a % m), b])
+            })
+            .collect();
+        Box::new(TupleIterator::new(tuples, schema))
+    }
+
+    /// Reads the first values of a of the tuples, checking each is produced once.
+    fn read_all(distinct: &mut Distinct) -> Result<Vec<i32>, CrustyError> {
+        let mut values = Vec::new();
+        while let Some(tuple) = distinct.next()? {
+            match tuple.field_vals[0] {
+                Field::IntField(a) => values.push(a),
+                _ => panic!("Expected an int"),
+            }
+        }
+        let mut sorted = values.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), values.len());
+        Ok(values)
+    }
+
+    #[test]
+    fn test_distinct_in_memory() -> Result<(), CrustyError> {
+        let mut distinct = Distinct::new(usize::MAX, child(100, 7));
+        distinct.open()?;
+        // Tuples are produced in the order they are first seen
+        assert_eq!((0..7).collect::<Vec<_>>(), read_all(&mut distinct)?);
+        assert!(distinct.pending.is_empty());
+        distinct.rewind()?;
+        assert_eq!((0..7).collect::<Vec<_>>(), read_all(&mut distinct)?);
+        distinct.close()
+    }
+
+    #[test]
+    fn test_distinct_spills() -> Result<(), CrustyError> {
+        // A budget of a few tuples spills most of them, and again for most partitions
+        let budget = 4 * memory_size(&Tuple::new(vec![Field::IntField(0), Field::Null]));
+        let mut distinct = Distinct::new(budget, child(3000, 500));
+        distinct.open()?;
+        let mut values = read_all(&mut distinct)?;
+        values.sort_unstable();
+        assert_eq!((0..500).collect::<Vec<_>>(), values);
+        assert!(distinct.pass > 1);
+
+        distinct.rewind()?;
+        assert_eq!(500, read_all(&mut distinct)?.len());
+        distinct.close()
+    }
+
+    #[test]
+    fn test_distinct_removes_spills() -> Result<(), CrustyError> {
+        let mut distinct = Distinct::new(0, child(200, 50));
+        distinct.open()?;
+        for _ in 0..20 {
+            distinct.next()?;
+        }
+        let paths: Vec<_> = distinct
+            .pending
+            .iter()
+            .map(|(file, _)| file.path.clone())
+            .collect();
+        assert!(!paths.is_empty());
+        assert!(paths.iter().all(|path| path.exists()));
+        distinct.close()?;
+        assert!(paths.iter().all(|path| !path.exists()));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        Distinct::new(0, child(1, 1)).next().unwrap();
+    }
+}
@@ -1,4 +1,6 @@
 use super::OpIterator;
+use common::ids::ValueId;
+use common::logical_plan::CompoundPredicateOp;
 use common::{CrustyError, Field, SimplePredicateOp, TableSchema, Tuple};
 
 /// Compares the fields of tuples.
@@ -32,7 +34,7 @@ impl FilterPredicate {
     /// # Arguments
     ///
     /// * `tuple` - Tuple to apply the filter to.
-    fn filter(&self, tuple: &Tuple) -> bool {
+    fn filter(&self, tuple: &Tuple) -> Option<bool> {
         let field = tuple.get_field(self.field_ind).unwrap();
         self.op.compare(field, &self.operand)
     }
@@ -40,8 +42,10 @@ impl FilterPredicate {
 
 /// Filter oeprator.
 pub struct Filter {
-    /// Predicate to filter by.
-    predicate: FilterPredicate,
+    /// Predicates to filter by.
+    predicates: Vec<FilterPredicate>,
+    /// Operation combining the results of the predicates.
+    compound_op: CompoundPredicateOp,
     /// Schema of the child.
     schema: TableSchema,
     /// Boolean determining if iterator is open.
@@ -62,14 +66,51 @@ impl Filter {
         field_ind: usize,
         operand: Field,
         child: Box<dyn OpIterator>,
+    ) -> Self {
+        Self::new_compound(
+            CompoundPredicateOp::And,
+            vec![(op, field_ind, operand)],
+            child,
+        )
+    }
+
+    /// Constructor for a filter combining several predicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `compound_op` - Operation combining the results of the predicates.
+    /// * `predicates` - Operation, field index and operand of each predicate.
+    /// * `child` - Child OpIterator passing data into the operator.
+    pub fn new_compound(
+        compound_op: CompoundPredicateOp,
+        predicates: Vec<(SimplePredicateOp, usize, Field)>,
+        child: Box<dyn OpIterator>,
     ) -> Self {
         Self {
-            predicate: FilterPredicate::new(op, field_ind, operand),
+            predicates: predicates
+                .into_iter()
+                .map(|(op, field_ind, operand)| FilterPredicate::new(op, field_ind, operand))
+                .collect(),
+            compound_op,
             schema: child.get_schema().clone(),
             open: false,
             child,
         }
     }
+
+    /// Apply the predicates to the specified tuple. Tuples for which the result is unknown
+    /// are filtered out.
+    ///
+    /// # Arguments
+    ///
+    /// * `tuple` - Tuple to apply the filter to.
+    fn filter(&self, tuple: &Tuple) -> bool {
+        let mut res = Some(self.compound_op.identity());
+        for p in &self.predicates {
+            res = self.compound_op.apply_unknown(res, p.filter(tuple));
+        }
+        res.unwrap_or(false)
+    }
 }
 
 impl OpIterator for Filter {
@@ -85,7 +126,7 @@ impl OpIterator for Filter {
 
         let mut res = None;
         while let Some(t) = self.child.next()? {
-            if self.predicate.filter(&t) {
+            if self.filter(&t) {
                 res = Some(t);
                 break;
             }
@@ -111,6 +152,18 @@ impl OpIterator for Filter {
     fn get_schema(&self) -> &TableSchema {
         &self.schema
     }
+
+    fn last_value_id(&self) -> Option<ValueId> {
+        self.child.last_value_id()
+    }
+
+    fn read_columns(&mut self, columns: &[usize]) {
+        let mut columns = columns.to_vec();
+        columns.extend(self.predicates.iter().map(|p| p.field_ind));
+        columns.sort_unstable();
+        columns.dedup();
+        self.child.read_columns(&columns);
+    }
 }
 
 #[cfg(test)]
@@ -245,4 +298,115 @@ mod test {
         assert!(filter.next()?.is_none());
         Ok(())
     }
+
+    #[test]
+    fn test_compound_and() -> Result<(), CrustyError> {
+        let predicates = vec![
+            (SimplePredicateOp::GreaterThan, 0, Field::IntField(-2)),
+            (SimplePredicateOp::LessThan, 1, Field::IntField(2)),
+        ];
+        let ti = mock_ti(-5, 5, WIDTH);
+        let mut filter = Filter::new_compound(CompoundPredicateOp::And, predicates, Box::new(ti));
+        let mut expected = mock_ti(-1, 2, WIDTH);
+        filter.open()?;
+        expected.open()?;
+        match_all_tuples(Box::new(filter), Box::new(expected))
+    }
+
+    #[test]
+    fn test_compound_or() -> Result<(), CrustyError> {
+        let predicates = vec![
+            (SimplePredicateOp::LessThan, 0, Field::IntField(-3)),
+            (SimplePredicateOp::GreaterThan, 2, Field::IntField(3)),
+        ];
+        let ti = mock_ti(-5, 5, WIDTH);
+        let mut filter = Filter::new_compound(CompoundPredicateOp::Or, predicates, Box::new(ti));
+        filter.open()?;
+        for i in [-5, -4, 4] {
+            assert_eq!(tuple_repeat_field(i, WIDTH), filter.next()?.unwrap());
+        }
+        assert!(filter.next()?.is_none());
+        Ok(())
+    }
+
+    /// Tuples of (a, b) where b is NULL when a is odd.
+    fn nullable_ti() -> TupleIterator {
+        let tuples = (0..4)
+            .map(|i| {
+                let b = if i % 2 == 0 {
+                    Field::IntField(i)
+                } else {
+                    Field::Null
+                };
+                Tuple::new(vec![Field::IntField(i), b])
+            })
+            .collect();
+        TupleIterator::new(tuples, get_int_table_schema(2))
+    }
+
+    fn filtered_keys(mut filter: Filter) -> Result<Vec<i32>, CrustyError> {
+        filter.open()?;
+        let mut keys = Vec::new();
+        while let Some(t) = filter.next()? {
+            keys.push(t.get_field(0).unwrap().unwrap_int_field());
+        }
+        Ok(keys)
+    }
+
+    #[test]
+    fn test_null() -> Result<(), CrustyError> {
+        let is_null = Filter::new(
+            SimplePredicateOp::IsNull,
+            1,
+            Field::Null,
+            Box::new(nullable_ti()),
+        );
+        assert_eq!(vec![1, 3], filtered_keys(is_null)?);
+        let not_null = Filter::new(
+            SimplePredicateOp::IsNotNull,
+            1,
+            Field::Null,
+            Box::new(nullable_ti()),
+        );
+        assert_eq!(vec![0, 2], filtered_keys(not_null)?);
+        // Comparisons with NULL are unknown, even NULL = NULL
+        let eq_null = Filter::new(
+            SimplePredicateOp::Equals,
+            1,
+            Field::Null,
+            Box::new(nullable_ti()),
+        );
+        assert!(filtered_keys(eq_null)?.is_empty());
+        let not_eq = Filter::new(
+            SimplePredicateOp::NotEq,
+            1,
+            Field::IntField(0),
+            Box::new(nullable_ti()),
+        );
+        assert_eq!(vec![2], filtered_keys(not_eq)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compound_null() -> Result<(), CrustyError> {
+        // a < 2 OR b = 0: true OR unknown is true, false OR unknown is unknown
+        let predicates = vec![
+            (SimplePredicateOp::LessThan, 0, Field::IntField(2)),
+            (SimplePredicateOp::Equals, 1, Field::IntField(0)),
+        ];
+        let or = Filter::new_compound(
+            CompoundPredicateOp::Or,
+            predicates.clone(),
+            Box::new(nullable_ti()),
+        );
+        assert_eq!(vec![0, 1], filtered_keys(or)?);
+        // a < 2 AND b = 0: true AND unknown is unknown
+        let and = Filter::new_compound(
+            CompoundPredicateOp::And,
+            predicates,
+            Box::new(nullable_ti()),
+        );
+        assert_eq!(vec![0], filtered_keys(and)?);
+        Ok(())
+    }
 }
@@ -0,0 +1,225 @@
+use super::{OpIterator, SeqScan};
+use crate::StorageManager;
+use common::ids::Permissions;
+use common::ids::{ContainerId, TransactionId, ValueId};
+use common::storage_trait::StorageTrait;
+use common::table::*;
+use common::{CrustyError, Field, TableSchema, Tuple};
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+/// Index scan operator, which reads the rows of a table whose keys in a secondary index are
+/// between bounds, in the order of their keys.
+pub struct IndexScan {
+    /// Table scanned, which upgrades the rows read to its current schema.
+    table: Table,
+    schema: TableSchema,
+    /// Columns read from each row, or None to read all of them.
+    columns: Option<Vec<usize>>,
+    open: bool,
+    storage_manager: &'static StorageManager,
+    /// Container of the index.
+    index_id: ContainerId,
+    /// Lowest key to read.
+    lower: Bound<Vec<Field>>,
+    /// Highest key to read.
+    upper: Bound<Vec<Field>>,
+    /// Ids of the rows found in the index, and the position of the next one to read.
+    ids: Vec<ValueId>,
+    position: usize,
+    transaction_id: TransactionId,
+}
+
+impl IndexScan {
+    /// Constructor for the index scan operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - Table to scan over.
+    /// * `table_alias` - Table alias given by the user.
+    /// * `index_id` - Container of the index.
+    /// * `lower` - Lowest key to read, which may hold values for only the first columns of the key.
+    /// * `upper` - Highest key to read, which may hold values for only the first columns of the key.
+    /// * `tid` - Transaction used to read the table.
+    pub fn new(
+        storage_manager: &'static StorageManager,
+        table: Arc<RwLock<Table>>,
+        table_alias: &str,
+        index_id: ContainerId,
+        lower: Bound<Vec<Field>>,
+        upper: Bound<Vec<Field>>,
+        tid: TransactionId,
+    ) -> Self {
+        let table = table.read().unwrap().clone();
+        Self {
+            schema: SeqScan::schema(&table.schema, table_alias),
+            table,
+            columns: None,
+            open: false,
+            storage_manager,
+            index_id,
+            lower,
+            upper,
+            ids: Vec::new(),
+            position: 0,
+            transaction_id: tid,
+        }
+    }
+
+    /// Looks up the ids of the rows between the bounds in the index.
+    fn find(&mut self) -> Result<(), CrustyError> {
+        self.ids = self.storage_manager.index_scan(
+            self.index_id,
+            self.lower.as_ref().map(Vec::as_slice),
+            self.upper.as_ref().map(Vec::as_slice),
+            self.transaction_id,
+        )?;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl OpIterator for IndexScan {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.find()?;
+        self.open = true;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        match self.ids.get(self.position) {
+            Some(id) => {
+                let bytes = self.storage_manager.get_value(
+                    *id,
+                    self.transaction_id,
+                    Permissions::ReadOnly,
+                )?;
+                self.position += 1;
+                Ok(Some(match &self.columns {
+                    Some(columns) => self.table.decode_columns(&bytes, columns),
+                    None => self.table.decode(&bytes),
+                }))
+            }
+            None => {
+                self.storage_manager
+                    .check_transaction(self.transaction_id)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.ids.clear();
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.find()
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+
+    fn last_value_id(&self) -> Option<ValueId> {
+        self.position.checked_sub(1).map(|i| self.ids[i])
+    }
+
+    fn read_columns(&mut self, columns: &[usize]) {
+        self.columns = Some(columns.to_vec());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::opiterator::testutil::sum_int_fields;
+    use common::index::{IndexMethod, SecondaryIndex};
+    use common::testutil::*;
+    use common::DataType;
+
+    const TABLE: &str = "IndexScan";
+
+    fn get_scan(lower: Bound<Vec<Field>>, upper: Bound<Vec<Field>>) -> IndexScan {
+        let (cid, index_id) = (0, 1);
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::Int]);
+        let mut table = Table::new(TABLE.to_string(), schema);
+        table.add_secondary_index(SecondaryIndex {
+            name: "index_scan_b".to_string(),
+            container_id: index_id,
+            columns: vec![1],
+            unique: false,
+            method: IndexMethod::BTree,
+            constraint: false,
+        });
+        let table = Arc::new(RwLock::new(table));
+        let sm: &'static StorageManager = Box::leak(Box::new(StorageManager::new_test_sm()));
+        sm.create_table(cid).unwrap();
+        sm.open_index(index_id, cid, table.clone()).unwrap();
+        let tid = TransactionId::new();
+        for (a, b) in [(1, 30), (2, 10), (3, 20), (4, 10)] {
+            let bytes = table.read().unwrap().encode(&int_vec_to_tuple(vec![a, b]));
+            sm.insert_value(cid, bytes, tid);
+        }
+        IndexScan::new(sm, table, TABLE, index_id, lower, upper, tid)
+    }
+
+    fn key(b: i32) -> Vec<Field> {
+        vec![Field::IntField(b)]
+    }
+
+    #[test]
+    fn test_next() -> Result<(), CrustyError> {
+        let mut scan = get_scan(Bound::Included(key(10)), Bound::Included(key(10)));
+        scan.open()?;
+        assert_eq!(sum_int_fields(&mut scan)?, 2 + 10 + 4 + 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_order() -> Result<(), CrustyError> {
+        let mut scan = get_scan(Bound::Excluded(key(10)), Bound::Unbounded);
+        scan.open()?;
+        let first = scan.next()?.unwrap();
+        let id = scan.last_value_id().unwrap();
+        let second = scan.next()?.unwrap();
+        assert!(scan.next()?.is_none());
+        assert_eq!(int_vec_to_tuple(vec![3, 20]), first);
+        assert_eq!(int_vec_to_tuple(vec![1, 30]), second);
+        assert_ne!(Some(id), scan.last_value_id());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewind() -> Result<(), CrustyError> {
+        let mut scan = get_scan(Bound::Unbounded, Bound::Excluded(key(30)));
+        scan.open()?;
+        let sum_before = sum_int_fields(&mut scan)?;
+        scan.rewind()?;
+        let sum_after = sum_int_fields(&mut scan)?;
+        assert_eq!(sum_before, sum_after);
+        assert_eq!(sum_before, 9 + 40);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        let mut scan = get_scan(Bound::Unbounded, Bound::Unbounded);
+        scan.next().unwrap();
+    }
+
+    #[test]
+    fn test_get_schema() {
+        let scan = get_scan(Bound::Unbounded, Bound::Unbounded);
+        let names: Vec<&str> = scan.get_schema().attributes().map(|a| a.name()).collect();
+        assert_eq!(vec!["IndexScan.a", "IndexScan.b"], names);
+    }
+}
@@ -0,0 +1,518 @@
+use super::OpIterator;
+use crate::StorageManager;
+use common::foreign_key::ForeignKeys;
+use common::ids::{ContainerId, TransactionId, ValueId};
+use common::storage_trait::StorageTrait;
+use common::table::Table;
+use common::{Attribute, CrustyError, DataType, Field, TableSchema, Tuple};
+
+/// Insert operator. Writes every tuple of its child into a table and returns a single
+/// tuple holding the number of rows inserted.
+pub struct Insert {
+    storage_manager: &'static StorageManager,
+    container_id: ContainerId,
+    /// Foreign keys of the table.
+    foreign_keys: ForeignKeys,
+    transaction_id: TransactionId,
+    /// Table inserted into.
+    table: Table,
+    /// Index in the table's schema of the column each field of the child's tuples goes in.
+    columns: Vec<usize>,
+    /// Number of rows inserted, once the child has been drained.
+    count: Option<i32>,
+    /// Whether the count has been returned since opening or rewinding.
+    returned: bool,
+    /// Schema of the output.
+    schema: TableSchema,
+    open: bool,
+    child: Box<dyn OpIterator>,
+}
+
+impl Insert {
+    /// Constructor for the insert operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - Table to insert into.
+    /// * `container_id` - Container of the table.
+    /// * `foreign_keys` - Foreign keys of the database.
+    /// * `columns` - Index in the table's schema of the column each field of child's tuples goes in.
+    /// * `tid` - Transaction to insert in.
+    /// * `child` - Source of the tuples to insert.
+    pub fn new(
+        storage_manager: &'static StorageManager,
+        table: Table,
+        container_id: ContainerId,
+        foreign_keys: ForeignKeys,
+        columns: Vec<usize>,
+        tid: TransactionId,
+        child: Box<dyn OpIterator>,
+    ) -> Self {
+        Self {
+            storage_manager,
+            container_id,
+            foreign_keys,
+            transaction_id: tid,
+            table,
+            columns,
+            count: None,
+            returned: false,
+            schema: TableSchema::new(vec![Attribute::new("count".to_string(), DataType::Int)]),
+            open: false,
+            child,
+        }
+    }
+
+    /// Checks that the child's tuples fit the columns they go in.
+    fn validate_child(&self) -> Result<(), CrustyError> {
+        let child_schema = self.child.get_schema();
+        if child_schema.size() != self.columns.len() {
+            return Err(CrustyError::ValidationError(format!(
+                "Expected {} values but got {}",
+                self.columns.len(),
+                child_schema.size()
+            )));
+        }
+        for (src, i) in child_schema.attributes().zip(self.columns.iter()) {
+            let dest = self.table.schema.get_attribute(*i).unwrap();
+            if !dest.dtype().accepts(src.dtype()) {
+                return Err(CrustyError::ValidationError(format!(
+                    "Column {} of type {:?} cannot hold {} of type {:?}",
+                    dest.name(),
+                    dest.dtype(),
+                    src.name(),
+                    src.dtype()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts the fields of a child tuple in the order of the table's columns. The columns the
+    /// child has no field for take their DEFAULT, or NULL.
+    fn to_table_order(&self, tuple: Tuple) -> Tuple {
+        let mut fields: Vec<Field> = self
+            .table
+            .schema
+            .attributes()
+            .map(|attr| attr.default.clone().unwrap_or(Field::Null))
+            .collect();
+        for (field, i) in tuple.field_vals.into_iter().zip(self.columns.iter()) {
+            fields[*i] = field;
+        }
+        Tuple::new(fields)
+    }
+
+    /// Drains the child and inserts its tuples, returning how many there were.
+    ///
+    /// Nothing is inserted if any tuple would break a key or a foreign key of the table.
+    fn insert_all(&mut self) -> Result<i32, CrustyError> {
+        let mut tuples = Vec::new();
+        while let Some(tuple) = self.child.next()? {
+            tuples.push(self.to_table_order(tuple));
+        }
+        let rows: Vec<(Option<ValueId>, &Tuple)> = tuples.iter().map(|t| (None, t)).collect();
+        self.table.check_rows(
+            self.storage_manager,
+            self.container_id,
+            self.transaction_id,
+            &rows,
+        )?;
+        let refs: Vec<&Tuple> = tuples.iter().collect();
+        self.foreign_keys.check_references(
+            self.storage_manager,
+            self.transaction_id,
+            &self.table,
+            self.container_id,
+            &refs,
+        )?;
+        let values = tuples.iter().map(|t| self.table.encode(t)).collect();
+        let ids = self
+            .storage_manager
+            .insert_values(self.container_id, values, self.transaction_id);
+        self.storage_manager
+            .check_transaction(self.transaction_id)?;
+        let written: Vec<(ValueId, &Tuple)> = ids.into_iter().zip(tuples.iter()).collect();
+        self.table
+            .check_written(self.storage_manager, self.transaction_id, &written)?;
+        Ok(tuples.len() as i32)
+    }
+}
+
+impl OpIterator for Insert {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.validate_child()?;
+        self.child.open()?;
+        self.open = true;
+        self.returned = false;
+        Ok(())
+    }
+
+    /// Inserts the child's tuples on the first call and returns their count.
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        if self.returned {
+            return Ok(None);
+        }
+        let count = match self.count {
+            Some(count) => count,
+            None => {
+                let count = self.insert_all()?;
+                self.count = Some(count);
+                count
+            }
+        };
+        self.returned = true;
+        Ok(Some(Tuple::new(vec![Field::IntField(count)])))
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.child.close()?;
+        self.open = false;
+        Ok(())
+    }
+
+    /// Returns the count again without inserting the rows a second time.
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.returned = false;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod test {
+    use super::*;
+    use crate::opiterator::{SeqScan, TupleIterator};
+    use common::foreign_key::{ForeignKey, ReferentialAction};
+    use common::index::{IndexMethod, SecondaryIndex};
+    use common::testutil::*;
+    use common::Constraint;
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    use std::time::Duration;
+
+    const TABLE: &str = "Insert";
+
+    fn setup() -> (&'static StorageManager, TableSchema, TransactionId) {
+        let smb = Box::new(StorageManager::new_test_sm());
+        let sm: &'static StorageManager = Box::leak(smb);
+        sm.create_table(0).unwrap();
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::String]);
+        (sm, schema, TransactionId::new())
+    }
+
+    fn table(schema: &TableSchema) -> Table {
+        Table::new(TABLE.to_string(), schema.clone())
+    }
+
+    fn rows(schema: TableSchema, rows: Vec<Tuple>) -> Box<dyn OpIterator> {
+        Box::new(TupleIterator::new(rows, schema))
+    }
+
+    fn scan(sm: &'static StorageManager, schema: &TableSchema, tid: TransactionId) -> Vec<Tuple> {
+        let table = Arc::new(RwLock::new(table(schema)));
+        let mut scan = SeqScan::new(sm, table, TABLE, &0, tid);
+        scan.open().unwrap();
+        let mut tuples = Vec::new();
+        while let Some(t) = scan.next().unwrap() {
+            tuples.push(t);
+        }
+        tuples
+    }
+
+    #[test]
+    fn test_insert_reorders_columns() -> Result<(), CrustyError> {
+        let (sm, schema, tid) = setup();
+        let src = TableSchema::from_vecs(vec!["b", "a"], vec![DataType::String, DataType::Int]);
+        let child = rows(
+            src,
+            vec![
+                Tuple::new(vec![Field::StringField("x".to_string()), Field::IntField(1)]),
+                Tuple::new(vec![Field::StringField("y".to_string()), Field::IntField(2)]),
+            ],
+        );
+        let mut insert =
+            Insert::new(sm, table(&schema), 0, ForeignKeys::default(), vec![1, 0], tid, child);
+        insert.open()?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(2)])), insert.next()?);
+        assert_eq!(None, insert.next()?);
+        // Rewinding does not insert again
+        insert.rewind()?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(2)])), insert.next()?);
+        insert.close()?;
+
+        let tuples = scan(sm, &schema, tid);
+        assert_eq!(2, tuples.len());
+        assert!(tuples.contains(&Tuple::new(vec![
+            Field::IntField(1),
+            Field::StringField("x".to_string())
+        ])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_omitted_columns() -> Result<(), CrustyError> {
+        let (sm, _, tid) = setup();
+        let mut c = Attribute::new("c".to_string(), DataType::Int);
+        c.default = Some(Field::IntField(7));
+        let schema = TableSchema::new(vec![
+            Attribute::new("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::Int),
+            c,
+        ]);
+        let child = rows(get_int_table_schema(1), vec![int_vec_to_tuple(vec![1])]);
+        let mut insert =
+            Insert::new(sm, table(&schema), 0, ForeignKeys::default(), vec![0], tid, child);
+        insert.open()?;
+        insert.next()?;
+        insert.close()?;
+        // b has no DEFAULT, so it is NULL
+        assert_eq!(
+            vec![Tuple::new(vec![Field::IntField(1), Field::Null, Field::IntField(7)])],
+            scan(sm, &schema, tid)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_type_mismatch() {
+        let (sm, schema, tid) = setup();
+        let child = rows(get_int_table_schema(2), vec![int_vec_to_tuple(vec![1, 2])]);
+        let mut insert =
+            Insert::new(sm, table(&schema), 0, ForeignKeys::default(), vec![0, 1], tid, child);
+        assert!(insert.open().is_err());
+        let child = rows(get_int_table_schema(1), vec![int_vec_to_tuple(vec![1])]);
+        let mut insert =
+            Insert::new(sm, table(&schema), 0, ForeignKeys::default(), vec![0, 1], tid, child);
+        assert!(insert.open().is_err());
+        assert!(scan(sm, &schema, tid).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        let (sm, schema, tid) = setup();
+        let child = rows(get_int_table_schema(2), Vec::new());
+        let mut insert =
+            Insert::new(sm, table(&schema), 0, ForeignKeys::default(), vec![0, 1], tid, child);
+        insert.next();
+    }
+
+    #[test]
+    fn test_insert_unique_key() -> Result<(), CrustyError> {
+        let (sm, _, tid) = setup();
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new_with_constraint("b".to_string(), DataType::Int, Constraint::Unique),
+        ]);
+        let keyed = table(&schema);
+        let insert = |rows: Vec<Vec<i32>>| {
+            let child = Box::new(TupleIterator::new(
+                create_tuple_list(rows),
+                get_int_table_schema(2),
+            ));
+            let mut insert = Insert::new(
+                sm,
+                keyed.clone(),
+                0,
+                ForeignKeys::default(),
+                vec![0, 1],
+                tid,
+                child,
+            );
+            insert.open().unwrap();
+            insert.next()
+        };
+        assert!(insert(vec![vec![1, 10], vec![2, 20]]).is_ok());
+        // Against stored rows, and within the rows inserted
+        for rows in [vec![vec![1, 30]], vec![vec![3, 20]], vec![vec![3, 30], vec![3, 40]]] {
+            assert!(matches!(insert(rows), Err(CrustyError::ValidationError(_))));
+        }
+        assert_eq!(2, scan(sm, &schema, tid).len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_concurrent_key() -> Result<(), CrustyError> {
+        let (sm, _, _) = setup();
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::Int),
+        ]);
+        let mut keyed = table(&schema);
+        keyed.add_secondary_index(SecondaryIndex {
+            name: "Insert_pkey".to_string(),
+            container_id: 1,
+            columns: vec![0],
+            unique: true,
+            method: IndexMethod::BTree,
+            constraint: true,
+        });
+        sm.open_index(1, 0, Arc::new(RwLock::new(keyed.clone())))?;
+        fn insert(
+            sm: &'static StorageManager,
+            keyed: Table,
+            tid: TransactionId,
+            row: Vec<i32>,
+        ) -> Result<(), CrustyError> {
+            let child = rows(get_int_table_schema(2), vec![int_vec_to_tuple(row)]);
+            let mut insert =
+                Insert::new(sm, keyed, 0, ForeignKeys::default(), vec![0, 1], tid, child);
+            insert.open()?;
+            insert.next().map(|_| ())
+        }
+        // The second writer of a key waits for the first, which it conflicts with only if
+        // the first commits
+        for commit in [true, false] {
+            let key = if commit { 1 } else { 2 };
+            let (t1, t2) = (TransactionId::new(), TransactionId::new());
+            insert(sm, keyed.clone(), t1, vec![key, 10])?;
+            let second = keyed.clone();
+            let waiting = thread::spawn(move || {
+                let res = insert(sm, second, t2, vec![key, 20]);
+                sm.transaction_finished(t2);
+                res
+            });
+            thread::sleep(Duration::from_millis(100));
+            if commit {
+                sm.transaction_finished(t1);
+                assert!(matches!(
+                    waiting.join().unwrap(),
+                    Err(CrustyError::ValidationError(_))
+                ));
+            } else {
+                sm.abort_transaction(t1);
+                assert_eq!(Ok(()), waiting.join().unwrap());
+            }
+        }
+        let mut tuples = scan(sm, &schema, TransactionId::new());
+        tuples.sort_by_key(|t| t.field_vals[0].clone());
+        assert_eq!(create_tuple_list(vec![vec![1, 10], vec![2, 20]]), tuples);
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_foreign_key() -> Result<(), CrustyError> {
+        let (sm, _, tid) = setup();
+        // A table whose column b refers to its own primary key a
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::Int),
+        ]);
+        let mut tree = table(&schema);
+        tree.add_foreign_key(ForeignKey {
+            name: "tree_b_fkey".to_string(),
+            columns: vec![1],
+            parent: 0,
+            parent_columns: vec![0],
+            on_delete: ReferentialAction::Restrict,
+        });
+        assert_eq!(Constraint::ForeignKey(0), tree.schema.get_attribute(1).unwrap().constraint);
+        let tables = HashMap::from([(0, Arc::new(RwLock::new(tree.clone())))]);
+        let foreign_keys = ForeignKeys::new(Arc::new(RwLock::new(tables)));
+        let insert = |rows: Vec<Vec<i32>>| {
+            let child = Box::new(TupleIterator::new(
+                create_tuple_list(rows),
+                get_int_table_schema(2),
+            ));
+            let mut insert =
+                Insert::new(sm, tree.clone(), 0, foreign_keys.clone(), vec![0, 1], tid, child);
+            insert.open().unwrap();
+            insert.next()
+        };
+        // Rows may refer to rows inserted along with them
+        assert!(insert(vec![vec![1, 1], vec![2, 1], vec![3, 2]]).is_ok());
+        assert!(insert(vec![vec![4, 3]]).is_ok());
+        match insert(vec![vec![5, 1], vec![6, 7]]) {
+            Err(CrustyError::ValidationError(msg)) => assert!(msg.contains("tree_b_fkey")),
+            res => panic!("expected a validation error, got {:?}", res),
+        }
+        assert_eq!(4, scan(sm, &schema, tid).len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_nulls() -> Result<(), CrustyError> {
+        let (sm, _, tid) = setup();
+        // a is the primary key, b is unique and refers to a, c is NOT NULL
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new_with_constraint("b".to_string(), DataType::Int, Constraint::Unique),
+            Attribute::new_with_constraint("c".to_string(), DataType::Int, Constraint::NotNull),
+        ]);
+        let mut keyed = table(&schema);
+        keyed.add_foreign_key(ForeignKey {
+            name: "keyed_b_fkey".to_string(),
+            columns: vec![1],
+            parent: 0,
+            parent_columns: vec![0],
+            on_delete: ReferentialAction::Restrict,
+        });
+        let tables = HashMap::from([(0, Arc::new(RwLock::new(keyed.clone())))]);
+        let foreign_keys = ForeignKeys::new(Arc::new(RwLock::new(tables)));
+        let insert = |rows: Vec<Vec<Field>>| {
+            let tuples = rows.into_iter().map(Tuple::new).collect();
+            let child = Box::new(TupleIterator::new(tuples, get_int_table_schema(3)));
+            let mut insert =
+                Insert::new(sm, keyed.clone(), 0, foreign_keys.clone(), vec![0, 1, 2], tid, child);
+            insert.open().unwrap();
+            insert.next()
+        };
+        let int = Field::IntField;
+        // NULLs in a unique column are never duplicates, and refer to no parent row
+        assert!(insert(vec![
+            vec![int(1), Field::Null, int(0)],
+            vec![int(2), Field::Null, int(0)]
+        ])
+        .is_ok());
+        for row in [vec![Field::Null, int(1), int(0)], vec![int(3), int(1), Field::Null]] {
+            match insert(vec![row]) {
+                Err(CrustyError::ValidationError(msg)) => assert!(msg.contains("not-null")),
+                res => panic!("expected a validation error, got {:?}", res),
+            }
+        }
+        assert_eq!(2, scan(sm, &schema, tid).len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_text_lengths() -> Result<(), CrustyError> {
+        let (sm, _, tid) = setup();
+        let schema =
+            TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Varchar(3), DataType::String]);
+        let insert = |a: &str, b: &str| {
+            let row = Tuple::new(vec![
+                Field::StringField(a.to_string()),
+                Field::StringField(b.to_string()),
+            ]);
+            let src = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::String; 2]);
+            let child = rows(src, vec![row]);
+            let mut insert =
+                Insert::new(sm, table(&schema), 0, ForeignKeys::default(), vec![0, 1], tid, child);
+            insert.open().unwrap();
+            insert.next()
+        };
+        // Lengths count characters, not bytes, and text without a length can span pages
+        let long = "x".repeat(common::PAGE_SIZE * 3);
+        assert!(insert("äöü", &long).is_ok());
+        match insert("abcd", "") {
+            Err(CrustyError::ValidationError(msg)) => assert!(msg.contains("too long")),
+            res => panic!("expected a validation error, got {:?}", res),
+        }
+        let tuples = scan(sm, &schema, tid);
+        assert_eq!(1, tuples.len());
+        assert_eq!(Field::StringField(long), tuples[0].field_vals[1]);
+        Ok(())
+    }
+}
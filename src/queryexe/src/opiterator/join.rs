@@ -1,15 +1,67 @@
-use super::{OpIterator, TupleIterator};
+use super::OpIterator;
+use crate::StorageManager;
+use common::ids::{ContainerId, Permissions, TransactionId, ValueId};
+use common::storage_trait::StorageTrait;
+use common::table::Table;
 use common::{CrustyError, Field, SimplePredicateOp, TableSchema, Tuple};
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
 
 /// Compares the fields of two tuples using a predicate.
 pub struct JoinPredicate {
+    /// Operation to comapre the fields with.
+    op: SimplePredicateOp,
+    /// Index of the field of the left table (tuple).
+    left_index: usize,
+    /// Index of the field of the right table (tuple).
+    right_index: usize,
 }
 
+impl JoinPredicate {
+    /// Constructor that determines if two tuples satisfy the join condition.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Operation to compare the two fields with.
+    /// * `left_index` - Index of the field to compare in the left tuple.
+    /// * `right_index` - Index of the field to compare in the right tuple.
+    fn new(op: SimplePredicateOp, left_index: usize, right_index: usize) -> Self {
+        Self {
+            op,
+            left_index,
+            right_index,
+        }
+    }
+
+    /// Returns whether the pair of tuples satisfies the predicate. Comparisons with NULL never
+    /// do, so NULLs do not join.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - Tuple of the left child.
+    /// * `right` - Tuple of the right child.
+    fn matches(&self, left: &Tuple, right: &Tuple) -> bool {
+        let left_field = left.get_field(self.left_index).unwrap();
+        let right_field = right.get_field(self.right_index).unwrap();
+        self.op.compare(left_field, right_field).unwrap_or(false)
+    }
+}
 
 /// Nested loop join implementation.
 pub struct Join {
+    /// Join condition.
+    predicate: JoinPredicate,
+    /// Left child node.
+    left_child: Box<dyn OpIterator>,
+    /// Right child node.
+    right_child: Box<dyn OpIterator>,
+    /// Tuple of the left child being joined with the tuples of the right child.
+    current: Option<Tuple>,
     /// Schema of the result.
     schema: TableSchema,
+    /// Boolean determining if the iterator is open.
+    open: bool,
 }
 
 impl Join {
@@ -23,27 +75,74 @@ impl Join {
     /// * `left_child` - Left child of join operator.
     /// * `right_child` - Left child of join operator.
     pub fn new(
+        op: SimplePredicateOp,
+        left_index: usize,
+        right_index: usize,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
     ) -> Self {
-        panic!("TODO milestone op");
+        let schema = left_child.get_schema().merge(right_child.get_schema());
+        Self {
+            predicate: JoinPredicate::new(op, left_index, right_index),
+            left_child,
+            right_child,
+            current: None,
+            schema,
+            open: false,
+        }
     }
 }
 
 impl OpIterator for Join {
     fn open(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        self.left_child.open()?;
+        self.right_child.open()?;
+        self.current = None;
+        self.open = true;
+        Ok(())
     }
 
     /// Calculates the next tuple for a nested loop join.
     fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
-        panic!("TODO milestone op");
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        loop {
+            let left = match &self.current {
+                Some(left) => left,
+                None => match self.left_child.next()? {
+                    Some(left) => {
+                        self.right_child.rewind()?;
+                        self.current.insert(left)
+                    }
+                    None => return Ok(None),
+                },
+            };
+            while let Some(right) = self.right_child.next()? {
+                if self.predicate.matches(left, &right) {
+                    return Ok(Some(left.merge(&right)));
+                }
+            }
+            self.current = None;
+        }
     }
 
     fn close(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        self.left_child.close()?;
+        self.right_child.close()?;
+        self.current = None;
+        self.open = false;
+        Ok(())
     }
 
     fn rewind(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.left_child.rewind()?;
+        self.right_child.rewind()?;
+        self.current = None;
+        Ok(())
     }
 
     fn get_schema(&self) -> &TableSchema {
@@ -53,7 +152,22 @@ impl OpIterator for Join {
 
 /// Hash equi-join implementation.
 pub struct HashEqJoin {
+    /// Index of the join field in the left tuples.
+    left_index: usize,
+    /// Index of the join field in the right tuples.
+    right_index: usize,
+    /// Left child node, which probes the hash table.
+    left_child: Box<dyn OpIterator>,
+    /// Right child node, which the hash table is built from.
+    right_child: Box<dyn OpIterator>,
+    /// Tuples of the right child by their join field. Tuples with a NULL join field are left
+    /// out, as they match nothing.
+    table: HashMap<Field, Vec<Tuple>>,
+    /// Tuple of the left child being joined, and the number of its matches returned.
+    current: Option<(Tuple, usize)>,
     schema: TableSchema,
+    /// Boolean determining if the iterator is open.
+    open: bool,
 }
 
 impl HashEqJoin {
@@ -68,26 +182,228 @@ impl HashEqJoin {
     /// * `right_child` - Left child of join operator.
     #[allow(dead_code)]
     pub fn new(
+        op: SimplePredicateOp,
+        left_index: usize,
+        right_index: usize,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
     ) -> Self {
-        panic!("TODO milestone op");
+        debug_assert!(matches!(op, SimplePredicateOp::Equals));
+        let schema = left_child.get_schema().merge(right_child.get_schema());
+        Self {
+            left_index,
+            right_index,
+            left_child,
+            right_child,
+            table: HashMap::new(),
+            current: None,
+            schema,
+            open: false,
+        }
     }
 }
 
 impl OpIterator for HashEqJoin {
     fn open(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        self.left_child.open()?;
+        self.right_child.open()?;
+        self.table.clear();
+        while let Some(t) = self.right_child.next()? {
+            let key = t.get_field(self.right_index).unwrap();
+            if !key.is_null() {
+                self.table.entry(key.clone()).or_default().push(t);
+            }
+        }
+        self.current = None;
+        self.open = true;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        loop {
+            if let Some((left, returned)) = &mut self.current {
+                let key = left.get_field(self.left_index).unwrap();
+                if let Some(right) = self.table.get(key).and_then(|m| m.get(*returned)) {
+                    *returned += 1;
+                    return Ok(Some(left.merge(right)));
+                }
+            }
+            match self.left_child.next()? {
+                Some(left) => self.current = Some((left, 0)),
+                None => {
+                    self.current = None;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.left_child.close()?;
+        self.right_child.close()?;
+        self.table.clear();
+        self.current = None;
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.left_child.rewind()?;
+        self.current = None;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+/// Index nested-loop join implementation, which finds the rows of the right table that join
+/// each left tuple through an index on the right join field.
+pub struct IndexNestedLoopJoin {
+    /// Join condition.
+    predicate: JoinPredicate,
+    /// Left child node.
+    left_child: Box<dyn OpIterator>,
+    /// Right table, which the rows found are decoded with.
+    table: Table,
+    /// Container of the index, whose key starts with the right join field.
+    index_id: ContainerId,
+    storage_manager: &'static StorageManager,
+    transaction_id: TransactionId,
+    /// Tuple of the left child being joined, and the ids of the right rows found for it that
+    /// are left to read.
+    current: Option<(Tuple, std::vec::IntoIter<ValueId>)>,
+    /// Schema of the result.
+    schema: TableSchema,
+    /// Boolean determining if the iterator is open.
+    open: bool,
+}
+
+impl IndexNestedLoopJoin {
+    /// Constructor for an index nested-loop join operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Operation in join condition, with the left field on its left.
+    /// * `left_index` - Index of the left field in join condition.
+    /// * `right_index` - Index of the right field in join condition, in the right table.
+    /// * `left_child` - Left child of join operator.
+    /// * `right_schema` - Schema of the right tuples, as a scan of the right table gives them.
+    /// * `table` - Right table.
+    /// * `index_id` - Container of the index on the right table.
+    /// * `tid` - Transaction used to read the right table.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        storage_manager: &'static StorageManager,
+        op: SimplePredicateOp,
+        left_index: usize,
+        right_index: usize,
+        left_child: Box<dyn OpIterator>,
+        right_schema: &TableSchema,
+        table: Arc<RwLock<Table>>,
+        index_id: ContainerId,
+        tid: TransactionId,
+    ) -> Self {
+        let schema = left_child.get_schema().merge(right_schema);
+        Self {
+            predicate: JoinPredicate::new(op, left_index, right_index),
+            left_child,
+            table: table.read().unwrap().clone(),
+            index_id,
+            storage_manager,
+            transaction_id: tid,
+            current: None,
+            schema,
+            open: false,
+        }
+    }
+
+    /// Looks up the ids of the right rows a left tuple may join in the index. Only the first
+    /// column of the key is compared, so the rows must still be checked with the predicate.
+    fn find(&self, left: &Tuple) -> Result<Vec<ValueId>, CrustyError> {
+        let value = left.get_field(self.predicate.left_index).unwrap();
+        if value.is_null() {
+            return Ok(Vec::new());
+        }
+        let key = std::slice::from_ref(value);
+        let (lower, upper) = match self.predicate.op {
+            SimplePredicateOp::Equals => (Bound::Included(key), Bound::Included(key)),
+            SimplePredicateOp::LessThan => (Bound::Excluded(key), Bound::Unbounded),
+            SimplePredicateOp::LessThanOrEq => (Bound::Included(key), Bound::Unbounded),
+            SimplePredicateOp::GreaterThan => (Bound::Unbounded, Bound::Excluded(key)),
+            SimplePredicateOp::GreaterThanOrEq => (Bound::Unbounded, Bound::Included(key)),
+            op => {
+                return Err(CrustyError::ExecutionError(format!(
+                    "Cannot join with an index on {:?}",
+                    op
+                )))
+            }
+        };
+        self.storage_manager
+            .index_scan(self.index_id, lower, upper, self.transaction_id)
+    }
+}
+
+impl OpIterator for IndexNestedLoopJoin {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.left_child.open()?;
+        self.current = None;
+        self.open = true;
+        Ok(())
     }
 
     fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
-        panic!("TODO milestone op");
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        loop {
+            if let Some((left, ids)) = &mut self.current {
+                for id in ids.by_ref() {
+                    let bytes = self.storage_manager.get_value(
+                        id,
+                        self.transaction_id,
+                        Permissions::ReadOnly,
+                    )?;
+                    let right = self.table.decode(&bytes);
+                    if self.predicate.matches(left, &right) {
+                        return Ok(Some(left.merge(&right)));
+                    }
+                }
+            }
+            match self.left_child.next()? {
+                Some(left) => {
+                    let ids = self.find(&left)?;
+                    self.current = Some((left, ids.into_iter()));
+                }
+                None => {
+                    self.current = None;
+                    return Ok(None);
+                }
+            }
+        }
     }
 
     fn close(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        self.left_child.close()?;
+        self.current = None;
+        self.open = false;
+        Ok(())
     }
 
     fn rewind(&mut self) -> Result<(), CrustyError> {
-        panic!("TODO milestone op");
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.left_child.rewind()?;
+        self.current = None;
+        Ok(())
     }
 
     fn get_schema(&self) -> &TableSchema {
@@ -99,6 +415,8 @@ impl OpIterator for HashEqJoin {
 mod test {
     use super::*;
     use crate::opiterator::testutil::*;
+    use crate::opiterator::TupleIterator;
+    use common::index::{IndexMethod, SecondaryIndex};
     use common::testutil::*;
 
     const WIDTH1: usize = 2;
@@ -106,6 +424,7 @@ mod test {
     enum JoinType {
         NestedLoop,
         HashEq,
+        IndexNestedLoop,
     }
 
     pub fn scan1() -> TupleIterator {
@@ -126,6 +445,47 @@ mod test {
         TupleIterator::new(tuples, ts)
     }
 
+    /// Joins scan1 with the rows of scan2 stored in a table with an index on its first field.
+    pub fn index_join(
+        op: SimplePredicateOp,
+        left_index: usize,
+        right_index: usize,
+    ) -> IndexNestedLoopJoin {
+        let (cid, index_id) = (0, 1);
+        let schema = get_int_table_schema(WIDTH2);
+        let mut table = Table::new("scan2".to_string(), schema.clone());
+        table.add_secondary_index(SecondaryIndex {
+            name: "scan2_0".to_string(),
+            container_id: index_id,
+            columns: vec![0],
+            unique: false,
+            method: IndexMethod::BTree,
+            constraint: false,
+        });
+        let table = Arc::new(RwLock::new(table));
+        let sm: &'static StorageManager = Box::leak(Box::new(StorageManager::new_test_sm()));
+        sm.create_table(cid).unwrap();
+        sm.open_index(index_id, cid, table.clone()).unwrap();
+        let tid = TransactionId::new();
+        let mut rows = scan2();
+        rows.open().unwrap();
+        while let Some(t) = rows.next().unwrap() {
+            sm.insert_value(cid, table.read().unwrap().encode(&t), tid);
+        }
+        let s1 = Box::new(scan1());
+        IndexNestedLoopJoin::new(
+            sm,
+            op,
+            left_index,
+            right_index,
+            s1,
+            &schema,
+            table,
+            index_id,
+            tid,
+        )
+    }
+
     pub fn eq_join() -> TupleIterator {
         let tuples = create_tuple_list(vec![
             vec![1, 2, 1, 2, 3],
@@ -163,10 +523,9 @@ mod test {
         let s1 = Box::new(scan1());
         let s2 = Box::new(scan2());
         match ty {
-            JoinType::NestedLoop => Box::new(Join::new(
-            )),
-            JoinType::HashEq => Box::new(HashEqJoin::new(
-            )),
+            JoinType::NestedLoop => Box::new(Join::new(op, left_index, right_index, s1, s2)),
+            JoinType::HashEq => Box::new(HashEqJoin::new(op, left_index, right_index, s1, s2)),
+            JoinType::IndexNestedLoop => Box::new(index_join(op, left_index, right_index)),
         }
     }
 
@@ -284,4 +643,40 @@ mod test {
             test_eq_join(JoinType::HashEq)
         }
     }
+
+    mod index_join {
+        use super::*;
+
+        #[test]
+        fn get_schema() {
+            test_get_schema(JoinType::IndexNestedLoop);
+        }
+
+        #[test]
+        #[should_panic]
+        fn next_not_open() {
+            test_next_not_open(JoinType::IndexNestedLoop);
+        }
+
+        #[test]
+        #[should_panic]
+        fn rewind_not_open() {
+            test_rewind_not_open(JoinType::IndexNestedLoop);
+        }
+
+        #[test]
+        fn rewind() -> Result<(), CrustyError> {
+            test_rewind(JoinType::IndexNestedLoop)
+        }
+
+        #[test]
+        fn eq_join() -> Result<(), CrustyError> {
+            test_eq_join(JoinType::IndexNestedLoop)
+        }
+
+        #[test]
+        fn gt_join() -> Result<(), CrustyError> {
+            test_gt_join(JoinType::IndexNestedLoop)
+        }
+    }
 }
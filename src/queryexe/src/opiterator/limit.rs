@@ -0,0 +1,191 @@
+use super::OpIterator;
+use common::{CrustyError, TableSchema, Tuple};
+
+/// Limit operator, which skips the first tuples of its child and then produces at most a
+/// number of tuples. It stops reading from its child once it has produced them all.
+pub struct Limit {
+    /// Most tuples to produce, or None for all of them.
+    limit: Option<usize>,
+    /// Tuples to skip before producing any.
+    offset: usize,
+    /// Tuples produced since the operator was opened or rewound.
+    produced: usize,
+    /// Whether the offset has been skipped since the operator was opened or rewound.
+    skipped: bool,
+    open: bool,
+    child: Box<dyn OpIterator>,
+}
+
+impl Limit {
+    /// Limit constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Most tuples to produce, or None for all of them.
+    /// * `offset` - Tuples to skip before producing any.
+    /// * `child` - Child operator to get the tuples from.
+    pub fn new(limit: Option<usize>, offset: usize, child: Box<dyn OpIterator>) -> Self {
+        Self {
+            limit,
+            offset,
+            produced: 0,
+            skipped: false,
+            open: false,
+            child,
+        }
+    }
+}
+
+impl OpIterator for Limit {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.child.open()?;
+        self.produced = 0;
+        self.skipped = false;
+        self.open = true;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        if self.limit.is_some_and(|limit| self.produced >= limit) {
+            return Ok(None);
+        }
+        if !self.skipped {
+            for _ in 0..self.offset {
+                if self.child.next()?.is_none() {
+                    break;
+                }
+            }
+            self.skipped = true;
+        }
+        let tuple = self.child.next()?;
+        if tuple.is_some() {
+            self.produced += 1;
+        }
+        Ok(tuple)
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.child.close()?;
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.child.rewind()?;
+        self.produced = 0;
+        self.skipped = false;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        self.child.get_schema()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::TupleIterator;
+    use super::*;
+    use common::{DataType, Field};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Iterator that counts the tuples read from it.
+    struct Counted {
+        child: TupleIterator,
+        read: Arc<AtomicUsize>,
+    }
+
+    impl OpIterator for Counted {
+        fn open(&mut self) -> Result<(), CrustyError> {
+            self.child.open()
+        }
+
+        fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+            self.read.fetch_add(1, Ordering::Relaxed);
+            self.child.next()
+        }
+
+        fn close(&mut self) -> Result<(), CrustyError> {
+            self.child.close()
+        }
+
+        fn rewind(&mut self) -> Result<(), CrustyError> {
+            self.child.rewind()
+        }
+
+        fn get_schema(&self) -> &TableSchema {
+            self.child.get_schema()
+        }
+    }
+
+    /// Limits the numbers below n, returning the limit and the count of tuples read from its
+    /// child.
+    fn limit(limit: Option<usize>, offset: usize, n: i32) -> (Limit, Arc<AtomicUsize>) {
+        let schema = TableSchema::from_vecs(vec!["a"], vec![DataType::Int]);
+        let tuples = (0..n)
+            .map(|a| Tuple::new(vec![Field::IntField(a)]))
+            .collect();
+        let read = Arc::new(AtomicUsize::new(0));
+        let child = Counted {
+            child: TupleIterator::new(tuples, schema),
+            read: read.clone(),
+        };
+        (Limit::new(limit, offset, Box::new(child)), read)
+    }
+
+    fn read_all(limit: &mut Limit) -> Result<Vec<i32>, CrustyError> {
+        let mut values = Vec::new();
+        while let Some(tuple) = limit.next()? {
+            match tuple.field_vals[0] {
+                Field::IntField(a) => values.push(a),
+                _ => panic!("Expected an int"),
+            }
+        }
+        Ok(values)
+    }
+
+    #[test]
+    fn test_limit_offset() -> Result<(), CrustyError> {
+        let (mut op, _) = limit(Some(3), 2, 10);
+        op.open()?;
+        assert_eq!(vec![2, 3, 4], read_all(&mut op)?);
+        op.rewind()?;
+        assert_eq!(vec![2, 3, 4], read_all(&mut op)?);
+        op.close()
+    }
+
+    #[test]
+    fn test_limit_stops_early() -> Result<(), CrustyError> {
+        let (mut op, read) = limit(Some(3), 0, 1000);
+        op.open()?;
+        assert_eq!(vec![0, 1, 2], read_all(&mut op)?);
+        assert_eq!(3, read.load(Ordering::Relaxed));
+        op.close()
+    }
+
+    #[test]
+    fn test_offset_only() -> Result<(), CrustyError> {
+        let (mut op, _) = limit(None, 7, 10);
+        op.open()?;
+        assert_eq!(vec![7, 8, 9], read_all(&mut op)?);
+        op.close()?;
+
+        let (mut op, _) = limit(Some(5), 20, 10);
+        op.open()?;
+        assert!(read_all(&mut op)?.is_empty());
+        op.close()
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        limit(Some(1), 0, 1).0.next().unwrap();
+    }
+}
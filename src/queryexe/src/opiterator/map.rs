@@ -0,0 +1,126 @@
+use super::OpIterator;
+use common::logical_plan::ValueExpr;
+use common::{Attribute, CrustyError, TableSchema, Tuple};
+
+/// Map operator. Appends columns computed from the fields of each tuple of its child.
+pub struct Map {
+    /// Expressions computing the appended columns.
+    exprs: Vec<ValueExpr>,
+    /// Schema of the child followed by the appended columns.
+    schema: TableSchema,
+    open: bool,
+    child: Box<dyn OpIterator>,
+}
+
+impl Map {
+    /// Map constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - Columns to append and the expressions computing them from the child's fields.
+    /// * `child` - Child operator to get the tuples from.
+    pub fn new(columns: Vec<(Attribute, ValueExpr)>, child: Box<dyn OpIterator>) -> Self {
+        let mut attrs: Vec<Attribute> = child.get_schema().attributes().cloned().collect();
+        let mut exprs = Vec::new();
+        for (attr, expr) in columns {
+            attrs.push(attr);
+            exprs.push(expr);
+        }
+        Self {
+            exprs,
+            schema: TableSchema::new(attrs),
+            open: false,
+            child,
+        }
+    }
+}
+
+impl OpIterator for Map {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.child.open()?;
+        self.open = true;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        match self.child.next()? {
+            Some(tuple) => {
+                let mut fields = tuple.field_vals.clone();
+                for expr in &self.exprs {
+                    fields.push(expr.eval(&tuple.field_vals)?);
+                }
+                Ok(Some(Tuple::new(fields)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.child.close()?;
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.child.rewind()
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::TupleIterator;
+    use super::*;
+    use common::logical_plan::ArithOp;
+    use common::temporal::{parse_date, TimeUnit};
+    use common::{DataType, Field};
+
+    #[test]
+    fn test_next() -> Result<(), CrustyError> {
+        let date = |s| Field::DateField(parse_date(s).unwrap());
+        let schema = TableSchema::from_vecs(vec!["d", "n"], vec![DataType::Date, DataType::Int]);
+        let tuples = vec![
+            Tuple::new(vec![date("2021-03-15"), Field::IntField(20)]),
+            Tuple::new(vec![Field::Null, Field::IntField(1)]),
+        ];
+        let columns = vec![
+            (
+                Attribute::new("month".to_string(), DataType::Int),
+                ValueExpr::Extract(TimeUnit::Month, Box::new(ValueExpr::Column(0))),
+            ),
+            (
+                Attribute::new("later".to_string(), DataType::Date),
+                ValueExpr::Arith(
+                    Box::new(ValueExpr::Column(0)),
+                    ArithOp::Plus,
+                    Box::new(ValueExpr::Column(1)),
+                ),
+            ),
+        ];
+        let mut map = Map::new(columns, Box::new(TupleIterator::new(tuples, schema)));
+        assert_eq!(Some(&3), map.get_schema().get_field_index("later"));
+        map.open()?;
+        let expected = vec![
+            date("2021-03-15"),
+            Field::IntField(20),
+            Field::IntField(3),
+            date("2021-04-04"),
+        ];
+        assert_eq!(expected, map.next()?.unwrap().field_vals);
+        let expected = vec![Field::Null, Field::IntField(1), Field::Null, Field::Null];
+        assert_eq!(expected, map.next()?.unwrap().field_vals);
+        assert_eq!(None, map.next()?);
+        map.rewind()?;
+        assert!(map.next()?.is_some());
+        map.close()
+    }
+}
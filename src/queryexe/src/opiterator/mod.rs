@@ -1,18 +1,38 @@
 pub use self::aggregate::Aggregate;
+pub use self::delete::Delete;
+pub use self::distinct::Distinct;
 pub use self::filter::{Filter, FilterPredicate};
-pub use self::join::{HashEqJoin, Join, JoinPredicate};
+pub use self::index_scan::IndexScan;
+pub use self::insert::Insert;
+pub use self::join::{HashEqJoin, IndexNestedLoopJoin, Join, JoinPredicate};
+pub use self::limit::Limit;
+pub use self::map::Map;
 pub use self::project::ProjectIterator;
 pub use self::seqscan::SeqScan;
+pub use self::sort::{Sort, SortField};
+pub use self::topn::TopN;
 pub use self::tuple_iterator::TupleIterator;
+pub use self::update::Update;
+use common::ids::ValueId;
 use common::{CrustyError, TableSchema, Tuple};
 
 mod aggregate;
+mod delete;
+mod distinct;
 mod filter;
+mod index_scan;
+mod insert;
 mod join;
+mod limit;
+mod map;
 mod project;
 mod seqscan;
+mod sort;
+mod spill;
 mod testutil;
+mod topn;
 mod tuple_iterator;
+mod update;
 
 pub trait OpIterator {
     /// Opens the iterator. This must be called before any of the other methods.
@@ -41,4 +61,19 @@ pub trait OpIterator {
 
     /// Returns the schema associated with this OpIterator.
     fn get_schema(&self) -> &TableSchema;
+
+    /// Returns the id of the stored record the last tuple returned by next was read from.
+    ///
+    /// Only operators that pass base-table tuples through unchanged have one.
+    fn last_value_id(&self) -> Option<ValueId> {
+        None
+    }
+
+    /// Tells the iterator that only some columns of its tuples are used, so it may leave the
+    /// others NULL. Scans then read only those columns from the stored rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - Columns used.
+    fn read_columns(&mut self, _columns: &[usize]) {}
 }
@@ -10,19 +10,21 @@ pub struct ProjectIterator {
 }
 
 impl ProjectIterator {
-    /// Constructor for the projection operator without aliases.
+    /// Constructor for the projection operator without aliases. The child is told to read
+    /// only the projected columns.
     ///
     /// # Arguments
     ///
     /// * `fields` - Columns to project.
     /// * `child` - Child nodes to get data from.
-    pub fn new(fields: Vec<usize>, child: Box<dyn OpIterator>) -> Self {
+    pub fn new(fields: Vec<usize>, mut child: Box<dyn OpIterator>) -> Self {
         let mut attributes = Vec::new();
         for i in &fields {
             let attr = child.get_schema().get_attribute(*i).unwrap();
             attributes.push(attr.clone());
         }
         let schema = TableSchema::new(attributes);
+        child.read_columns(&fields);
         Self {
             fields,
             open: false,
@@ -31,7 +33,8 @@ impl ProjectIterator {
         }
     }
 
-    /// Constructor for the projection operator with aliases.
+    /// Constructor for the projection operator with aliases. The child is told to read only
+    /// the projected columns.
     ///
     /// # Arguments
     ///
@@ -45,7 +48,7 @@ impl ProjectIterator {
     pub fn new_with_aliases(
         fields: Vec<usize>,
         field_names: Vec<&str>,
-        child: Box<dyn OpIterator>,
+        mut child: Box<dyn OpIterator>,
     ) -> Self {
         let mut attributes = Vec::new();
         let child_schema = child.get_schema();
@@ -55,6 +58,7 @@ impl ProjectIterator {
             attributes.push(attr);
         }
         let schema = TableSchema::new(attributes);
+        child.read_columns(&fields);
         Self {
             fields,
             open: false,
@@ -109,6 +113,11 @@ impl OpIterator for ProjectIterator {
     fn get_schema(&self) -> &TableSchema {
         &self.schema
     }
+
+    fn read_columns(&mut self, columns: &[usize]) {
+        let columns: Vec<usize> = columns.iter().map(|i| self.fields[*i]).collect();
+        self.child.read_columns(&columns);
+    }
 }
 
 #[cfg(test)]
@@ -1,8 +1,8 @@
 use super::OpIterator;
 use crate::StorageManager;
 use common::ids::Permissions;
-use common::ids::{ContainerId, TransactionId};
-use common::storage_trait::StorageTrait;
+use common::ids::{ContainerId, TransactionId, ValueId};
+use common::storage_trait::{StorageTrait, ValueIdIterator};
 use common::table::*;
 use common::{Attribute, CrustyError, TableSchema, Tuple};
 use std::sync::{Arc, RwLock};
@@ -10,7 +10,11 @@ use std::sync::{Arc, RwLock};
 /// Sequential scan operator
 pub struct SeqScan {
     file_iter: <StorageManager as StorageTrait>::ValIterator,
+    /// Table scanned, which upgrades the rows read to its current schema.
+    table: Table,
     schema: TableSchema,
+    /// Columns read from each row, or None to read all of them.
+    columns: Option<Vec<usize>>,
     open: bool,
     storage_manager: &'static StorageManager,
     container_id: ContainerId,
@@ -32,12 +36,13 @@ impl SeqScan {
         container_id: &ContainerId,
         tid: TransactionId,
     ) -> Self {
-        let table_ref = table.read().unwrap();
-        let schema = table_ref.schema.clone();
+        let table = table.read().unwrap().clone();
         let file_iter = storage_manager.get_iterator(*container_id, tid, Permissions::ReadOnly);
         Self {
             file_iter,
-            schema: Self::schema(&schema, table_alias),
+            schema: Self::schema(&table.schema, table_alias),
+            table,
+            columns: None,
             open: false,
             storage_manager,
             container_id: *container_id,
@@ -50,7 +55,7 @@ impl SeqScan {
     /// # Arguments
     /// * `src_schema` - Schema of the source.
     /// * `alias` - Alias of the table.
-    fn schema(src_schema: &TableSchema, alias: &str) -> TableSchema {
+    pub(super) fn schema(src_schema: &TableSchema, alias: &str) -> TableSchema {
         let mut attrs = Vec::new();
         for a in src_schema.attributes() {
             let new_name = format!("{}.{}", alias, a.name());
@@ -75,8 +80,16 @@ impl OpIterator for SeqScan {
             panic!("Operator has not been opened")
         }
         match self.file_iter.next() {
-            Some(bytes) => Ok(Some(Tuple::from_bytes(&bytes))),
-            None => Ok(None),
+            Some(bytes) => Ok(Some(match &self.columns {
+                Some(columns) => self.table.decode_columns(&bytes, columns),
+                None => self.table.decode(&bytes),
+            })),
+            // The scan also ends early if the storage manager aborted the transaction
+            None => {
+                self.storage_manager
+                    .check_transaction(self.transaction_id)?;
+                Ok(None)
+            }
         }
     }
 
@@ -100,6 +113,14 @@ impl OpIterator for SeqScan {
     fn get_schema(&self) -> &TableSchema {
         &self.schema
     }
+
+    fn last_value_id(&self) -> Option<ValueId> {
+        self.file_iter.last_value_id()
+    }
+
+    fn read_columns(&mut self, columns: &[usize]) {
+        self.columns = Some(columns.to_vec());
+    }
 }
 
 #[cfg(test)]
@@ -107,8 +128,10 @@ impl OpIterator for SeqScan {
 mod test {
     use super::*;
     use crate::opiterator::testutil::sum_int_fields;
+    use crate::opiterator::{Filter, ProjectIterator};
     use common::ids::TransactionId;
     use common::testutil::get_int_table_schema;
+    use common::{Field, SimplePredicateOp};
 
     use common::testutil::*;
 
@@ -193,6 +216,41 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_read_columns() -> Result<(), CrustyError> {
+        let table = Table::new(TABLE.to_string(), get_int_table_schema(WIDTH));
+        let sm: &'static StorageManager = Box::leak(Box::new(StorageManager::new_test_sm()));
+        sm.create_table(0)?;
+        let tid = TransactionId::new();
+        for i in 0..3 {
+            sm.insert_value(
+                0,
+                table.encode(&int_vec_to_tuple(vec![i, i + 1, i + 2])),
+                tid,
+            );
+        }
+        let table = Arc::new(RwLock::new(table));
+
+        // Only the columns projected and filtered on are read
+        let mut scan = SeqScan::new(sm, table.clone(), TABLE, &0, tid);
+        scan.read_columns(&[2]);
+        scan.open()?;
+        let expected = Tuple::new(vec![Field::Null, Field::Null, Field::IntField(2)]);
+        assert_eq!(Some(expected), scan.next()?);
+        let scan = Box::new(SeqScan::new(sm, table, TABLE, &0, tid));
+        let filter = Box::new(Filter::new(
+            SimplePredicateOp::Equals,
+            0,
+            Field::IntField(1),
+            scan,
+        ));
+        let mut project = ProjectIterator::new(vec![2], filter);
+        project.open()?;
+        assert_eq!(Some(int_vec_to_tuple(vec![3])), project.next()?);
+        assert_eq!(None, project.next()?);
+        Ok(())
+    }
+
     #[test]
     fn test_get_schema() {
         let scan = get_scan().unwrap();
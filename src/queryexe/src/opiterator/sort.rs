@@ -0,0 +1,386 @@
+use super::spill::{memory_size, SpillFile, SpillReader, SpillWriter};
+use super::OpIterator;
+use common::{CrustyError, TableSchema, Tuple};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+/// Most runs merged at once. When there are more, the first runs are merged into one first.
+const MAX_MERGE_RUNS: usize = 64;
+
+/// A field to sort the tuples by, and the order to sort its values in.
+#[derive(Clone, Debug)]
+pub struct SortField {
+    /// Index of the field.
+    pub field: usize,
+    /// Whether the values go from the highest to the lowest.
+    pub descending: bool,
+    /// Whether NULLs come before the other values rather than after them.
+    pub nulls_first: bool,
+}
+
+/// Compares two tuples by the fields to sort them by, comparing values as SQL does.
+pub(super) fn compare(fields: &[SortField], left: &Tuple, right: &Tuple) -> Ordering {
+    for sort in fields {
+        let (l, r) = (&left.field_vals[sort.field], &right.field_vals[sort.field]);
+        let null_first = if sort.nulls_first {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        };
+        let ordering = match (l.is_null(), r.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => null_first,
+            (false, true) => null_first.reverse(),
+            (false, false) if sort.descending => r.compare(l).unwrap_or(Ordering::Equal),
+            (false, false) => l.compare(r).unwrap_or(Ordering::Equal),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Writes the tuples a function gives, in order, to a new sorted run.
+///
+/// # Arguments
+///
+/// * `next` - Gives the next tuple, or None once there are no more.
+fn write_run(
+    mut next: impl FnMut() -> Result<Option<Tuple>, CrustyError>,
+) -> Result<SpillFile, CrustyError> {
+    let mut writer = SpillWriter::new("sort")?;
+    while let Some(tuple) = next()? {
+        writer.write(&tuple)?;
+    }
+    writer.finish()
+}
+
+/// The next tuple of a run being merged. Heads are ordered so that the heap of them gives the
+/// first tuple, taking it from the earliest run on ties.
+struct Head {
+    tuple: Tuple,
+    run: usize,
+    fields: Arc<[SortField]>,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(&self.fields, &other.tuple, &self.tuple).then(other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+/// Merges sorted runs into one sorted stream of tuples.
+struct Merge {
+    readers: Vec<SpillReader>,
+    heads: BinaryHeap<Head>,
+    fields: Arc<[SortField]>,
+}
+
+impl Merge {
+    fn new(runs: &[SpillFile], fields: Arc<[SortField]>) -> Result<Self, CrustyError> {
+        let mut merge = Merge {
+            readers: runs
+                .iter()
+                .map(SpillFile::reader)
+                .collect::<Result<_, _>>()?,
+            heads: BinaryHeap::with_capacity(runs.len()),
+            fields,
+        };
+        for run in 0..runs.len() {
+            merge.advance(run)?;
+        }
+        Ok(merge)
+    }
+
+    /// Reads the next tuple of a run into the heads.
+    fn advance(&mut self, run: usize) -> Result<(), CrustyError> {
+        if let Some(tuple) = self.readers[run].next()? {
+            let fields = self.fields.clone();
+            self.heads.push(Head { tuple, run, fields });
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        match self.heads.pop() {
+            Some(head) => {
+                self.advance(head.run)?;
+                Ok(Some(head.tuple))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// The tuples of the child, sorted.
+enum Sorted {
+    /// All the tuples, in memory, and the position of the next one.
+    Memory { tuples: Vec<Tuple>, position: usize },
+    /// Runs holding the tuples, and their merge.
+    Runs { runs: Vec<SpillFile>, merge: Merge },
+}
+
+/// Sort operator, which orders the tuples of its child by some of their fields. Tuples are
+/// sorted in memory until they take more than the memory budget, at which point they are
+/// spilled to a temporary file as a sorted run. The runs are then merged as the sorted
+/// tuples are read, so that a sort can order more tuples than fit in memory. Tuples whose
+/// fields compare equal keep the order of the child.
+pub struct Sort {
+    /// Fields to sort by, the first compared first.
+    fields: Arc<[SortField]>,
+    /// Bytes of tuples held in memory before they are spilled as a run.
+    budget: usize,
+    schema: TableSchema,
+    open: bool,
+    child: Box<dyn OpIterator>,
+    /// The sorted tuples, found when the operator is opened.
+    sorted: Option<Sorted>,
+}
+
+impl Sort {
+    /// Sort constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Fields to sort by, the first compared first.
+    /// * `budget` - Bytes of tuples to hold in memory before spilling them to disk.
+    /// * `child` - Child operator to get the tuples from.
+    pub fn new(fields: Vec<SortField>, budget: usize, child: Box<dyn OpIterator>) -> Self {
+        Self {
+            fields: fields.into(),
+            budget,
+            schema: child.get_schema().clone(),
+            open: false,
+            child,
+            sorted: None,
+        }
+    }
+
+    /// Sorts the tuples of a run in memory and spills them to disk.
+    fn spill(&self, tuples: &mut Vec<Tuple>) -> Result<SpillFile, CrustyError> {
+        tuples.sort_by(|l, r| compare(&self.fields, l, r));
+        let mut tuples = tuples.drain(..);
+        write_run(|| Ok(tuples.next()))
+    }
+
+    /// Reads the tuples of the child and sorts them.
+    fn sort(&mut self) -> Result<Sorted, CrustyError> {
+        let mut runs = Vec::new();
+        let mut tuples = Vec::new();
+        let mut size = 0;
+        while let Some(tuple) = self.child.next()? {
+            size += memory_size(&tuple);
+            tuples.push(tuple);
+            if size > self.budget {
+                runs.push(self.spill(&mut tuples)?);
+                size = 0;
+            }
+        }
+        if runs.is_empty() {
+            tuples.sort_by(|l, r| compare(&self.fields, l, r));
+            return Ok(Sorted::Memory {
+                tuples,
+                position: 0,
+            });
+        }
+        if !tuples.is_empty() {
+            runs.push(self.spill(&mut tuples)?);
+        }
+        // The first runs are merged into one until the rest can be merged at once
+        while runs.len() > MAX_MERGE_RUNS {
+            let first: Vec<SpillFile> = runs.drain(..MAX_MERGE_RUNS).collect();
+            let mut merge = Merge::new(&first, self.fields.clone())?;
+            runs.insert(0, write_run(|| merge.next())?);
+        }
+        let merge = Merge::new(&runs, self.fields.clone())?;
+        Ok(Sorted::Runs { runs, merge })
+    }
+}
+
+impl OpIterator for Sort {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.child.open()?;
+        self.sorted = Some(self.sort()?);
+        self.open = true;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        match self.sorted.as_mut().unwrap() {
+            Sorted::Memory { tuples, position } => {
+                let tuple = tuples.get(*position).cloned();
+                *position += 1;
+                Ok(tuple)
+            }
+            Sorted::Runs { merge, .. } => merge.next(),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.child.close()?;
+        self.sorted = None;
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        match self.sorted.as_mut().unwrap() {
+            Sorted::Memory { position, .. } => *position = 0,
+            Sorted::Runs { runs, merge } => *merge = Merge::new(runs, self.fields.clone())?,
+        }
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::TupleIterator;
+    use super::*;
+    use common::{DataType, Field};
+    use std::path::PathBuf;
+
+    fn sort_field(field: usize, descending: bool) -> SortField {
+        SortField {
+            field,
+            descending,
+            nulls_first: descending,
+        }
+    }
+
+    /// Tuples of (a, b) where a counts up and b cycles through 0..7 with every fourth b NULL.
+    fn child(n: i32) -> Box<dyn OpIterator> {
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::Int]);
+        let tuples = (0..n)
+            .map(|a| {
+                let b = match a % 4 {
+                    3 => Field::Null,
+                    _ => Field::IntField(a * 5 % 7),
+                };
+                Tuple::new(vec![Field::IntField(a), b])
+            })
+            .collect();
+        Box::new(TupleIterator::new(tuples, schema))
+    }
+
+    fn read_all(sort: &mut Sort) -> Result<Vec<Tuple>, CrustyError> {
+        let mut tuples = Vec::new();
+        while let Some(tuple) = sort.next()? {
+            tuples.push(tuple);
+        }
+        Ok(tuples)
+    }
+
+    /// Checks that tuples are ordered by b and then a, descending where asked.
+    fn assert_sorted(tuples: &[Tuple], n: usize, b_descending: bool, a_descending: bool) {
+        assert_eq!(n, tuples.len());
+        for pair in tuples.windows(2) {
+            let (l, r) = (&pair[0].field_vals, &pair[1].field_vals);
+            let ordering = match (&l[1], &r[1]) {
+                (Field::Null, Field::Null) => Ordering::Equal,
+                (Field::Null, _) => Ordering::Greater,
+                (_, Field::Null) => Ordering::Less,
+                (l, r) => l.cmp(r),
+            };
+            let ordering = if b_descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            let a = if a_descending {
+                r[0].cmp(&l[0])
+            } else {
+                l[0].cmp(&r[0])
+            };
+            assert_eq!(Ordering::Less, ordering.then(a), "{:?} {:?}", l, r);
+        }
+    }
+
+    #[test]
+    fn test_sort_in_memory() -> Result<(), CrustyError> {
+        let fields = vec![sort_field(1, false), sort_field(0, true)];
+        let mut sort = Sort::new(fields, usize::MAX, child(100));
+        sort.open()?;
+        assert!(matches!(sort.sorted, Some(Sorted::Memory { .. })));
+        let tuples = read_all(&mut sort)?;
+        assert_sorted(&tuples, 100, false, true);
+        assert!(tuples[99].field_vals[1].is_null());
+        sort.rewind()?;
+        assert_eq!(tuples, read_all(&mut sort)?);
+        sort.close()
+    }
+
+    #[test]
+    fn test_sort_nulls_first() -> Result<(), CrustyError> {
+        let fields = vec![sort_field(1, true), sort_field(0, false)];
+        let mut sort = Sort::new(fields, usize::MAX, child(20));
+        sort.open()?;
+        let tuples = read_all(&mut sort)?;
+        assert_sorted(&tuples, 20, true, false);
+        assert!(tuples[0].field_vals[1].is_null());
+        sort.close()
+    }
+
+    #[test]
+    fn test_sort_spills() -> Result<(), CrustyError> {
+        // A tuple over the budget spills each run, needing merges before the last one
+        let fields = vec![sort_field(1, true), sort_field(0, false)];
+        let mut sort = Sort::new(fields, 0, child(1000));
+        sort.open()?;
+        let paths: Vec<PathBuf> = match &sort.sorted {
+            Some(Sorted::Runs { runs, .. }) => runs.iter().map(|r| r.path.clone()).collect(),
+            _ => panic!("Expected the tuples to be spilled"),
+        };
+        assert!(paths.len() <= MAX_MERGE_RUNS);
+        let tuples = read_all(&mut sort)?;
+        assert_sorted(&tuples, 1000, true, false);
+        sort.rewind()?;
+        assert_eq!(tuples, read_all(&mut sort)?);
+
+        // The runs are removed once the sort is closed
+        assert!(paths.iter().all(|path| path.exists()));
+        sort.close()?;
+        assert!(paths.iter().all(|path| !path.exists()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_empty() -> Result<(), CrustyError> {
+        let mut sort = Sort::new(vec![sort_field(0, false)], 0, child(0));
+        sort.open()?;
+        assert_eq!(None, sort.next()?);
+        sort.close()
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        let mut sort = Sort::new(vec![sort_field(0, false)], 0, child(1));
+        sort.next().unwrap();
+    }
+}
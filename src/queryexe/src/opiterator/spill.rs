@@ -0,0 +1,97 @@
+use common::{CrustyError, Field, Tuple};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::mem::size_of;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of files spilled to by the process, which names them.
+static FILES_SPILLED: AtomicUsize = AtomicUsize::new(0);
+
+/// Bytes a tuple takes in memory, which operators holding tuples count against their budget.
+pub(super) fn memory_size(tuple: &Tuple) -> usize {
+    let fields: usize = tuple
+        .field_vals
+        .iter()
+        .map(|field| match field {
+            Field::StringField(s) => size_of::<Field>() + s.capacity(),
+            _ => size_of::<Field>(),
+        })
+        .sum();
+    size_of::<Tuple>() + fields
+}
+
+/// Temporary file that tuples which do not fit in memory are spilled to, each preceded by its
+/// length. The file is removed when dropped.
+pub(super) struct SpillFile {
+    pub(super) path: PathBuf,
+}
+
+impl SpillFile {
+    pub(super) fn reader(&self) -> Result<SpillReader, CrustyError> {
+        Ok(SpillReader {
+            reader: BufReader::new(File::open(&self.path)?),
+        })
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Writes tuples to a new spill file.
+pub(super) struct SpillWriter {
+    file: SpillFile,
+    writer: BufWriter<File>,
+}
+
+impl SpillWriter {
+    /// Creates a spill file in the temporary directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `operator` - Name of the operator spilling, which prefixes the name of the file.
+    pub(super) fn new(operator: &str) -> Result<Self, CrustyError> {
+        let n = FILES_SPILLED.fetch_add(1, Ordering::Relaxed);
+        let name = format!("crusty_{}_{}_{}", operator, std::process::id(), n);
+        let file = SpillFile {
+            path: std::env::temp_dir().join(name),
+        };
+        let writer = BufWriter::new(File::create(&file.path)?);
+        Ok(Self { file, writer })
+    }
+
+    pub(super) fn write(&mut self, tuple: &Tuple) -> Result<(), CrustyError> {
+        let bytes = tuple.get_bytes();
+        self.writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Flushes the tuples written and returns the file to read them from.
+    pub(super) fn finish(mut self) -> Result<SpillFile, CrustyError> {
+        self.writer.flush()?;
+        Ok(self.file)
+    }
+}
+
+/// Reads the tuples of a spill file in the order they were written.
+pub(super) struct SpillReader {
+    reader: BufReader<File>,
+}
+
+impl SpillReader {
+    pub(super) fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        let mut len = [0; 4];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(Some(Tuple::from_bytes(&bytes)))
+    }
+}
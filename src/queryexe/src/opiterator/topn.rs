@@ -0,0 +1,206 @@
+use super::sort::{compare, SortField};
+use super::OpIterator;
+use common::{CrustyError, TableSchema, Tuple};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+/// A tuple kept by a top-N, and the order it came from the child in. Entries are ordered as
+/// the tuples sort, later entries after earlier ones on ties, so that the top of the heap of
+/// them is the entry to drop first.
+struct Entry {
+    tuple: Tuple,
+    seq: usize,
+    fields: Arc<[SortField]>,
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(&self.fields, &self.tuple, &other.tuple).then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+/// Top-N operator, which produces the first tuples of its child as a sort would order them.
+/// Rather than sorting all the tuples, it keeps the first ones seen so far in a heap bounded
+/// by the limit, so it holds at most that many tuples in memory.
+pub struct TopN {
+    /// Fields to sort by, the first compared first.
+    fields: Arc<[SortField]>,
+    /// Number of first tuples to produce.
+    limit: usize,
+    open: bool,
+    child: Box<dyn OpIterator>,
+    /// The first tuples, sorted, found when the operator is opened.
+    tuples: Vec<Tuple>,
+    /// Position of the next tuple to produce.
+    position: usize,
+}
+
+impl TopN {
+    /// TopN constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Fields to sort by, the first compared first.
+    /// * `limit` - Number of first tuples to produce.
+    /// * `child` - Child operator to get the tuples from.
+    pub fn new(fields: Vec<SortField>, limit: usize, child: Box<dyn OpIterator>) -> Self {
+        Self {
+            fields: fields.into(),
+            limit,
+            open: false,
+            child,
+            tuples: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Reads the tuples of the child and keeps the first ones.
+    fn top(&mut self) -> Result<Vec<Tuple>, CrustyError> {
+        let mut heap = BinaryHeap::with_capacity(self.limit.min(1024) + 1);
+        let mut seq = 0;
+        while let Some(tuple) = self.child.next()? {
+            let entry = Entry {
+                tuple,
+                seq,
+                fields: self.fields.clone(),
+            };
+            seq += 1;
+            if heap.len() < self.limit {
+                heap.push(entry);
+            } else if heap.peek().is_some_and(|last| entry < *last) {
+                heap.pop();
+                heap.push(entry);
+            }
+        }
+        Ok(heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|entry| entry.tuple)
+            .collect())
+    }
+}
+
+impl OpIterator for TopN {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.child.open()?;
+        self.tuples = self.top()?;
+        self.position = 0;
+        self.open = true;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        let tuple = self.tuples.get(self.position).cloned();
+        self.position += 1;
+        Ok(tuple)
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.child.close()?;
+        self.tuples.clear();
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.position = 0;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        self.child.get_schema()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Sort, TupleIterator};
+    use super::*;
+    use common::{DataType, Field};
+
+    /// Tuples of (a, b) where a counts up and b cycles through a few values, some NULL.
+    fn child(n: i32) -> Box<dyn OpIterator> {
+        let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::Int, DataType::Int]);
+        let tuples = (0..n)
+            .map(|a| {
+                let b = match a % 5 {
+                    4 => Field::Null,
+                    _ => Field::IntField(a * 3 % 11),
+                };
+                Tuple::new(vec![Field::IntField(a), b])
+            })
+            .collect();
+        Box::new(TupleIterator::new(tuples, schema))
+    }
+
+    fn read_all(op: &mut dyn OpIterator) -> Result<Vec<Tuple>, CrustyError> {
+        let mut tuples = Vec::new();
+        while let Some(tuple) = op.next()? {
+            tuples.push(tuple);
+        }
+        Ok(tuples)
+    }
+
+    /// Checks that a top-N gives the first tuples a sort gives, ties included.
+    fn assert_top(fields: Vec<SortField>, limit: usize, n: i32) -> Result<(), CrustyError> {
+        let mut sort = Sort::new(fields.clone(), usize::MAX, child(n));
+        sort.open()?;
+        let mut sorted = read_all(&mut sort)?;
+        sorted.truncate(limit);
+        sort.close()?;
+
+        let mut top = TopN::new(fields, limit, child(n));
+        top.open()?;
+        assert_eq!(sorted, read_all(&mut top)?);
+        top.rewind()?;
+        assert_eq!(sorted, read_all(&mut top)?);
+        top.close()
+    }
+
+    #[test]
+    fn test_top_n() -> Result<(), CrustyError> {
+        let by_b = |descending| SortField {
+            field: 1,
+            descending,
+            nulls_first: descending,
+        };
+        // Many tuples tie on b alone, so these also check ties keep the order of the child
+        assert_top(vec![by_b(false)], 10, 200)?;
+        assert_top(vec![by_b(true)], 10, 200)?;
+        assert_top(vec![by_b(false)], 500, 200)?;
+        assert_top(vec![by_b(false)], 0, 200)?;
+        assert_top(vec![by_b(true)], 3, 0)
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        let fields = vec![SortField {
+            field: 0,
+            descending: false,
+            nulls_first: false,
+        }];
+        TopN::new(fields, 1, child(1)).next().unwrap();
+    }
+}
@@ -0,0 +1,531 @@
+use super::OpIterator;
+use crate::StorageManager;
+use common::foreign_key::ForeignKeys;
+use common::ids::{ContainerId, TransactionId, ValueId};
+use common::logical_plan::ColumnAssignment;
+use common::storage_trait::StorageTrait;
+use common::table::Table;
+use common::{Attribute, CrustyError, DataType, Field, TableSchema, Tuple};
+
+/// Update operator. Rewrites every record its child reads from a table and returns a
+/// single tuple holding the number of rows updated.
+pub struct Update {
+    storage_manager: &'static StorageManager,
+    /// Table updated.
+    table: Table,
+    container_id: ContainerId,
+    /// Foreign keys of the table and those referring to it.
+    foreign_keys: ForeignKeys,
+    transaction_id: TransactionId,
+    /// New values of the columns set by the update.
+    assignments: Vec<ColumnAssignment>,
+    /// Number of rows updated, once the child has been drained.
+    count: Option<i32>,
+    /// Whether the count has been returned since opening or rewinding.
+    returned: bool,
+    /// Schema of the output.
+    schema: TableSchema,
+    open: bool,
+    /// Source of the tuples to update, which must come from a scan of the table.
+    child: Box<dyn OpIterator>,
+}
+
+impl Update {
+    /// Constructor for the update operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - Table to update.
+    /// * `container_id` - Container of the table.
+    /// * `foreign_keys` - Foreign keys of the database.
+    /// * `assignments` - New values of the columns set by the update.
+    /// * `tid` - Transaction to update in.
+    /// * `child` - Tuples to update, read from the table.
+    pub fn new(
+        storage_manager: &'static StorageManager,
+        table: Table,
+        container_id: ContainerId,
+        foreign_keys: ForeignKeys,
+        assignments: Vec<ColumnAssignment>,
+        tid: TransactionId,
+        child: Box<dyn OpIterator>,
+    ) -> Self {
+        Self {
+            storage_manager,
+            table,
+            container_id,
+            foreign_keys,
+            transaction_id: tid,
+            assignments,
+            count: None,
+            returned: false,
+            schema: TableSchema::new(vec![Attribute::new("count".to_string(), DataType::Int)]),
+            open: false,
+            child,
+        }
+    }
+
+    /// Applies the assignments to a tuple, converting numbers to the types of their columns.
+    fn assign(&self, tuple: &Tuple) -> Result<Tuple, CrustyError> {
+        let mut fields = tuple.field_vals.clone();
+        for assignment in &self.assignments {
+            let value = assignment.value.eval(&tuple.field_vals)?;
+            let attr = self.table.schema.get_attribute(assignment.column).unwrap();
+            fields[assignment.column] = value.cast(attr.dtype()).ok_or_else(|| {
+                CrustyError::ExecutionError(format!(
+                    "Value {} is out of range for type {} of column {}",
+                    value,
+                    attr.dtype(),
+                    attr.name()
+                ))
+            })?;
+        }
+        Ok(Tuple::new(fields))
+    }
+
+    /// Drains the child and updates its records, returning how many there were.
+    ///
+    /// All new values are computed and checked against the keys of the table and the
+    /// foreign keys before any record is written, so the scan never sees rows this update
+    /// wrote.
+    fn update_all(&mut self) -> Result<i32, CrustyError> {
+        let mut updates: Vec<(ValueId, Tuple, Tuple)> = Vec::new();
+        while let Some(tuple) = self.child.next()? {
+            let id = self.child.last_value_id().ok_or_else(|| {
+                CrustyError::ExecutionError(String::from(
+                    "Cannot update tuples not read from a table",
+                ))
+            })?;
+            let new = self.assign(&tuple)?;
+            updates.push((id, tuple, new));
+        }
+        let rows: Vec<(Option<ValueId>, &Tuple)> =
+            updates.iter().map(|(id, _, t)| (Some(*id), t)).collect();
+        self.table.check_rows(
+            self.storage_manager,
+            self.container_id,
+            self.transaction_id,
+            &rows,
+        )?;
+        let new_rows: Vec<&Tuple> = updates.iter().map(|(_, _, t)| t).collect();
+        self.foreign_keys.check_references(
+            self.storage_manager,
+            self.transaction_id,
+            &self.table,
+            self.container_id,
+            &new_rows,
+        )?;
+        let changes: Vec<(ValueId, &Tuple, &Tuple)> =
+            updates.iter().map(|(id, old, new)| (*id, old, new)).collect();
+        self.foreign_keys.check_key_changes(
+            self.storage_manager,
+            self.transaction_id,
+            &self.table,
+            self.container_id,
+            &changes,
+        )?;
+        let mut written = Vec::new();
+        for (id, _, tuple) in &updates {
+            debug_assert_eq!(id.container_id, self.container_id);
+            let new_id = self.storage_manager.update_value(
+                self.table.encode(tuple),
+                *id,
+                self.transaction_id,
+            )?;
+            written.push((new_id, tuple));
+        }
+        self.storage_manager
+            .check_transaction(self.transaction_id)?;
+        self.table
+            .check_written(self.storage_manager, self.transaction_id, &written)?;
+        Ok(updates.len() as i32)
+    }
+}
+
+impl OpIterator for Update {
+    fn open(&mut self) -> Result<(), CrustyError> {
+        self.child.open()?;
+        self.open = true;
+        self.returned = false;
+        Ok(())
+    }
+
+    /// Updates the child's records on the first call and returns their count.
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        if self.returned {
+            return Ok(None);
+        }
+        let count = match self.count {
+            Some(count) => count,
+            None => {
+                let count = self.update_all()?;
+                self.count = Some(count);
+                count
+            }
+        };
+        self.returned = true;
+        Ok(Some(Tuple::new(vec![Field::IntField(count)])))
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.child.close()?;
+        self.open = false;
+        Ok(())
+    }
+
+    /// Returns the count again without updating the rows a second time.
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.returned = false;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod test {
+    use super::*;
+    use crate::opiterator::{Filter, SeqScan, TupleIterator};
+    use common::foreign_key::{ForeignKey, ReferentialAction};
+    use common::logical_plan::{ArithOp, ValueExpr};
+    use common::table::Table;
+    use common::testutil::*;
+    use common::{Double, SimplePredicateOp};
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    const TABLE: &str = "Update";
+
+    fn setup() -> (&'static StorageManager, TransactionId) {
+        let smb = Box::new(StorageManager::new_test_sm());
+        let sm: &'static StorageManager = Box::leak(smb);
+        sm.create_table(0).unwrap();
+        let tid = TransactionId::new();
+        let rows = (0..5).map(|i| int_vec_to_tuple(vec![i, i * 10]).get_bytes());
+        sm.insert_values(0, rows.collect(), tid);
+        (sm, tid)
+    }
+
+    fn table() -> Table {
+        Table::new(TABLE.to_string(), get_int_table_schema(2))
+    }
+
+    fn scan(sm: &'static StorageManager, tid: TransactionId) -> Box<dyn OpIterator> {
+        Box::new(SeqScan::new(
+            sm,
+            Arc::new(RwLock::new(table())),
+            TABLE,
+            &0,
+            tid,
+        ))
+    }
+
+    #[test]
+    fn test_update_where() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        // SET b = b + a WHERE a >= 3
+        let child = Filter::new(
+            SimplePredicateOp::GreaterThanOrEq,
+            0,
+            Field::IntField(3),
+            scan(sm, tid),
+        );
+        let value = ValueExpr::Arith(
+            Box::new(ValueExpr::Column(1)),
+            ArithOp::Plus,
+            Box::new(ValueExpr::Column(0)),
+        );
+        let assignments = vec![ColumnAssignment { column: 1, value }];
+        let mut update = Update::new(
+            sm,
+            table(),
+            0,
+            ForeignKeys::default(),
+            assignments,
+            tid,
+            Box::new(child),
+        );
+        update.open()?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(2)])), update.next()?);
+        assert_eq!(None, update.next()?);
+        update.close()?;
+
+        let mut tuples = Vec::new();
+        let mut check = scan(sm, tid);
+        check.open()?;
+        while let Some(t) = check.next()? {
+            tuples.push(t);
+        }
+        tuples.sort_by_key(|t| t.get_field(0).unwrap().unwrap_int_field());
+        let expected = create_tuple_list(vec![
+            vec![0, 0],
+            vec![1, 10],
+            vec![2, 20],
+            vec![3, 33],
+            vec![4, 44],
+        ]);
+        assert_eq!(expected, tuples);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_error_leaves_rows() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        // SET b = 100 / a fails on the row where a = 0
+        let value = ValueExpr::Arith(
+            Box::new(ValueExpr::Literal(Field::IntField(100))),
+            ArithOp::Divide,
+            Box::new(ValueExpr::Column(0)),
+        );
+        let assignments = vec![ColumnAssignment { column: 1, value }];
+        let mut update = Update::new(
+            sm,
+            table(),
+            0,
+            ForeignKeys::default(),
+            assignments,
+            tid,
+            scan(sm, tid),
+        );
+        update.open()?;
+        assert!(update.next().is_err());
+
+        let mut check = scan(sm, tid);
+        check.open()?;
+        while let Some(t) = check.next()? {
+            let a = t.get_field(0).unwrap().unwrap_int_field();
+            assert_eq!(a * 10, t.get_field(1).unwrap().unwrap_int_field());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_needs_value_ids() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        let child = TupleIterator::new(vec![int_vec_to_tuple(vec![1, 2])], get_int_table_schema(2));
+        let mut update = Update::new(
+            sm,
+            table(),
+            0,
+            ForeignKeys::default(),
+            Vec::new(),
+            tid,
+            Box::new(child),
+        );
+        update.open()?;
+        assert!(update.next().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_unique_key() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::Int),
+        ]);
+        let keyed = Table::new(TABLE.to_string(), schema);
+        let scan = |table: &Table| -> Box<dyn OpIterator> {
+            let table = Arc::new(RwLock::new(table.clone()));
+            Box::new(SeqScan::new(sm, table, TABLE, &0, tid))
+        };
+        // SET a = a + 1 gives each row a key another row held before
+        let value = ValueExpr::Arith(
+            Box::new(ValueExpr::Column(0)),
+            ArithOp::Plus,
+            Box::new(ValueExpr::Literal(Field::IntField(1))),
+        );
+        let assignments = vec![ColumnAssignment { column: 0, value }];
+        let mut update = Update::new(
+            sm,
+            keyed.clone(),
+            0,
+            ForeignKeys::default(),
+            assignments,
+            tid,
+            scan(&keyed),
+        );
+        update.open()?;
+        assert_eq!(Some(Tuple::new(vec![Field::IntField(5)])), update.next()?);
+
+        // SET a = 3 WHERE a = 1 collides with a row that is not updated
+        let value = ValueExpr::Literal(Field::IntField(3));
+        let assignments = vec![ColumnAssignment { column: 0, value }];
+        let child = Filter::new(SimplePredicateOp::Equals, 0, Field::IntField(1), scan(&keyed));
+        let mut update = Update::new(
+            sm,
+            keyed.clone(),
+            0,
+            ForeignKeys::default(),
+            assignments,
+            tid,
+            Box::new(child),
+        );
+        update.open()?;
+        match update.next() {
+            Err(CrustyError::ValidationError(msg)) => assert_eq!(
+                "Duplicate value (3) for primary key (a) of table Update",
+                msg
+            ),
+            res => panic!("expected a duplicate key error, got {:?}", res),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_foreign_key() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::Int),
+            Attribute::new("b".to_string(), DataType::Int),
+        ]);
+        let parent = Table::new(TABLE.to_string(), schema.clone());
+        // A child row in container 1 refers to row 1 of the table
+        let mut child = Table::new("child".to_string(), schema);
+        child.add_foreign_key(ForeignKey {
+            name: "child_b_fkey".to_string(),
+            columns: vec![1],
+            parent: 0,
+            parent_columns: vec![0],
+            on_delete: ReferentialAction::Restrict,
+        });
+        sm.create_table(1).unwrap();
+        sm.insert_values(1, vec![int_vec_to_tuple(vec![100, 1]).get_bytes()], tid);
+        let tables = HashMap::from([
+            (0, Arc::new(RwLock::new(parent.clone()))),
+            (1, Arc::new(RwLock::new(child.clone()))),
+        ]);
+        let foreign_keys = ForeignKeys::new(Arc::new(RwLock::new(tables)));
+        let update = |table: &Table, container_id: ContainerId, column: usize, from, to| {
+            let scan = Box::new(SeqScan::new(
+                sm,
+                Arc::new(RwLock::new(table.clone())),
+                &table.name,
+                &container_id,
+                tid,
+            ));
+            let child = Filter::new(SimplePredicateOp::Equals, 0, Field::IntField(from), scan);
+            let value = ValueExpr::Literal(Field::IntField(to));
+            let mut update = Update::new(
+                sm,
+                table.clone(),
+                container_id,
+                foreign_keys.clone(),
+                vec![ColumnAssignment { column, value }],
+                tid,
+                Box::new(child),
+            );
+            update.open().unwrap();
+            update.next()
+        };
+        // SET a = 7 WHERE a = 1 leaves the child row without a parent
+        assert!(matches!(update(&parent, 0, 0, 1, 7), Err(CrustyError::ValidationError(_))));
+        assert!(update(&parent, 0, 0, 2, 8).is_ok());
+        // SET b = 9 WHERE a = 100 refers to a row that does not exist
+        match update(&child, 1, 1, 100, 9) {
+            Err(CrustyError::ValidationError(msg)) => assert_eq!(
+                "Insert or update on table child violates foreign key child_b_fkey: \
+                 key (b)=(9) is not in table Update",
+                msg
+            ),
+            res => panic!("expected a foreign key error, got {:?}", res),
+        }
+        assert!(update(&child, 1, 1, 100, 3).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_numeric_types() -> Result<(), CrustyError> {
+        let (sm, tid) = setup();
+        let schema = TableSchema::from_vecs(
+            vec!["v", "d"],
+            vec![DataType::Double, DataType::Decimal(5, 2)],
+        );
+        let m = Table::new("m".to_string(), schema);
+        sm.create_table(1)?;
+        let row = Tuple::new(vec![
+            Field::DoubleField(Double(2.0)),
+            Field::DecimalField(150, 2),
+        ]);
+        sm.insert_value(1, m.encode(&row), tid);
+        let scan = || {
+            Box::new(SeqScan::new(
+                sm,
+                Arc::new(RwLock::new(m.clone())),
+                "m",
+                &1,
+                tid,
+            ))
+        };
+        let update = |column, value| -> Result<Tuple, CrustyError> {
+            let assignments = vec![ColumnAssignment { column, value }];
+            let mut update = Update::new(
+                sm,
+                m.clone(),
+                1,
+                ForeignKeys::default(),
+                assignments,
+                tid,
+                scan(),
+            );
+            update.open()?;
+            update.next()?;
+            let mut check = scan();
+            check.open()?;
+            Ok(check.next()?.unwrap())
+        };
+        let column = |i| Box::new(ValueExpr::Column(i));
+        let literal = |f| Box::new(ValueExpr::Literal(f));
+        // SET v = v * 1.5
+        let value = ValueExpr::Arith(
+            column(0),
+            ArithOp::Multiply,
+            literal(Field::DecimalField(15, 1)),
+        );
+        assert_eq!(
+            Field::DoubleField(Double(3.0)),
+            update(0, value)?.field_vals[0]
+        );
+        // SET d = d + 1.01
+        let value = ValueExpr::Arith(
+            column(1),
+            ArithOp::Plus,
+            literal(Field::DecimalField(101, 2)),
+        );
+        assert_eq!(Field::DecimalField(251, 2), update(1, value)?.field_vals[1]);
+        // SET v = -v
+        let zero = literal(Field::DoubleField(Double(0.0)));
+        let value = ValueExpr::Arith(zero, ArithOp::Minus, column(0));
+        assert_eq!(
+            Field::DoubleField(Double(-3.0)),
+            update(0, value)?.field_vals[0]
+        );
+        // SET d = d * 1.5 rounds to the scale of d, and SET d = v fits a double into it
+        let value = ValueExpr::Arith(
+            column(1),
+            ArithOp::Multiply,
+            literal(Field::DecimalField(15, 1)),
+        );
+        assert_eq!(Field::DecimalField(377, 2), update(1, value)?.field_vals[1]);
+        assert_eq!(
+            Field::DecimalField(-300, 2),
+            update(1, *column(0))?.field_vals[1]
+        );
+        // SET d = d * 1000 is too large for DECIMAL(5,2)
+        let value = ValueExpr::Arith(column(1), ArithOp::Multiply, literal(Field::IntField(1000)));
+        assert!(matches!(
+            update(1, value),
+            Err(CrustyError::ExecutionError(_))
+        ));
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,10 +1,13 @@
 use crate::opiterator::*;
 use crate::StorageManager;
 use common::catalog::Catalog;
+use common::foreign_key::ForeignKeys;
 use common::logical_plan::*;
 use common::physical_plan::*;
 use common::prelude::*;
-use common::{QueryResult, QueryResultType, QUERY_RESULT_TYPE};
+use common::{
+    QueryResult, QueryResultType, DISTINCT_BUDGET, QUERY_RESULT_TYPE, SORT_BUDGET,
+};
 
 /// Manages the execution of queries using OpIterators and converts a LogicalPlan to a tree of OpIterators and runs it.
 pub struct Executor {
@@ -102,7 +105,7 @@ impl Executor {
                 self.start()?;
                 while let Some(t) = &self.next()? {
                     for f in t.field_vals() {
-                        let s = format!("{},", f.to_string());
+                        let s = format!("{},", f.to_csv());
                         res.push_str(&s);
                     }
                     res.push('\n');
@@ -113,6 +116,19 @@ impl Executor {
         }
     }
 
+    /// Runs a plan that modifies a table, like an insert, and returns the number of rows it changed.
+    pub fn execute_count(&mut self) -> Result<usize, CrustyError> {
+        self.start()?;
+        let count = match self.next()? {
+            Some(t) => t.get_field(0).map(|f| f.unwrap_int_field()),
+            None => None,
+        };
+        self.close()?;
+        count
+            .map(|c| c as usize)
+            .ok_or_else(|| CrustyError::ExecutionError(String::from("Expected a row count")))
+    }
+
     /// Converts a physical_plan to an op_iterator.
     ///
     /// # Arguments
@@ -192,6 +208,24 @@ impl Executor {
                     alias
                 ))),
             },
+            PhysicalOp::IndexScan(PhysicalIndexScanNode {
+                alias,
+                container_id,
+                index_id,
+                lower,
+                upper,
+            }) => {
+                let table = catalog.get_table_ptr(*container_id)?;
+                Ok(Box::new(IndexScan::new(
+                    storage_manager,
+                    table,
+                    alias,
+                    *index_id,
+                    lower.clone(),
+                    upper.clone(),
+                    tid,
+                )))
+            }
             PhysicalOp::Project(PhysicalProjectNode { identifiers }) => {
                 let child = children.next().ok_or_else(|| err.clone())??;
                 match &identifiers {
@@ -226,6 +260,12 @@ impl Executor {
                 let (groupby_indices, groupby_names) =
                     Self::get_field_indices_names(group_by, child.get_schema())?;
                 let agg = Aggregate::new(
+                    groupby_indices,
+                    groupby_names,
+                    agg_indices,
+                    agg_names,
+                    ops,
+                    child,
                 );
                 Ok(Box::new(agg))
             }
@@ -242,14 +282,53 @@ impl Executor {
                     let left_index = Executor::get_field_index(left.column(), right_schema)?;
                     let right_index = Executor::get_field_index(right.column(), left_schema)?;
                     Ok(Box::new(Join::new(
+                        op.flip(),
+                        right_index,
+                        left_index,
+                        left_child,
+                        right_child,
                     )))
                 } else {
                     let left_index = Executor::get_field_index(left.column(), left_schema)?;
                     let right_index = Executor::get_field_index(right.column(), right_schema)?;
                     Ok(Box::new(Join::new(
+                        *op,
+                        left_index,
+                        right_index,
+                        left_child,
+                        right_child,
                     )))
                 }
             }
+            PhysicalOp::HashJoin(PhysicalHashJoinNode {
+                left,
+                right,
+                op,
+                index_id: Some(index_id),
+                ..
+            }) => {
+                let left_child = children.next().ok_or_else(|| err.clone())??;
+                // The right child scans the table probed, and only gives the schema of its rows
+                let right_child = children.next().ok_or_else(|| err.clone())??;
+                let right_schema = right_child.get_schema();
+                let left_index = Executor::get_field_index(left.column(), left_child.get_schema())?;
+                let right_index = Executor::get_field_index(right.column(), right_schema)?;
+                let table_id = catalog
+                    .get_table_id(right.table())
+                    .ok_or_else(|| err.clone())?;
+                let table = catalog.get_table_ptr(table_id)?;
+                Ok(Box::new(IndexNestedLoopJoin::new(
+                    storage_manager,
+                    *op,
+                    left_index,
+                    right_index,
+                    left_child,
+                    right_schema,
+                    table,
+                    *index_id,
+                    tid,
+                )))
+            }
             PhysicalOp::HashJoin(PhysicalHashJoinNode {
                 left, right, op, ..
             }) => {
@@ -263,82 +342,190 @@ impl Executor {
                     let left_index = Executor::get_field_index(left.column(), right_schema)?;
                     let right_index = Executor::get_field_index(right.column(), left_schema)?;
                     Ok(Box::new(HashEqJoin::new(
+                        op.flip(),
+                        right_index,
+                        left_index,
+                        left_child,
+                        right_child,
                     )))
                 } else {
                     let left_index = Executor::get_field_index(left.column(), left_schema)?;
                     let right_index = Executor::get_field_index(right.column(), right_schema)?;
                     Ok(Box::new(HashEqJoin::new(
+                        *op,
+                        left_index,
+                        right_index,
+                        left_child,
+                        right_child,
                     )))
                 }
             }
+            PhysicalOp::IndexNestedLoopJoin(PhysicalIndexNestedLoopJoinNode {
+                outer,
+                inner,
+                op,
+                container_id,
+                index_id,
+            }) => {
+                let left_child = children.next().ok_or_else(|| err.clone())??;
+                // The right child scans the inner table, and only gives the schema of its rows
+                let right_child = children.next().ok_or_else(|| err.clone())??;
+                let right_schema = right_child.get_schema();
+                let left_index =
+                    Executor::get_field_index(outer.column(), left_child.get_schema())?;
+                let right_index = Executor::get_field_index(inner.column(), right_schema)?;
+                let table = catalog.get_table_ptr(*container_id)?;
+                Ok(Box::new(IndexNestedLoopJoin::new(
+                    storage_manager,
+                    *op,
+                    left_index,
+                    right_index,
+                    left_child,
+                    right_schema,
+                    table,
+                    *index_id,
+                    tid,
+                )))
+            }
             PhysicalOp::Filter(PhysicalFilterNode { predicate, .. }) => {
                 let child = children.next().ok_or_else(|| err.clone())??;
-                let (identifiers, ops, operands, _compound_predicate_op) = match predicate {
+                let (identifiers, ops, operands, compound_predicate_op) = match predicate {
                     Predicate::SimplePredicate(SimplePredicate { left, op, right }) => {
                         match (left, right) {
-                            (PredExpr::Ident(i), PredExpr::Literal(f)) => {
-                                let comp: Option<CompoundPredicateOp> = None;
-                                (vec![i.clone()], vec![*op], vec![f.clone()], comp)
+                            (PredExpr::Ident(i) | PredExpr::Aggregate(i), PredExpr::Literal(f)) => {
+                                (
+                                    vec![i.clone()],
+                                    vec![*op],
+                                    vec![f.clone()],
+                                    CompoundPredicateOp::And,
+                                )
                             }
-                            (PredExpr::Literal(f), PredExpr::Ident(i)) => {
-                                (vec![i.clone()], vec![op.flip()], vec![f.clone()], None)
+                            (PredExpr::Literal(f), PredExpr::Ident(i) | PredExpr::Aggregate(i)) => {
+                                (
+                                    vec![i.clone()],
+                                    vec![op.flip()],
+                                    vec![f.clone()],
+                                    CompoundPredicateOp::And,
+                                )
                             }
                             _ => {
                                 return Err(err.clone());
                             }
                         }
                     }
-                    Predicate::CompoundPredicate(_) => {
-                        //Only supporting simple filter predicates right now
-                        unimplemented!()
-                    } // Predicate::CompoundPredicate(CompoundPredicate {
-                      //     op,
-                      //     simple_predicates,
-                      // }) => {
-                      //     let mut identifiers = Vec::new();
-                      //     let mut ops = Vec::new();
-                      //     let mut operands = Vec::new();
-                      //     for simple_predicate in simple_predicates {
-                      //         match (&simple_predicate.left, &simple_predicate.right) {
-                      //             (PredExpr::Ident(i), PredExpr::Literal(f)) => {
-                      //                 identifiers.push(i.clone());
-                      //                 ops.push(simple_predicate.op);
-                      //                 operands.push(f.clone());
-                      //             }
-                      //             (PredExpr::Literal(f), PredExpr::Ident(i)) => {
-                      //                 identifiers.push(i.clone());
-                      //                 ops.push(simple_predicate.op.flip());
-                      //                 operands.push(f.clone());
-                      //             }
-                      //             _ => {
-                      //                 return Err(err.clone());
-                      //             }
-                      //         }
-                      //     }
-
-                      //     (identifiers, ops, operands, Some(op.clone()))
-                      // }
+                    Predicate::CompoundPredicate(CompoundPredicate {
+                        op,
+                        simple_predicates,
+                    }) => {
+                        let mut identifiers = Vec::new();
+                        let mut ops = Vec::new();
+                        let mut operands = Vec::new();
+                        for simple_predicate in simple_predicates {
+                            match (&simple_predicate.left, &simple_predicate.right) {
+                                (
+                                    PredExpr::Ident(i) | PredExpr::Aggregate(i),
+                                    PredExpr::Literal(f),
+                                ) => {
+                                    identifiers.push(i.clone());
+                                    ops.push(simple_predicate.op);
+                                    operands.push(f.clone());
+                                }
+                                (
+                                    PredExpr::Literal(f),
+                                    PredExpr::Ident(i) | PredExpr::Aggregate(i),
+                                ) => {
+                                    identifiers.push(i.clone());
+                                    ops.push(simple_predicate.op.flip());
+                                    operands.push(f.clone());
+                                }
+                                _ => {
+                                    return Err(err.clone());
+                                }
+                            }
+                        }
+                        (identifiers, ops, operands, op.clone())
+                    }
                 };
-                let indexes: Vec<usize> = identifiers
-                    .iter()
-                    .map(|identifier| {
-                        Executor::get_field_index(identifier.column(), child.get_schema()).unwrap()
-                    })
-                    .collect();
-                if identifiers.len() > 1 {
-                    todo!(); // fix
-                } else if identifiers.is_empty() {
-                    Err(CrustyError::ExecutionError(String::from("No predicates")))
-                } else {
-                    //let idx = Executor::get_field_index(identifier.column(), child.get_schema())?;
-                    let filter = Filter::new(
-                        *ops.get(0).unwrap(),
-                        *indexes.get(0).unwrap(),
-                        operands.get(0).unwrap().clone(),
-                        child,
-                    );
-                    Ok(Box::new(filter))
+                if identifiers.is_empty() {
+                    return Err(CrustyError::ExecutionError(String::from("No predicates")));
                 }
+                let mut predicates = Vec::new();
+                for ((identifier, op), operand) in identifiers.iter().zip(ops).zip(operands) {
+                    let idx = Executor::get_field_index(identifier.column(), child.get_schema())?;
+                    predicates.push((op, idx, operand));
+                }
+                let filter = Filter::new_compound(compound_predicate_op, predicates, child);
+                Ok(Box::new(filter))
+            }
+            PhysicalOp::Map(PhysicalMapNode { columns }) => {
+                let child = children.next().ok_or_else(|| err.clone())??;
+                Ok(Box::new(Map::new(columns.clone(), child)))
+            }
+            PhysicalOp::Sort(PhysicalSortNode { fields }) => {
+                let child = children.next().ok_or_else(|| err.clone())??;
+                let fields = Executor::get_sort_fields(fields, child.get_schema())?;
+                Ok(Box::new(Sort::new(fields, SORT_BUDGET, child)))
+            }
+            PhysicalOp::TopN(PhysicalTopNNode { fields, limit }) => {
+                let child = children.next().ok_or_else(|| err.clone())??;
+                let fields = Executor::get_sort_fields(fields, child.get_schema())?;
+                Ok(Box::new(TopN::new(fields, *limit, child)))
+            }
+            PhysicalOp::Limit(PhysicalLimitNode { limit, offset }) => {
+                let child = children.next().ok_or_else(|| err.clone())??;
+                Ok(Box::new(Limit::new(*limit, *offset, child)))
+            }
+            PhysicalOp::Distinct(PhysicalDistinctNode {}) => {
+                let child = children.next().ok_or_else(|| err.clone())??;
+                Ok(Box::new(Distinct::new(DISTINCT_BUDGET, child)))
+            }
+            PhysicalOp::Values(PhysicalValuesNode { schema, rows }) => {
+                let tuples = rows.iter().map(|r| Tuple::new(r.clone())).collect();
+                Ok(Box::new(TupleIterator::new(tuples, schema.clone())))
+            }
+            PhysicalOp::Insert(PhysicalInsertNode {
+                container_id,
+                columns,
+                ..
+            }) => {
+                let child = children.next().ok_or_else(|| err.clone())??;
+                let table = catalog.get_table_ptr(*container_id)?.read().unwrap().clone();
+                Ok(Box::new(Insert::new(
+                    storage_manager,
+                    table,
+                    *container_id,
+                    ForeignKeys::new(catalog.get_tables()),
+                    columns.clone(),
+                    tid,
+                    child,
+                )))
+            }
+            PhysicalOp::Update(PhysicalUpdateNode {
+                container_id,
+                assignments,
+                ..
+            }) => {
+                let child = children.next().ok_or_else(|| err.clone())??;
+                let table = catalog.get_table_ptr(*container_id)?.read().unwrap().clone();
+                Ok(Box::new(Update::new(
+                    storage_manager,
+                    table,
+                    *container_id,
+                    ForeignKeys::new(catalog.get_tables()),
+                    assignments.clone(),
+                    tid,
+                    child,
+                )))
+            }
+            PhysicalOp::Delete(PhysicalDeleteNode { container_id, .. }) => {
+                let child = children.next().ok_or_else(|| err.clone())??;
+                Ok(Box::new(Delete::new(
+                    storage_manager,
+                    *container_id,
+                    ForeignKeys::new(catalog.get_tables()),
+                    tid,
+                    child,
+                )))
             }
             //MaterializedViews are not required
             PhysicalOp::MaterializedView(_) => unimplemented!(),
@@ -366,6 +553,28 @@ impl Executor {
             .ok_or_else(|| CrustyError::ExecutionError(String::from("Unrecognized column name")))
     }
 
+    /// Finds the fields to sort by in the given schema.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Fields to sort by and their orders.
+    /// * `schema` - Schema to look for the fields in.
+    fn get_sort_fields(
+        fields: &[OrderByField],
+        schema: &TableSchema,
+    ) -> Result<Vec<SortField>, CrustyError> {
+        fields
+            .iter()
+            .map(|f| {
+                Ok(SortField {
+                    field: Executor::get_field_index(f.field.column(), schema)?,
+                    descending: f.descending,
+                    nulls_first: f.nulls_first,
+                })
+            })
+            .collect()
+    }
+
     // TODO: Fix test cases to be able to address the clippy warning of pointer arguments.
     /// Finds the column indices and names of column alias present in the given schema.
     ///
@@ -1,9 +1,14 @@
 use common::catalog::Catalog;
+use common::ids::ContainerId;
 use common::logical_plan::*;
-use common::{get_name, CrustyError, DataType, Field, SimplePredicateOp};
+use common::temporal::{Interval, TimeUnit};
+use common::{
+    get_attr, get_name, Attribute, CrustyError, DataType, Double, Field, SimplePredicateOp,
+    TableSchema, MAX_DECIMAL_PRECISION,
+};
 use sqlparser::ast::{
-    BinaryOperator, Expr, Function, FunctionArg, JoinConstraint, JoinOperator, SelectItem, SetExpr,
-    TableFactor, Value,
+    Assignment, BinaryOperator, Expr, Function, FunctionArg, Ident, JoinConstraint, JoinOperator,
+    ObjectName, OrderByExpr, SelectItem, SetExpr, TableFactor, UnaryOperator, Value,
 };
 use std::collections::HashSet;
 
@@ -17,6 +22,9 @@ pub struct TranslateAndValidate<'a, T: Catalog> {
     catalog: &'a T,
     /// List of tables encountered. Used for field validation.
     tables: Vec<String>,
+    /// Columns computed from expressions by a Map node, named by the expression's text,
+    /// and their types.
+    computed: Vec<(FieldIdentifier, DataType)>,
 }
 
 impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
@@ -26,6 +34,7 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
             plan: LogicalPlan::new(),
             catalog,
             tables: Vec::new(),
+            computed: Vec::new(),
         }
     }
 
@@ -65,7 +74,7 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
         for table in &self.tables {
             let table_id = self
                 .catalog
-                .get_table_id(&identifiers[0].to_string())
+                .get_table_id(table)
                 .ok_or_else(|| CrustyError::CrustyError("Missing Table".to_string()))?;
 
             if self.catalog.is_valid_column(table_id, &orig) {
@@ -103,6 +112,698 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
         Ok(translator.plan)
     }
 
+    /// Translates an INSERT statement to a LogicalPlan whose root inserts into the table.
+    ///
+    /// Literal values are type-checked against the table's schema here; the types of rows
+    /// from a query are checked when they are inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Table to insert into.
+    /// * `columns` - Columns the values are for, or empty for all columns in schema order.
+    /// * `source` - VALUES or query producing the rows.
+    /// * `catalog` - Catalog for validation.
+    pub fn from_insert(
+        table_name: &ObjectName,
+        columns: &[Ident],
+        source: &sqlparser::ast::Query,
+        catalog: &T,
+    ) -> Result<LogicalPlan, CrustyError> {
+        let mut translator = TranslateAndValidate::new(catalog);
+        translator.process_insert(table_name, columns, source)?;
+        Ok(translator.plan)
+    }
+
+    /// Translates an UPDATE statement to a LogicalPlan whose root updates the rows of the
+    /// table that match the selection.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Table to update.
+    /// * `assignments` - Columns to set and the expressions giving their new values.
+    /// * `selection` - WHERE clause choosing the rows to update, if any.
+    /// * `catalog` - Catalog for validation.
+    pub fn from_update(
+        table_name: &ObjectName,
+        assignments: &[Assignment],
+        selection: &Option<Expr>,
+        catalog: &T,
+    ) -> Result<LogicalPlan, CrustyError> {
+        let mut translator = TranslateAndValidate::new(catalog);
+        let (table, container_id, node) = translator.process_target(table_name, selection)?;
+        let schema = catalog.get_table_schema(container_id)?;
+        let assignments = translator.process_assignments(assignments, &schema)?;
+        let op = UpdateNode {
+            table,
+            container_id,
+            assignments,
+        };
+        let idx = translator.plan.add_node(LogicalOp::Update(op));
+        translator.plan.add_edge(idx, node);
+        Ok(translator.plan)
+    }
+
+    /// Translates a DELETE statement to a LogicalPlan whose root deletes the rows of the
+    /// table that match the selection.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Table to delete from.
+    /// * `selection` - WHERE clause choosing the rows to delete, if any.
+    /// * `catalog` - Catalog for validation.
+    pub fn from_delete(
+        table_name: &ObjectName,
+        selection: &Option<Expr>,
+        catalog: &T,
+    ) -> Result<LogicalPlan, CrustyError> {
+        let mut translator = TranslateAndValidate::new(catalog);
+        let (table, container_id, node) = translator.process_target(table_name, selection)?;
+        let op = DeleteNode {
+            table,
+            container_id,
+        };
+        let idx = translator.plan.add_node(LogicalOp::Delete(op));
+        translator.plan.add_edge(idx, node);
+        Ok(translator.plan)
+    }
+
+    /// Helper function to process an INSERT statement.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Table to insert into.
+    /// * `columns` - Columns the values are for.
+    /// * `source` - VALUES or query producing the rows.
+    fn process_insert(
+        &mut self,
+        table_name: &ObjectName,
+        columns: &[Ident],
+        source: &sqlparser::ast::Query,
+    ) -> Result<(), CrustyError> {
+        let table = get_name(table_name)?;
+        let container_id = self
+            .catalog
+            .get_table_id(&table)
+            .ok_or_else(|| CrustyError::CrustyError("Missing Table".to_string()))?;
+        let schema = self.catalog.get_table_schema(container_id)?;
+        let columns = Self::insert_columns(&schema, columns)?;
+        let node = match &source.body {
+            SetExpr::Values(values) => self.process_values(&values.0, &schema, &columns)?,
+            _ => {
+                self.process_query(source)?;
+                self.plan.root().unwrap()
+            }
+        };
+        let op = InsertNode {
+            table,
+            container_id,
+            columns,
+        };
+        let idx = self.plan.add_node(LogicalOp::Insert(op));
+        self.plan.add_edge(idx, node);
+        Ok(())
+    }
+
+    /// Returns the index in schema of each column an INSERT gives values for. The columns
+    /// left out take their DEFAULT, or NULL.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Schema of the table inserted into.
+    /// * `columns` - Columns listed in the INSERT, or empty for all of them.
+    fn insert_columns(schema: &TableSchema, columns: &[Ident]) -> Result<Vec<usize>, CrustyError> {
+        if columns.is_empty() {
+            return Ok((0..schema.size()).collect());
+        }
+        let mut indices = Vec::new();
+        for col in columns {
+            let i = *schema.get_field_index(&col.value).ok_or_else(|| {
+                CrustyError::ValidationError(format!("Column {} does not exist", col.value))
+            })?;
+            if indices.contains(&i) {
+                return Err(CrustyError::ValidationError(format!(
+                    "Column {} is listed more than once",
+                    col.value
+                )));
+            }
+            indices.push(i);
+        }
+        Ok(indices)
+    }
+
+    /// Creates a Values LogicalOp for the rows of a VALUES clause, adds it to self.plan,
+    /// and returns the OpIndex.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of literal expressions.
+    /// * `schema` - Schema of the table the rows are for.
+    /// * `columns` - Index in schema of the column each value is for.
+    fn process_values(
+        &mut self,
+        rows: &[Vec<Expr>],
+        schema: &TableSchema,
+        columns: &[usize],
+    ) -> Result<OpIndex, CrustyError> {
+        let attrs: Vec<&Attribute> = columns
+            .iter()
+            .map(|i| schema.get_attribute(*i).unwrap())
+            .collect();
+        let mut values = Vec::new();
+        for row in rows {
+            if row.len() != attrs.len() {
+                return Err(CrustyError::ValidationError(format!(
+                    "Expected {} values but got {}",
+                    attrs.len(),
+                    row.len()
+                )));
+            }
+            let fields = row
+                .iter()
+                .zip(attrs.iter())
+                .map(|(expr, attr)| Self::literal_to_field(expr, attr))
+                .collect::<Result<Vec<Field>, CrustyError>>()?;
+            values.push(fields);
+        }
+        let schema = TableSchema::new(
+            attrs
+                .iter()
+                .map(|a| Attribute::new(a.name().to_string(), a.dtype().clone()))
+                .collect(),
+        );
+        let op = ValuesNode {
+            schema,
+            rows: values,
+        };
+        Ok(self.plan.add_node(LogicalOp::Values(op)))
+    }
+
+    /// Converts a literal to a field of the attribute's type. NULL is a value of every type.
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Literal to convert.
+    /// * `attr` - Attribute the value is for.
+    pub fn literal_to_field(expr: &Expr, attr: &Attribute) -> Result<Field, CrustyError> {
+        let mismatch = || {
+            CrustyError::ValidationError(format!(
+                "Value {} does not match type {} of column {}",
+                expr,
+                attr.dtype(),
+                attr.name()
+            ))
+        };
+        let text = match expr {
+            Expr::Value(Value::Null) => return Ok(Field::Null),
+            Expr::Value(Value::Interval { .. }) => {
+                return match (Self::typed_literal(expr)?, attr.dtype()) {
+                    ((field, DataType::Interval), DataType::Interval) => Ok(field),
+                    _ => Err(mismatch()),
+                }
+            }
+            // Dates, timestamps and intervals are written as strings, typed or not.
+            Expr::Value(Value::SingleQuotedString(s)) | Expr::TypedString { value: s, .. } => {
+                return match attr.dtype() {
+                    DataType::String | DataType::Varchar(_) if matches!(expr, Expr::Value(_)) => {
+                        Ok(Field::StringField(s.to_string()))
+                    }
+                    DataType::Date | DataType::Timestamp | DataType::Interval => {
+                        Field::parse(s, attr.dtype()).ok_or_else(mismatch)
+                    }
+                    _ => Err(mismatch()),
+                }
+            }
+            Expr::Value(Value::Boolean(b)) => match attr.dtype() {
+                DataType::Boolean => return Ok(Field::BoolField(*b)),
+                _ => return Err(mismatch()),
+            },
+            Expr::Value(Value::Number(s, _)) => s.to_string(),
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            } => match &**expr {
+                Expr::Value(Value::Number(s, _)) => format!("-{}", s),
+                _ => return Err(mismatch()),
+            },
+            _ => return Err(mismatch()),
+        };
+        match attr.dtype() {
+            DataType::String | DataType::Varchar(_) | DataType::Boolean => Err(mismatch()),
+            dtype => Field::parse(&text, dtype).ok_or_else(|| {
+                CrustyError::ValidationError(format!(
+                    "Value {} is out of range for type {} of column {}",
+                    text,
+                    dtype,
+                    attr.name()
+                ))
+            }),
+        }
+    }
+
+    /// Scans the table an UPDATE or DELETE changes and filters it by the WHERE clause.
+    ///
+    /// Returns the table's name, its container, and the OpIndex of the node producing the
+    /// rows to change.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Table to change.
+    /// * `selection` - WHERE clause choosing the rows to change, if any.
+    fn process_target(
+        &mut self,
+        table_name: &ObjectName,
+        selection: &Option<Expr>,
+    ) -> Result<(String, ContainerId, OpIndex), CrustyError> {
+        let table = get_name(table_name)?;
+        let mut node = self.process_table(table_name)?;
+        if let Some(expr) = selection {
+            node = self.process_where(expr, node)?;
+        }
+        let container_id = self.catalog.get_table_id(&table).unwrap();
+        Ok((table, container_id, node))
+    }
+
+    /// Converts the SET clause of an UPDATE to column assignments.
+    ///
+    /// # Arguments
+    ///
+    /// * `assignments` - Columns to set and the expressions giving their new values.
+    /// * `schema` - Schema of the table updated.
+    fn process_assignments(
+        &self,
+        assignments: &[Assignment],
+        schema: &TableSchema,
+    ) -> Result<Vec<ColumnAssignment>, CrustyError> {
+        let mut res: Vec<ColumnAssignment> = Vec::new();
+        for assignment in assignments {
+            let column = *schema
+                .get_field_index(&assignment.id.value)
+                .ok_or_else(|| {
+                    CrustyError::ValidationError(format!(
+                        "Column {} does not exist",
+                        assignment.id.value
+                    ))
+                })?;
+            if res.iter().any(|a| a.column == column) {
+                return Err(CrustyError::ValidationError(format!(
+                    "Column {} is assigned more than once",
+                    assignment.id.value
+                )));
+            }
+            let attr = schema.get_attribute(column).unwrap();
+            if Self::is_literal(&assignment.value) {
+                let value = ValueExpr::Literal(Self::literal_to_field(&assignment.value, attr)?);
+                res.push(ColumnAssignment { column, value });
+                continue;
+            }
+            let (value, dtype) = self.process_value_expr(&assignment.value, schema)?;
+            // Numbers are converted to the type of the column when assigned
+            let numeric = attr.dtype().is_numeric() && dtype.is_numeric();
+            if !attr.dtype().accepts(&dtype)
+                && !numeric
+                && !matches!(value, ValueExpr::Literal(Field::Null))
+            {
+                return Err(CrustyError::ValidationError(format!(
+                    "Value {} does not match type {} of column {}",
+                    assignment.value,
+                    attr.dtype(),
+                    attr.name()
+                )));
+            }
+            res.push(ColumnAssignment { column, value });
+        }
+        Ok(res)
+    }
+
+    /// Converts a typed literal, like `DATE '2021-01-01'` or `INTERVAL '3' DAY`, to a field
+    /// and its type.
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Literal to convert.
+    fn typed_literal(expr: &Expr) -> Result<(Field, DataType), CrustyError> {
+        let invalid = || CrustyError::ValidationError(format!("Invalid literal {}", expr));
+        match expr {
+            Expr::TypedString { data_type, value } => {
+                let dtype = get_attr(data_type)?;
+                let field = Field::parse(value, &dtype).ok_or_else(invalid)?;
+                Ok((field, dtype))
+            }
+            Expr::Value(Value::Interval {
+                value,
+                leading_field,
+                last_field: None,
+                ..
+            }) => {
+                let interval = match (value.trim().parse::<f64>(), leading_field) {
+                    (Ok(quantity), Some(unit)) => Interval::of(quantity, TimeUnit::from(unit)),
+                    (Ok(quantity), None) => Interval::of(quantity, TimeUnit::Second),
+                    (Err(_), _) => Interval::parse(value),
+                };
+                let field = Field::IntervalField(interval.ok_or_else(invalid)?);
+                Ok((field, DataType::Interval))
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Returns whether the expression is a literal value, possibly negated.
+    fn is_literal(expr: &Expr) -> bool {
+        match expr {
+            Expr::Value(_) | Expr::TypedString { .. } => true,
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            } => matches!(**expr, Expr::Value(Value::Number(_, _))),
+            _ => false,
+        }
+    }
+
+    /// Converts a number literal to the narrowest field holding it exactly: an Int or BigInt
+    /// if it is whole, else a Decimal, falling back to a Double.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Number to convert, like `-1.25`.
+    fn number_to_field(text: &str) -> Option<Field> {
+        if let Ok(i) = text.parse::<i32>() {
+            return Some(Field::IntField(i));
+        }
+        if let Ok(i) = text.parse::<i64>() {
+            return Some(Field::BigIntField(i));
+        }
+        let scale = text.split_once('.').map_or(0, |(_, frac)| frac.len());
+        if scale <= MAX_DECIMAL_PRECISION as usize {
+            let dtype = DataType::Decimal(MAX_DECIMAL_PRECISION, scale as u8);
+            if let Some(field) = Field::parse(text, &dtype) {
+                return Some(field);
+            }
+        }
+        Field::parse(text, &DataType::Double)
+    }
+
+    /// Converts a number in an expression to a literal of the narrowest type that holds it.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Number as written.
+    fn number_literal(text: &str) -> Result<(ValueExpr, DataType), CrustyError> {
+        let field = Self::number_to_field(text)
+            .ok_or_else(|| CrustyError::ValidationError(format!("Unsupported literal {}", text)))?;
+        let dtype = match field {
+            Field::IntField(_) => DataType::Int,
+            Field::BigIntField(_) => DataType::BigInt,
+            Field::DecimalField(_, scale) => DataType::Decimal(MAX_DECIMAL_PRECISION, scale),
+            _ => DataType::Double,
+        };
+        Ok((ValueExpr::Literal(field), dtype))
+    }
+
+    /// Returns whether the expression is a call to `date_trunc`.
+    fn is_date_trunc(expr: &Expr) -> bool {
+        match expr {
+            Expr::Function(Function { name, .. }) => {
+                matches!(get_name(name), Ok(name) if name.eq_ignore_ascii_case("date_trunc"))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether the expression computes a value from columns, rather than naming a
+    /// column, so it must be computed by a Map node before being filtered, grouped or
+    /// projected.
+    fn is_computed(expr: &Expr) -> bool {
+        match expr {
+            Expr::Extract { .. } => true,
+            Expr::Function(_) => Self::is_date_trunc(expr),
+            Expr::BinaryOp { op, .. } => matches!(
+                op,
+                BinaryOperator::Plus
+                    | BinaryOperator::Minus
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Modulus
+            ),
+            Expr::Nested(expr) => Self::is_computed(expr),
+            _ => false,
+        }
+    }
+
+    /// Collects the computed expressions in an expression, looking through predicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Expression to search.
+    /// * `computed` - Computed expressions found so far.
+    fn collect_computed<'e>(expr: &'e Expr, computed: &mut Vec<&'e Expr>) {
+        match expr {
+            _ if Self::is_computed(expr) => computed.push(expr),
+            Expr::BinaryOp { left, right, .. } => {
+                Self::collect_computed(left, computed);
+                Self::collect_computed(right, computed);
+            }
+            Expr::Nested(expr) | Expr::IsNull(expr) | Expr::IsNotNull(expr) => {
+                Self::collect_computed(expr, computed)
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the schema of the tuples the tables in the query produce when joined, with
+    /// columns named table.column.
+    fn joined_schema(&self) -> Result<TableSchema, CrustyError> {
+        let mut attrs = Vec::new();
+        for table in &self.tables {
+            let table_id = self
+                .catalog
+                .get_table_id(table)
+                .ok_or_else(|| CrustyError::CrustyError("Missing Table".to_string()))?;
+            for attr in self.catalog.get_table_schema(table_id)?.attributes() {
+                let name = format!("{}.{}", table, attr.name());
+                attrs.push(Attribute::new(name, attr.dtype().clone()));
+            }
+        }
+        Ok(TableSchema::new(attrs))
+    }
+
+    /// Returns the table of the first column an expression refers to.
+    fn expr_table(&self, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Identifier(_) | Expr::CompoundIdentifier(_) => {
+                self.expr_to_ident(expr).ok().map(|f| f.table().to_string())
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                self.expr_table(left).or_else(|| self.expr_table(right))
+            }
+            Expr::Nested(expr) | Expr::UnaryOp { expr, .. } | Expr::Extract { expr, .. } => {
+                self.expr_table(expr)
+            }
+            Expr::Function(Function { args, .. }) => args.iter().find_map(|arg| match arg {
+                FunctionArg::Named { arg, .. } | FunctionArg::Unnamed(arg) => self.expr_table(arg),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Creates a Map LogicalOp computing the computed expressions of a SELECT over node, adds
+    /// it to self.plan, and returns the OpIndex. Returns node if nothing is computed.
+    ///
+    /// # Arguments
+    ///
+    /// * `select` - Query whose projection, WHERE clause and GROUP BY to compute.
+    /// * `order_by` - ORDER BY of the query, also computed.
+    /// * `node` - Node producing the tuples to compute from.
+    fn process_computed(
+        &mut self,
+        select: &sqlparser::ast::Select,
+        order_by: &[OrderByExpr],
+        node: OpIndex,
+    ) -> Result<OpIndex, CrustyError> {
+        let mut exprs = Vec::new();
+        if let Some(expr) = &select.selection {
+            Self::collect_computed(expr, &mut exprs);
+        }
+        for item in &select.projection {
+            if let SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } = item {
+                Self::collect_computed(expr, &mut exprs);
+            }
+        }
+        for expr in &select.group_by {
+            Self::collect_computed(expr, &mut exprs);
+        }
+        for order in order_by {
+            Self::collect_computed(&order.expr, &mut exprs);
+        }
+        if exprs.is_empty() {
+            return Ok(node);
+        }
+        let schema = self.joined_schema()?;
+        let mut columns = Vec::new();
+        for expr in exprs {
+            let name = expr.to_string();
+            if self.computed.iter().any(|(f, _)| f.column() == name) {
+                continue;
+            }
+            let (value, dtype) = self.process_value_expr(expr, &schema)?;
+            let table = self
+                .expr_table(expr)
+                .unwrap_or_else(|| self.tables[0].clone());
+            self.computed
+                .push((FieldIdentifier::new(&table, &name), dtype.clone()));
+            columns.push((Attribute::new(name, dtype), value));
+        }
+        let idx = self.plan.add_node(LogicalOp::Map(MapNode { columns }));
+        self.plan.add_edge(idx, node);
+        Ok(idx)
+    }
+
+    /// Parses an expression over the columns of a table to a ValueExpr and its type.
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Expression to parse.
+    /// * `schema` - Schema of the table the columns are in.
+    fn process_value_expr(
+        &self,
+        expr: &Expr,
+        schema: &TableSchema,
+    ) -> Result<(ValueExpr, DataType), CrustyError> {
+        match expr {
+            Expr::Identifier(_) | Expr::CompoundIdentifier(_) => {
+                let field = self.expr_to_ident(expr)?;
+                let column = field.column().rsplit('.').next().unwrap();
+                let i = *schema
+                    .get_field_index(field.column())
+                    .or_else(|| schema.get_field_index(column))
+                    .ok_or_else(|| {
+                        CrustyError::ValidationError(format!("Column {} does not exist", column))
+                    })?;
+                let dtype = schema.get_attribute(i).unwrap().dtype().clone();
+                Ok((ValueExpr::Column(i), dtype))
+            }
+            Expr::TypedString { .. } | Expr::Value(Value::Interval { .. }) => {
+                let (field, dtype) = Self::typed_literal(expr)?;
+                Ok((ValueExpr::Literal(field), dtype))
+            }
+            Expr::Extract { field, expr: inner } => {
+                let (value, dtype) = self.process_value_expr(inner, schema)?;
+                if !matches!(
+                    dtype,
+                    DataType::Date | DataType::Timestamp | DataType::Interval
+                ) {
+                    return Err(CrustyError::ValidationError(format!(
+                        "Cannot extract {} from {} of type {}",
+                        field, inner, dtype
+                    )));
+                }
+                let unit = TimeUnit::from(field);
+                Ok((ValueExpr::Extract(unit, Box::new(value)), DataType::Int))
+            }
+            Expr::Function(Function { name, args, .. }) if Self::is_date_trunc(expr) => {
+                let args: Vec<&Expr> = args
+                    .iter()
+                    .map(|arg| match arg {
+                        FunctionArg::Named { arg, .. } | FunctionArg::Unnamed(arg) => arg,
+                    })
+                    .collect();
+                let unit = match args[..] {
+                    [Expr::Value(Value::SingleQuotedString(unit)), _] => TimeUnit::parse(unit),
+                    _ => None,
+                };
+                let unit = unit.ok_or_else(|| {
+                    CrustyError::ValidationError(format!(
+                        "Expected {}('unit', value), got {}",
+                        name, expr
+                    ))
+                })?;
+                let (value, dtype) = self.process_value_expr(args[1], schema)?;
+                if !matches!(dtype, DataType::Date | DataType::Timestamp) {
+                    return Err(CrustyError::ValidationError(format!(
+                        "Cannot truncate {} of type {}",
+                        args[1], dtype
+                    )));
+                }
+                Ok((ValueExpr::Trunc(unit, Box::new(value)), dtype))
+            }
+            Expr::Value(Value::SingleQuotedString(s)) => Ok((
+                ValueExpr::Literal(Field::StringField(s.to_string())),
+                DataType::String,
+            )),
+            // NULL has no type of its own, and arithmetic on it stays NULL.
+            Expr::Value(Value::Null) => Ok((ValueExpr::Literal(Field::Null), DataType::Int)),
+            Expr::Value(Value::Number(s, _)) => Self::number_literal(s),
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr: inner,
+            } if Self::is_literal(expr) => match &**inner {
+                Expr::Value(Value::Number(s, _)) => Self::number_literal(&format!("-{}", s)),
+                _ => unreachable!(),
+            },
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr: inner,
+            } if !matches!(**inner, Expr::Value(_)) => {
+                let (value, dtype) = self.process_value_expr(inner, schema)?;
+                let zero = match dtype {
+                    DataType::Int => Field::IntField(0),
+                    DataType::BigInt => Field::BigIntField(0),
+                    DataType::Double => Field::DoubleField(Double(0.0)),
+                    DataType::Decimal(_, scale) => Field::DecimalField(0, scale),
+                    _ => {
+                        return Err(CrustyError::ValidationError(format!(
+                            "Cannot negate {} of type {}",
+                            inner, dtype
+                        )))
+                    }
+                };
+                let zero = Box::new(ValueExpr::Literal(zero));
+                Ok((
+                    ValueExpr::Arith(zero, ArithOp::Minus, Box::new(value)),
+                    dtype,
+                ))
+            }
+            Expr::Value(_) | Expr::UnaryOp { .. } => {
+                let attr = Attribute::new(String::from("?column?"), DataType::Int);
+                let field = Self::literal_to_field(expr, &attr).map_err(|_| {
+                    CrustyError::ValidationError(format!("Unsupported value {}", expr))
+                })?;
+                Ok((ValueExpr::Literal(field), DataType::Int))
+            }
+            Expr::Nested(expr) => self.process_value_expr(expr, schema),
+            Expr::BinaryOp { left, op, right } => {
+                let op = match op {
+                    BinaryOperator::Plus => ArithOp::Plus,
+                    BinaryOperator::Minus => ArithOp::Minus,
+                    BinaryOperator::Multiply => ArithOp::Multiply,
+                    BinaryOperator::Divide => ArithOp::Divide,
+                    BinaryOperator::Modulus => ArithOp::Modulo,
+                    _ => {
+                        return Err(CrustyError::ValidationError(format!(
+                            "Expected arithmetic op, got {}",
+                            op
+                        )))
+                    }
+                };
+                let (left_val, left_type) = self.process_value_expr(left, schema)?;
+                let (right_val, right_type) = self.process_value_expr(right, schema)?;
+                let dtype = op.result_type(&left_type, &right_type).ok_or_else(|| {
+                    CrustyError::ValidationError(format!(
+                        "Cannot apply {} to {} and {} in {}",
+                        op, left_type, right_type, expr
+                    ))
+                })?;
+                Ok((
+                    ValueExpr::Arith(Box::new(left_val), op, Box::new(right_val)),
+                    dtype,
+                ))
+            }
+            _ => Err(CrustyError::ValidationError(format!(
+                "Unsupported value {}",
+                expr
+            ))),
+        }
+    }
+
     /// Helper function to recursively process sqlparser::ast::Query
     ///
     /// # Arguments
@@ -112,7 +813,8 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
         match &query.body {
             SetExpr::Select(b) => {
                 let select = &*b;
-                self.process_select(select)
+                let limit = Self::process_limit(query)?;
+                self.process_select(select, &query.order_by, limit)
             }
             SetExpr::Query(_) => {
                 //TODO NOT HANDLED
@@ -151,18 +853,17 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
     /// # Arguments
     ///
     /// * `query` - AST of a select query to process.
-    fn process_select(&mut self, select: &sqlparser::ast::Select) -> Result<(), CrustyError> {
+    /// * `order_by` - ORDER BY of the query.
+    /// * `limit` - LIMIT and OFFSET of the query, if it has either.
+    fn process_select(
+        &mut self,
+        select: &sqlparser::ast::Select,
+        order_by: &[OrderByExpr],
+        limit: Option<LimitNode>,
+    ) -> Result<(), CrustyError> {
         // Pointer to the current node.
         let mut node = None;
 
-        // Distinct
-        if select.distinct {
-            //TODO NOT HANDLED
-            return Err(CrustyError::ValidationError(String::from(
-                "Distinct not supported ",
-            )));
-        }
-
         // Doesn't need the for loop rn but keeping for the future when cross products are supported.
         // From
         if select.from.len() > 1 {
@@ -180,59 +881,12 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
             }
         }
 
+        // Expressions computed from the columns
+        node = Some(self.process_computed(select, order_by, node.unwrap())?);
+
         // Where
         if let Some(expr) = &select.selection {
-            let predicate = self.process_binary_op(expr)?;
-            // table references in filter
-            let table = match &predicate {
-                Predicate::SimplePredicate(simple_predicate) => {
-                    match (&simple_predicate.left, &simple_predicate.right) {
-                        (PredExpr::Literal(_), PredExpr::Ident(id)) => id.table().to_string(),
-                        (PredExpr::Ident(id), PredExpr::Literal(_)) => id.table().to_string(),
-                        _ => {
-                            return Err(CrustyError::ValidationError(String::from("Only where predicates with at least one indentifier and at least one literal are supported")));
-                        }
-                    }
-                }
-                Predicate::CompoundPredicate(compound_predicates) => {
-                    let simple_predicate = &compound_predicates.simple_predicates[0];
-                    let table;
-                    match (&simple_predicate.left, &simple_predicate.right) {
-                        (PredExpr::Literal(_), PredExpr::Ident(id)) => table = id.table(),
-                        (PredExpr::Ident(id), PredExpr::Literal(_)) => table = id.table(),
-                        _ => {
-                            return Err(CrustyError::ValidationError(String::from("Only where predicates with at least one indentifier and at least one literal are supported")));
-                        }
-                    }
-                    for simple_predicate in &compound_predicates.simple_predicates {
-                        let id = match (&simple_predicate.left, &simple_predicate.right) {
-                            (PredExpr::Literal(_), PredExpr::Ident(id)) => id,
-                            (PredExpr::Ident(id), PredExpr::Literal(_)) => id,
-                            _ => {
-                                return Err(CrustyError::ValidationError(String::from("Only where predicates with at least one indentifier and at least one literal are supported")));
-                            }
-                        };
-                        if id.table() != table {
-                            return Err(CrustyError::ValidationError(String::from(
-                                "Where includes identifiers to columns in multiple tables",
-                            )));
-                        }
-                    }
-                    table.to_string()
-                }
-            };
-
-            let op = FilterNode { table, predicate };
-            let idx = self.plan.add_node(LogicalOp::Filter(op));
-            self.plan.add_edge(idx, node.unwrap());
-            node = Some(idx);
-        }
-
-        if select.having.is_some() {
-            //TODO NOT HANDLED
-            return Err(CrustyError::ValidationError(String::from(
-                "Having not supported",
-            )));
+            node = Some(self.process_where(expr, node.unwrap())?);
         }
 
         // Select
@@ -269,27 +923,44 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
             fields.push(field);
         }
 
-        // Aggregates and group by
-        if has_agg {
+        // Order by
+        let mut order_fields = Vec::new();
+        for order in order_by {
+            let field = self.order_by_field(&order.expr, &fields, wildcard)?;
+            if field.agg_op().is_some() {
+                has_agg = true;
+            }
+            let descending = order.asc == Some(false);
+            order_fields.push(OrderByField {
+                field,
+                descending,
+                // NULLs sort as the highest values
+                nulls_first: order.nulls_first.unwrap_or(descending),
+            });
+        }
+
+        // Aggregates and group by, which HAVING implies even without aggregates selected
+        if has_agg || select.having.is_some() {
             let mut group_by = Vec::new();
             {
                 let mut group_set = HashSet::new();
                 for expr in &select.group_by {
-                    let col = match expr {
-                        Expr::Identifier(name) => name,
+                    let field = match expr {
+                        Expr::Identifier(name) => self.disambiguate_name(vec![&name.value])?,
+                        _ if Self::is_computed(expr) => self.expr_to_ident(expr)?,
                         _ => {
                             return Err(CrustyError::ValidationError(String::from(
                                 "Group by unsupported expression",
                             )));
                         }
                     };
-                    let field = self.disambiguate_name(vec![&col.value])?;
                     group_set.insert(field.column().to_string());
                     group_by.push(field);
                 }
 
-                // Checks that only aggregates and group by fields are projected out
-                for f in &fields {
+                // Checks that only aggregates and group by fields are projected out or sorted by
+                let order = order_fields.iter().map(|o| &o.field);
+                for f in fields.iter().chain(order) {
                     if f.agg_op().is_none() && !group_set.contains(f.column()) {
                         return Err(CrustyError::ValidationError(format!(
                             "The expression '{}' must be part of an aggregate function or group by",
@@ -298,22 +969,50 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
                     }
                 }
             }
+
+            // Aggregates sorted by but not selected are also computed
+            let mut agg_fields = fields.clone();
+            for order in &order_fields {
+                let f = &order.field;
+                if f.agg_op().is_some() && !agg_fields.iter().any(|a| a.alias() == f.alias()) {
+                    agg_fields.push(f.clone());
+                }
+            }
+            let having = match &select.having {
+                Some(expr) => Some(self.process_having(expr, &group_by, &mut agg_fields)?),
+                None => None,
+            };
             let op = AggregateNode {
-                fields: fields.clone(),
-                group_by,
+                fields: agg_fields,
+                group_by: group_by.clone(),
             };
             let idx = self.plan.add_node(LogicalOp::Aggregate(op));
             self.plan.add_edge(idx, node.unwrap());
             node = Some(idx);
 
-            // Replace field column names with aliases to project
+            // Having, filtering the groups the aggregate outputs
+            if let Some(op) = having {
+                let idx = self.plan.add_node(LogicalOp::Filter(op));
+                self.plan.add_edge(idx, node.unwrap());
+                node = Some(idx);
+            }
+
+            // Replace field column names with the names the aggregate outputs them under
             fields = fields
                 .iter()
-                .map(|f| {
-                    let name = f.alias().unwrap_or_else(|| f.column());
-                    FieldIdentifier::new(f.table(), name)
-                })
+                .map(|f| Self::aggregate_output(f, &group_by))
                 .collect();
+            for order in &mut order_fields {
+                order.field = Self::aggregate_output(&order.field, &group_by);
+            }
+        }
+
+        // Distinct, which compares the tuples as projected, so that they are sorted and limited
+        // after the projection by the names it outputs the fields under
+        if select.distinct && !wildcard {
+            for order in &mut order_fields {
+                order.field = Self::distinct_order_field(&order.field, &fields)?;
+            }
         }
         let identifiers = if wildcard {
             ProjectIdentifiers::Wildcard
@@ -321,11 +1020,315 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
             ProjectIdentifiers::List(fields)
         };
         let op = ProjectNode { identifiers };
-        let idx = self.plan.add_node(LogicalOp::Project(op));
-        self.plan.add_edge(idx, node.unwrap());
+        if select.distinct {
+            let idx = self.plan.add_node(LogicalOp::Project(op));
+            self.plan.add_edge(idx, node.unwrap());
+            let distinct = self.plan.add_node(LogicalOp::Distinct(DistinctNode {}));
+            self.plan.add_edge(distinct, idx);
+            self.process_sort_limit(order_fields, limit, distinct);
+        } else {
+            let node = self.process_sort_limit(order_fields, limit, node.unwrap());
+            let idx = self.plan.add_node(LogicalOp::Project(op));
+            self.plan.add_edge(idx, node);
+        }
         Ok(())
     }
 
+    /// Parses a HAVING clause to a filter over the groups an aggregate outputs, referring to
+    /// aggregates and group by fields by the names the aggregate outputs them under. Adds the
+    /// aggregates it refers to that are not already computed to the aggregate fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Expression of the HAVING clause.
+    /// * `group_by` - Fields of the GROUP BY.
+    /// * `agg_fields` - Fields the aggregate computes.
+    fn process_having(
+        &self,
+        expr: &Expr,
+        group_by: &[FieldIdentifier],
+        agg_fields: &mut Vec<FieldIdentifier>,
+    ) -> Result<FilterNode, CrustyError> {
+        let mut output = |pred_expr: PredExpr| match pred_expr {
+            PredExpr::Aggregate(f) => {
+                // An aggregate already computed is read under the name it is computed as
+                let computed = agg_fields
+                    .iter()
+                    .find(|a| a.agg_op() == f.agg_op() && a.column() == f.column());
+                let f = match computed {
+                    Some(a) => a.clone(),
+                    None => {
+                        agg_fields.push(f.clone());
+                        f
+                    }
+                };
+                Ok(PredExpr::Aggregate(Self::aggregate_output(&f, group_by)))
+            }
+            PredExpr::Ident(f) => {
+                if !group_by.iter().any(|g| g.column() == f.column()) {
+                    return Err(CrustyError::ValidationError(format!(
+                        "The expression '{}' must be part of an aggregate function or group by",
+                        f.column()
+                    )));
+                }
+                Ok(PredExpr::Ident(Self::aggregate_output(&f, group_by)))
+            }
+            literal => Ok(literal),
+        };
+        let mut output_predicate = |p: SimplePredicate| -> Result<SimplePredicate, CrustyError> {
+            Ok(SimplePredicate {
+                left: output(p.left)?,
+                op: p.op,
+                right: output(p.right)?,
+            })
+        };
+        let predicate = match self.process_binary_op(expr)? {
+            Predicate::SimplePredicate(p) => Predicate::SimplePredicate(output_predicate(p)?),
+            Predicate::CompoundPredicate(CompoundPredicate {
+                op,
+                simple_predicates,
+            }) => Predicate::CompoundPredicate(CompoundPredicate {
+                op,
+                simple_predicates: simple_predicates
+                    .into_iter()
+                    .map(output_predicate)
+                    .collect::<Result<_, _>>()?,
+            }),
+        };
+        Ok(FilterNode {
+            table: self.tables[0].clone(),
+            predicate,
+        })
+    }
+
+    /// Creates the Sort and Limit LogicalOps of a query over node, adds them to self.plan, and
+    /// returns the OpIndex of the last one. Returns node if the query has neither.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_fields` - Fields of the ORDER BY.
+    /// * `limit` - LIMIT and OFFSET of the query, if it has either.
+    /// * `node` - Node producing the tuples to sort and limit.
+    fn process_sort_limit(
+        &mut self,
+        order_fields: Vec<OrderByField>,
+        limit: Option<LimitNode>,
+        mut node: OpIndex,
+    ) -> OpIndex {
+        if !order_fields.is_empty() {
+            let op = SortNode {
+                fields: order_fields,
+            };
+            let idx = self.plan.add_node(LogicalOp::Sort(op));
+            self.plan.add_edge(idx, node);
+            node = idx;
+        }
+
+        // Limit, directly above the sort so that it can keep only the first tuples
+        if let Some(op) = limit {
+            let idx = self.plan.add_node(LogicalOp::Limit(op));
+            self.plan.add_edge(idx, node);
+            node = idx;
+        }
+        node
+    }
+
+    /// Returns the field a SELECT DISTINCT outputs a field it sorts by as. The field must be
+    /// selected, as the tuples are only sorted once projected.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - Field of the ORDER BY.
+    /// * `fields` - Fields of the select list.
+    fn distinct_order_field(
+        field: &FieldIdentifier,
+        fields: &[FieldIdentifier],
+    ) -> Result<FieldIdentifier, CrustyError> {
+        fields
+            .iter()
+            .find(|f| f.column() == field.column() && f.agg_op() == field.agg_op())
+            .map(|f| FieldIdentifier::new(f.table(), f.alias().unwrap_or_else(|| f.column())))
+            .ok_or_else(|| {
+                CrustyError::ValidationError(format!(
+                    "For SELECT DISTINCT, ORDER BY expression '{}' must appear in select list",
+                    field.column()
+                ))
+            })
+    }
+
+    /// Returns the LimitNode for the LIMIT and OFFSET of a query, or None if it has neither.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Query whose LIMIT and OFFSET to process.
+    fn process_limit(query: &sqlparser::ast::Query) -> Result<Option<LimitNode>, CrustyError> {
+        let count = |expr: &Expr| match expr {
+            Expr::Value(Value::Number(n, _)) => n.parse::<usize>().ok(),
+            _ => None,
+        };
+        let limit = match &query.limit {
+            Some(expr) => Some(count(expr).ok_or_else(|| {
+                CrustyError::ValidationError(format!("Unsupported LIMIT {}", expr))
+            })?),
+            None => None,
+        };
+        let offset = match &query.offset {
+            Some(offset) => count(&offset.value).ok_or_else(|| {
+                CrustyError::ValidationError(format!("Unsupported OFFSET {}", offset.value))
+            })?,
+            None => 0,
+        };
+        if limit.is_none() && offset == 0 {
+            return Ok(None);
+        }
+        Ok(Some(LimitNode { limit, offset }))
+    }
+
+    /// Returns the field an ORDER BY expression sorts by: the field at a position in the select
+    /// list counting from 1, a selected field named by its alias, or an expression over the
+    /// columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Expression of the ORDER BY.
+    /// * `fields` - Fields of the select list.
+    /// * `wildcard` - Whether the select list is a wildcard, which has no positions.
+    fn order_by_field(
+        &self,
+        expr: &Expr,
+        fields: &[FieldIdentifier],
+        wildcard: bool,
+    ) -> Result<FieldIdentifier, CrustyError> {
+        match expr {
+            Expr::Value(Value::Number(n, _)) => {
+                if wildcard {
+                    return Err(CrustyError::ValidationError(String::from(
+                        "Cannot order by position with a wildcard select",
+                    )));
+                }
+                match n.parse::<usize>() {
+                    Ok(position) if (1..=fields.len()).contains(&position) => {
+                        Ok(fields[position - 1].clone())
+                    }
+                    _ => Err(CrustyError::ValidationError(format!(
+                        "ORDER BY position {} is not in select list",
+                        n
+                    ))),
+                }
+            }
+            Expr::Identifier(name) => match fields
+                .iter()
+                .find(|f| f.alias() == Some(name.value.as_str()))
+            {
+                Some(f) => Ok(f.clone()),
+                None => self.expr_to_ident(expr),
+            },
+            _ => {
+                let field = self.expr_to_ident(expr)?;
+                // An aggregate also selected is sorted by under its name in the select list
+                let selected = fields.iter().find(|f| {
+                    f.agg_op().is_some()
+                        && f.agg_op() == field.agg_op()
+                        && f.column() == field.column()
+                });
+                Ok(selected.cloned().unwrap_or(field))
+            }
+        }
+    }
+
+    /// Returns the field an aggregate outputs a selected field as, named by its alias for an
+    /// aggregate or by the name given in the GROUP BY for a group by field.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - Field of the select list.
+    /// * `group_by` - Fields of the GROUP BY.
+    fn aggregate_output(field: &FieldIdentifier, group_by: &[FieldIdentifier]) -> FieldIdentifier {
+        let group = group_by
+            .iter()
+            .find(|g| field.agg_op().is_none() && g.column() == field.column());
+        match group {
+            Some(g) => {
+                let name = g.alias().unwrap_or_else(|| g.column());
+                let mut output = FieldIdentifier::new(g.table(), name);
+                if let Some(alias) = field.alias() {
+                    output.set_alias(alias.to_string());
+                }
+                output
+            }
+            None => {
+                let name = field.alias().unwrap_or_else(|| field.column());
+                FieldIdentifier::new(field.table(), name)
+            }
+        }
+    }
+
+    /// Creates a Filter LogicalOp for a WHERE clause over node, adds it to self.plan,
+    /// and returns the OpIndex.
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Expression of the WHERE clause.
+    /// * `node` - Node producing the tuples to filter.
+    fn process_where(&mut self, expr: &Expr, node: OpIndex) -> Result<OpIndex, CrustyError> {
+        let predicate = self.process_binary_op(expr)?;
+        let simple_predicates = match &predicate {
+            Predicate::SimplePredicate(p) => std::slice::from_ref(p),
+            Predicate::CompoundPredicate(p) => &p.simple_predicates[..],
+        };
+        let is_agg = |e: &PredExpr| matches!(e, PredExpr::Aggregate(_));
+        if simple_predicates
+            .iter()
+            .any(|p| is_agg(&p.left) || is_agg(&p.right))
+        {
+            return Err(CrustyError::ValidationError(String::from(
+                "Aggregates are not allowed in WHERE, only in HAVING",
+            )));
+        }
+        // table references in filter
+        let table = match &predicate {
+            Predicate::SimplePredicate(simple_predicate) => {
+                match (&simple_predicate.left, &simple_predicate.right) {
+                    (PredExpr::Literal(_), PredExpr::Ident(id)) => id.table().to_string(),
+                    (PredExpr::Ident(id), PredExpr::Literal(_)) => id.table().to_string(),
+                    _ => {
+                        return Err(CrustyError::ValidationError(String::from("Only where predicates with at least one indentifier and at least one literal are supported")));
+                    }
+                }
+            }
+            Predicate::CompoundPredicate(compound_predicates) => {
+                let simple_predicate = &compound_predicates.simple_predicates[0];
+                let table = match (&simple_predicate.left, &simple_predicate.right) {
+                    (PredExpr::Literal(_), PredExpr::Ident(id)) => id.table(),
+                    (PredExpr::Ident(id), PredExpr::Literal(_)) => id.table(),
+                    _ => {
+                        return Err(CrustyError::ValidationError(String::from("Only where predicates with at least one indentifier and at least one literal are supported")));
+                    }
+                };
+                for simple_predicate in &compound_predicates.simple_predicates {
+                    let id = match (&simple_predicate.left, &simple_predicate.right) {
+                        (PredExpr::Literal(_), PredExpr::Ident(id)) => id,
+                        (PredExpr::Ident(id), PredExpr::Literal(_)) => id,
+                        _ => {
+                            return Err(CrustyError::ValidationError(String::from("Only where predicates with at least one indentifier and at least one literal are supported")));
+                        }
+                    };
+                    if id.table() != table {
+                        return Err(CrustyError::ValidationError(String::from(
+                            "Where includes identifiers to columns in multiple tables",
+                        )));
+                    }
+                }
+                table.to_string()
+            }
+        };
+
+        let op = FilterNode { table, predicate };
+        let idx = self.plan.add_node(LogicalOp::Filter(op));
+        self.plan.add_edge(idx, node);
+        Ok(idx)
+    }
+
     /// Creates a corresponding LogicalOp, adds it to self.plan, and returns the OpIndex.
     ///
     /// Helper function to process sqlparser::ast::TableFactor.
@@ -338,30 +1341,37 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
         tf: &sqlparser::ast::TableFactor,
     ) -> Result<OpIndex, CrustyError> {
         match tf {
-            TableFactor::Table { name, .. } => {
-                let name = get_name(name)?;
-                let table_id = self
-                    .catalog
-                    .get_table_id(&name)
-                    .ok_or_else(|| CrustyError::CrustyError("Missing Table".to_string()))?;
-                if !self.catalog.is_valid_table(table_id) {
-                    return Err(CrustyError::ValidationError(String::from(
-                        "Invalid table name",
-                    )));
-                }
-                self.tables.push(name.clone());
-                let op = ScanNode {
-                    alias: name,
-                    container_id: table_id,
-                };
-                Ok(self.plan.add_node(LogicalOp::Scan(op)))
-            }
+            TableFactor::Table { name, .. } => self.process_table(name),
             _ => Err(CrustyError::ValidationError(String::from(
                 "Nested joins and derived tables not supported",
             ))),
         }
     }
 
+    /// Creates a Scan LogicalOp for a table, adds it to self.plan, and returns the OpIndex.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the table to scan.
+    fn process_table(&mut self, name: &ObjectName) -> Result<OpIndex, CrustyError> {
+        let name = get_name(name)?;
+        let table_id = self
+            .catalog
+            .get_table_id(&name)
+            .ok_or_else(|| CrustyError::CrustyError("Missing Table".to_string()))?;
+        if !self.catalog.is_valid_table(table_id) {
+            return Err(CrustyError::ValidationError(String::from(
+                "Invalid table name",
+            )));
+        }
+        self.tables.push(name.clone());
+        let op = ScanNode {
+            alias: name,
+            container_id: table_id,
+        };
+        Ok(self.plan.add_node(LogicalOp::Scan(op)))
+    }
+
     /// Returns the name of the table from the node, if the node is a table level operator, like scan. Otherwise, return none.
     ///
     /// # Arguments
@@ -435,6 +1445,9 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
     /// * `expr` - Expression to parse.
     fn process_binary_op(&self, expr: &Expr) -> Result<Predicate, CrustyError> {
         match expr {
+            Expr::IsNull(_) | Expr::IsNotNull(_) => Ok(Predicate::SimplePredicate(
+                self.process_simple_predicate(expr)?,
+            )),
             Expr::BinaryOp { op, .. } => match Self::binary_op_to_predicate_op(op)? {
                 PredicateOp::SimplePredicateOp(_) => Ok(Predicate::SimplePredicate(
                     self.process_simple_predicate(expr)?,
@@ -457,16 +1470,73 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
     fn process_simple_predicate(&self, expr: &Expr) -> Result<SimplePredicate, CrustyError> {
         match expr {
             Expr::BinaryOp { left, op, right } => Ok(SimplePredicate {
-                left: self.expr_to_pred_expr(left)?,
-                right: self.expr_to_pred_expr(right)?,
+                left: self.coerce_pred_expr(self.expr_to_pred_expr(left)?, right)?,
+                right: self.coerce_pred_expr(self.expr_to_pred_expr(right)?, left)?,
                 op: Self::binary_op_to_simple_predicate_op(op)?,
             }),
+            Expr::IsNull(expr) => Ok(SimplePredicate {
+                left: self.expr_to_pred_expr(expr)?,
+                right: PredExpr::Literal(Field::Null),
+                op: SimplePredicateOp::IsNull,
+            }),
+            Expr::IsNotNull(expr) => Ok(SimplePredicate {
+                left: self.expr_to_pred_expr(expr)?,
+                right: PredExpr::Literal(Field::Null),
+                op: SimplePredicateOp::IsNotNull,
+            }),
             _ => Err(CrustyError::ValidationError(String::from(
                 "Expected binary operation",
             ))),
         }
     }
 
+    /// Converts a string literal compared with a date, timestamp or interval column to the
+    /// column's type, so that they compare in time rather than as strings.
+    ///
+    /// # Arguments
+    ///
+    /// * `pred_expr` - Side of the comparison to convert.
+    /// * `other` - Other side of the comparison.
+    fn coerce_pred_expr(&self, pred_expr: PredExpr, other: &Expr) -> Result<PredExpr, CrustyError> {
+        let s = match &pred_expr {
+            PredExpr::Literal(Field::StringField(s)) => s,
+            _ => return Ok(pred_expr),
+        };
+        let field = match self.expr_to_ident(other) {
+            Ok(field) => field,
+            Err(_) => return Ok(pred_expr),
+        };
+        let computed = self
+            .computed
+            .iter()
+            .find(|(f, _)| f.column() == field.column());
+        let attr = match computed {
+            Some((f, dtype)) => Some(Attribute::new(f.column().to_string(), dtype.clone())),
+            None => {
+                let column = field.column().rsplit('.').next().unwrap();
+                self.catalog
+                    .get_table_id(field.table())
+                    .and_then(|id| self.catalog.get_table_schema(id).ok())
+                    .and_then(|schema| {
+                        let i = *schema.get_field_index(column)?;
+                        schema.get_attribute(i).cloned()
+                    })
+            }
+        };
+        match attr {
+            Some(attr)
+                if matches!(
+                    attr.dtype(),
+                    DataType::Date | DataType::Timestamp | DataType::Interval
+                ) =>
+            {
+                let literal = Expr::Value(Value::SingleQuotedString(s.to_string()));
+                Ok(PredExpr::Literal(Self::literal_to_field(&literal, &attr)?))
+            }
+            _ => Ok(pred_expr),
+        }
+    }
+
     /// Parses an expression to a compound predicate.
     ///
     /// # Arguments
@@ -501,6 +1571,7 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
         test_compound_op: CompoundPredicateOp,
     ) -> Result<Vec<SimplePredicate>, CrustyError> {
         match expr {
+            Expr::IsNull(_) | Expr::IsNotNull(_) => Ok(vec![self.process_simple_predicate(expr)?]),
             Expr::BinaryOp { left, op, right } => match Self::binary_op_to_predicate_op(op)? {
                 PredicateOp::SimplePredicateOp(_) => Ok(vec![self.process_simple_predicate(expr)?]),
                 PredicateOp::CompoundPredicateOp(compound_op) => {
@@ -532,23 +1603,44 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
     /// * `expr` - Non-operator part of the expression to parse.
     fn expr_to_pred_expr(&self, expr: &Expr) -> Result<PredExpr, CrustyError> {
         match expr {
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr: inner,
+            } if Self::is_literal(expr) => match &**inner {
+                Expr::Value(Value::Number(s, _)) => Self::number_to_field(&format!("-{}", s))
+                    .map(PredExpr::Literal)
+                    .ok_or_else(|| {
+                        CrustyError::ValidationError(format!("Unsupported literal -{}", s))
+                    }),
+                _ => unreachable!(),
+            },
+            Expr::TypedString { .. } | Expr::Value(Value::Interval { .. }) => {
+                Ok(PredExpr::Literal(Self::typed_literal(expr)?.0))
+            }
             Expr::Value(val) => match val {
                 Value::Number(s, _) => {
-                    let i = s.parse::<i32>().map_err(|_| {
+                    let f = Self::number_to_field(s).ok_or_else(|| {
                         CrustyError::ValidationError(format!("Unsupported literal {}", s))
                     })?;
-                    let f = Field::IntField(i);
                     Ok(PredExpr::Literal(f))
                 }
+                Value::Boolean(b) => Ok(PredExpr::Literal(Field::BoolField(*b))),
                 Value::SingleQuotedString(s) => {
                     let f = Field::StringField(s.to_string());
                     Ok(PredExpr::Literal(f))
                 }
+                Value::Null => Ok(PredExpr::Literal(Field::Null)),
                 _ => Err(CrustyError::ValidationError(String::from(
                     "Unsupported literal in predicate",
                 ))),
             },
-            _ => Ok(PredExpr::Ident(self.expr_to_ident(expr)?)),
+            _ => {
+                let field = self.expr_to_ident(expr)?;
+                match field.agg_op() {
+                    Some(_) => Ok(PredExpr::Aggregate(field)),
+                    None => Ok(PredExpr::Ident(field)),
+                }
+            }
         }
     }
 
@@ -651,9 +1743,16 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
             .unwrap();
 
         match attr.dtype() {
-            DataType::Int => Ok(()),
-            DataType::String => match op {
-                AggOp::Count | AggOp::Max | AggOp::Min => Ok(()),
+            DataType::Int | DataType::BigInt | DataType::Double | DataType::Decimal(_, _) => Ok(()),
+            DataType::String
+            | DataType::Varchar(_)
+            | DataType::Boolean
+            | DataType::Date
+            | DataType::Timestamp
+            | DataType::Interval => match op {
+                AggOp::Count | AggOp::CountAll | AggOp::CountDistinct | AggOp::Max | AggOp::Min => {
+                    Ok(())
+                }
                 _ => Err(CrustyError::ValidationError(format!(
                     "Cannot perform operation {} on field {}",
                     op, alias,
@@ -662,6 +1761,26 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
         }
     }
 
+    /// Returns the field COUNT(*) is computed over. COUNT(*) counts rows rather than values,
+    /// so any column will do, and the first column of the first table is used.
+    fn count_all_field(&self) -> Result<FieldIdentifier, CrustyError> {
+        let table = self.tables.first().ok_or_else(|| {
+            CrustyError::ValidationError(String::from("COUNT(*) needs a table to count"))
+        })?;
+        let table_id = self
+            .catalog
+            .get_table_id(table)
+            .ok_or_else(|| CrustyError::CrustyError("Missing Table".to_string()))?;
+        let schema = self.catalog.get_table_schema(table_id)?;
+        let attr = schema.get_attribute(0).ok_or_else(|| {
+            CrustyError::ValidationError(format!("Table {} has no columns", table))
+        })?;
+        Ok(FieldIdentifier::new(
+            table,
+            &format!("{}.{}", table, attr.name()),
+        ))
+    }
+
     /// Converts a sqparser::ast::Expr to a LogicalOp::FieldIdent.
     ///
     /// # Arguments
@@ -669,12 +1788,27 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
     /// * `expr` - Expression to be converted.
     fn expr_to_ident(&self, expr: &Expr) -> Result<FieldIdentifier, CrustyError> {
         match expr {
+            _ if Self::is_computed(expr) => {
+                let name = expr.to_string();
+                self.computed
+                    .iter()
+                    .find(|(f, _)| f.column() == name)
+                    .map(|(f, _)| f.clone())
+                    .ok_or_else(|| {
+                        CrustyError::ValidationError(format!("Unsupported expression {}", expr))
+                    })
+            }
             Expr::Identifier(name) => self.disambiguate_name(vec![&name.value]),
             Expr::CompoundIdentifier(names) => {
                 self.disambiguate_name(names.iter().map(|s| s.value.as_ref()).collect())
             }
-            Expr::Function(Function { name, args, .. }) => {
-                let op = match &get_name(name)?.to_uppercase()[..] {
+            Expr::Function(Function {
+                name,
+                args,
+                distinct,
+                ..
+            }) => {
+                let mut op = match &get_name(name)?.to_uppercase()[..] {
                     "AVG" => AggOp::Avg,
                     "COUNT" => AggOp::Count,
                     "MAX" => AggOp::Max,
@@ -698,12 +1832,31 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
                 };
                 let mut field = match arg {
                     Expr::Identifier(_) | Expr::CompoundIdentifier(_) => self.expr_to_ident(arg)?,
+                    Expr::Wildcard if op == AggOp::Count => {
+                        op = AggOp::CountAll;
+                        self.count_all_field()?
+                    }
                     _ => {
                         return Err(CrustyError::ValidationError(String::from(
                             "Aggregate over unsupported expression",
                         )));
                     }
                 };
+                if *distinct {
+                    // The distinct values have the same minimum and maximum as all of them
+                    op = match op {
+                        AggOp::Avg => AggOp::AvgDistinct,
+                        AggOp::Count => AggOp::CountDistinct,
+                        AggOp::Sum => AggOp::SumDistinct,
+                        AggOp::Max | AggOp::Min => op,
+                        _ => {
+                            return Err(CrustyError::ValidationError(format!(
+                                "Unsupported DISTINCT in {}",
+                                expr
+                            )));
+                        }
+                    };
+                }
                 field.set_op(op);
                 field.default_alias();
                 self.validate_aggregate(&field)?;
@@ -716,6 +1869,179 @@ impl<'a, T: 'a + Catalog> TranslateAndValidate<'a, T> {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::database::Database;
+    use common::ids::StateType;
+    use common::table::Table;
+    use sqlparser::ast::Statement;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::parser::Parser;
+    use std::sync::{Arc, RwLock};
+
+    /// Creates a database with the table t(a int, b int).
+    fn new_db() -> Database {
+        let db = Database::new(String::from("test"));
+        let id = db
+            .get_new_container_id(StateType::BaseTable, Some(String::from("t")))
+            .unwrap();
+        let attrs = vec![
+            Attribute::new(String::from("a"), DataType::Int),
+            Attribute::new(String::from("b"), DataType::Int),
+        ];
+        let table = Table::new(String::from("t"), TableSchema::new(attrs));
+        db.tables
+            .write()
+            .unwrap()
+            .insert(id, Arc::new(RwLock::new(table)));
+        db
+    }
+
+    /// Translates a query over db to a logical plan.
+    fn translate(db: &Database, sql: &str) -> Result<LogicalPlan, CrustyError> {
+        let statements = Parser::parse_sql(&GenericDialect {}, sql).unwrap();
+        match &statements[0] {
+            Statement::Query(query) => TranslateAndValidate::from_sql(query, db),
+            _ => panic!("Not a query"),
+        }
+    }
+
+    /// Finds the aggregate of a plan and the filter over it, if it has one.
+    fn having(plan: &LogicalPlan) -> (AggregateNode, Option<FilterNode>) {
+        let mut aggregate = None;
+        let mut filter = None;
+        for (idx, _) in plan.node_references() {
+            match plan.get_operator(idx).unwrap() {
+                LogicalOp::Aggregate(op) => aggregate = Some((idx, op.clone())),
+                LogicalOp::Filter(op) => filter = Some((idx, op.clone())),
+                _ => {}
+            }
+        }
+        let (agg_idx, aggregate) = aggregate.expect("No aggregate");
+        let filter = filter.map(|(idx, op)| {
+            assert!(plan.edges(idx).any(|e| e == agg_idx));
+            op
+        });
+        (aggregate, filter)
+    }
+
+    /// Counts the aggregates an aggregate node computes.
+    fn aggregates(aggregate: &AggregateNode) -> usize {
+        aggregate
+            .fields
+            .iter()
+            .filter(|f| f.agg_op().is_some())
+            .count()
+    }
+
+    /// Gets the simple predicates of a predicate.
+    fn simple_predicates(predicate: &Predicate) -> Vec<&SimplePredicate> {
+        match predicate {
+            Predicate::SimplePredicate(p) => vec![p],
+            Predicate::CompoundPredicate(p) => p.simple_predicates.iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_having_aggregate_not_selected() {
+        let db = new_db();
+        let plan = translate(&db, "SELECT b FROM t GROUP BY b HAVING SUM(a) > 3").unwrap();
+        let (aggregate, filter) = having(&plan);
+        let sum = aggregate
+            .fields
+            .iter()
+            .find(|f| f.agg_op() == Some(AggOp::Sum))
+            .expect("SUM(a) not computed");
+        assert_eq!(sum.column(), "t.a");
+        let filter = filter.expect("No HAVING filter");
+        match &simple_predicates(&filter.predicate)[..] {
+            [SimplePredicate {
+                left: PredExpr::Aggregate(f),
+                op: SimplePredicateOp::GreaterThan,
+                right: PredExpr::Literal(Field::IntField(3)),
+            }] => assert_eq!(Some(f.column()), sum.alias()),
+            p => panic!("Unexpected HAVING predicate {:?}", p),
+        }
+    }
+
+    #[test]
+    fn test_having_reuses_selected_aggregate() {
+        let db = new_db();
+        let sql = "SELECT b, COUNT(a) FROM t GROUP BY b HAVING COUNT(a) > 1";
+        let (aggregate, filter) = having(&translate(&db, sql).unwrap());
+        assert_eq!(aggregates(&aggregate), 1);
+        assert!(filter.is_some());
+    }
+
+    #[test]
+    fn test_having_group_by_column() {
+        let db = new_db();
+        let sql = "SELECT b, COUNT(a) FROM t GROUP BY b HAVING b = 2";
+        let (_, filter) = having(&translate(&db, sql).unwrap());
+        match &simple_predicates(&filter.unwrap().predicate)[..] {
+            [SimplePredicate {
+                left: PredExpr::Ident(f),
+                op: SimplePredicateOp::Equals,
+                ..
+            }] => assert_eq!(f.column(), "b"),
+            p => panic!("Unexpected HAVING predicate {:?}", p),
+        }
+    }
+
+    #[test]
+    fn test_having_or() {
+        let db = new_db();
+        let sql = "SELECT b FROM t GROUP BY b HAVING COUNT(a) > 1 OR MAX(a) = 4";
+        let (aggregate, filter) = having(&translate(&db, sql).unwrap());
+        assert_eq!(aggregates(&aggregate), 2);
+        match filter.unwrap().predicate {
+            Predicate::CompoundPredicate(CompoundPredicate {
+                op: CompoundPredicateOp::Or,
+                simple_predicates,
+            }) => {
+                assert_eq!(simple_predicates.len(), 2);
+                for p in simple_predicates {
+                    assert!(matches!(p.left, PredExpr::Aggregate(_)));
+                }
+            }
+            p => panic!("Unexpected HAVING predicate {:?}", p),
+        }
+    }
+
+    #[test]
+    fn test_having_without_group_by() {
+        let db = new_db();
+        let sql = "SELECT COUNT(a) FROM t HAVING COUNT(a) > 1";
+        let (aggregate, filter) = having(&translate(&db, sql).unwrap());
+        assert!(aggregate.group_by.is_empty());
+        assert!(filter.is_some());
+    }
+
+    #[test]
+    fn test_having_refused() {
+        let db = new_db();
+        let refused = |sql| match translate(&db, sql) {
+            Err(CrustyError::ValidationError(e)) => e,
+            r => panic!("{} translated to {:?}", sql, r.map(|p| p.to_json())),
+        };
+        // Column neither grouped nor aggregated
+        assert_eq!(
+            refused("SELECT b FROM t GROUP BY b HAVING a > 1"),
+            "The expression 't.a' must be part of an aggregate function or group by"
+        );
+        assert_eq!(
+            refused("SELECT COUNT(a) FROM t HAVING b > 1"),
+            "The expression 't.b' must be part of an aggregate function or group by"
+        );
+        // Aggregates filter groups, not rows
+        assert_eq!(
+            refused("SELECT b FROM t WHERE COUNT(a) > 1 GROUP BY b"),
+            "Aggregates are not allowed in WHERE, only in HAVING"
+        );
+    }
+}
+
 /* FIXME
 #[cfg(test)]
 mod test {
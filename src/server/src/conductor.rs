@@ -1,12 +1,11 @@
-use sqlparser::ast::Statement;
+use sqlparser::ast::{ObjectType, Statement, TransactionIsolationLevel, TransactionMode};
 use std::sync::Arc;
 
 use crate::queryexe::query::TranslateAndValidate;
-use common::ids::LogicalTimeStamp;
+use common::ids::{IsolationLevel, LogicalTimeStamp, TransactionId};
 use common::physical_plan::PhysicalPlan;
 use common::{get_name, testutil, CrustyError, QueryResult};
 use optimizer::optimizer::Optimizer;
-use txn_manager::transactions::Transaction;
 
 use crate::csv_utils;
 use crate::database_state::DatabaseState;
@@ -122,7 +121,9 @@ impl Conductor {
                     server_state.begin_query(query_name, None, timestamp, client_id)?;
 
                 // Run query.
-                self.run_query(query_plan, db_state, timestamp)?;
+                server_state.run_in_transaction(client_id, |tid| {
+                    self.run_query(query_plan, db_state, timestamp, tid)
+                })?;
 
                 // Update metadata after finishing query.
                 server_state.finish_query(query_name, client_id)?;
@@ -221,6 +222,20 @@ impl Conductor {
                     None => Ok(String::from("No active DB or DB not found")),
                 }
             }
+            commands::Commands::ShowIndexes => {
+                info!("Processing COMMAND::ShowIndexes");
+                let db_id_ref = server_state.active_connections.read().unwrap();
+                match db_id_ref.get(&client_id) {
+                    Some(db_id) => {
+                        let db_ref = server_state.id_to_db.read().unwrap();
+                        let db_state = db_ref.get(db_id).unwrap();
+
+                        let index_names = db_state.get_index_names()?;
+                        Ok(index_names)
+                    }
+                    None => Ok(String::from("No active DB or DB not found")),
+                }
+            }
             commands::Commands::ShowQueries => {
                 info!("Processing COMMAND::ShowQueries");
                 let db_id_ref = server_state.active_connections.read().unwrap();
@@ -306,12 +321,16 @@ impl Conductor {
     /// # Arguments
     ///
     /// * `cmd` - Tokenized command into statements.
-    /// * `id` - Thread id for lock management.
+    /// * `db_state` - Database the client is connected to.
+    /// * `client_id` - id of client running the statements.
+    /// * `server_state` - the shared ref to server, which holds the client's transaction.
     #[allow(unused_variables)]
     pub fn run_sql(
         &mut self,
         cmd: Vec<Statement>,
         db_state: &'static DatabaseState,
+        client_id: u64,
+        server_state: &'static ServerState,
     ) -> Result<common::QueryResult, CrustyError> {
         if cmd.is_empty() {
             Err(CrustyError::CrustyError(String::from("Empty SQL command")))
@@ -326,6 +345,29 @@ impl Conductor {
                     info!("Processing CREATE table: {:?}", table_name);
                     db_state.create_table(&get_name(table_name)?, columns, constraints)
                 }
+                Statement::CreateIndex {
+                    name,
+                    table_name,
+                    columns,
+                    unique,
+                    if_not_exists,
+                } => {
+                    info!("Processing CREATE INDEX {:?} on {:?}", name, table_name);
+                    no_transaction(server_state, client_id, "CREATE INDEX")?;
+                    let method = SQLParser::index_method(name);
+                    let (name, table_name) = (get_name(name)?, get_name(table_name)?);
+                    server_state.run_in_transaction(client_id, |tid| {
+                        db_state.create_index(
+                            &name,
+                            &table_name,
+                            columns,
+                            *unique,
+                            method,
+                            *if_not_exists,
+                            tid,
+                        )
+                    })
+                }
                 Statement::Query(qbox) => {
                     debug!("Processing SQL Query");
                     let db = &db_state.database;
@@ -343,20 +385,131 @@ impl Conductor {
                         self.optimizer
                             .logical_plan_to_physical_plan(logical_plan, db, false)?;
                     debug!("physical plan {:?}", physical_plan);
-                    self.run_query(
-                        Arc::new(physical_plan),
-                        db_state,
-                        db_state.get_current_time(),
-                    )
+                    let physical_plan = Arc::new(physical_plan);
+                    server_state.run_in_transaction(client_id, |tid| {
+                        self.run_query(physical_plan, db_state, db_state.get_current_time(), tid)
+                    })
+                }
+                Statement::StartTransaction { modes } => {
+                    info!("Processing BEGIN for client {:?}", client_id);
+                    let msg = server_state.begin_transaction(client_id, isolation_level(modes))?;
+                    Ok(QueryResult::new(&msg))
+                }
+                Statement::SetTransaction { modes } => match isolation_level(modes) {
+                    Some(isolation) => {
+                        info!("Setting isolation {:?} for client {:?}", isolation, client_id);
+                        Ok(QueryResult::new(&server_state.set_isolation(client_id, isolation)))
+                    }
+                    None => Err(CrustyError::CrustyError(String::from(
+                        "Only the isolation level can be set",
+                    ))),
+                },
+                Statement::Commit { .. } => {
+                    info!("Processing COMMIT for client {:?}", client_id);
+                    let msg = server_state.commit_transaction(client_id)?;
+                    Ok(QueryResult::new(&msg))
+                }
+                Statement::Rollback { .. } => {
+                    info!("Processing ROLLBACK for client {:?}", client_id);
+                    let msg = server_state.rollback_transaction(client_id)?;
+                    Ok(QueryResult::new(&msg))
                 }
                 Statement::Insert {
                     table_name,
                     columns,
                     source,
                     ..
-                } => Err(CrustyError::CrustyError(String::from(
-                    "Inserts not currently supported",
-                ))),
+                } => {
+                    info!("Processing INSERT into {:?}", table_name);
+                    let db = &db_state.database;
+                    let logical_plan =
+                        TranslateAndValidate::from_insert(table_name, columns, source, db)?;
+                    let physical_plan =
+                        self.optimizer
+                            .logical_plan_to_physical_plan(logical_plan, db, false)?;
+                    let physical_plan = Arc::new(physical_plan);
+                    let count = server_state.run_in_transaction(client_id, |tid| {
+                        self.run_count(physical_plan, db_state, db_state.get_current_time(), tid)
+                    })?;
+                    Ok(QueryResult::new(&format!("{} rows inserted", count)))
+                }
+                Statement::Update {
+                    table_name,
+                    assignments,
+                    selection,
+                } => {
+                    info!("Processing UPDATE of {:?}", table_name);
+                    let db = &db_state.database;
+                    let logical_plan =
+                        TranslateAndValidate::from_update(table_name, assignments, selection, db)?;
+                    let physical_plan =
+                        self.optimizer
+                            .logical_plan_to_physical_plan(logical_plan, db, false)?;
+                    let physical_plan = Arc::new(physical_plan);
+                    let count = server_state.run_in_transaction(client_id, |tid| {
+                        self.run_count(physical_plan, db_state, db_state.get_current_time(), tid)
+                    })?;
+                    Ok(QueryResult::new(&format!("{} rows updated", count)))
+                }
+                Statement::Delete {
+                    table_name,
+                    selection,
+                } => {
+                    info!("Processing DELETE from {:?}", table_name);
+                    let db = &db_state.database;
+                    let logical_plan =
+                        TranslateAndValidate::from_delete(table_name, selection, db)?;
+                    let physical_plan =
+                        self.optimizer
+                            .logical_plan_to_physical_plan(logical_plan, db, false)?;
+                    let physical_plan = Arc::new(physical_plan);
+                    let count = server_state.run_in_transaction(client_id, |tid| {
+                        self.run_count(physical_plan, db_state, db_state.get_current_time(), tid)
+                    })?;
+                    Ok(QueryResult::new(&format!("{} rows deleted", count)))
+                }
+                Statement::Drop {
+                    object_type,
+                    if_exists,
+                    names,
+                    cascade,
+                    ..
+                } => {
+                    info!("Processing DROP of {:?}", names);
+                    let names = names.iter().map(get_name).collect::<Result<Vec<_>, _>>()?;
+                    match object_type {
+                        ObjectType::Table => {
+                            no_transaction(server_state, client_id, "DROP TABLE")?;
+                            db_state.drop_tables(&names, *if_exists, *cascade)
+                        }
+                        ObjectType::Index => {
+                            no_transaction(server_state, client_id, "DROP INDEX")?;
+                            db_state.drop_indexes(&names, *if_exists)
+                        }
+                        _ => Err(CrustyError::CrustyError(String::from(
+                            "Only DROP TABLE and DROP INDEX are supported",
+                        ))),
+                    }
+                }
+                Statement::Truncate {
+                    table_name,
+                    partitions,
+                } => {
+                    info!("Processing TRUNCATE of {:?}", table_name);
+                    let cascade = SQLParser::truncate_cascades(partitions);
+                    if partitions.is_some() && !cascade {
+                        return Err(CrustyError::CrustyError(String::from(
+                            "TRUNCATE with partitions is not supported",
+                        )));
+                    }
+                    no_transaction(server_state, client_id, "TRUNCATE")?;
+                    db_state.truncate_table(&get_name(table_name)?, cascade)
+                }
+                Statement::AlterTable { name, operation } => {
+                    info!("Processing ALTER of {:?}", name);
+                    no_transaction(server_state, client_id, "ALTER TABLE")?;
+                    db_state.alter_table(&get_name(name)?, operation)
+                }
                 _ => Err(CrustyError::CrustyError(String::from("Not supported"))),
             }
         }
@@ -367,37 +520,90 @@ impl Conductor {
     /// # Arguments
     ///
     /// * `query` - Query to run.
-    /// * `id` - Thread id for lock management.
-    #[allow(unused_variables)]
+    /// * `tid` - Transaction to run the query in.
     fn run_query(
         &mut self,
         physical_plan: Arc<PhysicalPlan>,
         db_state: &'static DatabaseState,
         timestamp: LogicalTimeStamp,
+        tid: TransactionId,
     ) -> Result<QueryResult, CrustyError> {
-        let db = &db_state.database;
+        self.configure_query(physical_plan, db_state, timestamp, tid)?;
+
+        // Finally, execute the query
+        debug!("Executing query");
+        self.executor.execute()
+    }
+
+    /// Runs a plan that modifies a table and returns the number of rows changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `physical_plan` - Plan to run.
+    /// * `tid` - Transaction to run the plan in.
+    fn run_count(
+        &mut self,
+        physical_plan: Arc<PhysicalPlan>,
+        db_state: &'static DatabaseState,
+        timestamp: LogicalTimeStamp,
+        tid: TransactionId,
+    ) -> Result<usize, CrustyError> {
+        self.configure_query(physical_plan, db_state, timestamp, tid)?;
+        debug!("Executing modification");
+        self.executor.execute_count()
+    }
 
-        // Start transaction
-        let txn = Transaction::new();
+    /// Sets the executor up to run a physical plan.
+    fn configure_query(
+        &mut self,
+        physical_plan: Arc<PhysicalPlan>,
+        db_state: &'static DatabaseState,
+        timestamp: LogicalTimeStamp,
+        tid: TransactionId,
+    ) -> Result<(), CrustyError> {
+        let db = &db_state.database;
 
         debug!("Configuring Storage Manager");
         let op_iterator = Executor::physical_plan_to_op_iterator(
             db_state.storage_manager,
             db,
             &physical_plan,
-            txn.tid(),
+            tid,
             timestamp,
         )?;
         // We populate the executor with the state: physical plan, and storage manager ref
         debug!("Configuring Physical Plan");
         self.executor.configure_query(op_iterator);
+        Ok(())
+    }
+}
 
-        // Finally, execute the query
-        debug!("Executing query");
-        let res = self.executor.execute();
-        match res {
-            Ok(qr) => Ok(qr),
-            Err(e) => Err(e),
-        }
+/// Refuses a statement that changes a table's heap file or schema while the client has a
+/// transaction open, since rolling the transaction back could not undo it.
+fn no_transaction(
+    server_state: &ServerState,
+    client_id: u64,
+    statement: &str,
+) -> Result<(), CrustyError> {
+    if server_state.in_transaction(client_id) {
+        return Err(CrustyError::CrustyError(format!(
+            "{} cannot run inside a transaction",
+            statement
+        )));
     }
+    Ok(())
+}
+
+/// The isolation level a BEGIN or SET TRANSACTION asks for, if any. Read uncommitted runs
+/// as read committed, and repeatable read and serializable as snapshot isolation.
+fn isolation_level(modes: &[TransactionMode]) -> Option<IsolationLevel> {
+    modes.iter().rev().find_map(|mode| match mode {
+        TransactionMode::IsolationLevel(level) => Some(match level {
+            TransactionIsolationLevel::ReadUncommitted
+            | TransactionIsolationLevel::ReadCommitted => IsolationLevel::ReadCommitted,
+            TransactionIsolationLevel::RepeatableRead
+            | TransactionIsolationLevel::Serializable => IsolationLevel::Snapshot,
+        }),
+        _ => None,
+    })
 }
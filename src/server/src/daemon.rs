@@ -1,6 +1,6 @@
-use crate::ServerState;
+use crate::{ServerState, StorageTrait};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub(crate) struct Daemon {
     _server_state: &'static ServerState,
@@ -8,11 +8,28 @@ pub(crate) struct Daemon {
 }
 
 impl Daemon {
-    pub(crate) fn new(server_state: &'static ServerState, sleep_sec: u64) -> Self {
+    /// Start the daemon thread, which vacuums the storage manager every interval and checks
+    /// for deadlocks between transactions every deadlock_interval.
+    pub(crate) fn new(
+        server_state: &'static ServerState,
+        interval: Duration,
+        deadlock_interval: Duration,
+    ) -> Self {
         // This should be async or moved into the workers
+        let mut last_vacuum = Instant::now();
         let thread = std::thread::spawn(move || loop {
-            debug!("Daemon doing stuff");
-            thread::sleep(Duration::new(sleep_sec, 0));
+            let victims = server_state.storage_manager.detect_deadlocks();
+            if !victims.is_empty() {
+                info!("Daemon aborted deadlocked transactions {:?}", victims);
+            }
+            if last_vacuum.elapsed() >= interval {
+                match server_state.storage_manager.vacuum() {
+                    Ok(removed) => debug!("Daemon vacuumed {} dead versions", removed),
+                    Err(e) => error!("Daemon vacuum failed: {:?}", e),
+                }
+                last_vacuum = Instant::now();
+            }
+            thread::sleep(deadlock_interval);
         });
         Daemon {
             _server_state: server_state,
@@ -20,3 +37,52 @@ impl Daemon {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::ids::TransactionId;
+    use common::testutil::gen_random_dir;
+    use common::CrustyError;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_daemon_aborts_deadlock() {
+        let path = gen_random_dir().to_string_lossy().to_string();
+        let (sender, _receiver) = mpsc::channel();
+        let state = ServerState::new(path.clone(), path, sender).unwrap();
+        let state: &'static ServerState = Box::leak(Box::new(state));
+        let sm = state.storage_manager;
+        // Only the daemon can break the deadlock before the lock timeout
+        sm.set_lock_timeout(Duration::from_secs(60));
+        let _daemon = Daemon::new(state, Duration::from_secs(60), Duration::from_millis(10));
+
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        let setup = TransactionId::new();
+        let a = sm.insert_value(cid, vec![0], setup);
+        let b = sm.insert_value(cid, vec![1], setup);
+        sm.transaction_finished(setup);
+
+        let t1 = TransactionId::new();
+        let t2 = TransactionId::new();
+        sm.update_value(vec![2], a, t1).unwrap();
+        sm.update_value(vec![3], b, t2).unwrap();
+        let spawn = |tid, id| {
+            thread::spawn(move || {
+                let res = sm.update_value(vec![4], id, tid).map(|_| ());
+                sm.transaction_finished(tid);
+                res
+            })
+        };
+        let h1 = spawn(t1, b);
+        let h2 = spawn(t2, a);
+        // The younger transaction is the victim, which lets the older one finish
+        assert_eq!(
+            Err(CrustyError::TransactionAbortedError),
+            h2.join().unwrap()
+        );
+        assert_eq!(Ok(()), h1.join().unwrap());
+        sm.reset().unwrap();
+    }
+}
@@ -4,22 +4,27 @@ use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::ops::Bound;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
+use crate::queryexe::query::TranslateAndValidate;
 use crate::{StorageManager, StorageTrait};
 use common::catalog::Catalog;
 use common::database::Database;
-use common::ids::{AtomicTimeStamp, StateMeta, StateType};
+use common::foreign_key::{ForeignKey, ReferentialAction};
+use common::ids::{AtomicTimeStamp, Permissions, StateMeta, StateType};
+use common::index::{IndexMethod, SecondaryIndex};
 use common::physical_plan::PhysicalPlan;
 use common::prelude::*;
 use common::table::Table;
-use common::{get_attr, Attribute, QueryResult};
-use sqlparser::ast::ColumnDef;
+use common::{get_attr, get_name, Attribute, Field, QueryResult};
+use sqlparser::ast::OrderByExpr;
 use sqlparser::ast::TableConstraint;
+use sqlparser::ast::{self, AlterTableOperation, ColumnDef, ColumnOption, Expr, Ident};
 
 use crate::query_registrar::QueryRegistrar;
-use crate::sql_parser::{ParserResponse, SQLParser};
+use crate::sql_parser::{ForeignKeyDef, ParserResponse, SQLParser};
 
 use std::sync::atomic::AtomicU32;
 
@@ -132,6 +137,17 @@ impl DatabaseState {
             atomic_time: AtomicU32::new(0),
             query_registrar: QueryRegistrar::new(),
         };
+        let tables: Vec<(ContainerId, Arc<RwLock<Table>>)> = db_state
+            .database
+            .tables
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, table)| (*id, table.clone()))
+            .collect();
+        for (table_id, table) in tables {
+            open_indexes(sm, table_id, &table)?;
+        }
         Ok(db_state)
     }
 
@@ -190,6 +206,46 @@ impl DatabaseState {
         }
     }
 
+    /// Lists the indexes, each as `name ON table (columns)`, with USING HASH before the
+    /// columns of a hash index and followed by UNIQUE if it is.
+    pub fn get_index_names(&self) -> Result<String, CrustyError> {
+        let mut index_names = Vec::new();
+        {
+            let tables = self.database.get_tables();
+            let tables_ref = tables.read().unwrap();
+            for table in tables_ref.values() {
+                let table = table.read().unwrap();
+                for index in table.secondary_indexes() {
+                    let columns: Vec<&str> = index
+                        .columns
+                        .iter()
+                        .map(|i| table.schema.get_attribute(*i).unwrap().name())
+                        .collect();
+                    let method = match index.method {
+                        IndexMethod::BTree => "",
+                        IndexMethod::Hash => " USING HASH",
+                    };
+                    let unique = if index.unique { " UNIQUE" } else { "" };
+                    index_names.push(format!(
+                        "{} ON {}{} ({}){}",
+                        index.name,
+                        table.name,
+                        method,
+                        columns.join(", "),
+                        unique
+                    ));
+                }
+            }
+        }
+        index_names.sort();
+        let index_names = index_names.join("\n");
+        if index_names.is_empty() {
+            Ok(String::from("No indexes"))
+        } else {
+            Ok(index_names)
+        }
+    }
+
     pub fn get_registered_query_names(&self) -> Result<String, CrustyError> {
         self.query_registrar.get_registered_query_names()
     }
@@ -242,36 +298,72 @@ impl DatabaseState {
         columns: &[ColumnDef],
         constraints: &[TableConstraint],
     ) -> Result<QueryResult, CrustyError> {
-        // Constraints aren't implemented yet
-
-        let db = &self.database;
-        let mut tables_ref = db.tables.write().unwrap();
-        let table_id =
-            db.get_new_container_id(StateType::BaseTable, Some(table_name.to_string()))?;
         let pks = match SQLParser::get_pks(columns, constraints) {
             Ok(pks) => pks,
             Err(ParserResponse::SQLConstraintError(s)) => return Err(CrustyError::CrustyError(s)),
             _ => unreachable!(),
         };
+        let col_constraints = match SQLParser::get_column_constraints(columns, constraints, &pks) {
+            Ok(col_constraints) => col_constraints,
+            Err(ParserResponse::SQLConstraintError(s)) => return Err(CrustyError::CrustyError(s)),
+            _ => unreachable!(),
+        };
+        let fk_defs = match SQLParser::get_foreign_keys(columns, constraints) {
+            Ok(fk_defs) => fk_defs,
+            Err(ParserResponse::SQLConstraintError(s)) => return Err(CrustyError::CrustyError(s)),
+            _ => unreachable!(),
+        };
 
         let mut attributes: Vec<Attribute> = Vec::new();
-        for col in columns {
-            let constraint = if pks.contains(&col.name) {
-                common::Constraint::PrimaryKey
-            } else {
-                common::Constraint::None
-            };
-            let attr = Attribute {
+        for (col, constraint) in columns.iter().zip(col_constraints) {
+            let mut attr = Attribute {
                 name: col.name.value.clone(),
                 dtype: get_attr(&col.data_type)?,
                 constraint,
+                default: None,
             };
+            attr.default = column_default(col, &attr)?;
             attributes.push(attr);
         }
         let schema = TableSchema::new(attributes);
         debug!("Creating table with schema: {:?}", schema);
 
-        let table = Table::new(table_name.to_string(), schema);
+        let db = &self.database;
+        let mut tables_ref = db.tables.write().unwrap();
+        let table_id =
+            db.get_new_container_id(StateType::BaseTable, Some(table_name.to_string()))?;
+
+        let mut table = Table::new(table_name.to_string(), schema);
+        let mut container_ids = vec![table_id];
+        let res = fk_defs.into_iter().try_for_each(|fk_def| {
+            let fk = resolve_foreign_key(db, &tables_ref, &table, table_id, fk_def)?;
+            table.add_foreign_key(fk);
+            Ok(())
+        });
+        // The primary key and each unique column get a B+ tree to look their keys up in
+        let res = res.and_then(|_| {
+            for columns in table.unique_keys() {
+                let name = constraint_index_name(&table, &columns);
+                let index_id = db.get_new_container_id(StateType::Index, Some(name.clone()))?;
+                container_ids.push(index_id);
+                table.add_secondary_index(SecondaryIndex {
+                    name,
+                    container_id: index_id,
+                    columns,
+                    unique: true,
+                    method: IndexMethod::BTree,
+                    constraint: true,
+                });
+            }
+            Ok(())
+        });
+        if let Err(e) = res {
+            let mut named_containers = db.named_containers.write().unwrap();
+            for id in container_ids {
+                named_containers.remove(&id);
+            }
+            return Err(e);
+        }
         self.storage_manager.create_container(
             table_id,
             StorageManager::get_simple_config(),
@@ -279,10 +371,461 @@ impl DatabaseState {
             common::ids::StateType::BaseTable,
             None,
         )?;
-        tables_ref.insert(table_id, Arc::new(RwLock::new(table)));
+        let table = Arc::new(RwLock::new(table));
+        open_indexes(self.storage_manager, table_id, &table)?;
+        tables_ref.insert(table_id, table);
         Ok(QueryResult::new(&format!("Table {} created", table_name)))
     }
 
+    /// Drops tables, removing their heap files and catalog entries.
+    ///
+    /// Nothing is dropped unless every table can be.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_names` - Names of the tables to drop.
+    /// * `if_exists` - Skip tables that do not exist instead of failing.
+    /// * `cascade` - Also unregister the queries that use the tables and drop the foreign keys
+    ///   of other tables that refer to them.
+    pub fn drop_tables(
+        &self,
+        table_names: &[String],
+        if_exists: bool,
+        cascade: bool,
+    ) -> Result<QueryResult, CrustyError> {
+        let mut to_drop = Vec::new();
+        let mut msgs = Vec::new();
+        for table_name in table_names {
+            match self.database.get_table_id(table_name) {
+                Some(table_id) if to_drop.iter().any(|(_, id)| *id == table_id) => {}
+                Some(table_id) => {
+                    self.check_dependents("drop", table_name, table_id, cascade)?;
+                    to_drop.push((table_name, table_id));
+                }
+                None if if_exists => {
+                    msgs.push(format!("Table {} does not exist, skipping", table_name))
+                }
+                None => {
+                    return Err(CrustyError::CrustyError(format!(
+                        "Table {} does not exist",
+                        table_name
+                    )))
+                }
+            }
+        }
+        let dropped: Vec<ContainerId> = to_drop.iter().map(|(_, id)| *id).collect();
+        for (table_name, table_id) in &to_drop {
+            let referring = self.referring_keys(*table_id);
+            if let Some((_, child, fk)) = referring.iter().find(|(id, ..)| !dropped.contains(id)) {
+                if !cascade {
+                    return Err(CrustyError::CrustyError(format!(
+                        "Cannot drop table {} because foreign key {} of table {} refers to it; use CASCADE",
+                        table_name, fk.name, child
+                    )));
+                }
+            }
+        }
+        for (table_name, table_id) in to_drop {
+            for (child_id, child, fk) in self.referring_keys(table_id) {
+                if !dropped.contains(&child_id) {
+                    let child_ptr = self.database.get_table_ptr(child_id)?;
+                    child_ptr.write().unwrap().remove_foreign_keys_to(table_id);
+                    msgs.push(format!("Foreign key {} of table {} dropped", fk.name, child));
+                }
+            }
+            self.remove_dependents(table_id)?;
+            self.storage_manager.remove_container(table_id)?;
+            self.database.remove_table(table_id);
+            msgs.push(format!("Table {} dropped", table_name));
+        }
+        Ok(QueryResult::new(&msgs.join("\n")))
+    }
+
+    /// Removes every row of a table by replacing its heap file with an empty one.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Name of the table to truncate.
+    /// * `cascade` - Also unregister the queries that use the table and truncate the tables
+    ///   whose foreign keys refer to it.
+    pub fn truncate_table(
+        &self,
+        table_name: &str,
+        cascade: bool,
+    ) -> Result<QueryResult, CrustyError> {
+        let table_id = self.database.get_table_id(table_name).ok_or_else(|| {
+            CrustyError::CrustyError(format!("Table {} does not exist", table_name))
+        })?;
+        let mut to_truncate = vec![(table_name.to_string(), table_id)];
+        let mut i = 0;
+        while i < to_truncate.len() {
+            let (name, id) = to_truncate[i].clone();
+            self.check_dependents("truncate", &name, id, cascade)?;
+            for (child_id, child, fk) in self.referring_keys(id) {
+                if to_truncate.iter().any(|(_, t)| *t == child_id) {
+                    continue;
+                }
+                if !cascade {
+                    return Err(CrustyError::CrustyError(format!(
+                        "Cannot truncate table {} because foreign key {} of table {} refers to it; use CASCADE",
+                        name, fk.name, child
+                    )));
+                }
+                to_truncate.push((child, child_id));
+            }
+            i += 1;
+        }
+        let mut msgs = Vec::new();
+        for (name, id) in to_truncate {
+            self.remove_dependents(id)?;
+            self.storage_manager.remove_container(id)?;
+            self.storage_manager.create_container(
+                id,
+                StorageManager::get_simple_config(),
+                Some(name.clone()),
+                common::ids::StateType::BaseTable,
+                None,
+            )?;
+            let table_ptr = self.database.get_table_ptr(id)?;
+            open_indexes(self.storage_manager, id, &table_ptr)?;
+            msgs.push(format!("Table {} truncated", name));
+        }
+        Ok(QueryResult::new(&msgs.join("\n")))
+    }
+
+    /// Creates a secondary index on columns of a table and builds it from the rows stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_name` - Name of the new index.
+    /// * `table_name` - Name of the table to index.
+    /// * `columns` - Columns of the key, in the order they are compared.
+    /// * `unique` - Whether no two rows may share a key.
+    /// * `method` - How the index is stored.
+    /// * `if_not_exists` - Do nothing if an index with the name exists instead of failing.
+    /// * `tid` - Transaction to read the rows in, to check a unique index holds.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_index(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        columns: &[OrderByExpr],
+        unique: bool,
+        method: IndexMethod,
+        if_not_exists: bool,
+        tid: TransactionId,
+    ) -> Result<QueryResult, CrustyError> {
+        if if_not_exists && self.database.get_index_id(index_name).is_some() {
+            return Ok(QueryResult::new(&format!(
+                "Index {} already exists, skipping",
+                index_name
+            )));
+        }
+        // Unique indexes are checked by scanning their keys in order
+        if unique && method == IndexMethod::Hash {
+            return Err(CrustyError::ValidationError(format!(
+                "Cannot create unique index {}: hash indexes cannot be unique",
+                index_name
+            )));
+        }
+        let table_id = self.database.get_table_id(table_name).ok_or_else(|| {
+            CrustyError::CrustyError(format!("Table {} does not exist", table_name))
+        })?;
+        let table_ptr = self.database.get_table_ptr(table_id)?;
+        let columns = {
+            let table = table_ptr.read().unwrap();
+            columns
+                .iter()
+                .map(|c| index_column(&table, c))
+                .collect::<Result<Vec<usize>, CrustyError>>()?
+        };
+        let index_id = self
+            .database
+            .get_new_container_id(StateType::Index, Some(index_name.to_string()))?;
+        table_ptr
+            .write()
+            .unwrap()
+            .add_secondary_index(SecondaryIndex {
+                name: index_name.to_string(),
+                container_id: index_id,
+                columns: columns.clone(),
+                unique,
+                method,
+                constraint: false,
+            });
+        let mut res = self
+            .storage_manager
+            .open_index(index_id, table_id, table_ptr.clone());
+        if res.is_ok() && unique {
+            res = self.check_unique_index(index_id, &table_ptr, &columns, tid);
+            if res.is_err() {
+                self.storage_manager.drop_index(index_id)?;
+            }
+        }
+        if let Err(e) = res {
+            table_ptr.write().unwrap().remove_secondary_index(index_id);
+            self.database
+                .named_containers
+                .write()
+                .unwrap()
+                .remove(&index_id);
+            return Err(e);
+        }
+        Ok(QueryResult::new(&format!("Index {} created", index_name)))
+    }
+
+    /// Fails if two rows the transaction can see share a key of a unique index.
+    fn check_unique_index(
+        &self,
+        index_id: ContainerId,
+        table_ptr: &Arc<RwLock<Table>>,
+        columns: &[usize],
+        tid: TransactionId,
+    ) -> Result<(), CrustyError> {
+        let ids =
+            self.storage_manager
+                .index_scan(index_id, Bound::Unbounded, Bound::Unbounded, tid)?;
+        let table = table_ptr.read().unwrap();
+        let mut last: Option<Vec<Field>> = None;
+        for id in ids {
+            let bytes = self
+                .storage_manager
+                .get_value(id, tid, Permissions::ReadOnly)?;
+            let key = table.decode_fields(&bytes, columns);
+            if key.iter().any(Field::is_null) {
+                continue;
+            }
+            if last.as_ref() == Some(&key) {
+                let index = table.secondary_index(index_id).unwrap();
+                return Err(CrustyError::ValidationError(format!(
+                    "Cannot create unique index {}: key {} is duplicated",
+                    index.name,
+                    table.describe_key(columns, &key)
+                )));
+            }
+            last = Some(key);
+        }
+        Ok(())
+    }
+
+    /// Drops secondary indexes, removing their trees and catalog entries.
+    ///
+    /// Nothing is dropped unless every index can be.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_names` - Names of the indexes to drop.
+    /// * `if_exists` - Skip indexes that do not exist instead of failing.
+    pub fn drop_indexes(
+        &self,
+        index_names: &[String],
+        if_exists: bool,
+    ) -> Result<QueryResult, CrustyError> {
+        let mut to_drop = Vec::new();
+        let mut msgs = Vec::new();
+        for index_name in index_names {
+            match self.database.get_index_id(index_name) {
+                Some(index_id) if to_drop.iter().any(|(_, id)| *id == index_id) => {}
+                Some(index_id) => {
+                    self.check_index_droppable(index_name, index_id)?;
+                    to_drop.push((index_name, index_id))
+                }
+                None if if_exists => {
+                    msgs.push(format!("Index {} does not exist, skipping", index_name))
+                }
+                None => {
+                    return Err(CrustyError::CrustyError(format!(
+                        "Index {} does not exist",
+                        index_name
+                    )))
+                }
+            }
+        }
+        for (index_name, index_id) in to_drop {
+            if let Some(table_id) = self.database.get_index_table(index_id) {
+                let table_ptr = self.database.get_table_ptr(table_id)?;
+                table_ptr.write().unwrap().remove_secondary_index(index_id);
+            }
+            self.storage_manager.drop_index(index_id)?;
+            self.database
+                .named_containers
+                .write()
+                .unwrap()
+                .remove(&index_id);
+            msgs.push(format!("Index {} dropped", index_name));
+        }
+        Ok(QueryResult::new(&msgs.join("\n")))
+    }
+
+    /// Fails if an index enforces the primary key or a unique column of its table, which
+    /// only goes away with the key.
+    fn check_index_droppable(
+        &self,
+        index_name: &str,
+        index_id: ContainerId,
+    ) -> Result<(), CrustyError> {
+        let table_id = match self.database.get_index_table(index_id) {
+            Some(table_id) => table_id,
+            None => return Ok(()),
+        };
+        let table_ptr = self.database.get_table_ptr(table_id)?;
+        let table = table_ptr.read().unwrap();
+        match table.secondary_index(index_id) {
+            Some(index) if index.constraint => Err(CrustyError::CrustyError(format!(
+                "Cannot drop index {} because it enforces a key of table {}",
+                index_name, table.name
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Changes the columns or the name of a table. Rows already stored are left as they are
+    /// and upgraded to the new schema when read.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Name of the table to alter.
+    /// * `operation` - Change to make.
+    pub fn alter_table(
+        &self,
+        table_name: &str,
+        operation: &AlterTableOperation,
+    ) -> Result<QueryResult, CrustyError> {
+        let table_id = self.database.get_table_id(table_name).ok_or_else(|| {
+            CrustyError::CrustyError(format!("Table {} does not exist", table_name))
+        })?;
+        let table_ptr = self.database.get_table_ptr(table_id)?;
+        let cascade = matches!(
+            operation,
+            AlterTableOperation::DropColumn { cascade: true, .. }
+        );
+        self.check_dependents("alter", table_name, table_id, cascade)?;
+        let msg = match operation {
+            AlterTableOperation::AddColumn { column_def } => {
+                let (attr, default) = column_with_default(column_def)?;
+                table_ptr.write().unwrap().add_column(attr, default)?;
+                format!("Column {} added to {}", column_def.name, table_name)
+            }
+            AlterTableOperation::DropColumn {
+                column_name,
+                if_exists,
+                ..
+            } => {
+                let index = table_ptr
+                    .read()
+                    .unwrap()
+                    .schema
+                    .get_field_index(&column_name.value)
+                    .copied();
+                match index {
+                    None if *if_exists => {
+                        format!("Column {} does not exist, skipping", column_name)
+                    }
+                    None => {
+                        return Err(CrustyError::CrustyError(format!(
+                            "Column {} does not exist",
+                            column_name
+                        )))
+                    }
+                    Some(i) => {
+                        let referring = self.referring_keys(table_id);
+                        if let Some((_, child, fk)) =
+                            referring.iter().find(|(_, _, fk)| fk.parent_columns.contains(&i))
+                        {
+                            return Err(CrustyError::CrustyError(format!(
+                                "Cannot drop column {} because foreign key {} of table {} refers to it",
+                                column_name, fk.name, child
+                            )));
+                        }
+                        let dropped = table_ptr.write().unwrap().drop_column(&column_name.value)?;
+                        for index in dropped {
+                            self.storage_manager.drop_index(index.container_id)?;
+                            self.database
+                                .named_containers
+                                .write()
+                                .unwrap()
+                                .remove(&index.container_id);
+                        }
+                        let mut children: Vec<ContainerId> =
+                            referring.iter().map(|(id, ..)| *id).collect();
+                        children.dedup();
+                        for child_id in children {
+                            let child_ptr = self.database.get_table_ptr(child_id)?;
+                            child_ptr.write().unwrap().parent_column_dropped(table_id, i);
+                        }
+                        format!("Column {} dropped from {}", column_name, table_name)
+                    }
+                }
+            }
+            AlterTableOperation::RenameColumn {
+                old_column_name,
+                new_column_name,
+            } => {
+                table_ptr
+                    .write()
+                    .unwrap()
+                    .rename_column(&old_column_name.value, &new_column_name.value)?;
+                format!("Column {} renamed to {}", old_column_name, new_column_name)
+            }
+            AlterTableOperation::RenameTable { table_name: name } => {
+                let name = get_name(name)?;
+                self.database.rename_table(table_id, &name)?;
+                format!("Table {} renamed to {}", table_name, name)
+            }
+            _ => {
+                return Err(CrustyError::CrustyError(format!(
+                    "ALTER TABLE {} is not supported",
+                    operation
+                )))
+            }
+        };
+        self.remove_dependents(table_id)?;
+        Ok(QueryResult::new(&msg))
+    }
+
+    /// Fails if registered queries use the table and the statement does not cascade to them.
+    fn check_dependents(
+        &self,
+        action: &str,
+        table_name: &str,
+        table_id: ContainerId,
+        cascade: bool,
+    ) -> Result<(), CrustyError> {
+        let dependents = self.query_registrar.queries_using(table_id);
+        if !dependents.is_empty() && !cascade {
+            return Err(CrustyError::CrustyError(format!(
+                "Cannot {} table {} because registered queries {} depend on it; use CASCADE",
+                action,
+                table_name,
+                dependents.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Foreign keys of the tables referring to a table, including itself, with the container
+    /// and name of the table each belongs to.
+    fn referring_keys(&self, table_id: ContainerId) -> Vec<(ContainerId, String, ForeignKey)> {
+        let tables = self.database.tables.read().unwrap();
+        let mut res = Vec::new();
+        for (id, table) in tables.iter() {
+            let table = table.read().unwrap();
+            for fk in table.foreign_keys().iter().filter(|fk| fk.parent == table_id) {
+                res.push((*id, table.name.clone(), fk.clone()));
+            }
+        }
+        res.sort_by_key(|(id, ..)| *id);
+        res
+    }
+
+    /// Unregisters the queries that use the table.
+    fn remove_dependents(&self, table_id: ContainerId) -> Result<(), CrustyError> {
+        for query_name in self.query_registrar.queries_using(table_id) {
+            self.query_registrar.unregister_query(&query_name)?;
+        }
+        Ok(())
+    }
+
     pub fn reset(&self) -> Result<(), CrustyError> {
         self.query_registrar.reset()?;
         let mut conns = self.active_client_connections.write().unwrap();
@@ -336,3 +879,367 @@ impl DatabaseState {
         self.query_registrar.finish_query(query_name)
     }
 }
+
+/// Looks up the parent of a foreign key declared by CREATE TABLE and checks the columns it
+/// refers to form its primary key or a unique column.
+///
+/// # Arguments
+///
+/// * `db` - Database the table is created in.
+/// * `tables` - Tables of the database.
+/// * `table` - Table being created.
+/// * `table_id` - Container of the table being created.
+/// * `fk_def` - Foreign key as declared.
+fn resolve_foreign_key(
+    db: &Database,
+    tables: &HashMap<ContainerId, Arc<RwLock<Table>>>,
+    table: &Table,
+    table_id: ContainerId,
+    fk_def: ForeignKeyDef,
+) -> Result<ForeignKey, CrustyError> {
+    let no_table = || CrustyError::CrustyError(format!("Table {} does not exist", fk_def.parent));
+    let (parent_id, parent) = if fk_def.parent == table.name {
+        (table_id, table.clone())
+    } else {
+        let parent_id = db.get_table_id(&fk_def.parent).ok_or_else(no_table)?;
+        let parent = tables.get(&parent_id).ok_or_else(no_table)?;
+        let parent = parent.read().unwrap().clone();
+        (parent_id, parent)
+    };
+    let index = |t: &Table, column: &Ident| {
+        t.schema.get_field_index(&column.value).copied().ok_or_else(|| {
+            CrustyError::CrustyError(format!(
+                "Column {} does not exist in table {}",
+                column, t.name
+            ))
+        })
+    };
+    let columns = fk_def
+        .columns
+        .iter()
+        .map(|c| index(table, c))
+        .collect::<Result<Vec<usize>, CrustyError>>()?;
+    let unique_keys = parent.unique_keys();
+    let parent_columns = if fk_def.parent_columns.is_empty() {
+        unique_keys[0].clone()
+    } else {
+        fk_def
+            .parent_columns
+            .iter()
+            .map(|c| index(&parent, c))
+            .collect::<Result<Vec<usize>, CrustyError>>()?
+    };
+    let mut sorted = parent_columns.clone();
+    sorted.sort_unstable();
+    if !unique_keys.contains(&sorted) {
+        return Err(CrustyError::CrustyError(format!(
+            "Foreign key must refer to the primary key or a unique column of {}",
+            parent.name
+        )));
+    }
+    if columns.len() != parent_columns.len() {
+        return Err(CrustyError::CrustyError(format!(
+            "Foreign key on {} columns refers to {} columns of {}",
+            columns.len(),
+            parent_columns.len(),
+            parent.name
+        )));
+    }
+    for (c, p) in columns.iter().zip(parent_columns.iter()) {
+        let (c, p) = (
+            table.schema.get_attribute(*c).unwrap(),
+            parent.schema.get_attribute(*p).unwrap(),
+        );
+        if !c.dtype().accepts(p.dtype()) || !p.dtype().accepts(c.dtype()) {
+            return Err(CrustyError::CrustyError(format!(
+                "Column {} of type {:?} cannot refer to column {} of type {:?}",
+                c.name(),
+                c.dtype(),
+                p.name(),
+                p.dtype()
+            )));
+        }
+    }
+    let on_delete = match fk_def.on_delete {
+        None | Some(ast::ReferentialAction::Restrict) | Some(ast::ReferentialAction::NoAction) => {
+            ReferentialAction::Restrict
+        }
+        Some(ast::ReferentialAction::Cascade) => ReferentialAction::Cascade,
+        Some(ast::ReferentialAction::SetNull) => {
+            let not_null = columns
+                .iter()
+                .map(|c| table.schema.get_attribute(*c).unwrap())
+                .find(|attr| attr.constraint.is_not_null());
+            if let Some(attr) = not_null {
+                return Err(CrustyError::CrustyError(format!(
+                    "ON DELETE SET NULL cannot set column {} to NULL, as it is NOT NULL",
+                    attr.name()
+                )));
+            }
+            ReferentialAction::SetNull
+        }
+        Some(action) => {
+            return Err(CrustyError::CrustyError(format!(
+                "ON DELETE {} is not supported",
+                action
+            )))
+        }
+    };
+    let name = fk_def.name.unwrap_or_else(|| {
+        let names: Vec<&str> = fk_def.columns.iter().map(|c| c.value.as_str()).collect();
+        format!("{}_{}_fkey", table.name, names.join("_"))
+    });
+    Ok(ForeignKey {
+        name,
+        columns,
+        parent: parent_id,
+        parent_columns,
+        on_delete,
+    })
+}
+
+/// Opens the secondary indexes of a table in the storage manager, which builds the trees it
+/// does not hold from the rows of the table.
+///
+/// # Arguments
+///
+/// * `sm` - Storage manager holding the table.
+/// * `table_id` - Container of the table.
+/// * `table` - The table.
+fn open_indexes(
+    sm: &StorageManager,
+    table_id: ContainerId,
+    table: &Arc<RwLock<Table>>,
+) -> Result<(), CrustyError> {
+    let indexes = table.read().unwrap().secondary_indexes().to_vec();
+    for index in indexes {
+        sm.open_index(index.container_id, table_id, table.clone())?;
+    }
+    Ok(())
+}
+
+/// Name of the index enforcing a key of a table: the table name followed by pkey for the
+/// primary key, or by the column and key for a unique column.
+///
+/// # Arguments
+///
+/// * `table` - Table of the key.
+/// * `columns` - Columns of the key.
+fn constraint_index_name(table: &Table, columns: &[usize]) -> String {
+    let attr = table.schema.get_attribute(columns[0]).unwrap();
+    if attr.constraint == common::Constraint::PrimaryKey {
+        format!("{}_pkey", table.name)
+    } else {
+        format!("{}_{}_key", table.name, attr.name())
+    }
+}
+
+/// Finds the column a CREATE INDEX lists in a table. Only plain, ascending columns can be
+/// indexed.
+///
+/// # Arguments
+///
+/// * `table` - Table being indexed.
+/// * `column` - Column as listed.
+fn index_column(table: &Table, column: &OrderByExpr) -> Result<usize, CrustyError> {
+    if column.asc == Some(false) || column.nulls_first.is_some() {
+        return Err(CrustyError::CrustyError(format!(
+            "Index column {} must be in ascending order",
+            column.expr
+        )));
+    }
+    match &column.expr {
+        Expr::Identifier(name) => table
+            .schema
+            .get_field_index(&name.value)
+            .copied()
+            .ok_or_else(|| {
+                CrustyError::CrustyError(format!(
+                    "Column {} does not exist in table {}",
+                    name, table.name
+                ))
+            }),
+        expr => Err(CrustyError::CrustyError(format!(
+            "Cannot index expression {}",
+            expr
+        ))),
+    }
+}
+
+/// Reads the DEFAULT of a column, which is None without one or for DEFAULT NULL.
+///
+/// # Arguments
+///
+/// * `column_def` - Definition of the column.
+/// * `attr` - Column defined, whose type the DEFAULT must match.
+fn column_default(column_def: &ColumnDef, attr: &Attribute) -> Result<Option<Field>, CrustyError> {
+    let mut default = None;
+    for option in &column_def.options {
+        if let ColumnOption::Default(expr) = &option.option {
+            default = Some(TranslateAndValidate::<Database>::literal_to_field(expr, attr)?);
+        }
+    }
+    Ok(default.filter(|f| !f.is_null()))
+}
+
+/// Reads the column an ALTER TABLE ADD COLUMN adds, and the value it takes in existing rows,
+/// which is NULL without a DEFAULT.
+///
+/// # Arguments
+///
+/// * `column_def` - Definition of the column.
+fn column_with_default(column_def: &ColumnDef) -> Result<(Attribute, Field), CrustyError> {
+    let mut attr = Attribute::new(column_def.name.value.clone(), get_attr(&column_def.data_type)?);
+    for option in &column_def.options {
+        match &option.option {
+            ColumnOption::Unique { is_primary: true } => {
+                attr.constraint = common::Constraint::PrimaryKey
+            }
+            ColumnOption::NotNull => attr.constraint = common::Constraint::NotNull,
+            ColumnOption::ForeignKey { .. } => {
+                return Err(CrustyError::CrustyError(String::from(
+                    "Foreign keys can only be declared by CREATE TABLE",
+                )))
+            }
+            _ => {}
+        }
+    }
+    attr.default = column_default(column_def, &attr)?;
+    let default = attr.default.clone().unwrap_or(Field::Null);
+    if default.is_null() && attr.constraint.is_not_null() {
+        return Err(CrustyError::CrustyError(format!(
+            "Column {} needs a non-NULL DEFAULT to be added",
+            column_def.name
+        )));
+    }
+    Ok((attr, default))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlparser::ast::Statement;
+
+    fn new_db() -> DatabaseState {
+        let sm: &'static StorageManager = Box::leak(Box::new(StorageManager::new_test_sm()));
+        DatabaseState::new_from_name("test", sm).unwrap()
+    }
+
+    fn create(db: &DatabaseState, sql: &str) -> Result<QueryResult, CrustyError> {
+        match SQLParser::parse_sql(sql.to_string()) {
+            ParserResponse::SQL(ast) => match &ast[0] {
+                Statement::CreateTable {
+                    name,
+                    columns,
+                    constraints,
+                    ..
+                } => db.create_table(&get_name(name)?, columns, constraints),
+                s => panic!("expected CREATE TABLE, got {:?}", s),
+            },
+            _ => panic!("{} did not parse", sql),
+        }
+    }
+
+    #[test]
+    fn test_drop_and_truncate_refused() -> Result<(), CrustyError> {
+        let db = new_db();
+        create(&db, "CREATE TABLE p (id INT PRIMARY KEY);").unwrap();
+        create(
+            &db,
+            "CREATE TABLE c (id INT PRIMARY KEY, p INT REFERENCES p(id));",
+        )
+        .unwrap();
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert!(db.drop_tables(&names(&["missing"]), false, false).is_err());
+        assert!(db.truncate_table("missing", false).is_err());
+        assert_eq!(
+            "Table missing does not exist, skipping",
+            db.drop_tables(&names(&["missing"]), true, false)?.result()
+        );
+        // Nothing is dropped if one of the tables cannot be
+        assert!(db
+            .drop_tables(&names(&["c", "missing"]), false, false)
+            .is_err());
+        assert!(db.database.get_table_id("c").is_some());
+
+        // A foreign key refers to p
+        assert!(db.drop_tables(&names(&["p"]), false, false).is_err());
+        assert!(db.truncate_table("p", false).is_err());
+
+        // A registered query reads c
+        let c_id = db.database.get_table_id("c").unwrap();
+        let mut plan = PhysicalPlan::new();
+        plan.add_base_table(c_id);
+        db.register_query("q".to_string(), "q.json".to_string(), Arc::new(plan))?;
+        let err = db.drop_tables(&names(&["c"]), false, false).unwrap_err();
+        assert_eq!(
+            CrustyError::CrustyError(String::from(
+                "Cannot drop table c because registered queries q depend on it; use CASCADE"
+            )),
+            err
+        );
+        assert!(db.truncate_table("c", false).is_err());
+        assert!(db.database.get_table_id("c").is_some());
+
+        db.drop_tables(&names(&["c"]), false, true)?;
+        assert!(db.database.get_table_id("c").is_none());
+        assert!(db.query_registrar.queries_using(c_id).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_ddl_refused() -> Result<(), CrustyError> {
+        let db = new_db();
+        create(&db, "CREATE TABLE t (a INT PRIMARY KEY, b INT);")?;
+        let table_id = db.database.get_table_id("t").unwrap();
+        let table_ptr = db.database.get_table_ptr(table_id)?;
+        let tid = TransactionId::new();
+        for a in 0..2 {
+            let row = table_ptr
+                .read()
+                .unwrap()
+                .encode(&Tuple::new(vec![Field::IntField(a), Field::IntField(5)]));
+            db.storage_manager.insert_value(table_id, row, tid);
+        }
+        db.storage_manager.transaction_finished(tid);
+        let column = |name: &str| OrderByExpr {
+            expr: Expr::Identifier(Ident::new(name)),
+            asc: None,
+            nulls_first: None,
+        };
+        let index = |name, table, col, unique, method| {
+            let tid = TransactionId::new();
+            let res = db.create_index(name, table, &[column(col)], unique, method, false, tid);
+            db.storage_manager.transaction_finished(tid);
+            res
+        };
+
+        // Two rows share b, so it cannot be unique, and nothing of the index is kept
+        let err = index("t_b", "t", "b", true, IndexMethod::BTree).unwrap_err();
+        assert_eq!(
+            CrustyError::ValidationError(String::from(
+                "Cannot create unique index t_b: key (b)=(5) is duplicated"
+            )),
+            err
+        );
+        assert!(db.database.get_index_id("t_b").is_none());
+        assert_eq!("t_pkey ON t (a) UNIQUE", db.get_index_names()?);
+        assert!(index("t_b", "t", "b", true, IndexMethod::Hash).is_err());
+        assert!(index("t_c", "t", "c", false, IndexMethod::BTree).is_err());
+        assert!(index("t_b", "missing", "b", false, IndexMethod::BTree).is_err());
+
+        index("t_b", "t", "b", false, IndexMethod::BTree)?;
+        assert!(index("t_b", "t", "a", false, IndexMethod::BTree).is_err());
+
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert!(db.drop_indexes(&names(&["missing"]), false).is_err());
+        // The primary key needs its index, and nothing is dropped if one index cannot be
+        assert!(db.drop_indexes(&names(&["t_b", "t_pkey"]), false).is_err());
+        assert_eq!(
+            "t_b ON t (b)\nt_pkey ON t (a) UNIQUE",
+            db.get_index_names()?
+        );
+        Ok(())
+    }
+}
@@ -1,7 +1,6 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use std::io::{Read, Write};
 use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::conductor::Conductor;
 use crate::server_state::ServerState;
@@ -11,6 +10,9 @@ use crate::Executor;
 use common::commands::{Commands, Response};
 use optimizer::optimizer::Optimizer;
 
+/// Id given to the next client connection.
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(0);
+
 /// Waits for user commands and dispatches the commands.
 ///
 /// # Arguments
@@ -23,13 +25,8 @@ pub fn handle_client_request(mut stream: TcpStream, server_state: &'static Serve
     let optimizer = Optimizer::new();
     let mut conductor = Conductor::new(parser, optimizer, executor).unwrap();
 
-    // FIXME: id is hash(incoming-ip), make this right
-    // TODO: create a session for this client
-    error!("TODO create session for client properly");
-    let peer_ip_string = stream.peer_addr().unwrap().ip().to_string();
-    let mut s = DefaultHasher::new();
-    peer_ip_string.hash(&mut s);
-    let client_id = s.finish();
+    // Each connection is a session of its own, even if others come from the same host
+    let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
 
     let mut quiet = false;
 
@@ -76,7 +73,7 @@ pub fn handle_client_request(mut stream: TcpStream, server_state: &'static Serve
                                     Some(db_id) => {
                                         let db_ref = server_state.id_to_db.read().unwrap();
                                         let db_state = db_ref.get(db_id).unwrap();
-                                        match conductor.run_sql(ast, db_state) {
+                                        match conductor.run_sql(ast, db_state, client_id, server_state) {
                                             Ok(qr) => {
                                                 info!("Success running SQL query");
                                                 Response::QueryResult(qr)
@@ -146,3 +143,63 @@ pub fn handle_client_request(mut stream: TcpStream, server_state: &'static Serve
         }
     } {}
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::testutil::gen_random_dir;
+    use serde::Deserialize;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Sends a command over a client connection and reads the response.
+    fn send(stream: &mut TcpStream, command: Commands) -> Response {
+        stream
+            .write_all(&serde_cbor::to_vec(&command).unwrap())
+            .unwrap();
+        let mut de = serde_cbor::Deserializer::from_reader(stream);
+        Response::deserialize(&mut de).unwrap()
+    }
+
+    fn sql(stream: &mut TcpStream, sql: &str) -> Response {
+        send(stream, Commands::ExecuteSQL(sql.to_string()))
+    }
+
+    fn result(res: &str) -> Response {
+        Response::QueryResult(common::QueryResult::new(res))
+    }
+
+    #[test]
+    fn test_sessions_from_same_address() {
+        let path = gen_random_dir().to_string_lossy().to_string();
+        let (sender, _receiver) = mpsc::channel();
+        let state = ServerState::new(path.clone(), path, sender).unwrap();
+        let state: &'static ServerState = Box::leak(Box::new(state));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                thread::spawn(move || handle_client_request(stream, state));
+            }
+        });
+
+        let mut a = TcpStream::connect(addr).unwrap();
+        let mut b = TcpStream::connect(addr).unwrap();
+        send(&mut a, Commands::Create("sessions".to_string()));
+        send(&mut a, Commands::Connect("sessions".to_string()));
+        send(&mut b, Commands::Connect("sessions".to_string()));
+        sql(&mut a, "CREATE TABLE t (id INT PRIMARY KEY);");
+        assert_eq!(result("BEGIN"), sql(&mut a, "BEGIN;"));
+        sql(&mut a, "INSERT INTO t VALUES (1);");
+
+        // B neither sees nor ends the transaction of A
+        assert_eq!(result(""), sql(&mut b, "SELECT * FROM t;"));
+        assert_eq!(result("BEGIN"), sql(&mut b, "BEGIN;"));
+        assert_eq!(result("ROLLBACK"), sql(&mut b, "ROLLBACK;"));
+        assert_eq!(result("COMMIT"), sql(&mut a, "COMMIT;"));
+        assert_eq!(result("1,\n"), sql(&mut b, "SELECT * FROM t;"));
+        state.reset_database().unwrap();
+    }
+}
@@ -12,6 +12,7 @@ use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
+use std::time::Duration;
 
 use crate::daemon::Daemon;
 use crate::server_state::ServerState;
@@ -119,6 +120,13 @@ fn main() {
                 .help("Number of seconds for daemon thread to sleep between work")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("deadlock_interval_ms")
+                .long("deadlock_interval_ms")
+                .default_value("500")
+                .help("Milliseconds between daemon thread checks for deadlocks")
+                .takes_value(true),
+        )
         .get_matches();
 
     let config = if let Some(c) = matches.value_of("config") {
@@ -145,6 +153,11 @@ fn main() {
     };
 
     let daemon_seconds = matches.value_of("daemon").unwrap().parse::<u64>().unwrap();
+    let deadlock_millis = matches
+        .value_of("deadlock_interval_ms")
+        .unwrap()
+        .parse::<u64>()
+        .unwrap();
 
     info!("Starting crustydb... {:?}", config);
 
@@ -157,7 +170,11 @@ fn main() {
         Box::new(ServerState::new(config.db_path, config.hf_path, sender).unwrap());
     let server_state: &'static ServerState = Box::leak(server_state_box);
     //Create daemon thread
-    let mut _daemon_thread = Daemon::new(server_state, daemon_seconds);
+    let mut _daemon_thread = Daemon::new(
+        server_state,
+        Duration::from_secs(daemon_seconds),
+        Duration::from_millis(deadlock_millis),
+    );
 
     //Create a worker pool and start it.
     let mut workers = Vec::with_capacity(config.workers);
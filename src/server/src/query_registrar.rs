@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
-use common::ids::LogicalTimeStamp;
+use common::ids::{ContainerId, LogicalTimeStamp};
 use common::physical_plan::PhysicalPlan;
 use common::CrustyError;
 
@@ -68,6 +68,47 @@ impl QueryRegistrar {
         Ok(())
     }
 
+    /// Removes a registered query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_name` - Query to remove.
+    pub fn unregister_query(&self, query_name: &str) -> Result<(), CrustyError> {
+        if self
+            .in_progress_queries
+            .read()
+            .unwrap()
+            .contains_key(query_name)
+        {
+            return Err(CrustyError::CrustyError(format!(
+                "Query \"{}\" is in progress.",
+                query_name
+            )));
+        }
+        self.query_plans.write().unwrap().remove(query_name);
+        self.query_filenames.write().unwrap().remove(query_name);
+        self.query_watermarks.write().unwrap().remove(query_name);
+        Ok(())
+    }
+
+    /// Returns the names of the registered queries that read or write a base table, in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_id` - Container of the table.
+    pub fn queries_using(&self, container_id: ContainerId) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .query_plans
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, plan)| plan.base_tables().contains(&container_id))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
     /// Begin running a registered query.
     ///
     /// # Arguments
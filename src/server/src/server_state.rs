@@ -8,7 +8,8 @@ use crate::database_state::DatabaseState;
 use crate::worker;
 use crate::worker::Message;
 use common::catalog::Catalog;
-use common::ids::LogicalTimeStamp;
+use common::foreign_key::ForeignKeys;
+use common::ids::{IsolationLevel, LogicalTimeStamp, TransactionId};
 use common::physical_plan::PhysicalPlan;
 use common::CrustyError;
 use std::sync::atomic::Ordering;
@@ -36,6 +37,12 @@ pub struct ServerState {
 
     workers: Mutex<Vec<worker::Worker>>,
 
+    /// Transactions opened with BEGIN, by client_id of the connection
+    transactions: Mutex<HashMap<u64, Transaction>>,
+
+    /// Isolation level set with SET TRANSACTION, by client_id of the connection
+    isolation: Mutex<HashMap<u64, IsolationLevel>>,
+
     pub storage_manager: &'static StorageManager,
 }
 
@@ -81,6 +88,8 @@ impl ServerState {
             storage_path,
             task_queue: Mutex::new(task_queue),
             workers: Mutex::new(Vec::new()),
+            transactions: Mutex::new(HashMap::new()),
+            isolation: Mutex::new(HashMap::new()),
             storage_manager: sm,
         };
 
@@ -131,6 +140,12 @@ impl ServerState {
             }
         }
 
+        // roll back the transactions clients left open, so none of their changes are kept
+        for (client_id, mut txn) in self.transactions.lock().unwrap().drain() {
+            info!("Aborting open transaction {:?} of client {:?}", txn.tid(), client_id);
+            let _ = txn.abort(self.storage_manager);
+        }
+
         // call shutdown on SM to ensure stateful shutdown
         self.storage_manager.shutdown();
         error!("TODO no one is shutting down daemon properly");
@@ -155,8 +170,10 @@ impl ServerState {
         }
         db_states.clear();
 
-        // Reset active connections
+        // Reset active connections and their transactions
         conns.clear();
+        self.transactions.lock().unwrap().clear();
+        self.isolation.lock().unwrap().clear();
 
         // Clear the storage manager
         self.storage_manager.reset()?;
@@ -166,6 +183,13 @@ impl ServerState {
     }
 
     pub fn close_client_connection(&self, client_id: u64) {
+        // abort a transaction the client left open
+        if let Some(mut txn) = self.transactions.lock().unwrap().remove(&client_id) {
+            info!("Aborting open transaction {:?} of client {:?}", txn.tid(), client_id);
+            let _ = txn.abort(self.storage_manager);
+        }
+        self.isolation.lock().unwrap().remove(&client_id);
+
         // indicate DB this client is disconnecting
         let db_id_ref = self.active_connections.read().unwrap();
         match db_id_ref.get(&client_id) {
@@ -187,6 +211,134 @@ impl ServerState {
         );
     }
 
+    /// Sets the isolation level of the client's transactions from now on.
+    pub fn set_isolation(&self, client_id: u64, isolation: IsolationLevel) -> String {
+        self.isolation.lock().unwrap().insert(client_id, isolation);
+        String::from("SET")
+    }
+
+    /// The isolation level of the client's transactions.
+    fn session_isolation(&self, client_id: u64) -> IsolationLevel {
+        self.isolation
+            .lock()
+            .unwrap()
+            .get(&client_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Opens a transaction that the client's statements run in until it commits or rolls back.
+    /// It runs at the given isolation level, or the client's if there is none.
+    pub fn begin_transaction(
+        &self,
+        client_id: u64,
+        isolation: Option<IsolationLevel>,
+    ) -> Result<String, CrustyError> {
+        let isolation = isolation.unwrap_or_else(|| self.session_isolation(client_id));
+        let mut transactions = self.transactions.lock().unwrap();
+        if transactions.contains_key(&client_id) {
+            return Err(CrustyError::CrustyError(String::from(
+                "A transaction is already in progress",
+            )));
+        }
+        let mut txn = Transaction::new().with_isolation(isolation);
+        txn.start();
+        transactions.insert(client_id, txn);
+        Ok(String::from("BEGIN"))
+    }
+
+    /// Commits the client's open transaction. If a statement of it failed, it is rolled
+    /// back instead.
+    pub fn commit_transaction(&self, client_id: u64) -> Result<String, CrustyError> {
+        let mut txn = self.take_transaction(client_id)?;
+        if txn.failed() {
+            txn.abort(self.storage_manager)?;
+            return Ok(String::from("ROLLBACK"));
+        }
+        txn.commit(self.storage_manager)?;
+        Ok(String::from("COMMIT"))
+    }
+
+    /// Rolls back the client's open transaction, undoing its changes.
+    pub fn rollback_transaction(&self, client_id: u64) -> Result<String, CrustyError> {
+        let mut txn = self.take_transaction(client_id)?;
+        txn.abort(self.storage_manager)?;
+        Ok(String::from("ROLLBACK"))
+    }
+
+    /// Whether the client has an open transaction.
+    pub fn in_transaction(&self, client_id: u64) -> bool {
+        self.transactions.lock().unwrap().contains_key(&client_id)
+    }
+
+    fn take_transaction(&self, client_id: u64) -> Result<Transaction, CrustyError> {
+        self.transactions
+            .lock()
+            .unwrap()
+            .remove(&client_id)
+            .ok_or_else(|| CrustyError::CrustyError(String::from("No transaction in progress")))
+    }
+
+    /// Runs f in the client's open transaction. If the storage manager aborts that
+    /// transaction, it is rolled back and closed. If f fails, the transaction is marked
+    /// failed, as f may have written part of its changes: it then refuses other statements
+    /// until it is rolled back, and rolls back if committed. Without an open transaction,
+    /// f runs in one of its own that commits if f succeeds and rolls back if it fails.
+    /// Either way f starts a new statement of the transaction, which may take a new
+    /// snapshot to read from.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - Client running f.
+    /// * `f` - Work to run, given the id of the transaction to run it in.
+    pub fn run_in_transaction<T, F>(&self, client_id: u64, f: F) -> Result<T, CrustyError>
+    where
+        F: FnOnce(TransactionId) -> Result<T, CrustyError>,
+    {
+        let open = self
+            .transactions
+            .lock()
+            .unwrap()
+            .get(&client_id)
+            .map(|txn| (txn.tid(), txn.isolation(), txn.failed()));
+        match open {
+            Some((_, _, true)) => Err(CrustyError::CrustyError(String::from(
+                "Current transaction is aborted, statements are ignored until ROLLBACK",
+            ))),
+            Some((tid, isolation, false)) => {
+                self.storage_manager.begin_statement(tid, isolation);
+                let res = f(tid);
+                if self.storage_manager.check_transaction(tid).is_err() {
+                    self.rollback_transaction(client_id)?;
+                    return Err(CrustyError::TransactionAbortedError);
+                }
+                if res.is_err() {
+                    if let Some(txn) = self.transactions.lock().unwrap().get_mut(&client_id) {
+                        txn.fail();
+                    }
+                }
+                res
+            }
+            None => {
+                let mut txn =
+                    Transaction::new().with_isolation(self.session_isolation(client_id));
+                txn.start();
+                self.storage_manager
+                    .begin_statement(txn.tid(), txn.isolation());
+                match f(txn.tid()) {
+                    Ok(res) => {
+                        txn.commit(self.storage_manager)?;
+                        Ok(res)
+                    }
+                    Err(e) => {
+                        txn.abort(self.storage_manager)?;
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+
     /// Add workers to the worker queue
     pub(crate) fn add_workers(&self, new_workers: Vec<worker::Worker>) {
         let mut workers = self.workers.lock().unwrap();
@@ -267,7 +419,6 @@ impl ServerState {
     pub fn import_database(&self, path: String, client_id: u64) -> Result<String, CrustyError> {
         // TODO: Fix serialization.
         let (table_name, new_path) = self.parse_name_and_path(&path);
-        let txn = Transaction::new();
 
         let db_id_ref = self.active_connections.read().unwrap();
         let db_state = match db_id_ref.get(&client_id) {
@@ -286,22 +437,25 @@ impl ServerState {
         let time = db_state.atomic_time.fetch_add(1, Ordering::SeqCst);
 
         let db = &db_state.database;
-        let tables = db.tables.read().unwrap();
         let table_id = db
             .get_table_id(&table_name.to_string())
             .ok_or_else(|| CrustyError::CrustyError("Cannot find table id".to_string()))?;
+        let table = db.tables.read().unwrap().get(&table_id).cloned();
 
         // Check if table name exists in active database.
-        if let Some(table) = tables.get(&table_id) {
+        if let Some(table) = table {
             let table_ref = &table.read().unwrap();
-            // FIXME: Error check on import_csv.
-            let _ = self.storage_manager.import_csv(
-                table_ref,
-                new_path.to_string(),
-                txn.tid(),
-                table_id,
-                time,
-            )?;
+            let foreign_keys = ForeignKeys::new(db.get_tables());
+            self.run_in_transaction(client_id, |tid| {
+                self.storage_manager.import_csv(
+                    table_ref,
+                    &foreign_keys,
+                    new_path.to_string(),
+                    tid,
+                    table_id,
+                    time,
+                )
+            })?;
             Ok(format!(
                 "Data from path: {:?} imported to table: {:?}",
                 &path,
@@ -391,3 +545,72 @@ impl ServerState {
         db_state.finish_query(query_name)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::ids::Permissions;
+    use common::testutil::gen_random_dir;
+
+    fn new_state(path: &str) -> &'static ServerState {
+        let (sender, _receiver) = mpsc::channel();
+        let state = ServerState::new(path.to_string(), path.to_string(), sender).unwrap();
+        Box::leak(Box::new(state))
+    }
+
+    #[test]
+    fn test_shutdown_aborts_open_transactions() {
+        let path = gen_random_dir().to_string_lossy().to_string();
+        let state = new_state(&path);
+        let sm = state.storage_manager;
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        state.begin_transaction(0, None).unwrap();
+        state
+            .run_in_transaction(0, |tid| {
+                sm.insert_value(cid, vec![1], tid);
+                Ok(())
+            })
+            .unwrap();
+        state.shutdown().unwrap();
+        assert!(!state.in_transaction(0));
+
+        let state = new_state(&path);
+        let tid = TransactionId::new();
+        let sm = state.storage_manager;
+        assert_eq!(0, sm.get_iterator(cid, tid, Permissions::ReadOnly).count());
+        state.reset_database().unwrap();
+    }
+
+    #[test]
+    fn test_failed_statement_fails_transaction() {
+        let path = gen_random_dir().to_string_lossy().to_string();
+        let state = new_state(&path);
+        let sm = state.storage_manager;
+        let cid = 1;
+        sm.create_table(cid).unwrap();
+        state.begin_transaction(0, None).unwrap();
+        // The statement fails after writing part of its changes
+        let res: Result<(), CrustyError> = state.run_in_transaction(0, |tid| {
+            sm.insert_value(cid, vec![1], tid);
+            Err(CrustyError::ExecutionError(String::from("Failed")))
+        });
+        assert!(res.is_err());
+
+        let res = state.run_in_transaction(0, |tid| {
+            sm.insert_value(cid, vec![2], tid);
+            Ok(())
+        });
+        assert_eq!(
+            Err(CrustyError::CrustyError(String::from(
+                "Current transaction is aborted, statements are ignored until ROLLBACK"
+            ))),
+            res
+        );
+        assert_eq!(Ok(String::from("ROLLBACK")), state.commit_transaction(0));
+        assert!(!state.in_transaction(0));
+        let tid = TransactionId::new();
+        assert_eq!(0, sm.get_iterator(cid, tid, Permissions::ReadOnly).count());
+        state.reset_database().unwrap();
+    }
+}
@@ -1,11 +1,22 @@
+use common::index::IndexMethod;
+use common::Constraint;
 use sqlparser::parser::Parser;
 
 use sqlparser::ast::TableConstraint;
-use sqlparser::ast::{ColumnDef, ColumnOption, Ident, Statement};
+use sqlparser::ast::{
+    ColumnDef, ColumnOption, Expr, Ident, ObjectName, ReferentialAction, Statement,
+};
+use sqlparser::dialect::Dialect;
+use sqlparser::dialect::keywords::Keyword;
 use sqlparser::parser::ParserError;
+use sqlparser::tokenizer::{Token, Tokenizer};
 
 pub struct SQLParser {}
 
+/// Quote style marking the name of an index created with `USING HASH`, which the tokenizer
+/// never gives an identifier.
+const HASH_INDEX_MARK: char = '#';
+
 #[allow(clippy::upper_case_acronyms)]
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -16,6 +27,21 @@ pub enum ParserResponse {
     SQLConstraintError(String),
 }
 
+/// A foreign key as declared by CREATE TABLE, before the parent table is looked up.
+#[derive(Debug, PartialEq)]
+pub struct ForeignKeyDef {
+    /// Name given with CONSTRAINT, if any.
+    pub name: Option<String>,
+    /// Columns of the new table.
+    pub columns: Vec<Ident>,
+    /// Name of the parent table.
+    pub parent: String,
+    /// Columns of the parent referred to; empty for its primary key.
+    pub parent_columns: Vec<Ident>,
+    /// ON DELETE action, if given.
+    pub on_delete: Option<ReferentialAction>,
+}
+
 impl SQLParser {
     pub fn new() -> SQLParser {
         SQLParser {}
@@ -56,14 +82,118 @@ impl SQLParser {
     }
 
     /// Returns Request::SQL if given string is valid sql, else returns Request::SQLError
+    ///
+    /// sqlparser does not know `TRUNCATE TABLE t CASCADE`, so the CASCADE is taken off before
+    /// parsing and recorded as an empty partition list, which the grammar never produces.
+    /// Use `truncate_cascades` to read it back.
+    ///
+    /// Nor does it know the `USING HASH` or `USING BTREE` of `CREATE INDEX`, which is taken off
+    /// as well, a hash index being recorded in the quote style of its name. Use `index_method`
+    /// to read it back.
     fn validate_sql(sql: String) -> ParserResponse {
         let dialect = sqlparser::dialect::GenericDialect {};
+        let (sql, cascade) = SQLParser::strip_truncate_cascade(&dialect, sql);
+        let (sql, hash) = SQLParser::strip_index_method(&dialect, sql);
         match Parser::parse_sql(&dialect, &sql) {
-            Ok(a) => ParserResponse::SQL(a),
+            Ok(mut a) => {
+                if let (true, Some(Statement::Truncate { partitions, .. })) = (cascade, a.first_mut())
+                {
+                    *partitions = Some(Vec::new());
+                }
+                if let (true, Some(Statement::CreateIndex { name, .. })) = (hash, a.first_mut()) {
+                    if let Some(ident) = name.0.last_mut() {
+                        ident.quote_style = Some(HASH_INDEX_MARK);
+                    }
+                }
+                ParserResponse::SQL(a)
+            }
             Err(e) => ParserResponse::SQLError(e),
         }
     }
 
+    /// Removes the CASCADE ending a TRUNCATE statement, returning the rest of the statement
+    /// and whether there was one.
+    fn strip_truncate_cascade(dialect: &dyn Dialect, sql: String) -> (String, bool) {
+        let mut tokens = match Tokenizer::new(dialect, &sql).tokenize() {
+            Ok(tokens) => tokens,
+            Err(_) => return (sql, false),
+        };
+        let words: Vec<usize> = (0..tokens.len())
+            .filter(|i| !matches!(tokens[*i], Token::Whitespace(_) | Token::SemiColon))
+            .collect();
+        let is_keyword = |i: Option<&usize>, keyword: Keyword| {
+            matches!(i.map(|i| &tokens[*i]), Some(Token::Word(w)) if w.keyword == keyword)
+        };
+        if !is_keyword(words.first(), Keyword::TRUNCATE)
+            || !is_keyword(words.last(), Keyword::CASCADE)
+        {
+            return (sql, false);
+        }
+        tokens.remove(*words.last().unwrap());
+        (tokens.iter().map(|t| t.to_string()).collect(), true)
+    }
+
+    /// Removes the `USING HASH` or `USING BTREE` of a CREATE INDEX statement, returning the
+    /// rest of the statement and whether it asked for a hash index.
+    fn strip_index_method(dialect: &dyn Dialect, sql: String) -> (String, bool) {
+        let mut tokens = match Tokenizer::new(dialect, &sql).tokenize() {
+            Ok(tokens) => tokens,
+            Err(_) => return (sql, false),
+        };
+        let words: Vec<usize> = (0..tokens.len())
+            .filter(|i| !matches!(tokens[*i], Token::Whitespace(_) | Token::SemiColon))
+            .collect();
+        let is_keyword = |i: Option<&usize>, keyword: Keyword| {
+            matches!(i.map(|i| &tokens[*i]), Some(Token::Word(w)) if w.keyword == keyword)
+        };
+        let creates_index = is_keyword(words.first(), Keyword::CREATE)
+            && (is_keyword(words.get(1), Keyword::INDEX)
+                || is_keyword(words.get(1), Keyword::UNIQUE)
+                    && is_keyword(words.get(2), Keyword::INDEX));
+        if !creates_index {
+            return (sql, false);
+        }
+        let method = |i: &usize| match &tokens[*i] {
+            Token::Word(w) if w.quote_style.is_none() => Some(w.value.to_uppercase()),
+            _ => None,
+        };
+        let using = (0..words.len().saturating_sub(1)).find(|i| {
+            is_keyword(words.get(*i), Keyword::USING)
+                && matches!(method(&words[i + 1]).as_deref(), Some("HASH" | "BTREE"))
+        });
+        match using {
+            Some(i) => {
+                let hash = method(&words[i + 1]).as_deref() == Some("HASH");
+                tokens.remove(words[i + 1]);
+                tokens.remove(words[i]);
+                (tokens.iter().map(|t| t.to_string()).collect(), hash)
+            }
+            None => (sql, false),
+        }
+    }
+
+    /// Returns how a CREATE INDEX statement stores the index, from the name it was parsed
+    /// with.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the index in the parsed CREATE INDEX statement.
+    pub fn index_method(name: &ObjectName) -> IndexMethod {
+        match name.0.last() {
+            Some(ident) if ident.quote_style == Some(HASH_INDEX_MARK) => IndexMethod::Hash,
+            _ => IndexMethod::BTree,
+        }
+    }
+
+    /// Returns whether a TRUNCATE statement's partitions mark it as CASCADE.
+    ///
+    /// # Arguments
+    ///
+    /// * `partitions` - Partitions of the parsed TRUNCATE statement.
+    pub fn truncate_cascades(partitions: &Option<Vec<Expr>>) -> bool {
+        matches!(partitions, Some(p) if p.is_empty())
+    }
+
     /// Returns a vector of the Idents of tables that are primary keys if valid
     /// Returns an error (as request) if there is a problem
     ///
@@ -129,6 +259,151 @@ impl SQLParser {
         }
         Ok(res)
     }
+
+    /// Returns the constraint of each column, in order, given the table's primary keys
+    /// Returns an error (as request) if there is a problem
+    ///
+    /// Returns an error in the following cases
+    /// unique constraints over several columns: create table _ (a int, b int, unique(a, b))
+    /// unique constraints on unknown columns: create table _ (a int, unique(b))
+    pub fn get_column_constraints(
+        columns: &[ColumnDef],
+        constraints: &[TableConstraint],
+        pks: &[Ident],
+    ) -> Result<Vec<Constraint>, ParserResponse> {
+        let mut unique: Vec<bool> = columns
+            .iter()
+            .map(|c| {
+                c.options
+                    .iter()
+                    .any(|o| o.option == ColumnOption::Unique { is_primary: false })
+            })
+            .collect();
+        for constraint in constraints {
+            if let TableConstraint::Unique {
+                is_primary: false,
+                columns: unique_columns,
+                ..
+            } = constraint
+            {
+                if unique_columns.len() != 1 {
+                    return Err(ParserResponse::SQLConstraintError(String::from(
+                        "Unique constraints over several columns are not supported",
+                    )));
+                }
+                match columns.iter().position(|c| c.name == unique_columns[0]) {
+                    Some(i) => unique[i] = true,
+                    None => {
+                        return Err(ParserResponse::SQLConstraintError(format!(
+                            "Unique column {} does not exist",
+                            unique_columns[0]
+                        )))
+                    }
+                }
+            }
+        }
+        let res = columns
+            .iter()
+            .zip(unique)
+            .map(|(column, unique)| {
+                let not_null = column
+                    .options
+                    .iter()
+                    .any(|o| o.option == ColumnOption::NotNull);
+                if pks.contains(&column.name) {
+                    Constraint::PrimaryKey
+                } else {
+                    match (unique, not_null) {
+                        (true, true) => Constraint::UniqueNotNull,
+                        (true, false) => Constraint::Unique,
+                        (false, true) => Constraint::NotNull,
+                        (false, false) => Constraint::None,
+                    }
+                }
+            })
+            .collect();
+        Ok(res)
+    }
+
+    /// Returns the foreign keys declared inline with REFERENCES or with FOREIGN KEY constraints
+    /// Returns an error (as request) if there is a problem
+    ///
+    /// Returns an error in the following cases
+    /// ON UPDATE actions other than RESTRICT: create table _ (a int references p on update cascade)
+    /// foreign keys on unknown columns: create table _ (a int, foreign key (b) references p (b))
+    /// column counts that differ: create table _ (a int, foreign key (a) references p (b, c))
+    pub fn get_foreign_keys(
+        columns: &[ColumnDef],
+        constraints: &[TableConstraint],
+    ) -> Result<Vec<ForeignKeyDef>, ParserResponse> {
+        let mut res = Vec::new();
+        for column in columns {
+            for column_option in &column.options {
+                if let ColumnOption::ForeignKey {
+                    foreign_table,
+                    referred_columns,
+                    on_delete,
+                    on_update,
+                } = &column_option.option
+                {
+                    if !matches!(
+                        on_update,
+                        None | Some(ReferentialAction::Restrict) | Some(ReferentialAction::NoAction)
+                    ) {
+                        return Err(ParserResponse::SQLConstraintError(format!(
+                            "ON UPDATE {} is not supported",
+                            on_update.as_ref().unwrap()
+                        )));
+                    }
+                    res.push(ForeignKeyDef {
+                        name: column_option.name.as_ref().map(|n| n.value.clone()),
+                        columns: vec![column.name.clone()],
+                        parent: foreign_table.to_string(),
+                        parent_columns: referred_columns.clone(),
+                        on_delete: on_delete.clone(),
+                    });
+                }
+            }
+        }
+        for constraint in constraints {
+            if let TableConstraint::ForeignKey {
+                name,
+                columns: fk_columns,
+                foreign_table,
+                referred_columns,
+            } = constraint
+            {
+                let missing = fk_columns
+                    .iter()
+                    .find(|c| !columns.iter().any(|d| d.name == **c));
+                if let Some(c) = missing {
+                    return Err(ParserResponse::SQLConstraintError(format!(
+                        "Foreign key column {} does not exist",
+                        c
+                    )));
+                }
+                res.push(ForeignKeyDef {
+                    name: name.as_ref().map(|n| n.value.clone()),
+                    columns: fk_columns.clone(),
+                    parent: foreign_table.to_string(),
+                    parent_columns: referred_columns.clone(),
+                    on_delete: None,
+                });
+            }
+        }
+        if let Some(fk) = res
+            .iter()
+            .find(|fk| !fk.parent_columns.is_empty() && fk.parent_columns.len() != fk.columns.len())
+        {
+            return Err(ParserResponse::SQLConstraintError(format!(
+                "Foreign key on {} columns refers to {} columns of {}",
+                fk.columns.len(),
+                fk.parent_columns.len(),
+                fk.parent
+            )));
+        }
+        Ok(res)
+    }
     /*
     fn is_create_table(ast: &Request) -> bool {
         let mut create_table_check = false;
@@ -334,4 +609,101 @@ mod libtests {
             }
         }
     }
+
+    #[test]
+    fn test_get_foreign_keys() {
+        let fks = |sql: &str| match SQLParser::parse_sql(String::from(sql)) {
+            ParserResponse::SQL(ast) => match ast.first().unwrap() {
+                Statement::CreateTable {
+                    columns,
+                    constraints,
+                    ..
+                } => SQLParser::get_foreign_keys(columns, constraints),
+                s => panic!("expected CREATE TABLE, got {:?}", s),
+            },
+            _ => panic!("{} did not parse", sql),
+        };
+        let res = fks("create table c (a int primary key, p int references p on delete cascade)")
+            .unwrap();
+        assert_eq!(
+            vec![ForeignKeyDef {
+                name: None,
+                columns: vec![Ident::new("p")],
+                parent: "p".to_string(),
+                parent_columns: Vec::new(),
+                on_delete: Some(ReferentialAction::Cascade),
+            }],
+            res
+        );
+        let sql = "create table c (a int, b int, primary key (a), \
+                   constraint c_fk foreign key (a, b) references p (x, y))";
+        let res = fks(sql).unwrap();
+        assert_eq!(Some("c_fk".to_string()), res[0].name);
+        assert_eq!(vec![Ident::new("a"), Ident::new("b")], res[0].columns);
+        assert_eq!(vec![Ident::new("x"), Ident::new("y")], res[0].parent_columns);
+        assert_eq!(None, res[0].on_delete);
+
+        for sql in [
+            "create table c (a int primary key references p on update cascade)",
+            "create table c (a int primary key, foreign key (b) references p (b))",
+            "create table c (a int primary key, foreign key (a) references p (b, c))",
+        ] {
+            assert!(fks(sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_truncate_cascade() {
+        let truncate = |sql: &str| match SQLParser::parse_sql(String::from(sql)) {
+            ParserResponse::SQL(ast) => match ast.first().unwrap() {
+                Statement::Truncate {
+                    table_name,
+                    partitions,
+                } => (table_name.to_string(), SQLParser::truncate_cascades(partitions)),
+                s => panic!("expected TRUNCATE, got {:?}", s),
+            },
+            _ => panic!("{} did not parse", sql),
+        };
+        assert_eq!(("test".to_string(), false), truncate("truncate table test"));
+        assert_eq!(("test".to_string(), true), truncate("TRUNCATE TABLE test CASCADE;"));
+        assert_eq!(("test".to_string(), true), truncate("truncate table test cascade"));
+        // Other statements ending in CASCADE are left alone
+        match SQLParser::parse_sql(String::from("select a from test cascade")) {
+            ParserResponse::SQL(ast) => assert!(matches!(ast[0], Statement::Query(_))),
+            _ => panic!("select did not parse"),
+        }
+    }
+
+    #[test]
+    fn test_index_method() {
+        let method = |sql: &str| match SQLParser::parse_sql(String::from(sql)) {
+            ParserResponse::SQL(ast) => match ast.first().unwrap() {
+                Statement::CreateIndex {
+                    name,
+                    table_name,
+                    unique,
+                    ..
+                } => {
+                    assert_eq!("test", table_name.to_string());
+                    assert_eq!("test_a", common::get_name(name).unwrap());
+                    (SQLParser::index_method(name), *unique)
+                }
+                s => panic!("expected CREATE INDEX, got {:?}", s),
+            },
+            _ => panic!("{} did not parse", sql),
+        };
+        let btree = (IndexMethod::BTree, false);
+        assert_eq!(btree, method("create index test_a on test (a)"));
+        assert_eq!(btree, method("create index test_a on test using btree (a)"));
+        let hash = (IndexMethod::Hash, false);
+        assert_eq!(hash, method("CREATE INDEX test_a ON test USING HASH (a);"));
+        assert_eq!(hash, method("create index if not exists test_a on test using hash (a)"));
+        let unique = (IndexMethod::Hash, true);
+        assert_eq!(unique, method("create unique index test_a on test using hash (a)"));
+        // Other methods are not known
+        assert!(matches!(
+            SQLParser::parse_sql(String::from("create index test_a on test using gist (a)")),
+            ParserResponse::SQLError(_)
+        ));
+    }
 }
@@ -1,3 +1,4 @@
 #[macro_use]
 extern crate log;
+pub mod lock_manager;
 pub mod transactions;
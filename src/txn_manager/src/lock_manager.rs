@@ -0,0 +1,495 @@
+use common::crusty_graph::{CrustyGraph, NodeIndex};
+use common::ids::{ContainerId, PageId, Permissions, TransactionId, ValueId};
+use common::CrustyError;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a lock request waits before its transaction is aborted.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Something that can be locked. Containers hold pages and pages hold values, so a
+/// transaction locking a page or value first takes an intention lock on what holds it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockId {
+    Container(ContainerId),
+    Page(ContainerId, PageId),
+    Value(ValueId),
+}
+
+/// Lock modes, from weakest to strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LockMode {
+    /// Intends to read something inside the locked item.
+    IntentionShared,
+    /// Intends to write something inside the locked item.
+    IntentionExclusive,
+    Shared,
+    Exclusive,
+}
+
+impl LockMode {
+    /// The lock needed to read or write the item itself.
+    pub fn for_permissions(perm: Permissions) -> Self {
+        match perm {
+            Permissions::ReadOnly => LockMode::Shared,
+            Permissions::ReadWrite => LockMode::Exclusive,
+        }
+    }
+
+    /// The intention lock needed on the container of an item read or written with perm.
+    pub fn intention_for(perm: Permissions) -> Self {
+        match perm {
+            Permissions::ReadOnly => LockMode::IntentionShared,
+            Permissions::ReadWrite => LockMode::IntentionExclusive,
+        }
+    }
+
+    /// Whether two different transactions can hold these modes on the same item.
+    pub fn compatible(self, other: LockMode) -> bool {
+        use LockMode::*;
+        match (self, other) {
+            (Exclusive, _) | (_, Exclusive) => false,
+            (IntentionShared, _) | (_, IntentionShared) => true,
+            (IntentionExclusive, IntentionExclusive) => true,
+            (Shared, Shared) => true,
+            _ => false,
+        }
+    }
+
+    /// The weakest mode that grants everything both modes grant.
+    /// Holding IX and asking for S (or the reverse) needs X, as there is no SIX mode.
+    pub fn combine(self, other: LockMode) -> LockMode {
+        use LockMode::*;
+        match (self, other) {
+            (IntentionExclusive, Shared) | (Shared, IntentionExclusive) => Exclusive,
+            _ => self.max(other),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LockTable {
+    /// The mode each transaction holds on each locked item.
+    locks: HashMap<LockId, HashMap<TransactionId, LockMode>>,
+    /// Every item each transaction holds a lock on, so they can be released together.
+    held: HashMap<TransactionId, HashSet<LockId>>,
+    /// Transactions that failed to get a lock. They keep their locks until they finish.
+    aborted: HashSet<TransactionId>,
+    /// The request each blocked transaction is waiting on.
+    waiting: HashMap<TransactionId, (LockId, LockMode)>,
+}
+
+impl LockTable {
+    /// Whether tid can be granted mode on lock_id right now.
+    fn grantable(&self, tid: TransactionId, lock_id: &LockId, mode: LockMode) -> bool {
+        match self.locks.get(lock_id) {
+            None => true,
+            Some(holders) => holders
+                .iter()
+                .all(|(holder, held)| *holder == tid || mode.compatible(*held)),
+        }
+    }
+
+    /// The mode tid holds on lock_id, if any.
+    fn held_mode(&self, tid: TransactionId, lock_id: &LockId) -> Option<LockMode> {
+        self.locks.get(lock_id).and_then(|h| h.get(&tid)).copied()
+    }
+
+    fn grant(&mut self, tid: TransactionId, lock_id: LockId, mode: LockMode) {
+        self.locks.entry(lock_id).or_default().insert(tid, mode);
+        self.held.entry(tid).or_default().insert(lock_id);
+    }
+
+    /// The waits-for graph: an edge from each blocked transaction to every transaction
+    /// holding a conflicting lock on the item it wants. Aborted waiters are left out
+    /// since they will give up as soon as they wake.
+    fn waits_for(&self) -> CrustyGraph<TransactionId> {
+        let mut graph = CrustyGraph::new();
+        let mut nodes: HashMap<TransactionId, NodeIndex> = HashMap::new();
+        let mut node = |graph: &mut CrustyGraph<TransactionId>, tid: TransactionId| {
+            *nodes.entry(tid).or_insert_with(|| graph.add_node(tid))
+        };
+        for (waiter, (lock_id, mode)) in &self.waiting {
+            if self.aborted.contains(waiter) {
+                continue;
+            }
+            let holders = match self.locks.get(lock_id) {
+                Some(holders) => holders,
+                None => continue,
+            };
+            for (holder, held) in holders {
+                if holder != waiter && !mode.compatible(*held) {
+                    let source = node(&mut graph, *waiter);
+                    let target = node(&mut graph, *holder);
+                    graph.add_edge(source, target);
+                }
+            }
+        }
+        graph
+    }
+}
+
+/// Whether start can reach itself by following edges.
+fn on_cycle<T>(graph: &CrustyGraph<T>, start: NodeIndex) -> bool {
+    let mut visited = HashSet::new();
+    let mut todo: Vec<NodeIndex> = graph.edges(start).collect();
+    while let Some(next) = todo.pop() {
+        if next == start {
+            return true;
+        }
+        if visited.insert(next) {
+            todo.extend(graph.edges(next));
+        }
+    }
+    false
+}
+
+/// A lock manager for strict two-phase locking.
+///
+/// Locks are only ever released all at once with `release_all`, which the storage
+/// manager calls when a transaction finishes. A request that conflicts with locks held
+/// by other transactions blocks until it can be granted; if that takes longer than the
+/// timeout the transaction is marked aborted and the request fails with
+/// `CrustyError::TransactionAbortedError`. Re-requesting a lock that is held is a no-op,
+/// and requesting a stronger mode upgrades it.
+///
+/// Deadlocks are broken either by that timeout or, sooner, by `detect_deadlocks`, which
+/// the server runs periodically.
+pub struct LockManager {
+    table: Mutex<LockTable>,
+    released: Condvar,
+    timeout: Mutex<Duration>,
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOCK_TIMEOUT)
+    }
+}
+
+impl LockManager {
+    /// Create a lock manager whose requests wait at most timeout.
+    pub fn new(timeout: Duration) -> Self {
+        LockManager {
+            table: Mutex::new(LockTable::default()),
+            released: Condvar::new(),
+            timeout: Mutex::new(timeout),
+        }
+    }
+
+    /// Change how long requests wait before aborting.
+    pub fn set_timeout(&self, timeout: Duration) {
+        *self.timeout.lock().unwrap() = timeout;
+    }
+
+    /// Acquire mode on lock_id for tid, waiting for conflicting locks to be released.
+    pub fn acquire(
+        &self,
+        tid: TransactionId,
+        lock_id: LockId,
+        mode: LockMode,
+    ) -> Result<(), CrustyError> {
+        let deadline = Instant::now() + *self.timeout.lock().unwrap();
+        let mut table = self.table.lock().unwrap();
+        let mode = match table.held_mode(tid, &lock_id) {
+            Some(held) => held.combine(mode),
+            None => mode,
+        };
+        let result = loop {
+            if table.aborted.contains(&tid) {
+                break Err(CrustyError::TransactionAbortedError);
+            }
+            if table.held_mode(tid, &lock_id) == Some(mode) {
+                break Ok(());
+            }
+            if table.grantable(tid, &lock_id, mode) {
+                table.grant(tid, lock_id, mode);
+                break Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                debug!("Transaction {:?} timed out waiting for {:?}", tid, lock_id);
+                table.aborted.insert(tid);
+                break Err(CrustyError::TransactionAbortedError);
+            }
+            table.waiting.insert(tid, (lock_id, mode));
+            table = self.released.wait_timeout(table, deadline - now).unwrap().0;
+        };
+        table.waiting.remove(&tid);
+        result
+    }
+
+    /// Acquire mode on lock_id for tid only if it can be granted without waiting.
+    pub fn try_acquire(&self, tid: TransactionId, lock_id: LockId, mode: LockMode) -> bool {
+        let mut table = self.table.lock().unwrap();
+        if table.aborted.contains(&tid) {
+            return false;
+        }
+        let mode = match table.held_mode(tid, &lock_id) {
+            Some(held) => held.combine(mode),
+            None => mode,
+        };
+        if table.grantable(tid, &lock_id, mode) {
+            table.grant(tid, lock_id, mode);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Lock a whole container for reading or writing.
+    pub fn lock_container(
+        &self,
+        tid: TransactionId,
+        container_id: ContainerId,
+        perm: Permissions,
+    ) -> Result<(), CrustyError> {
+        self.acquire(
+            tid,
+            LockId::Container(container_id),
+            LockMode::for_permissions(perm),
+        )
+    }
+
+    /// Lock a page for reading or writing, with an intention lock on its container.
+    pub fn lock_page(
+        &self,
+        tid: TransactionId,
+        container_id: ContainerId,
+        page_id: PageId,
+        perm: Permissions,
+    ) -> Result<(), CrustyError> {
+        self.acquire(
+            tid,
+            LockId::Container(container_id),
+            LockMode::intention_for(perm),
+        )?;
+        self.acquire(
+            tid,
+            LockId::Page(container_id, page_id),
+            LockMode::for_permissions(perm),
+        )
+    }
+
+    /// Lock a value for reading or writing, with intention locks on its page and container.
+    pub fn lock_value(
+        &self,
+        tid: TransactionId,
+        id: ValueId,
+        perm: Permissions,
+    ) -> Result<(), CrustyError> {
+        let intention = LockMode::intention_for(perm);
+        self.acquire(tid, LockId::Container(id.container_id), intention)?;
+        if let Some(page_id) = id.page_id {
+            self.acquire(tid, LockId::Page(id.container_id, page_id), intention)?;
+        }
+        self.acquire(tid, LockId::Value(id), LockMode::for_permissions(perm))
+    }
+
+    /// Mark tid as aborted. Its pending and future requests fail until it finishes.
+    pub fn abort(&self, tid: TransactionId) {
+        self.table.lock().unwrap().aborted.insert(tid);
+        self.released.notify_all();
+    }
+
+    /// Find cycles in the waits-for graph and abort the youngest transaction in each,
+    /// which wakes it so its request fails. Returns the transactions aborted.
+    pub fn detect_deadlocks(&self) -> Vec<TransactionId> {
+        let mut table = self.table.lock().unwrap();
+        let mut victims = Vec::new();
+        loop {
+            let graph = table.waits_for();
+            if graph.cycle_free() {
+                break;
+            }
+            let victim = graph
+                .node_references()
+                .filter(|(i, _)| on_cycle(&graph, *i))
+                .map(|(_, n)| *n.data())
+                .max_by_key(|tid| tid.id())
+                .expect("a graph with a cycle has a node on it");
+            debug!("Aborting {:?} to break a deadlock", victim);
+            table.aborted.insert(victim);
+            victims.push(victim);
+        }
+        if !victims.is_empty() {
+            self.released.notify_all();
+        }
+        victims
+    }
+
+    /// Whether tid has been aborted and not yet finished.
+    pub fn is_aborted(&self, tid: TransactionId) -> bool {
+        self.table.lock().unwrap().aborted.contains(&tid)
+    }
+
+    /// The mode tid holds on lock_id, if any.
+    pub fn held_mode(&self, tid: TransactionId, lock_id: &LockId) -> Option<LockMode> {
+        self.table.lock().unwrap().held_mode(tid, lock_id)
+    }
+
+    /// Release every lock tid holds and forget that it was aborted.
+    pub fn release_all(&self, tid: TransactionId) {
+        let mut table = self.table.lock().unwrap();
+        if let Some(lock_ids) = table.held.remove(&tid) {
+            for lock_id in lock_ids {
+                if let Some(holders) = table.locks.get_mut(&lock_id) {
+                    holders.remove(&tid);
+                    if holders.is_empty() {
+                        table.locks.remove(&lock_id);
+                    }
+                }
+            }
+        }
+        table.aborted.remove(&tid);
+        self.released.notify_all();
+    }
+
+    /// Drop every lock and abort flag.
+    pub fn reset(&self) {
+        let mut table = self.table.lock().unwrap();
+        *table = LockTable::default();
+        self.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    const RO: Permissions = Permissions::ReadOnly;
+    const RW: Permissions = Permissions::ReadWrite;
+
+    #[test]
+    fn test_compatibility() {
+        use LockMode::*;
+        assert!(IntentionShared.compatible(IntentionExclusive));
+        assert!(IntentionShared.compatible(Shared));
+        assert!(!IntentionShared.compatible(Exclusive));
+        assert!(IntentionExclusive.compatible(IntentionExclusive));
+        assert!(!IntentionExclusive.compatible(Shared));
+        assert!(Shared.compatible(Shared));
+        assert!(!Shared.compatible(Exclusive));
+        assert_eq!(Exclusive, IntentionExclusive.combine(Shared));
+        assert_eq!(Shared, IntentionShared.combine(Shared));
+    }
+
+    #[test]
+    fn test_shared_locks() {
+        let lm = LockManager::new(Duration::from_millis(50));
+        let t1 = TransactionId::new();
+        let t2 = TransactionId::new();
+        let id = ValueId::new_slot(1, 0, 0);
+        lm.lock_value(t1, id, RO).unwrap();
+        lm.lock_value(t2, id, RO).unwrap();
+        assert_eq!(Err(CrustyError::TransactionAbortedError), lm.lock_value(t2, id, RW));
+        assert!(lm.is_aborted(t2));
+        lm.release_all(t2);
+        assert!(!lm.is_aborted(t2));
+    }
+
+    #[test]
+    fn test_deadlock_aborts_youngest() {
+        let lm = Arc::new(LockManager::new(Duration::from_secs(30)));
+        let t1 = TransactionId::new();
+        let t2 = TransactionId::new();
+        let a = ValueId::new_slot(1, 0, 0);
+        let b = ValueId::new_slot(1, 0, 1);
+        lm.lock_value(t1, a, RW).unwrap();
+        lm.lock_value(t2, b, RW).unwrap();
+        assert!(lm.detect_deadlocks().is_empty());
+
+        let spawn = |tid, id| {
+            let lm = lm.clone();
+            thread::spawn(move || {
+                let res = lm.lock_value(tid, id, RW);
+                if res.is_err() {
+                    lm.release_all(tid);
+                }
+                res
+            })
+        };
+        let h1 = spawn(t1, b);
+        let h2 = spawn(t2, a);
+        let mut victims = Vec::new();
+        while victims.is_empty() {
+            thread::sleep(Duration::from_millis(10));
+            victims = lm.detect_deadlocks();
+        }
+        assert_eq!(vec![t2], victims);
+        assert_eq!(Err(CrustyError::TransactionAbortedError), h2.join().unwrap());
+        assert_eq!(Ok(()), h1.join().unwrap());
+        assert!(lm.detect_deadlocks().is_empty());
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let lm = LockManager::new(Duration::from_millis(50));
+        let t1 = TransactionId::new();
+        lm.lock_page(t1, 1, 0, RO).unwrap();
+        lm.lock_page(t1, 1, 0, RW).unwrap();
+        assert_eq!(
+            Some(LockMode::Exclusive),
+            lm.held_mode(t1, &LockId::Page(1, 0))
+        );
+        assert_eq!(
+            Some(LockMode::IntentionExclusive),
+            lm.held_mode(t1, &LockId::Container(1))
+        );
+    }
+
+    #[test]
+    fn test_container_lock_conflicts_with_intention() {
+        let lm = LockManager::new(Duration::from_millis(50));
+        let t1 = TransactionId::new();
+        let t2 = TransactionId::new();
+        lm.lock_container(t1, 1, RO).unwrap();
+        // Reading a page under a shared container lock is fine, writing is not
+        lm.lock_page(t2, 1, 3, RO).unwrap();
+        assert!(!lm.try_acquire(t2, LockId::Container(1), LockMode::IntentionExclusive));
+        assert!(lm.lock_page(t2, 1, 4, RW).is_err());
+    }
+
+    #[test]
+    fn test_blocks_until_release() {
+        let lm = Arc::new(LockManager::new(Duration::from_secs(10)));
+        let t1 = TransactionId::new();
+        let t2 = TransactionId::new();
+        lm.lock_container(t1, 1, RW).unwrap();
+
+        let lm2 = lm.clone();
+        let waiter = thread::spawn(move || {
+            let start = Instant::now();
+            lm2.lock_container(t2, 1, RO).unwrap();
+            start.elapsed()
+        });
+        thread::sleep(Duration::from_millis(100));
+        lm.release_all(t1);
+        let waited = waiter.join().unwrap();
+        assert!(waited >= Duration::from_millis(100));
+        assert_eq!(
+            Some(LockMode::Shared),
+            lm.held_mode(t2, &LockId::Container(1))
+        );
+    }
+
+    #[test]
+    fn test_abort_wakes_waiter() {
+        let lm = Arc::new(LockManager::new(Duration::from_secs(10)));
+        let t1 = TransactionId::new();
+        let t2 = TransactionId::new();
+        lm.lock_container(t1, 1, RW).unwrap();
+
+        let lm2 = lm.clone();
+        let waiter = thread::spawn(move || lm2.lock_container(t2, 1, RW));
+        thread::sleep(Duration::from_millis(50));
+        lm.abort(t2);
+        assert_eq!(
+            Err(CrustyError::TransactionAbortedError),
+            waiter.join().unwrap()
+        );
+    }
+}
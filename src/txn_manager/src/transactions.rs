@@ -1,10 +1,14 @@
-use common::ids::TransactionId;
+use common::ids::{IsolationLevel, TransactionId};
+use common::storage_trait::StorageTrait;
 use common::CrustyError;
 
 /// Transaction implementation.
 pub struct Transaction {
     tid: TransactionId,
     started: bool,
+    isolation: IsolationLevel,
+    /// Whether a statement of the transaction failed, after which it can only roll back.
+    failed: bool,
 }
 
 impl Default for Transaction {
@@ -19,9 +23,17 @@ impl Transaction {
         Self {
             tid: TransactionId::new(),
             started: false,
+            isolation: IsolationLevel::default(),
+            failed: false,
         }
     }
 
+    /// Sets the isolation level the transaction's statements run at.
+    pub fn with_isolation(mut self, isolation: IsolationLevel) -> Self {
+        self.isolation = isolation;
+        self
+    }
+
     /// Starts the transaction.
     pub fn start(&mut self) {
         self.started = true
@@ -32,26 +44,58 @@ impl Transaction {
         self.tid
     }
 
+    /// Returns the isolation level.
+    pub fn isolation(&self) -> IsolationLevel {
+        self.isolation
+    }
+
+    /// Marks that a statement of the transaction failed, so that the changes it made before
+    /// failing are never committed.
+    pub fn fail(&mut self) {
+        self.failed = true
+    }
+
+    /// Returns whether a statement of the transaction failed.
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
     /// Commits the transaction.
-    pub fn commit(&mut self) -> Result<(), CrustyError> {
-        self.complete(true)
+    ///
+    /// # Arguments
+    ///
+    /// * `sm` - Storage manager the transaction ran against.
+    pub fn commit<T: StorageTrait>(&mut self, sm: &T) -> Result<(), CrustyError> {
+        self.complete(sm, true)
     }
 
     /// Aborts the transaction.
-    pub fn abort(&mut self) -> Result<(), CrustyError> {
-        self.complete(false)
+    ///
+    /// # Arguments
+    ///
+    /// * `sm` - Storage manager the transaction ran against.
+    pub fn abort<T: StorageTrait>(&mut self, sm: &T) -> Result<(), CrustyError> {
+        self.complete(sm, false)
     }
 
-    /// Completes the transaction.
+    /// Completes the transaction, letting the storage manager release its locks.
+    /// Aborting rolls back the transaction's changes. If the storage manager already
+    /// aborted the transaction, its changes are rolled back and `TransactionAbortedError`
+    /// is returned even when committing.
     ///
     /// # Arguments
     ///
+    /// * `sm` - Storage manager the transaction ran against.
     /// * `commit` - True if the transaction should commit.
-    pub fn complete(&mut self, commit: bool) -> Result<(), CrustyError> {
+    pub fn complete<T: StorageTrait>(&mut self, sm: &T, commit: bool) -> Result<(), CrustyError> {
         if self.started {
-            error!("Error: FIXME, need to notify on txn complete {}", commit);
-            //FIXME DBSERVER.transaction_complete(self.tid, commit)?;
             self.started = false;
+            if commit {
+                let status = sm.check_transaction(self.tid);
+                sm.transaction_finished(self.tid);
+                return status;
+            }
+            sm.abort_transaction(self.tid);
         }
         Ok(())
     }